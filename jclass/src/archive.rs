@@ -0,0 +1,482 @@
+//! A compact encoding of parsed class files, for archives that are decoded instead of parsed.
+//!
+//! Unlike the class file format it keeps what the parser found: attributes are already split by
+//! kind and nothing is validated again. The encoding has no version of its own, whoever stores it
+//! has to tell a stale one apart.
+
+use crate::ClassFile;
+use crate::attribute::method::{
+    CodeAttributeInfo, LineNumberEntry, LocalVariableEntry, LocalVariableTypeEntry, StackMapFrame,
+    VerificationTypeInfo,
+};
+use crate::attribute::{
+    Annotation, BootstrapMethodEntry, ClassAttribute, CodeAttribute, ElementValue,
+    ElementValuePair, ExceptionTableEntry, FieldAttribute, InnerClassEntry, LocalVarEntry,
+    MethodAttribute, MethodParameterEntry, ParameterAnnotations, SharedAttribute, TargetInfo,
+    TypeAnnotation, TypePath, TypePathEntry,
+};
+use crate::constant_pool::{
+    ConstantEntry, ConstantPool, Dynamic, MethodHandle, NameAndType, Reference,
+};
+use crate::flags::{ClassFlags, FieldFlags, MethodFlags};
+use crate::member::{FieldInfo, MethodInfo};
+use common::error::ClassFormatErr;
+use common::utils::cursor::ByteCursor;
+
+impl ClassFile {
+    /// The encoding of the class file, see [`ClassFile::from_archived`].
+    pub fn to_archived(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode(&mut out);
+        out
+    }
+
+    /// The class file encoded by [`ClassFile::to_archived`], the whole of `bytes`.
+    pub fn from_archived(bytes: &[u8]) -> Result<Self, ClassFormatErr> {
+        let mut cursor = ByteCursor::new(bytes);
+        let cf = Self::decode(&mut cursor)?;
+        if cursor.is_eof() {
+            Ok(cf)
+        } else {
+            Err(ClassFormatErr::TrailingBytes)
+        }
+    }
+}
+
+trait Encode: Sized {
+    fn encode(&self, out: &mut Vec<u8>);
+    fn decode(cursor: &mut ByteCursor<'_>) -> Result<Self, ClassFormatErr>;
+}
+
+macro_rules! encode_number {
+    ($($ty:ident),*) => {$(
+        impl Encode for $ty {
+            fn encode(&self, out: &mut Vec<u8>) {
+                out.extend_from_slice(&self.to_be_bytes());
+            }
+
+            fn decode(cursor: &mut ByteCursor<'_>) -> Result<Self, ClassFormatErr> {
+                Ok(cursor.$ty()?)
+            }
+        }
+    )*};
+}
+
+encode_number!(u8, u16, u32, i32, i64, f32, f64);
+
+impl Encode for String {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (self.len() as u32).encode(out);
+        out.extend_from_slice(self.as_bytes());
+    }
+
+    fn decode(cursor: &mut ByteCursor<'_>) -> Result<Self, ClassFormatErr> {
+        let len = cursor.u32()? as usize;
+        Ok(String::from_utf8_lossy(cursor.bytes(len)?).into_owned())
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (self.len() as u32).encode(out);
+        for item in self {
+            item.encode(out);
+        }
+    }
+
+    fn decode(cursor: &mut ByteCursor<'_>) -> Result<Self, ClassFormatErr> {
+        let len = cursor.u32()? as usize;
+        // the length is not trusted with the capacity, every item takes at least a byte
+        let mut items = Vec::with_capacity(len.min(cursor.remaining()));
+        for _ in 0..len {
+            items.push(T::decode(cursor)?);
+        }
+        Ok(items)
+    }
+}
+
+macro_rules! encode_flags {
+    ($($ty:ident),*) => {$(
+        impl Encode for $ty {
+            fn encode(&self, out: &mut Vec<u8>) {
+                self.get_raw().encode(out);
+            }
+
+            fn decode(cursor: &mut ByteCursor<'_>) -> Result<Self, ClassFormatErr> {
+                Ok($ty::new(cursor.u16()?))
+            }
+        }
+    )*};
+}
+
+encode_flags!(ClassFlags, FieldFlags, MethodFlags);
+
+// the fields in the order they are encoded in
+macro_rules! encode_struct {
+    ($($ty:ident { $($field:ident),* $(,)? })*) => {$(
+        impl Encode for $ty {
+            fn encode(&self, out: &mut Vec<u8>) {
+                $(self.$field.encode(out);)*
+            }
+
+            fn decode(cursor: &mut ByteCursor<'_>) -> Result<Self, ClassFormatErr> {
+                Ok(Self {
+                    $($field: Encode::decode(cursor)?,)*
+                })
+            }
+        }
+    )*};
+}
+
+encode_struct! {
+    ClassFile {
+        minor_version, major_version, cp, access_flags, this_class, super_class, interfaces,
+        fields, methods, attributes,
+    }
+    ConstantPool { inner }
+    Reference { class_index, name_and_type_index }
+    NameAndType { name_index, descriptor_index }
+    Dynamic { bootstrap_method_attr_index, name_and_type_index }
+    MethodHandle { reference_kind, reference_index }
+    FieldInfo { access_flags, name_index, descriptor_index, attributes }
+    MethodInfo { access_flags, name_index, descriptor_index, attributes }
+    BootstrapMethodEntry { bootstrap_method_idx, bootstrap_arguments }
+    InnerClassEntry {
+        inner_class_info_index, outer_class_info_index, inner_name_index, inner_class_access_flags,
+    }
+    ExceptionTableEntry { start_pc, end_pc, handler_pc, catch_type }
+    LineNumberEntry { start_pc, line_number }
+    LocalVariableEntry { start_pc, length, name_index, descriptor_index, index }
+    LocalVariableTypeEntry { start_pc, length, name_index, signature_index, index }
+    MethodParameterEntry { name_index, access_flags }
+    ParameterAnnotations { annotations }
+    Annotation { type_index, element_value_pairs }
+    ElementValuePair { element_name_index, value }
+    TypeAnnotation { target_info, target_path, type_index, element_value_pairs }
+    LocalVarEntry { start_pc, length, index }
+    TypePath { path }
+    TypePathEntry { type_path_kind, type_argument_index }
+}
+
+// like the struct ones, with the code copied as a whole rather than byte by byte
+impl Encode for CodeAttribute {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.max_stack.encode(out);
+        self.max_locals.encode(out);
+        (self.code.len() as u32).encode(out);
+        out.extend_from_slice(&self.code);
+        self.exception_table.encode(out);
+        self.attributes.encode(out);
+    }
+
+    fn decode(cursor: &mut ByteCursor<'_>) -> Result<Self, ClassFormatErr> {
+        let max_stack = cursor.u16()?;
+        let max_locals = cursor.u16()?;
+        let code_len = cursor.u32()? as usize;
+        Ok(Self {
+            max_stack,
+            max_locals,
+            code: cursor.bytes(code_len)?.to_vec(),
+            exception_table: Encode::decode(cursor)?,
+            attributes: Encode::decode(cursor)?,
+        })
+    }
+}
+
+// a tag byte for the variant, then its fields in order; the tags are the encoding, so a variant
+// keeps its tag for good
+macro_rules! encode_enum {
+    ($($ty:ident {
+        $($tag:literal => $variant:ident
+            $(($($value:ident),*))?
+            $({$($field:ident),*})?),* $(,)?
+    })*) => {$(
+        impl Encode for $ty {
+            fn encode(&self, out: &mut Vec<u8>) {
+                match self {
+                    $(Self::$variant $(($($value),*))? $({$($field),*})? => {
+                        out.push($tag);
+                        $($($value.encode(out);)*)?
+                        $($($field.encode(out);)*)?
+                    })*
+                }
+            }
+
+            fn decode(cursor: &mut ByteCursor<'_>) -> Result<Self, ClassFormatErr> {
+                match cursor.u8()? {
+                    $($tag => Ok(Self::$variant
+                        $(($({
+                            let $value = Encode::decode(cursor)?;
+                            $value
+                        }),*))?
+                        $({$($field: Encode::decode(cursor)?),*})?),)*
+                    tag => Err(ClassFormatErr::UnknownTag(tag)),
+                }
+            }
+        }
+    )*};
+}
+
+encode_enum! {
+    ConstantEntry {
+        0 => Unused,
+        1 => Utf8(value),
+        2 => Integer(value),
+        3 => Float(value),
+        4 => Long(value),
+        5 => Double(value),
+        6 => Class(name_index),
+        7 => String(string_index),
+        8 => MethodRef(reference),
+        9 => FieldRef(reference),
+        10 => InterfaceMethodRef(reference),
+        11 => NameAndType(name_and_type),
+        12 => Dynamic(dynamic),
+        13 => InvokeDynamic(dynamic),
+        14 => MethodHandle(handle),
+        15 => MethodType(descriptor_index),
+    }
+    ClassAttribute {
+        0 => Shared(shared),
+        1 => SourceFile(index),
+        2 => InnerClasses(entries),
+        3 => EnclosingMethod(class_index, method_index),
+        4 => SourceDebugExtension,
+        5 => BootstrapMethods(entries),
+        6 => Module,
+        7 => ModulePackages,
+        8 => ModuleMainClass,
+        9 => NestHost(index),
+        10 => NestMembers(indices),
+        11 => Record,
+        12 => PermittedSubclasses(indices),
+    }
+    SharedAttribute {
+        0 => Synthetic,
+        1 => Deprecated,
+        2 => Signature(index),
+        3 => RuntimeVisibleAnnotations(annotations),
+        4 => RuntimeInvisibleAnnotations(annotations),
+        5 => RuntimeVisibleTypeAnnotations(annotations),
+        6 => RuntimeInvisibleTypeAnnotations(annotations),
+    }
+    FieldAttribute {
+        0 => Shared(shared),
+        1 => ConstantValue(index),
+    }
+    MethodAttribute {
+        0 => Shared(shared),
+        1 => Code(code),
+        2 => Exceptions(indices),
+        3 => RuntimeVisibleParameterAnnotations(annotations),
+        4 => RuntimeInvisibleParameterAnnotations(annotations),
+        5 => AnnotationsDefault,
+        6 => MethodParameters(parameters),
+    }
+    CodeAttributeInfo {
+        0 => LineNumberTable(entries),
+        1 => LocalVariableTable(entries),
+        2 => StackMapTable(frames),
+        3 => LocalVariableTypeTable(entries),
+        4 => RuntimeVisibleTypeAnnotations,
+        5 => RuntimeInvisibleTypeAnnotations,
+    }
+    StackMapFrame {
+        0 => Same { offset_delta },
+        1 => SameLocals1StackItem { offset_delta, stack },
+        2 => SameLocals1StackItemExtended { offset_delta, stack },
+        3 => Chop { k, offset_delta },
+        4 => SameExtended { offset_delta },
+        5 => Append { k, offset_delta, locals },
+        6 => Full { offset_delta, locals, stack },
+    }
+    VerificationTypeInfo {
+        0 => Top,
+        1 => Integer,
+        2 => Float,
+        3 => Double,
+        4 => Long,
+        5 => Null,
+        6 => UninitializedThis,
+        7 => Object(class_index),
+        8 => Uninitialized(offset),
+    }
+    ElementValue {
+        0 => Byte(index),
+        1 => Char(index),
+        2 => Double(index),
+        3 => Float(index),
+        4 => Int(index),
+        5 => Long(index),
+        6 => Short(index),
+        7 => Boolean(index),
+        8 => String(index),
+        9 => EnumConstValue { type_name_index, const_name_index },
+        10 => Class(index),
+        11 => AnnotationValue(annotation),
+        12 => Array(values),
+    }
+    TargetInfo {
+        0 => TypeParameter { type_parameter_index },
+        1 => Supertype { supertype_index },
+        2 => TypeParameterBound { type_parameter_index, bound_index },
+        3 => Empty,
+        4 => MethodFormalParameter { formal_parameter_index },
+        5 => Throws { throws_type_index },
+        6 => LocalVar { localvar_table },
+        7 => Catch { exception_table_index },
+        8 => Offset { offset },
+        9 => TypeArgument { offset, type_argument_index },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `static int answer() { return 42; }` with a bit of most kinds of attributes
+    fn sample_class_file() -> ClassFile {
+        let utf8 = |value: &str| ConstantEntry::Utf8(value.to_string());
+        ClassFile {
+            minor_version: 0,
+            major_version: 69,
+            cp: ConstantPool {
+                inner: vec![
+                    ConstantEntry::Unused,
+                    utf8("Sample"),
+                    ConstantEntry::Class(1),
+                    utf8("java/lang/Object"),
+                    ConstantEntry::Class(3),
+                    utf8("answer"),
+                    utf8("()I"),
+                    ConstantEntry::Long(-1),
+                    ConstantEntry::Unused,
+                    ConstantEntry::Double(0.5),
+                    ConstantEntry::Unused,
+                    ConstantEntry::NameAndType(NameAndType::new(5, 6)),
+                    ConstantEntry::MethodRef(Reference::new(2, 11)),
+                    ConstantEntry::MethodHandle(MethodHandle::new(6, 12)),
+                    ConstantEntry::InvokeDynamic(Dynamic::new(0, 11)),
+                ],
+            },
+            access_flags: ClassFlags::new(0x0021),
+            this_class: 2,
+            super_class: 4,
+            interfaces: vec![],
+            fields: vec![FieldInfo {
+                access_flags: FieldFlags::new(0x0018),
+                name_index: 5,
+                descriptor_index: 6,
+                attributes: vec![
+                    FieldAttribute::ConstantValue(7),
+                    FieldAttribute::Shared(SharedAttribute::Deprecated),
+                ],
+            }],
+            methods: vec![MethodInfo {
+                access_flags: MethodFlags::new(0x0008),
+                name_index: 5,
+                descriptor_index: 6,
+                attributes: vec![
+                    MethodAttribute::Code(CodeAttribute {
+                        max_stack: 1,
+                        max_locals: 0,
+                        code: vec![0x10, 42, 0xAC],
+                        exception_table: vec![ExceptionTableEntry {
+                            start_pc: 0,
+                            end_pc: 2,
+                            handler_pc: 2,
+                            catch_type: 0,
+                        }],
+                        attributes: vec![
+                            CodeAttributeInfo::LineNumberTable(vec![LineNumberEntry {
+                                start_pc: 0,
+                                line_number: 3,
+                            }]),
+                            CodeAttributeInfo::StackMapTable(vec![
+                                StackMapFrame::Append {
+                                    k: 1,
+                                    offset_delta: 2,
+                                    locals: vec![VerificationTypeInfo::Object(2)],
+                                },
+                                StackMapFrame::Full {
+                                    offset_delta: 0,
+                                    locals: vec![],
+                                    stack: vec![VerificationTypeInfo::Uninitialized(1)],
+                                },
+                            ]),
+                        ],
+                    }),
+                    MethodAttribute::Shared(SharedAttribute::RuntimeVisibleAnnotations(vec![
+                        Annotation {
+                            type_index: 1,
+                            element_value_pairs: vec![ElementValuePair {
+                                element_name_index: 5,
+                                value: ElementValue::Array(vec![
+                                    ElementValue::EnumConstValue {
+                                        type_name_index: 1,
+                                        const_name_index: 5,
+                                    },
+                                    ElementValue::Int(7),
+                                ]),
+                            }],
+                        },
+                    ])),
+                    MethodAttribute::Shared(SharedAttribute::RuntimeInvisibleTypeAnnotations(
+                        vec![TypeAnnotation {
+                            target_info: TargetInfo::LocalVar {
+                                localvar_table: vec![LocalVarEntry {
+                                    start_pc: 0,
+                                    length: 3,
+                                    index: 0,
+                                }],
+                            },
+                            target_path: TypePath {
+                                path: vec![TypePathEntry {
+                                    type_path_kind: 3,
+                                    type_argument_index: 0,
+                                }],
+                            },
+                            type_index: 1,
+                            element_value_pairs: vec![],
+                        }],
+                    )),
+                ],
+            }],
+            attributes: vec![
+                ClassAttribute::SourceFile(1),
+                ClassAttribute::EnclosingMethod(4, 11),
+                ClassAttribute::BootstrapMethods(vec![BootstrapMethodEntry::new(13, vec![6])]),
+                ClassAttribute::NestMembers(vec![2, 4]),
+                ClassAttribute::Record,
+            ],
+        }
+    }
+
+    #[test]
+    fn archived_class_file_decodes_to_the_same() {
+        // given
+        let cf = sample_class_file();
+
+        // when
+        let decoded = ClassFile::from_archived(&cf.to_archived()).unwrap();
+
+        // then
+        assert_eq!(format!("{decoded:?}"), format!("{cf:?}"));
+    }
+
+    #[test]
+    fn truncated_archived_class_file_is_an_error() {
+        // given
+        let archived = sample_class_file().to_archived();
+
+        // when
+        let results = [
+            ClassFile::from_archived(&archived[..archived.len() - 1]),
+            ClassFile::from_archived(&[archived.as_slice(), &[0]].concat()),
+        ];
+
+        // then
+        assert!(matches!(results[0], Err(ClassFormatErr::Cursor(_))));
+        assert!(matches!(results[1], Err(ClassFormatErr::TrailingBytes)));
+    }
+}
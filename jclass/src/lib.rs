@@ -10,6 +10,7 @@ use crate::member::{FieldInfo, MethodInfo};
 use common::error::ClassFormatErr;
 use common::utils::cursor::ByteCursor;
 
+mod archive;
 pub mod attribute;
 pub mod bytecode;
 pub mod constant_pool;
@@ -0,0 +1,312 @@
+use common::utils::cursor::{ByteCursor, ByteOrder, CursorError};
+use flate2::read::ZlibDecoder;
+use std::fmt::Display;
+use std::io::Read;
+
+// CompressedResourceHeader: magic, compressed size, uncompressed size, decompressor name offset,
+// plugin configuration offset and whether it is the last one of the chain
+pub(crate) const HEADER_MAGIC: u32 = 0xCAFEFAFA;
+pub(crate) const HEADER_SIZE: usize = 29;
+
+pub(crate) const ZIP: &[u8] = b"zip";
+const COMPACT_CP: &[u8] = b"compact-cp";
+
+const CONSTANT_UTF8: u8 = 1;
+const CONSTANT_LONG: u8 = 5;
+const CONSTANT_DOUBLE: u8 = 6;
+// a Utf8 entry moved to the image string table
+const EXTERNALIZED_STRING: u8 = 23;
+// a descriptor with its class names moved to the image string table
+const EXTERNALIZED_STRING_DESCRIPTOR: u8 = 25;
+
+#[derive(Debug)]
+pub enum DecompressError {
+    Cursor(CursorError),
+    Zip(std::io::Error),
+    UnknownDecompressor(String),
+    BadStringOffset(u32),
+    BadConstantPoolTag(u8),
+    Utf8TooLong(usize),
+    SizeMismatch { expected: u64, actual: usize },
+}
+
+impl From<CursorError> for DecompressError {
+    fn from(value: CursorError) -> Self {
+        DecompressError::Cursor(value)
+    }
+}
+
+impl Display for DecompressError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecompressError::Cursor(e) => write!(f, "{e}"),
+            DecompressError::Zip(e) => write!(f, "zip: {e}"),
+            DecompressError::UnknownDecompressor(name) => {
+                write!(f, "unknown decompressor \"{name}\"")
+            }
+            DecompressError::BadStringOffset(offset) => {
+                write!(f, "no string at offset {offset}")
+            }
+            DecompressError::BadConstantPoolTag(tag) => {
+                write!(f, "bad constant pool tag {tag}")
+            }
+            DecompressError::Utf8TooLong(len) => {
+                write!(f, "Utf8 constant of {len} bytes")
+            }
+            DecompressError::SizeMismatch { expected, actual } => {
+                write!(f, "expected {expected} bytes, got {actual}")
+            }
+        }
+    }
+}
+
+/// Undoes the compression of a resource. Each compressor wraps what it produced in a header
+/// naming it, the headers are peeled off until the original bytes are left.
+/// `strings` gives the image string at an offset, without the terminating NUL.
+pub(crate) fn decompress<'s>(
+    resource: &[u8],
+    order: ByteOrder,
+    strings: impl Fn(u32) -> Option<&'s [u8]>,
+) -> Result<Vec<u8>, DecompressError> {
+    let mut result = resource.to_vec();
+    while let Some((uncompressed_size, decompressor_off)) = read_header(&result, order)? {
+        let content = &result[HEADER_SIZE..];
+        let decompressed = match strings(decompressor_off) {
+            Some(ZIP) => inflate(content)?,
+            Some(COMPACT_CP) => expand_constant_pool(content, &strings)?,
+            Some(name) => {
+                return Err(DecompressError::UnknownDecompressor(
+                    String::from_utf8_lossy(name).into_owned(),
+                ));
+            }
+            None => return Err(DecompressError::BadStringOffset(decompressor_off)),
+        };
+        if decompressed.len() as u64 != uncompressed_size {
+            return Err(DecompressError::SizeMismatch {
+                expected: uncompressed_size,
+                actual: decompressed.len(),
+            });
+        }
+        result = decompressed;
+    }
+    Ok(result)
+}
+
+// the uncompressed size and the decompressor name offset, `None` if there is no header
+fn read_header(resource: &[u8], order: ByteOrder) -> Result<Option<(u64, u32)>, CursorError> {
+    if resource.len() < HEADER_SIZE {
+        return Ok(None);
+    }
+    let mut cur = ByteCursor::with_order(resource, order);
+    if cur.u32()? != HEADER_MAGIC {
+        return Ok(None);
+    }
+    let _compressed_size = cur.u64()?;
+    let uncompressed_size = cur.u64()?;
+    let decompressor_off = cur.u32()?;
+    Ok(Some((uncompressed_size, decompressor_off)))
+}
+
+// ZipDecompressor, java.util.zip.Inflater with the zlib wrapper
+fn inflate(content: &[u8]) -> Result<Vec<u8>, DecompressError> {
+    let mut result = Vec::new();
+    ZlibDecoder::new(content)
+        .read_to_end(&mut result)
+        .map_err(DecompressError::Zip)?;
+    Ok(result)
+}
+
+// StringSharingDecompressor: the class file is copied as is, except for the constant pool
+// strings that were shared through the image string table
+fn expand_constant_pool<'s>(
+    content: &[u8],
+    strings: &impl Fn(u32) -> Option<&'s [u8]>,
+) -> Result<Vec<u8>, DecompressError> {
+    let string = |offset: u32| strings(offset).ok_or(DecompressError::BadStringOffset(offset));
+    let mut cur = ByteCursor::with_order(content, ByteOrder::BigEndian);
+    let mut result = Vec::with_capacity(content.len() * 2);
+    // magic, minor and major version
+    result.extend_from_slice(cur.slice(8)?);
+    let count = cur.u16()?;
+    result.extend_from_slice(&count.to_be_bytes());
+    let mut index = 1;
+    while index < count {
+        let tag = cur.u8()?;
+        match tag {
+            CONSTANT_UTF8 => {
+                let len = cur.u16()? as usize;
+                push_utf8(&mut result, cur.slice(len)?)?;
+            }
+            EXTERNALIZED_STRING => push_utf8(&mut result, string(read_index(&mut cur)?)?)?,
+            EXTERNALIZED_STRING_DESCRIPTOR => {
+                let descriptor = string(read_index(&mut cur)?)?;
+                let indexes_len = read_index(&mut cur)? as usize;
+                let mut indexes = ByteCursor::new(cur.slice(indexes_len)?);
+                // every L of the descriptor is followed by the package and the simple name
+                let mut expanded = Vec::with_capacity(descriptor.len() * 2);
+                for &b in descriptor {
+                    expanded.push(b);
+                    if b == b'L' {
+                        let package = string(read_index(&mut indexes)?)?;
+                        if !package.is_empty() {
+                            expanded.extend_from_slice(package);
+                            expanded.push(b'/');
+                        }
+                        expanded.extend_from_slice(string(read_index(&mut indexes)?)?);
+                    }
+                }
+                push_utf8(&mut result, &expanded)?;
+            }
+            _ => {
+                let size = constant_size(tag).ok_or(DecompressError::BadConstantPoolTag(tag))?;
+                result.push(tag);
+                result.extend_from_slice(cur.slice(size)?);
+                // longs and doubles take two entries
+                if tag == CONSTANT_LONG || tag == CONSTANT_DOUBLE {
+                    index += 1;
+                }
+            }
+        }
+        index += 1;
+    }
+    result.extend_from_slice(cur.slice(cur.remaining())?);
+    Ok(result)
+}
+
+fn push_utf8(result: &mut Vec<u8>, bytes: &[u8]) -> Result<(), DecompressError> {
+    let len = u16::try_from(bytes.len()).map_err(|_| DecompressError::Utf8TooLong(bytes.len()))?;
+    result.push(CONSTANT_UTF8);
+    result.extend_from_slice(&len.to_be_bytes());
+    result.extend_from_slice(bytes);
+    Ok(())
+}
+
+// the size of the constant pool entries that are copied as is
+fn constant_size(tag: u8) -> Option<usize> {
+    match tag {
+        7 | 8 | 16 | 19 | 20 => Some(2),
+        15 => Some(3),
+        3 | 4 | 9..=12 | 17 | 18 => Some(4),
+        5 | 6 => Some(8),
+        _ => None,
+    }
+}
+
+// CompressIndexes: with the top bit set, bits 5-6 are the length in bytes and the low five
+// bits the most significant ones of the value, otherwise the value takes four bytes
+fn read_index(cur: &mut ByteCursor) -> Result<u32, CursorError> {
+    let header = cur.u8()?;
+    let (len, mut value) = if header & 0x80 != 0 {
+        ((header >> 5) & 0x03, (header & 0x1F) as u32)
+    } else {
+        (4, header as u32)
+    };
+    for _ in 1..len {
+        value = (value << 8) | cur.u8()? as u32;
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::Compression;
+    use flate2::write::ZlibEncoder;
+    use std::io::Write;
+
+    fn strings(offset: u32) -> Option<&'static [u8]> {
+        match offset {
+            1 => Some(b"zip"),
+            2 => Some(b"compact-cp"),
+            3 => Some(b"java/lang/Object"),
+            4 => Some(b"(L;)V"),
+            5 => Some(b"java/lang"),
+            6 => Some(b"String"),
+            7 => Some(b"lz4"),
+            _ => None,
+        }
+    }
+
+    fn with_header(decompressor_off: u32, uncompressed_size: u64, content: &[u8]) -> Vec<u8> {
+        let mut resource = Vec::new();
+        resource.extend_from_slice(&HEADER_MAGIC.to_le_bytes());
+        resource.extend_from_slice(&(content.len() as u64).to_le_bytes());
+        resource.extend_from_slice(&uncompressed_size.to_le_bytes());
+        resource.extend_from_slice(&decompressor_off.to_le_bytes());
+        resource.extend_from_slice(&0u32.to_le_bytes());
+        resource.push(1);
+        resource.extend_from_slice(content);
+        resource
+    }
+
+    #[test]
+    fn read_compressed_and_plain_indexes() {
+        // given
+        let bytes = [0xC1, 0x02, 0x00, 0x00, 0x01, 0x00, 0xA3];
+
+        // when
+        let mut cur = ByteCursor::new(&bytes);
+        let indexes = [
+            read_index(&mut cur).unwrap(),
+            read_index(&mut cur).unwrap(),
+            read_index(&mut cur).unwrap(),
+        ];
+
+        // then
+        assert_eq!(indexes, [0x102, 0x100, 3]);
+    }
+
+    #[test]
+    fn inflate_zip_resource() {
+        // given
+        let original = b"class file bytes, class file bytes, class file bytes";
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(original).unwrap();
+        let resource = with_header(1, original.len() as u64, &encoder.finish().unwrap());
+
+        // when
+        let result = decompress(&resource, ByteOrder::LittleEndian, strings).unwrap();
+
+        // then
+        assert_eq!(result, original);
+    }
+
+    #[test]
+    fn expand_shared_constant_pool_strings() {
+        // given
+        let mut content = vec![0xCA, 0xFE, 0xBA, 0xBE, 0, 0, 0, 69, 0, 5];
+        // #1 externalized "java/lang/Object"
+        content.extend_from_slice(&[EXTERNALIZED_STRING, 0xA3]);
+        // #2 "(Ljava/lang/String;)V" as descriptor (L;)V with package 5 and name 6
+        content.extend_from_slice(&[EXTERNALIZED_STRING_DESCRIPTOR, 0xA4, 0xA2, 0xA5, 0xA6]);
+        // #3 a long, which takes #4 as well
+        content.extend_from_slice(&[CONSTANT_LONG, 0, 0, 0, 0, 0, 0, 0, 42]);
+        // the rest of the class file
+        content.extend_from_slice(&[0x00, 0x21]);
+        let mut expected = vec![0xCA, 0xFE, 0xBA, 0xBE, 0, 0, 0, 69, 0, 5];
+        expected.extend_from_slice(&[CONSTANT_UTF8, 0, 16]);
+        expected.extend_from_slice(b"java/lang/Object");
+        expected.extend_from_slice(&[CONSTANT_UTF8, 0, 21]);
+        expected.extend_from_slice(b"(Ljava/lang/String;)V");
+        expected.extend_from_slice(&[CONSTANT_LONG, 0, 0, 0, 0, 0, 0, 0, 42, 0x00, 0x21]);
+        let resource = with_header(2, expected.len() as u64, &content);
+
+        // when
+        let result = decompress(&resource, ByteOrder::LittleEndian, strings).unwrap();
+
+        // then
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn reject_unknown_decompressor() {
+        // given
+        let resource = with_header(7, 3, b"abc");
+
+        // when
+        let result = decompress(&resource, ByteOrder::LittleEndian, strings);
+
+        // then
+        assert!(matches!(result, Err(DecompressError::UnknownDecompressor(name)) if name == "lz4"));
+    }
+}
@@ -1,9 +1,73 @@
-use common::utils::cursor::{ByteCursor, ByteOrder};
+use common::utils::cursor::{ByteCursor, ByteOrder, CursorError};
 use memmap2::Mmap;
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::fmt::Display;
 use std::fs::File;
 use std::path::Path;
 
+mod decompressor;
+mod writer;
+
+pub use decompressor::DecompressError;
+pub use writer::{Compression, JImageWriter};
+
+const MAGIC: u32 = 0xCAFE_DADA;
+const MAJOR_VERSION: u16 = 1;
+const MINOR_VERSION: u16 = 0;
+// seven u32 fields, the version being two u16
+const HEADER_SIZE: usize = 28;
 const HASH_MUL: u32 = 0x01_00_01_93;
+// the pseudo modules of the directory entries, `/packages/java.lang` and `/modules/java.base`
+const PACKAGES: &str = "packages";
+const MODULES: &str = "modules";
+
+#[derive(Debug)]
+pub enum JImageError {
+    Io(std::io::Error),
+    BadMagic(u32),
+    UnsupportedVersion {
+        major: u16,
+        minor: u16,
+    },
+    Truncated {
+        expected: usize,
+        actual: usize,
+    },
+    Decompress {
+        name: String,
+        error: DecompressError,
+    },
+}
+
+impl From<std::io::Error> for JImageError {
+    fn from(value: std::io::Error) -> Self {
+        JImageError::Io(value)
+    }
+}
+
+impl Display for JImageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JImageError::Io(e) => write!(f, "{e}"),
+            JImageError::BadMagic(magic) => {
+                write!(f, "not a jimage file, bad magic 0x{magic:08X}")
+            }
+            JImageError::UnsupportedVersion { major, minor } => {
+                write!(f, "unsupported jimage version {major}.{minor}")
+            }
+            JImageError::Truncated { expected, actual } => write!(
+                f,
+                "truncated jimage, the index takes {expected} bytes but the file has {actual}"
+            ),
+            JImageError::Decompress { name, error } => {
+                write!(f, "cannot decompress {name}: {error}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for JImageError {}
 
 #[derive(Debug)]
 pub struct Header {
@@ -17,20 +81,80 @@ pub struct Header {
     pub strings_size: u32,
 }
 
-#[derive(Debug)]
-struct Entry {
-    module_off: u32,
-    parent_off: u32,
-    base_off: u32,
-    ext_off: u32,
-    content_off: u64,
-    compressed_size: u64,
-    uncompressed_size: u64,
+impl Header {
+    fn read(cur: &mut ByteCursor) -> Result<Self, CursorError> {
+        let magic = cur.u32()?;
+        let version = cur.u32()?;
+        Ok(Self {
+            magic,
+            major: (version >> 16) as u16,
+            minor: (version & 0xFFFF) as u16,
+            flags: cur.u32()?,
+            resource_count: cur.u32()?,
+            table_length: cur.u32()?,
+            locations_size: cur.u32()?,
+            strings_size: cur.u32()?,
+        })
+    }
+
+    /// The size of the header with the redirect, offset, location and string tables, where the
+    /// resource content starts.
+    pub fn index_size(&self) -> usize {
+        HEADER_SIZE
+            + self.table_length as usize * 8
+            + self.locations_size as usize
+            + self.strings_size as usize
+    }
+}
+
+/// A resource of the image, named `/module/parent/base.extension`. Offsets are relative to the
+/// end of the index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceLocation {
+    pub module: String,
+    pub parent: String,
+    pub base: String,
+    pub extension: String,
+    pub content_offset: u64,
+    pub compressed_size: u64,
+    pub uncompressed_size: u64,
+}
+
+impl ResourceLocation {
+    pub fn full_name(&self) -> String {
+        if self.module.is_empty() {
+            self.path()
+        } else {
+            format!("/{}/{}", self.module, self.path())
+        }
+    }
+
+    /// The name within the module, like `java/lang/Object.class`.
+    pub fn path(&self) -> String {
+        let mut path = String::new();
+        if !self.parent.is_empty() {
+            path.push_str(&self.parent);
+            path.push('/');
+        }
+        path.push_str(&self.base);
+        if !self.extension.is_empty() {
+            path.push('.');
+            path.push_str(&self.extension);
+        }
+        path
+    }
+
+    /// Whether it is a directory entry, `/packages` and `/modules` or one of their pseudo
+    /// modules.
+    pub fn is_directory(&self) -> bool {
+        self.module.is_empty() || self.module == PACKAGES || self.module == MODULES
+    }
 }
 
 pub struct JImage {
     mmap: Mmap,
     pub header: Header,
+    order: ByteOrder,
     redirect_off: usize,
     offsets_off: usize,
     locations_off: usize,
@@ -39,45 +163,54 @@ pub struct JImage {
 }
 
 impl JImage {
-    pub fn new<P: AsRef<Path>>(p: P) -> Self {
-        let file = File::open(p).unwrap();
-        let mmap = unsafe { Mmap::map(&file).unwrap() };
-        let mut cur = ByteCursor::with_order(&mmap, ByteOrder::LittleEndian);
-
-        let magic = cur.u32().unwrap();
-        assert_eq!(magic, 0xCAFEDADA, "bad magic: 0x{magic:08X}");
-
-        let ver = cur.u32().unwrap();
-        let major = (ver >> 16) as u16;
-        let minor = (ver & 0xFFFF) as u16;
-
-        let header = Header {
-            magic,
-            major,
-            minor,
-            flags: cur.u32().unwrap(),
-            resource_count: cur.u32().unwrap(),
-            table_length: cur.u32().unwrap(),
-            locations_size: cur.u32().unwrap(),
-            strings_size: cur.u32().unwrap(),
+    pub fn new<P: AsRef<Path>>(p: P) -> Result<Self, JImageError> {
+        let file = File::open(p)?;
+        // the image is only read, and like HotSpot we expect nobody to change it while it is in use
+        let mmap = unsafe { Mmap::map(&file)? };
+        let truncated = |expected| JImageError::Truncated {
+            expected,
+            actual: mmap.len(),
+        };
+        // the image is written in the byte order of the platform it was built for
+        let magic = mmap
+            .first_chunk::<4>()
+            .map(|magic| u32::from_le_bytes(*magic))
+            .ok_or_else(|| truncated(HEADER_SIZE))?;
+        let order = if magic == MAGIC {
+            ByteOrder::LittleEndian
+        } else if magic.swap_bytes() == MAGIC {
+            ByteOrder::BigEndian
+        } else {
+            return Err(JImageError::BadMagic(magic));
         };
+        let header = Header::read(&mut ByteCursor::with_order(&mmap, order))
+            .map_err(|_| truncated(HEADER_SIZE))?;
+        if header.major != MAJOR_VERSION || header.minor != MINOR_VERSION {
+            return Err(JImageError::UnsupportedVersion {
+                major: header.major,
+                minor: header.minor,
+            });
+        }
+        if header.index_size() > mmap.len() {
+            return Err(truncated(header.index_size()));
+        }
 
-        let header_size = cur.position();
-        let redirect_off = header_size;
+        let redirect_off = HEADER_SIZE;
         let offsets_off = redirect_off + (header.table_length as usize) * 4;
         let locations_off = offsets_off + (header.table_length as usize) * 4;
         let strings_off = locations_off + (header.locations_size as usize);
         let data_base = strings_off + (header.strings_size as usize);
 
-        Self {
+        Ok(Self {
             mmap,
             header,
+            order,
             redirect_off,
             offsets_off,
             locations_off,
             strings_off,
             data_base,
-        }
+        })
     }
 
     fn hash_seeded(seed: u32, name: &str) -> u32 {
@@ -91,15 +224,21 @@ impl JImage {
         Self::hash_seeded(HASH_MUL, name)
     }
 
+    fn u32_in(&self, bytes: &[u8]) -> u32 {
+        let b = bytes[..4].try_into().unwrap();
+        match self.order {
+            ByteOrder::LittleEndian => u32::from_le_bytes(b),
+            ByteOrder::BigEndian => u32::from_be_bytes(b),
+        }
+    }
+    fn u32_at(&self, o: usize) -> u32 {
+        self.u32_in(&self.mmap[o..o + 4])
+    }
     fn redirect_at(&self, i: u32) -> i32 {
-        let o = self.redirect_off + (i as usize) * 4;
-        let b = self.mmap[o..o + 4].try_into().unwrap();
-        i32::from_le_bytes(b)
+        self.u32_at(self.redirect_off + (i as usize) * 4) as i32
     }
     fn offset_at(&self, i: u32) -> u32 {
-        let o = self.offsets_off + (i as usize) * 4;
-        let b = self.mmap[o..o + 4].try_into().unwrap();
-        u32::from_le_bytes(b)
+        self.u32_at(self.offsets_off + (i as usize) * 4)
     }
 
     fn lookup_slot(&self, path: &str) -> Option<(u32, usize)> {
@@ -123,20 +262,22 @@ impl JImage {
         Some((slot, loc_off))
     }
 
-    fn decode_location(&self, loc_off: usize) -> Entry {
+    // attributes are a tag byte, kind in the upper five bits and length - 1 in the lower three,
+    // followed by a big endian value, up to an end tag. A corrupt one is no location at all.
+    fn decode_location(&self, loc_off: usize) -> Option<ResourceLocation> {
         let loc = &self.mmap[self.locations_off + loc_off
             ..self.locations_off + self.header.locations_size as usize];
         let mut c = ByteCursor::with_order(loc, ByteOrder::BigEndian);
 
         let mut v = [0u64; 8];
         loop {
-            let tag = c.u8().unwrap();
+            let tag = c.u8().ok()?;
             if tag <= 0x07 {
                 break;
             }
             let kind = (tag >> 3) as usize;
             let len = (tag & 0x07) as usize + 1;
-            let s = c.slice(len).unwrap();
+            let s = c.slice(len).ok()?;
             let mut x = 0u64;
             for &b in s {
                 x = (x << 8) | (b as u64);
@@ -146,57 +287,231 @@ impl JImage {
             }
         }
 
-        Entry {
-            module_off: v[1] as u32,
-            parent_off: v[2] as u32,
-            base_off: v[3] as u32,
-            ext_off: v[4] as u32,
-            content_off: v[5],
+        Some(ResourceLocation {
+            module: self.string_at(v[1] as usize),
+            parent: self.string_at(v[2] as usize),
+            base: self.string_at(v[3] as usize),
+            extension: self.string_at(v[4] as usize),
+            content_offset: v[5],
             compressed_size: v[6],
             uncompressed_size: v[7],
-        }
+        })
     }
 
+    // offset 0 is the empty string
     fn string_at(&self, off: usize) -> String {
-        let s = &self.mmap
-            [self.strings_off + off..self.strings_off + self.header.strings_size as usize];
-        let end = s.iter().position(|&b| b == 0).unwrap();
-        String::from_utf8_lossy(&s[..end]).into_owned()
-    }
-
-    fn make_name(&self, e: &Entry) -> String {
-        let mut name = String::new();
-        if e.module_off != 0 {
-            name.push('/');
-            name.push_str(&self.string_at(e.module_off as usize));
-            name.push('/');
-        }
-        if e.parent_off != 0 {
-            name.push_str(&self.string_at(e.parent_off as usize));
-            name.push('/');
-        }
-        name.push_str(&self.string_at(e.base_off as usize));
-        if e.ext_off != 0 {
-            name.push('.');
-            name.push_str(&self.string_at(e.ext_off as usize));
+        String::from_utf8_lossy(self.string_bytes_at(off).unwrap_or_default()).into_owned()
+    }
+
+    // the modified UTF-8 bytes of a string of the string table, without the terminating NUL
+    fn string_bytes_at(&self, off: usize) -> Option<&[u8]> {
+        let s = self
+            .mmap
+            .get(self.strings_off + off..self.strings_off + self.header.strings_size as usize)?;
+        let end = s.iter().position(|&b| b == 0)?;
+        Some(&s[..end])
+    }
+
+    /// A CRC-32 of the header and the tables, which tells images apart without reading their
+    /// content: a resource that changes size moves the offsets of the ones behind it.
+    pub fn index_checksum(&self) -> u32 {
+        crc32fast::hash(&self.mmap[..self.data_base])
+    }
+
+    /// Every location of the image, the directory entries included, in the order of the
+    /// location table.
+    pub fn resources(&self) -> impl Iterator<Item = ResourceLocation> + '_ {
+        (0..self.header.table_length).filter_map(|slot| {
+            let loc_off = self.offset_at(slot) as usize;
+            if loc_off >= self.header.locations_size as usize {
+                return None;
+            }
+            self.decode_location(loc_off)
+        })
+    }
+
+    /// The names of the modules with resources, sorted.
+    pub fn modules(&self) -> Vec<String> {
+        self.resources()
+            .filter(|location| !location.is_directory())
+            .map(|location| location.module)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    /// Every package, like `java.lang`, with the module that has its classes, sorted by package.
+    pub fn packages(&self) -> Vec<(String, String)> {
+        let mut packages = self
+            .resources()
+            .filter(|location| location.module == PACKAGES)
+            .filter_map(|location| {
+                let package = location.path();
+                let module = self.package_module(&package)?;
+                Some((package, module))
+            })
+            .collect::<Vec<_>>();
+        packages.sort();
+        packages
+    }
+
+    /// The module with classes in `package`, given in internal form like `java/util/logging`.
+    /// Read from the `/packages/<package>` entry, a list of (is empty, module name offset) pairs.
+    pub fn package_module(&self, package: &str) -> Option<String> {
+        let location = self.find(&format!("/{PACKAGES}/{}", package.replace('/', ".")))?;
+        // directory entries are never compressed
+        let content = self.content(&location).ok()?;
+        content.chunks_exact(8).find_map(|pair| {
+            let is_empty = self.u32_in(&pair[..4]);
+            let module_off = self.u32_in(&pair[4..]);
+            (is_empty == 0).then(|| self.string_at(module_off as usize))
+        })
+    }
+
+    /// The class file of the class `name`, in internal form, from whichever module has its
+    /// package.
+    pub fn open_class(&self, name: &str) -> Result<Option<Cow<'_, [u8]>>, JImageError> {
+        match self.class_resource_name(name) {
+            Some(full_name) => self.open(&full_name),
+            None => Ok(None),
         }
-        name
     }
 
-    pub fn open_java_base_class(&self, path: &str) -> Option<&[u8]> {
-        let full_name = format!("/java.base/{path}.class");
-        let (_, loc_off) = self.lookup_slot(&full_name)?;
-        let e = self.decode_location(loc_off);
+    /// Whether the image has the class `name`, without reading it.
+    pub fn contains_class(&self, name: &str) -> bool {
+        self.class_resource_name(name)
+            .and_then(|full_name| self.find(&full_name))
+            .is_some()
+    }
+
+    fn class_resource_name(&self, name: &str) -> Option<String> {
+        let module = self.package_module(&name[..name.rfind('/')?])?;
+        Some(format!("/{module}/{name}.class"))
+    }
+
+    fn find(&self, full_name: &str) -> Option<ResourceLocation> {
+        let (_, loc_off) = self.lookup_slot(full_name)?;
+        let location = self.decode_location(loc_off)?;
         // verify name, just in case
-        if self.make_name(&e) != full_name {
-            return None;
+        (location.full_name() == full_name).then_some(location)
+    }
+
+    /// The content of the resource `full_name`, like `/java.base/java/lang/Object.class`.
+    pub fn open(&self, full_name: &str) -> Result<Option<Cow<'_, [u8]>>, JImageError> {
+        match self.find(full_name) {
+            Some(location) => self.content(&location).map(Some),
+            None => Ok(None),
         }
+    }
 
-        let start = self.data_base + (e.content_off as usize);
-        if e.compressed_size != 0 {
-            unimplemented!("compressed resources not supported yet");
+    /// The content of a resource, decompressed into an owned buffer if it was stored compressed.
+    pub fn content(&self, location: &ResourceLocation) -> Result<Cow<'_, [u8]>, JImageError> {
+        let start = self.data_base + (location.content_offset as usize);
+        let stored_size = match location.compressed_size {
+            0 => location.uncompressed_size,
+            compressed_size => compressed_size,
+        };
+        let end = start + (stored_size as usize);
+        let bytes = self.mmap.get(start..end).ok_or(JImageError::Truncated {
+            expected: end,
+            actual: self.mmap.len(),
+        })?;
+        if location.compressed_size == 0 {
+            return Ok(Cow::Borrowed(bytes));
         }
-        let end = start + (e.uncompressed_size as usize);
-        Some(&self.mmap[start..end])
+        decompressor::decompress(bytes, self.order, |off| self.string_bytes_at(off as usize))
+            .map(Cow::Owned)
+            .map_err(|error| JImageError::Decompress {
+                name: location.full_name(),
+                error,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image_file(name: &str, bytes: &[u8]) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("jimage-{}-{name}", std::process::id()));
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    fn header_bytes(table_length: u32) -> Vec<u8> {
+        [MAGIC, 1 << 16, 0, table_length, table_length, 16, 16]
+            .iter()
+            .flat_map(|field| field.to_le_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn reject_bad_magic() {
+        // given
+        let path = image_file("bad-magic", &[0xCA, 0xFE, 0xBA, 0xBE, 0, 0, 0, 0]);
+
+        // when
+        let result = JImage::new(&path);
+
+        // then
+        assert!(matches!(result, Err(JImageError::BadMagic(0xBEBAFECA))));
+    }
+
+    #[test]
+    fn reject_truncated_index() {
+        // given
+        let path = image_file("truncated", &header_bytes(100));
+
+        // when
+        let result = JImage::new(&path);
+
+        // then
+        assert!(matches!(
+            result,
+            Err(JImageError::Truncated {
+                expected: 860,
+                actual: 28
+            })
+        ));
+    }
+
+    #[test]
+    fn open_empty_image() {
+        // given
+        let mut bytes = header_bytes(0);
+        bytes.extend_from_slice(&[0; 32]);
+        let path = image_file("empty", &bytes);
+
+        // when
+        let image = JImage::new(&path).unwrap();
+
+        // then
+        assert_eq!(image.resources().count(), 0);
+        assert!(image.modules().is_empty());
+        assert!(
+            image
+                .open("/java.base/java/lang/Object.class")
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn resource_names() {
+        // given
+        let location = ResourceLocation {
+            module: "java.base".to_string(),
+            parent: "java/lang".to_string(),
+            base: "Object".to_string(),
+            extension: "class".to_string(),
+            content_offset: 0,
+            compressed_size: 0,
+            uncompressed_size: 0,
+        };
+
+        // then
+        assert_eq!(location.path(), "java/lang/Object.class");
+        assert_eq!(location.full_name(), "/java.base/java/lang/Object.class");
+        assert!(!location.is_directory());
     }
 }
@@ -0,0 +1,124 @@
+use jimage::{Compression, JImage, JImageWriter, ResourceLocation};
+use std::collections::HashMap;
+use std::path::Path;
+
+const USAGE: &str = "Usage: jimage <command> <jimage>
+
+  list                    Prints the names of all the entries in the jimage, by module
+  info                    Prints the information of the jimage header
+  extract <module> <dir>  Writes the entries of <module> to <dir>
+  create [--zip] <dir>    Writes a jimage with a module for every directory of <dir>";
+
+fn open(path: &str) -> JImage {
+    JImage::new(path).unwrap_or_else(|e| fail(format!("cannot open {path}: {e}")))
+}
+
+fn fail(message: String) -> ! {
+    eprintln!("Error: {message}");
+    std::process::exit(1);
+}
+
+fn usage() -> ! {
+    eprintln!("{USAGE}");
+    std::process::exit(2);
+}
+
+// the resources of every module sorted by full name, like the JDK tool which puts
+// java.management.rmi before java.management
+fn resources_by_module(image: &JImage) -> Vec<(String, Vec<ResourceLocation>)> {
+    let mut modules = HashMap::<String, Vec<ResourceLocation>>::new();
+    for location in image
+        .resources()
+        .filter(|location| !location.is_directory())
+    {
+        modules
+            .entry(location.module.clone())
+            .or_default()
+            .push(location);
+    }
+    let mut modules = modules.into_iter().collect::<Vec<_>>();
+    modules.sort_by_key(|(module, _)| format!("/{module}/"));
+    for (_, resources) in &mut modules {
+        resources.sort_by_key(ResourceLocation::path);
+    }
+    modules
+}
+
+fn list(path: &str) {
+    let image = open(path);
+    println!("jimage: {path}");
+    for (module, resources) in resources_by_module(&image) {
+        println!();
+        println!("Module: {module}");
+        for location in resources {
+            println!("    {}", location.path());
+        }
+    }
+}
+
+fn info(path: &str) {
+    let header = &open(path).header;
+    println!(" Major Version:  {}", header.major);
+    println!(" Minor Version:  {}", header.minor);
+    println!(" Flags:          {}", header.flags);
+    println!(" Resource Count: {}", header.resource_count);
+    println!(" Table Length:   {}", header.table_length);
+    println!(" Offsets Size:   {}", header.table_length * 4);
+    println!(" Redirects Size: {}", header.table_length * 4);
+    println!(" Locations Size: {}", header.locations_size);
+    println!(" Strings Size:   {}", header.strings_size);
+    println!(" Index Size:     {}", header.index_size());
+}
+
+fn extract(path: &str, module: &str, dir: &Path) {
+    let image = open(path);
+    let Some((_, resources)) = resources_by_module(&image)
+        .into_iter()
+        .find(|(name, _)| name == module)
+    else {
+        fail(format!("no module {module} in {path}"));
+    };
+    for location in resources {
+        let target = dir.join(location.path());
+        let written = image
+            .content(&location)
+            .map_err(|e| e.to_string())
+            .and_then(|content| {
+                if let Some(parent) = target.parent() {
+                    std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                }
+                std::fs::write(&target, content).map_err(|e| e.to_string())
+            });
+        if let Err(e) = written {
+            fail(format!("cannot extract {}: {e}", location.full_name()));
+        }
+    }
+}
+
+fn create(path: &str, dir: &Path, compression: Compression) {
+    let mut writer = JImageWriter::new().compression(compression);
+    let modules = std::fs::read_dir(dir)
+        .and_then(|entries| entries.collect::<Result<Vec<_>, _>>())
+        .unwrap_or_else(|e| fail(format!("cannot read {}: {e}", dir.display())));
+    for module in modules.iter().filter(|module| module.path().is_dir()) {
+        let name = module.file_name().to_string_lossy().into_owned();
+        if let Err(e) = writer.add_dir(&name, module.path()) {
+            fail(format!("cannot read module {name}: {e}"));
+        }
+    }
+    if let Err(e) = writer.write_to(path) {
+        fail(format!("cannot write {path}: {e}"));
+    }
+}
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["list", path] => list(path),
+        ["info", path] => info(path),
+        ["extract", module, dir, path] => extract(path, module, Path::new(dir)),
+        ["create", dir, path] => create(path, Path::new(dir), Compression::None),
+        ["create", "--zip", dir, path] => create(path, Path::new(dir), Compression::Zip),
+        _ => usage(),
+    }
+}
@@ -0,0 +1,471 @@
+use crate::decompressor::{HEADER_MAGIC, HEADER_SIZE as COMPRESSED_HEADER_SIZE, ZIP};
+use crate::{HASH_MUL, JImage, MAGIC, MAJOR_VERSION, MINOR_VERSION, PACKAGES};
+use common::utils::cursor::ByteOrder;
+use flate2::write::ZlibEncoder;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::Write;
+use std::path::Path;
+
+// the location attribute kinds, ATTRIBUTE_END being 0
+const ATTRIBUTE_MODULE: u8 = 1;
+const ATTRIBUTE_PARENT: u8 = 2;
+const ATTRIBUTE_BASE: u8 = 3;
+const ATTRIBUTE_EXTENSION: u8 = 4;
+const ATTRIBUTE_OFFSET: u8 = 5;
+const ATTRIBUTE_COMPRESSED: u8 = 6;
+const ATTRIBUTE_UNCOMPRESSED: u8 = 7;
+
+/// How the resources are stored in the image.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Compression {
+    #[default]
+    None,
+    /// Like `jlink --compress=2`, every resource that gets smaller is deflated.
+    Zip,
+}
+
+/// Builds a jimage file out of resources, the way `jlink` lays it out: the header, the
+/// perfect hash redirect table, the location offsets, the locations, the strings and then the
+/// content. Only the `/packages/<package>` directory entries are written, the `/modules` tree
+/// used by the jrt file system is not.
+#[derive(Debug, Default)]
+pub struct JImageWriter {
+    order: Option<ByteOrder>,
+    compression: Compression,
+    // (module, path) to content
+    resources: BTreeMap<(String, String), Vec<u8>>,
+}
+
+// a resource or directory entry as it is written, its content possibly compressed
+struct Entry {
+    name: String,
+    module: String,
+    parent: String,
+    base: String,
+    extension: String,
+    content: Vec<u8>,
+    uncompressed_size: usize,
+}
+
+// the string table, offset 0 being the empty string
+struct Strings {
+    bytes: Vec<u8>,
+    offsets: HashMap<String, u32>,
+}
+
+impl Strings {
+    fn new() -> Self {
+        let mut strings = Self {
+            bytes: Vec::new(),
+            offsets: HashMap::new(),
+        };
+        strings.add("");
+        strings
+    }
+
+    fn add(&mut self, s: &str) -> u32 {
+        if let Some(&offset) = self.offsets.get(s) {
+            return offset;
+        }
+        let offset = self.bytes.len() as u32;
+        self.bytes.extend_from_slice(s.as_bytes());
+        self.bytes.push(0);
+        self.offsets.insert(s.to_string(), offset);
+        offset
+    }
+}
+
+impl JImageWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The byte order of the image, the one of the platform by default.
+    pub fn order(mut self, order: ByteOrder) -> Self {
+        self.order = Some(order);
+        self
+    }
+
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Adds the resource `path` of `module`, like `java/lang/Object.class` of `java.base`,
+    /// replacing the one added before under the same name.
+    pub fn add(&mut self, module: &str, path: &str, content: impl Into<Vec<u8>>) {
+        self.resources
+            .insert((module.to_string(), path.to_string()), content.into());
+    }
+
+    /// Adds every file under `dir` to `module`, named by its path relative to `dir`.
+    pub fn add_dir(&mut self, module: &str, dir: impl AsRef<Path>) -> std::io::Result<()> {
+        self.add_dir_entries(module, dir.as_ref(), "")
+    }
+
+    fn add_dir_entries(&mut self, module: &str, dir: &Path, prefix: &str) -> std::io::Result<()> {
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let path = format!("{prefix}{name}");
+            if entry.file_type()?.is_dir() {
+                self.add_dir_entries(module, &entry.path(), &format!("{path}/"))?;
+            } else {
+                self.add(module, &path, std::fs::read(entry.path())?);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn write_to(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        self.write(&mut file)?;
+        file.flush()
+    }
+
+    pub fn write(&self, out: &mut impl Write) -> std::io::Result<()> {
+        let order = self.order.unwrap_or(if cfg!(target_endian = "big") {
+            ByteOrder::BigEndian
+        } else {
+            ByteOrder::LittleEndian
+        });
+        let mut strings = Strings::new();
+        let entries = self.entries(order, &mut strings);
+
+        // offset 0 is no location, it holds a lone end attribute
+        let mut locations = vec![0u8];
+        let mut location_offsets = Vec::with_capacity(entries.len());
+        let mut content_offset = 0u64;
+        for entry in &entries {
+            location_offsets.push(locations.len() as u32);
+            let compressed_size = if entry.content.len() == entry.uncompressed_size {
+                0
+            } else {
+                entry.content.len() as u64
+            };
+            let attributes = [
+                (ATTRIBUTE_MODULE, strings.add(&entry.module) as u64),
+                (ATTRIBUTE_PARENT, strings.add(&entry.parent) as u64),
+                (ATTRIBUTE_BASE, strings.add(&entry.base) as u64),
+                (ATTRIBUTE_EXTENSION, strings.add(&entry.extension) as u64),
+                (ATTRIBUTE_OFFSET, content_offset),
+                (ATTRIBUTE_COMPRESSED, compressed_size),
+                (ATTRIBUTE_UNCOMPRESSED, entry.uncompressed_size as u64),
+            ];
+            for (kind, value) in attributes.into_iter().filter(|&(_, value)| value != 0) {
+                push_attribute(&mut locations, kind, value);
+            }
+            locations.push(0);
+            content_offset += entry.content.len() as u64;
+        }
+
+        let names = entries
+            .iter()
+            .map(|entry| entry.name.clone())
+            .collect::<Vec<_>>();
+        let (redirect, slots) = perfect_hash(&names);
+        let mut offsets = vec![0u32; names.len()];
+        for (entry, &slot) in slots.iter().enumerate() {
+            offsets[slot] = location_offsets[entry];
+        }
+
+        let header = [
+            MAGIC,
+            ((MAJOR_VERSION as u32) << 16) | MINOR_VERSION as u32,
+            0,
+            names.len() as u32,
+            names.len() as u32,
+            locations.len() as u32,
+            strings.bytes.len() as u32,
+        ];
+        for value in header {
+            out.write_all(&u32_bytes(value, order))?;
+        }
+        for value in redirect {
+            out.write_all(&u32_bytes(value as u32, order))?;
+        }
+        for value in offsets {
+            out.write_all(&u32_bytes(value, order))?;
+        }
+        out.write_all(&locations)?;
+        out.write_all(&strings.bytes)?;
+        for entry in &entries {
+            out.write_all(&entry.content)?;
+        }
+        Ok(())
+    }
+
+    // the resources followed by a `/packages/<package>` entry for every package with classes
+    fn entries(&self, order: ByteOrder, strings: &mut Strings) -> Vec<Entry> {
+        let mut entries = Vec::with_capacity(self.resources.len());
+        let mut packages = BTreeMap::<String, BTreeSet<&str>>::new();
+        for ((module, path), content) in &self.resources {
+            let (parent, file) = path.rsplit_once('/').unwrap_or(("", path));
+            let (base, extension) = file.rsplit_once('.').unwrap_or((file, ""));
+            if extension == "class" && !parent.is_empty() {
+                packages
+                    .entry(parent.replace('/', "."))
+                    .or_default()
+                    .insert(module);
+            }
+            let stored = match self.compression {
+                Compression::None => None,
+                Compression::Zip => self.zip(content, order, strings),
+            };
+            entries.push(Entry {
+                name: format!("/{module}/{path}"),
+                module: module.clone(),
+                parent: parent.to_string(),
+                base: base.to_string(),
+                extension: extension.to_string(),
+                content: stored.unwrap_or_else(|| content.clone()),
+                uncompressed_size: content.len(),
+            });
+        }
+        // (is empty, module name offset) pairs
+        for (package, modules) in packages {
+            let content = modules
+                .into_iter()
+                .flat_map(|module| [u32_bytes(0, order), u32_bytes(strings.add(module), order)])
+                .flatten()
+                .collect::<Vec<_>>();
+            entries.push(Entry {
+                name: format!("/{PACKAGES}/{package}"),
+                module: PACKAGES.to_string(),
+                parent: String::new(),
+                base: package,
+                extension: String::new(),
+                uncompressed_size: content.len(),
+                content,
+            });
+        }
+        entries
+    }
+
+    // the deflated content behind its compressed resource header, if it is smaller
+    fn zip(&self, content: &[u8], order: ByteOrder, strings: &mut Strings) -> Option<Vec<u8>> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::best());
+        encoder.write_all(content).ok()?;
+        let deflated = encoder.finish().ok()?;
+        if COMPRESSED_HEADER_SIZE + deflated.len() >= content.len() {
+            return None;
+        }
+        let decompressor_off = strings.add(std::str::from_utf8(ZIP).ok()?);
+        let mut resource = Vec::with_capacity(COMPRESSED_HEADER_SIZE + deflated.len());
+        resource.extend_from_slice(&u32_bytes(HEADER_MAGIC, order));
+        resource.extend_from_slice(&u64_bytes(deflated.len() as u64, order));
+        resource.extend_from_slice(&u64_bytes(content.len() as u64, order));
+        resource.extend_from_slice(&u32_bytes(decompressor_off, order));
+        // no plugin configuration, and the last compressor of the chain
+        resource.extend_from_slice(&u32_bytes(u32::MAX, order));
+        resource.push(1);
+        resource.extend_from_slice(&deflated);
+        Some(resource)
+    }
+}
+
+fn u32_bytes(value: u32, order: ByteOrder) -> [u8; 4] {
+    match order {
+        ByteOrder::LittleEndian => value.to_le_bytes(),
+        ByteOrder::BigEndian => value.to_be_bytes(),
+    }
+}
+
+fn u64_bytes(value: u64, order: ByteOrder) -> [u8; 8] {
+    match order {
+        ByteOrder::LittleEndian => value.to_le_bytes(),
+        ByteOrder::BigEndian => value.to_be_bytes(),
+    }
+}
+
+// the kind in the upper five bits of the tag byte, the value length - 1 in the lower three,
+// then the value in big endian with as few bytes as possible
+fn push_attribute(locations: &mut Vec<u8>, kind: u8, value: u64) {
+    let len = (8 - value.leading_zeros() as usize / 8).max(1);
+    locations.push((kind << 3) | (len as u8 - 1));
+    locations.extend_from_slice(&value.to_be_bytes()[8 - len..]);
+}
+
+// PerfectHashBuilder: the names are put in buckets by their hash. A bucket with a single name
+// redirects to its slot as -1 - slot, the others to a seed that spreads their names over free
+// slots. Returns the redirect table and the slot of every name.
+fn perfect_hash(names: &[String]) -> (Vec<i32>, Vec<usize>) {
+    let len = names.len();
+    let mut buckets = vec![Vec::new(); len];
+    for (entry, name) in names.iter().enumerate() {
+        buckets[JImage::hash_seeded(HASH_MUL, name) as usize % len].push(entry);
+    }
+    let mut order = (0..len).collect::<Vec<_>>();
+    order.sort_by_key(|&bucket| std::cmp::Reverse(buckets[bucket].len()));
+
+    let mut redirect = vec![0i32; len];
+    let mut slots = vec![0usize; len];
+    let mut used = vec![false; len];
+    let mut free = 0;
+    for bucket in order {
+        match buckets[bucket][..] {
+            [] => {}
+            [entry] => {
+                while used[free] {
+                    free += 1;
+                }
+                used[free] = true;
+                slots[entry] = free;
+                redirect[bucket] = -1 - free as i32;
+            }
+            ref entries => {
+                let mut seed = 1;
+                let taken = loop {
+                    let taken = entries
+                        .iter()
+                        .map(|&entry| JImage::hash_seeded(seed, &names[entry]) as usize % len)
+                        .collect::<Vec<_>>();
+                    let distinct = taken.iter().collect::<BTreeSet<_>>().len() == taken.len();
+                    if distinct && taken.iter().all(|&slot| !used[slot]) {
+                        break taken;
+                    }
+                    seed += 1;
+                };
+                for (&entry, &slot) in entries.iter().zip(&taken) {
+                    used[slot] = true;
+                    slots[entry] = slot;
+                }
+                redirect[bucket] = seed as i32;
+            }
+        }
+    }
+    (redirect, slots)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open(name: &str, writer: &JImageWriter) -> JImage {
+        let path =
+            std::env::temp_dir().join(format!("jimage-writer-{}-{name}", std::process::id()));
+        writer.write_to(&path).unwrap();
+        JImage::new(&path).unwrap()
+    }
+
+    fn class_file(name: &str) -> Vec<u8> {
+        let mut bytes = vec![0xCA, 0xFE, 0xBA, 0xBE, 0, 0, 0, 69];
+        for _ in 0..20 {
+            bytes.extend_from_slice(name.as_bytes());
+        }
+        bytes
+    }
+
+    fn sample_writer() -> JImageWriter {
+        let mut writer = JImageWriter::new();
+        for name in ["java/lang/Object", "java/lang/String", "module-info"] {
+            writer.add("java.base", &format!("{name}.class"), class_file(name));
+        }
+        writer.add(
+            "java.base",
+            "META-INF/services/java.nio.Charset",
+            b"x".to_vec(),
+        );
+        writer.add(
+            "java.logging",
+            "java/util/logging/Logger.class",
+            class_file("Logger"),
+        );
+        writer
+    }
+
+    #[test]
+    fn round_trip_classes_and_packages() {
+        // given
+        let writer = sample_writer();
+
+        // when
+        let image = open("plain", &writer);
+
+        // then
+        assert_eq!(image.modules(), ["java.base", "java.logging"]);
+        assert_eq!(
+            image.packages(),
+            [
+                ("java.lang".to_string(), "java.base".to_string()),
+                ("java.util.logging".to_string(), "java.logging".to_string()),
+            ]
+        );
+        assert_eq!(
+            image
+                .open_class("java/util/logging/Logger")
+                .unwrap()
+                .unwrap(),
+            &class_file("Logger")[..]
+        );
+        assert_eq!(
+            image.open("/java.base/module-info.class").unwrap().unwrap(),
+            &class_file("module-info")[..]
+        );
+        assert_eq!(
+            image
+                .open("/java.base/META-INF/services/java.nio.Charset")
+                .unwrap()
+                .unwrap(),
+            &b"x"[..]
+        );
+        assert!(!image.contains_class("java/lang/Integer"));
+    }
+
+    #[test]
+    fn round_trip_zip_compressed_big_endian() {
+        // given
+        let writer = sample_writer()
+            .order(ByteOrder::BigEndian)
+            .compression(Compression::Zip);
+
+        // when
+        let image = open("zip", &writer);
+
+        // then
+        let object = image
+            .resources()
+            .find(|location| location.full_name() == "/java.base/java/lang/Object.class")
+            .unwrap();
+        assert_ne!(object.compressed_size, 0);
+        assert_eq!(
+            image.content(&object).unwrap(),
+            &class_file("java/lang/Object")[..]
+        );
+        // too small to get smaller
+        assert_eq!(
+            image
+                .open("/java.base/META-INF/services/java.nio.Charset")
+                .unwrap()
+                .unwrap(),
+            &b"x"[..]
+        );
+        assert_eq!(image.package_module("java/lang").unwrap(), "java.base");
+    }
+
+    #[test]
+    fn find_every_class_of_a_large_image() {
+        // given
+        let mut writer = JImageWriter::new();
+        for i in 0..2000 {
+            writer.add(
+                "java.base",
+                &format!("p{}/C{i}.class", i % 7),
+                i.to_string(),
+            );
+        }
+
+        // when
+        let image = open("large", &writer);
+
+        // then
+        assert_eq!(image.resources().count(), 2007);
+        for i in 0..2000 {
+            let name = format!("p{}/C{i}", i % 7);
+            assert_eq!(
+                image.open_class(&name).unwrap().unwrap(),
+                i.to_string().as_bytes()
+            );
+        }
+    }
+}
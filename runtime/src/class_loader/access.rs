@@ -0,0 +1,407 @@
+use crate::error::JvmError;
+use crate::heap::HeapRef;
+use crate::heap::method_area::MethodArea;
+use crate::keys::{ClassId, FieldKey, MethodId};
+use crate::rt::module::ModuleRef;
+use crate::thread::JavaThreadState;
+use crate::{VirtualMachine, throw_exception};
+use common::jtype::AllocationType;
+use jclass::flags::FieldFlags;
+
+// the public flag of classes, fields and methods
+const ACC_PUBLIC: i32 = 0x0001;
+
+impl VirtualMachine {
+    /// A class is accessible if it is public or in the runtime package of the accessor, JVMS 5.4.4.
+    /// Array classes are as accessible as their element class.
+    pub(crate) fn check_class_access(
+        &self,
+        accessor: ClassId,
+        class_id: ClassId,
+    ) -> Result<(), JvmError> {
+        let ma = self.method_area_read();
+        let mut target = class_id;
+        while let Some(element) = ma.get_class(&target).get_array_element_class_id() {
+            target = element;
+        }
+        if Self::is_same_package(&ma, accessor, target) {
+            return Ok(());
+        }
+        if ma.get_class(&target).get_raw_flags() & ACC_PUBLIC != 0 {
+            return match self.module_access_error(&ma, accessor, target) {
+                None => Ok(()),
+                Some(message) => throw_exception!(IllegalAccessError, message),
+            };
+        }
+        throw_exception!(
+            IllegalAccessError,
+            "failed to access class {} from class {} ({})",
+            self.external_name(&ma, target),
+            self.external_name(&ma, accessor),
+            self.describe_modules(&ma, target, accessor)
+        )
+    }
+
+    // HotSpot's Reflection::verify_class_access for a public class of another module, which has to
+    // be readable from the accessor's module and export its package to it
+    fn module_access_error(
+        &self,
+        ma: &MethodArea,
+        accessor: ClassId,
+        target: ClassId,
+    ) -> Option<String> {
+        let (Some(from), Some(to)) = (ma.module_of(&accessor), ma.module_of(&target)) else {
+            return None;
+        };
+        let modules = ma.modules();
+        let (accessor_name, target_name) = (
+            self.external_name(ma, accessor),
+            self.external_name(ma, target),
+        );
+        if !modules.can_read(from, to) {
+            let from = self.module_name(ma, from);
+            let to = self.module_name(ma, to);
+            return Some(format!(
+                "class {accessor_name} (in {from}) cannot access class {target_name} (in {to}) because {from} does not read {to}"
+            ));
+        }
+        let ModuleRef::Named(to_ref) = to else {
+            return None;
+        };
+        let package = ma.package_of(&target)?;
+        // the module's loader, a platform module's classes are defined by the bootstrap loader
+        if modules.is_exported_to(modules.get(&to_ref)?.loader, package, from) {
+            return None;
+        }
+        let package = self.interner().resolve(&package).replace('/', ".");
+        let from = self.module_name(ma, from);
+        let to = self.module_name(ma, to);
+        Some(format!(
+            "class {accessor_name} (in {from}) cannot access class {target_name} (in {to}) because {to} does not export {package} to {from}"
+        ))
+    }
+
+    // "module java.base", or "unnamed module @0x1b6d3586" with the identity hash of the Module
+    fn module_name(&self, ma: &MethodArea, module: ModuleRef) -> String {
+        match module {
+            ModuleRef::Named(module_ref) => match ma.modules().get(&module_ref) {
+                Some(module) => format!("module {}", self.interner().resolve(&module.name)),
+                None => "module <unknown>".to_string(),
+            },
+            ModuleRef::Unnamed { .. } => {
+                let module_ref = ma
+                    .module_object(module, &self.heap_read())
+                    .ok()
+                    .and_then(|module| module.as_nullable_obj_ref().ok().flatten())
+                    .unwrap_or_default();
+                format!("unnamed module @0x{:x}", module_ref as u32)
+            }
+        }
+    }
+
+    /// Checks the field `field_key` of `declaring_class`, found through the reference of
+    /// `accessor_id` to `resolved_class`.
+    pub(crate) fn check_field_access(
+        &self,
+        thread: &mut JavaThreadState,
+        accessor_id: &MethodId,
+        resolved_class: ClassId,
+        declaring_class: ClassId,
+        field_key: &FieldKey,
+        flags: FieldFlags,
+    ) -> Result<(), JvmError> {
+        let accessor = self.method_area_read().get_method(accessor_id).class_id();
+        let flags = MemberFlags(*flags.get_raw());
+        if self.is_member_accessible(thread, accessor, resolved_class, declaring_class, flags)? {
+            return Ok(());
+        }
+        let ma = self.method_area_read();
+        throw_exception!(
+            IllegalAccessError,
+            "class {} tried to access {}field {}.{} ({}){}",
+            self.external_name(&ma, accessor),
+            flags.modifiers(),
+            self.external_name(&ma, declaring_class),
+            self.interner().resolve(&field_key.name),
+            self.describe_modules(&ma, accessor, declaring_class),
+            self.private_access_errors(&ma, flags, accessor, declaring_class)
+        )
+    }
+
+    /// Checks `method_id`, found through the reference of `accessor_id` to `resolved_class`.
+    pub(crate) fn check_method_access(
+        &self,
+        thread: &mut JavaThreadState,
+        accessor_id: &MethodId,
+        resolved_class: ClassId,
+        method_id: MethodId,
+    ) -> Result<(), JvmError> {
+        let (accessor, declaring_class, flags) = {
+            let ma = self.method_area_read();
+            let method = ma.get_method(&method_id);
+            (
+                ma.get_method(accessor_id).class_id(),
+                method.class_id(),
+                MemberFlags(*method.flags().get_raw()),
+            )
+        };
+        if self.is_member_accessible(thread, accessor, resolved_class, declaring_class, flags)? {
+            return Ok(());
+        }
+        let ma = self.method_area_read();
+        let method = ma.get_method(&method_id);
+        let signature = ma
+            .get_method_descriptor(&method.descriptor_id())
+            .to_java_signature(
+                self.interner()
+                    .resolve(&ma.get_class(&declaring_class).get_name()),
+                self.interner().resolve(&method.name),
+            );
+        throw_exception!(
+            IllegalAccessError,
+            "class {} tried to access {}{}method '{}' ({})",
+            self.external_name(&ma, accessor),
+            if method.is_abstract() {
+                "abstract "
+            } else {
+                ""
+            },
+            flags.modifiers(),
+            signature,
+            self.describe_modules(&ma, accessor, declaring_class)
+        )
+    }
+
+    // HotSpot's Reflection::verify_member_access, the protected case included: an instance member
+    // has to be reached through the accessor's own hierarchy
+    fn is_member_accessible(
+        &self,
+        thread: &mut JavaThreadState,
+        accessor: ClassId,
+        resolved_class: ClassId,
+        declaring_class: ClassId,
+        flags: MemberFlags,
+    ) -> Result<bool, JvmError> {
+        if accessor == declaring_class || flags.is_public() {
+            return Ok(true);
+        }
+        {
+            let ma = self.method_area_read();
+            let is_subclass = |class: ClassId, of: ClassId| ma.is_assignable_from(of, class);
+            if flags.is_protected()
+                && !ma.get_class(&accessor).is_interface()
+                && is_subclass(accessor, declaring_class)
+                && (flags.is_static()
+                    || accessor == resolved_class
+                    || declaring_class == resolved_class
+                    || is_subclass(resolved_class, accessor)
+                    || is_subclass(accessor, resolved_class))
+            {
+                return Ok(true);
+            }
+            if !flags.is_private() {
+                return Ok(Self::is_same_package(&ma, accessor, declaring_class));
+            }
+        }
+        Ok(self.nest_host(thread, accessor)? == self.nest_host(thread, declaring_class)?)
+    }
+
+    /// The class named by the NestHost attribute if it is in the same runtime package and lists
+    /// `class_id` as a member, otherwise the class itself, JVMS 5.4.4.
+    pub(crate) fn nest_host(
+        &self,
+        thread: &mut JavaThreadState,
+        class_id: ClassId,
+    ) -> Result<ClassId, JvmError> {
+        let (host_name, loader) = {
+            let ma = self.method_area_read();
+            let class = ma.get_class_like(&class_id)?;
+            if let Some(host) = class.get_nest_host() {
+                return Ok(host);
+            }
+            (class.get_nest_host_name(), class.get_loader())
+        };
+        // like HotSpot, a nest host that fails to load or to validate leaves the class on its own
+        // and the reason is kept for the messages
+        let (host, error) = match host_name {
+            None => (class_id, None),
+            Some(host_name) => match self.load_class_with(thread, loader, host_name) {
+                Ok(host) => match self.validate_nest_host(class_id, host) {
+                    Ok(()) => (host, None),
+                    Err(error) => (class_id, Some(error)),
+                },
+                Err(JvmError::JavaException(e)) => {
+                    let ma = self.method_area_read();
+                    let error = format!(
+                        "Nest host resolution of {} with host {} failed: {} {}",
+                        self.external_name(&ma, class_id),
+                        self.interner().resolve(&host_name).replace('/', "."),
+                        e.kind.class_name_dot(),
+                        e.message
+                            .map(|message| message.into_resolved(self.interner()))
+                            .unwrap_or_default()
+                    );
+                    (class_id, Some(error))
+                }
+                Err(e) => return Err(e),
+            },
+        };
+        let ma = self.method_area_read();
+        let class = ma.get_class_like(&class_id)?;
+        class.set_nest_host(host, error);
+        // another thread may have validated the host first
+        Ok(class.get_nest_host().unwrap_or(host))
+    }
+
+    fn validate_nest_host(&self, class_id: ClassId, host: ClassId) -> Result<(), String> {
+        let ma = self.method_area_read();
+        let reason = if !Self::is_same_package(&ma, host, class_id) {
+            "types are in different packages"
+        } else if !ma.get_class_like(&host).is_ok_and(|host_class| {
+            host_class.has_nest_member_named(ma.get_class(&class_id).get_name())
+        }) {
+            "current type is not listed as a nest member"
+        } else {
+            return Ok(());
+        };
+        Err(format!(
+            "Type {} (loader: {}) is not a nest member of type {} (loader: {}): {reason}",
+            self.external_name(&ma, class_id),
+            self.loader_description(&ma, class_id),
+            self.external_name(&ma, host),
+            self.loader_description(&ma, host)
+        ))
+    }
+
+    // HotSpot's print_nest_host_error_on, why the nest hosts of a private access were rejected
+    fn private_access_errors(
+        &self,
+        ma: &MethodArea,
+        flags: MemberFlags,
+        first: ClassId,
+        second: ClassId,
+    ) -> String {
+        if !flags.is_private() {
+            return String::new();
+        }
+        let errors = [first, second]
+            .iter()
+            .filter_map(|class_id| {
+                ma.get_class_like(class_id)
+                    .ok()?
+                    .get_nest_host_error()
+                    .map(str::to_string)
+            })
+            .collect::<Vec<_>>();
+        if errors.is_empty() {
+            String::new()
+        } else {
+            format!(", ({})", errors.join(", "))
+        }
+    }
+
+    // a runtime package is a package name together with the defining loader, JVMS 5.3
+    pub(crate) fn is_same_package(ma: &MethodArea, a: ClassId, b: ClassId) -> bool {
+        let (a, b) = (ma.get_class(&a), ma.get_class(&b));
+        let package = |name| {
+            let name = ma.interner().resolve(&name);
+            name.rfind('/').map_or("", |slash| &name[..slash])
+        };
+        a.get_loader() == b.get_loader() && package(a.get_name()) == package(b.get_name())
+    }
+
+    fn external_name(&self, ma: &MethodArea, class_id: ClassId) -> String {
+        ma.external_name(&class_id)
+    }
+
+    // the module part of HotSpot's messages, e.g. "A and B are in unnamed module of loader 'app'";
+    // the boot loader only serves java.base for now
+    fn describe_modules(&self, ma: &MethodArea, first: ClassId, second: ClassId) -> String {
+        let module_of_loader = |class_id: ClassId| {
+            let module = match ma.module_of(&class_id) {
+                Some(ModuleRef::Named(module_ref)) => {
+                    self.module_name(ma, ModuleRef::Named(module_ref))
+                }
+                Some(ModuleRef::Unnamed { .. }) => "unnamed module".to_string(),
+                // the bootstrap loader's classes until java.base is defined
+                None => "module java.base".to_string(),
+            };
+            format!(
+                "{module} of loader {}",
+                self.loader_description(ma, class_id)
+            )
+        };
+        let (first_name, second_name) = (
+            self.external_name(ma, first),
+            self.external_name(ma, second),
+        );
+        if ma.module_of(&first) == ma.module_of(&second) {
+            format!(
+                "{first_name} and {second_name} are in {}",
+                module_of_loader(first)
+            )
+        } else {
+            format!(
+                "{first_name} is in {}; {second_name} is in {}",
+                module_of_loader(first),
+                module_of_loader(second)
+            )
+        }
+    }
+
+    fn loader_description(&self, ma: &MethodArea, class_id: ClassId) -> String {
+        match ma.get_class(&class_id).get_loader() {
+            None => "'bootstrap'".to_string(),
+            Some(loader) => self.loader_name(ma, loader),
+        }
+    }
+
+    // ClassLoader.nameAndId, like 'app' or the class name and identity hash of an unnamed loader
+    pub(crate) fn loader_name(&self, ma: &MethodArea, loader: HeapRef) -> String {
+        let heap = self.heap_read();
+        let name_and_id = heap.get_class_id(loader).ok().and_then(|loader_class_id| {
+            let offset = ma
+                .get_instance_field(&loader_class_id, &self.br().class_loader_name_and_id_fk)
+                .ok()?
+                .offset;
+            let value = heap
+                .read_field(loader, offset, AllocationType::Reference)
+                .ok()?;
+            heap.get_rust_string_from_java_string(value.as_nullable_obj_ref().ok()??)
+                .ok()
+        });
+        name_and_id.unwrap_or_else(|| "<unknown>".to_string())
+    }
+}
+
+// the access flags fields and methods have in common, JVMS tables 4.5-A and 4.6-A
+#[derive(Clone, Copy)]
+struct MemberFlags(u16);
+
+impl MemberFlags {
+    fn is_public(self) -> bool {
+        self.0 & 0x0001 != 0
+    }
+
+    fn is_private(self) -> bool {
+        self.0 & 0x0002 != 0
+    }
+
+    fn is_protected(self) -> bool {
+        self.0 & 0x0004 != 0
+    }
+
+    fn is_static(self) -> bool {
+        self.0 & 0x0008 != 0
+    }
+
+    fn modifiers(self) -> &'static str {
+        if self.is_protected() {
+            "protected "
+        } else if self.is_private() {
+            "private "
+        } else {
+            ""
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use common::utils::cursor::{ByteCursor, ByteOrder};
+use jimage::JImage;
+use memmap2::Mmap;
+use std::fs::File;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+// "LGCA", the version, the image key and the class count, then an index of (name offset,
+// name length, data offset, data length) sorted by name, then the names and the parsed class
+// files in jclass' archived encoding; a change to that encoding needs a new version
+const MAGIC: u32 = 0x4C47_4341;
+const VERSION: u32 = 2;
+const HEADER_SIZE: usize = 32;
+const INDEX_ENTRY_SIZE: usize = 16;
+
+/// The runtime image an archive was dumped from: the checksum of its index, with the size and
+/// modification time of the file for a change that keeps every resource size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct ImageKey {
+    checksum: u32,
+    len: u64,
+    modified: u64,
+}
+
+impl ImageKey {
+    pub fn of(path: &Path, jimage: &JImage) -> std::io::Result<Self> {
+        let metadata = std::fs::metadata(path)?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        Ok(Self {
+            checksum: jimage.index_checksum(),
+            len: metadata.len(),
+            modified,
+        })
+    }
+}
+
+/// CDS in miniature: the boot classes earlier runs loaded, parsed already, in one file that is
+/// mapped and looked up instead of the runtime image.
+pub(super) struct ClassArchive {
+    mmap: Mmap,
+    count: usize,
+}
+
+impl ClassArchive {
+    /// The archive at `path`, if there is one dumped from the image `key`.
+    pub fn open(path: &Path, key: ImageKey) -> Option<Self> {
+        let file = File::open(path).ok()?;
+        // like the image, an archive is replaced by a new file rather than changed
+        let mmap = unsafe { Mmap::map(&file).ok()? };
+        let mut cur = ByteCursor::with_order(&mmap, ByteOrder::LittleEndian);
+        if cur.u32().ok()? != MAGIC || cur.u32().ok()? != VERSION {
+            return None;
+        }
+        let archived = ImageKey {
+            checksum: cur.u32().ok()?,
+            len: cur.u64().ok()?,
+            modified: cur.u64().ok()?,
+        };
+        let count = cur.u32().ok()? as usize;
+        if archived != key || HEADER_SIZE + count * INDEX_ENTRY_SIZE > mmap.len() {
+            return None;
+        }
+        Some(Self { mmap, count })
+    }
+
+    // the name and the archived class file of the entry `i` of the index
+    fn entry(&self, i: usize) -> Option<(&[u8], &[u8])> {
+        let mut cur = ByteCursor::with_order(
+            &self.mmap[HEADER_SIZE + i * INDEX_ENTRY_SIZE..],
+            ByteOrder::LittleEndian,
+        );
+        let mut slice = || -> Option<&[u8]> {
+            let offset = cur.u32().ok()? as usize;
+            let len = cur.u32().ok()? as usize;
+            self.mmap.get(offset..offset + len)
+        };
+        Some((slice()?, slice()?))
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (&[u8], &[u8])> {
+        (0..self.count).filter_map(|i| self.entry(i))
+    }
+
+    /// The archived class file of `name`, a slice of the mapping to decode with
+    /// `ClassFile::from_archived`.
+    pub fn get(&self, name: &str) -> Option<&[u8]> {
+        let (mut low, mut high) = (0, self.count);
+        while low < high {
+            let mid = (low + high) / 2;
+            let (entry_name, class_file) = self.entry(mid)?;
+            match entry_name.cmp(name.as_bytes()) {
+                std::cmp::Ordering::Less => low = mid + 1,
+                std::cmp::Ordering::Greater => high = mid,
+                std::cmp::Ordering::Equal => return Some(class_file),
+            }
+        }
+        None
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Writes the archive of `classes`, names with archived class files, for the image `key`. It
+    /// goes through a temporary file, so runs sharing the archive never map half of one.
+    pub fn dump(
+        path: &Path,
+        key: ImageKey,
+        mut classes: Vec<(Vec<u8>, Vec<u8>)>,
+    ) -> std::io::Result<()> {
+        classes.sort_by(|(a, _), (b, _)| a.cmp(b));
+        classes.dedup_by(|(a, _), (b, _)| a == b);
+        let u32_of = |value: usize| {
+            u32::try_from(value)
+                .map_err(|_| std::io::Error::other("class archive larger than 4 GiB"))
+        };
+
+        let mut out = Vec::new();
+        out.extend_from_slice(&MAGIC.to_le_bytes());
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        out.extend_from_slice(&key.checksum.to_le_bytes());
+        out.extend_from_slice(&key.len.to_le_bytes());
+        out.extend_from_slice(&key.modified.to_le_bytes());
+        out.extend_from_slice(&u32_of(classes.len())?.to_le_bytes());
+        let mut offset = HEADER_SIZE + classes.len() * INDEX_ENTRY_SIZE;
+        for (name, class_file) in &classes {
+            for len in [name.len(), class_file.len()] {
+                out.extend_from_slice(&u32_of(offset)?.to_le_bytes());
+                out.extend_from_slice(&u32_of(len)?.to_le_bytes());
+                offset += len;
+            }
+        }
+        for (name, class_file) in &classes {
+            out.extend_from_slice(name);
+            out.extend_from_slice(class_file);
+        }
+
+        let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+        std::fs::write(&tmp, &out)?;
+        std::fs::rename(&tmp, path)
+    }
+}
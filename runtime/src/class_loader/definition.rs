@@ -0,0 +1,123 @@
+use crate::build_exception;
+use crate::error::JvmError;
+use common::error::{ClassFormatErr, LinkageError};
+use jclass::ClassFile;
+
+// class file versions of Java 1.1 up to the JDK the VM runs with
+const MIN_MAJOR_VERSION: u16 = 45;
+const MAX_MAJOR_VERSION: u16 = 69;
+const PREVIEW_MINOR_VERSION: u16 = 0xFFFF;
+// Java 12, minor versions other than 0 and the preview one became invalid
+const JAVA_12_VERSION: u16 = 56;
+
+/// Parses `bytes` and checks the class file version, with HotSpot's errors. `name` is the internal
+/// name the class is expected to have, for the messages.
+pub(crate) fn parse_class_file(bytes: Vec<u8>, name: &str) -> Result<ClassFile, JvmError> {
+    let cf = ClassFile::try_from(bytes).map_err(|e| class_format_error(e, name))?;
+    check_version(&cf, name)?;
+    Ok(cf)
+}
+
+pub(crate) fn class_format_error(err: ClassFormatErr, name: &str) -> JvmError {
+    match err {
+        ClassFormatErr::WrongMagic(magic) => build_exception!(
+            ClassFormatError,
+            "Incompatible magic value {magic} in class file {name}"
+        ),
+        ClassFormatErr::Cursor(_) => build_exception!(ClassFormatError, "Truncated class file"),
+        ClassFormatErr::TrailingBytes => build_exception!(
+            ClassFormatError,
+            "Extra bytes at the end of class file {name}"
+        ),
+        ClassFormatErr::UnknownTag(tag) => build_exception!(
+            ClassFormatError,
+            "Unknown constant tag {tag} in class file {name}"
+        ),
+        ClassFormatErr::ConstantNotFound(idx) => build_exception!(
+            ClassFormatError,
+            "Invalid constant pool index {idx} in class file {name}"
+        ),
+        err => build_exception!(ClassFormatError, "{err} in class file {name}"),
+    }
+}
+
+/// Maps what went wrong while creating the runtime class of `name` to the Java error HotSpot
+/// throws for it. Errors that are not about the class file itself are returned as they are.
+pub(crate) fn linking_error(err: JvmError, name: &str) -> JvmError {
+    let linkage = match err {
+        JvmError::Linkage(linkage) => linkage,
+        JvmError::Cursor(_) => return build_exception!(ClassFormatError, "Truncated class file"),
+        JvmError::RuntimePool(e) => {
+            return build_exception!(ClassFormatError, "{e:?} in class file {name}");
+        }
+        JvmError::TypeDescriptorErr(e) => {
+            return build_exception!(ClassFormatError, "{e} in class file {name}");
+        }
+        err => return err,
+    };
+    let duplicated = |attribute: &str| {
+        build_exception!(
+            ClassFormatError,
+            "Multiple {attribute} attributes in class file {name}"
+        )
+    };
+    match linkage {
+        LinkageError::ClassFile(e) => class_format_error(e, name),
+        LinkageError::Cursor(_) => build_exception!(ClassFormatError, "Truncated class file"),
+        LinkageError::UnsupportedOpCode(opcode) => build_exception!(
+            VerifyError,
+            "Bad instruction: {opcode:#04x} in class file {name}"
+        ),
+        LinkageError::Instruction(e) => build_exception!(VerifyError, "{e} in class file {name}"),
+        LinkageError::DuplicatedCodeAttr => duplicated("Code"),
+        LinkageError::DuplicatedSignatureAttr => duplicated("Signature"),
+        LinkageError::DuplicatedStackMapTable => duplicated("StackMapTable"),
+        LinkageError::DuplicatedExceptionAttribute => duplicated("Exceptions"),
+        LinkageError::DuplicatedRuntimeVisibleAnnotationsAttr => {
+            duplicated("RuntimeVisibleAnnotations")
+        }
+        LinkageError::DuplicatedRuntimeInvisibleAnnotationsAttr => {
+            duplicated("RuntimeInvisibleAnnotations")
+        }
+        LinkageError::CodeAttrIsAmbiguousForNative => build_exception!(
+            ClassFormatError,
+            "Code attribute in native or abstract methods in class file {name}"
+        ),
+        e => build_exception!(ClassFormatError, "{e:?} in class file {name}"),
+    }
+}
+
+fn check_version(cf: &ClassFile, name: &str) -> Result<(), JvmError> {
+    let (major, minor) = (cf.major_version, cf.minor_version);
+    if major > MAX_MAJOR_VERSION {
+        return Err(build_exception!(
+            UnsupportedClassVersionError,
+            "{name} has been compiled by a more recent version of the Java Runtime (class file version {major}.{minor}), this version of the Java Runtime only recognizes class file versions up to {MAX_MAJOR_VERSION}.0"
+        ));
+    }
+    if major < MIN_MAJOR_VERSION {
+        return Err(build_exception!(
+            UnsupportedClassVersionError,
+            "{name} (class file version {major}.{minor}) was compiled with an invalid major version"
+        ));
+    }
+    if major < JAVA_12_VERSION || minor == 0 {
+        return Ok(());
+    }
+    if minor != PREVIEW_MINOR_VERSION {
+        return Err(build_exception!(
+            UnsupportedClassVersionError,
+            "{name} (class file version {major}.{minor}) was compiled with an invalid non-zero minor version"
+        ));
+    }
+    if major != MAX_MAJOR_VERSION {
+        return Err(build_exception!(
+            UnsupportedClassVersionError,
+            "{name} (class file version {major}.{minor}) was compiled with preview features that are unsupported. This version of the Java Runtime only recognizes preview features for class file version {MAX_MAJOR_VERSION}.{PREVIEW_MINOR_VERSION}"
+        ));
+    }
+    Err(build_exception!(
+        UnsupportedClassVersionError,
+        "Preview features are not enabled for {name} (class file version {major}.{minor}). Try running with '--enable-preview'"
+    ))
+}
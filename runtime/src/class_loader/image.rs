@@ -0,0 +1,81 @@
+use crate::error::JvmError;
+use jimage::JImage;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Where the classes of the JDK modules come from: the `lib/modules` image of a JDK, or the
+/// `modules` directory of an exploded build, with a `<module>/<class>.class` tree per module.
+pub(super) enum RuntimeImage {
+    JImage(JImage),
+    Exploded {
+        root: PathBuf,
+        // package in internal form to the module that has its classes
+        packages: HashMap<String, String>,
+    },
+}
+
+impl RuntimeImage {
+    pub fn exploded(root: &Path) -> Self {
+        let mut packages = HashMap::new();
+        let classes = WalkDir::new(root)
+            .min_depth(2)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| {
+                entry.file_type().is_file()
+                    && entry.path().extension().is_some_and(|ext| ext == "class")
+            });
+        for class in classes {
+            let Ok(rel) = class.path().strip_prefix(root) else {
+                continue;
+            };
+            let mut components = rel
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect::<Vec<_>>();
+            // module-info.class sits right in the module directory
+            if components.len() < 3 {
+                continue;
+            }
+            components.pop();
+            let module = components.remove(0);
+            packages.entry(components.join("/")).or_insert(module);
+        }
+        Self::Exploded {
+            root: root.to_path_buf(),
+            packages,
+        }
+    }
+
+    /// The module with classes in `package`, given in internal form like `java/lang`.
+    pub fn package_module(&self, package: &str) -> Option<String> {
+        match self {
+            RuntimeImage::JImage(jimage) => jimage.package_module(package),
+            RuntimeImage::Exploded { packages, .. } => packages.get(package).cloned(),
+        }
+    }
+
+    pub fn open_class(&self, name: &str) -> Result<Option<Vec<u8>>, JvmError> {
+        match self {
+            RuntimeImage::JImage(jimage) => Ok(jimage.open_class(name)?.map(|c| c.into_owned())),
+            RuntimeImage::Exploded { root, .. } => Ok(self
+                .exploded_class_path(root, name)
+                .and_then(|path| std::fs::read(path).ok())),
+        }
+    }
+
+    pub fn contains_class(&self, name: &str) -> bool {
+        match self {
+            RuntimeImage::JImage(jimage) => jimage.contains_class(name),
+            RuntimeImage::Exploded { root, .. } => self
+                .exploded_class_path(root, name)
+                .is_some_and(|path| path.is_file()),
+        }
+    }
+
+    fn exploded_class_path(&self, root: &Path, name: &str) -> Option<PathBuf> {
+        let module = self.package_module(&name[..name.rfind('/')?])?;
+        Some(root.join(module).join(format!("{name}.class")))
+    }
+}
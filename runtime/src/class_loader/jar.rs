@@ -0,0 +1,518 @@
+use byteorder::{ByteOrder, LittleEndian};
+use flate2::read::DeflateDecoder;
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+// https://pkware.cachefly.net/webdocs/casestudies/APPNOTE.TXT
+const LOCAL_HEADER_SIG: u32 = 0x0403_4b50;
+const CENTRAL_HEADER_SIG: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIR_SIG: u32 = 0x0605_4b50;
+const LOCAL_HEADER_LEN: usize = 30;
+const CENTRAL_HEADER_LEN: usize = 46;
+const END_OF_CENTRAL_DIR_LEN: usize = 22;
+
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATED: u16 = 8;
+
+const MANIFEST_NAME: &str = "META-INF/MANIFEST.MF";
+const VERSIONS_PREFIX: &str = "META-INF/versions/";
+
+#[derive(Debug, Clone, Copy)]
+struct ZipEntry {
+    method: u16,
+    compressed_size: usize,
+    uncompressed_size: usize,
+    local_header_offset: usize,
+}
+
+/// A JAR (or any ZIP) file, read into memory once. Only stored and deflated entries are
+/// supported, which is all `jar` and the build tools produce.
+#[derive(Debug)]
+pub struct JarFile {
+    path: PathBuf,
+    data: Vec<u8>,
+    entries: HashMap<String, ZipEntry>,
+}
+
+impl JarFile {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let data = std::fs::read(&path)?;
+        let entries = Self::read_central_directory(&data)?;
+        Ok(Self {
+            path,
+            data,
+            entries,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(String::as_str)
+    }
+
+    /// Uncompressed content of the entry `name`, `None` if there is no such entry.
+    pub fn read(&self, name: &str) -> io::Result<Option<Vec<u8>>> {
+        let Some(entry) = self.entries.get(name) else {
+            return Ok(None);
+        };
+        let header = self.slice(entry.local_header_offset, LOCAL_HEADER_LEN)?;
+        if LittleEndian::read_u32(header) != LOCAL_HEADER_SIG {
+            return Err(invalid_data("bad local file header signature"));
+        }
+        let name_len = LittleEndian::read_u16(&header[26..]) as usize;
+        let extra_len = LittleEndian::read_u16(&header[28..]) as usize;
+        let start = entry.local_header_offset + LOCAL_HEADER_LEN + name_len + extra_len;
+        let compressed = self.slice(start, entry.compressed_size)?;
+
+        let bytes = match entry.method {
+            METHOD_STORED => compressed.to_vec(),
+            METHOD_DEFLATED => {
+                let mut bytes = Vec::with_capacity(entry.uncompressed_size);
+                DeflateDecoder::new(compressed).read_to_end(&mut bytes)?;
+                bytes
+            }
+            method => {
+                return Err(invalid_data(&format!(
+                    "unsupported compression method {method} for {name}"
+                )));
+            }
+        };
+        if bytes.len() != entry.uncompressed_size {
+            return Err(invalid_data(&format!("size mismatch for {name}")));
+        }
+        Ok(Some(bytes))
+    }
+
+    pub fn manifest(&self) -> io::Result<Option<Manifest>> {
+        Ok(self
+            .read(MANIFEST_NAME)?
+            .map(|bytes| Manifest::parse(&String::from_utf8_lossy(&bytes))))
+    }
+
+    /// Entry names of the classes visible on the given Java release, keyed by class name
+    /// (`com/example/Foo`). For a multi-release JAR, the entry under the highest
+    /// `META-INF/versions/N/` with `N <= release` wins over the root one.
+    pub fn class_entries(&self, release: u32) -> io::Result<HashMap<String, String>> {
+        let multi_release = self
+            .manifest()?
+            .and_then(|manifest| manifest.get("Multi-Release").map(str::to_string))
+            .is_some_and(|value| value.eq_ignore_ascii_case("true"));
+
+        let mut classes = HashMap::new();
+        let mut versions = HashMap::new();
+        for name in self.names() {
+            let Some(class_name) = name.strip_suffix(".class") else {
+                continue;
+            };
+            match class_name.strip_prefix(VERSIONS_PREFIX) {
+                None => {
+                    classes
+                        .entry(class_name.to_string())
+                        .or_insert_with(|| name.to_string());
+                }
+                Some(versioned) if multi_release => {
+                    let Some((version, class_name)) = versioned.split_once('/') else {
+                        continue;
+                    };
+                    let Ok(version) = version.parse::<u32>() else {
+                        continue;
+                    };
+                    if version <= release {
+                        let best = versions.entry(class_name.to_string()).or_insert((0, ""));
+                        if version > best.0 {
+                            *best = (version, name);
+                        }
+                    }
+                }
+                Some(_) => {}
+            }
+        }
+        for (class_name, (_, name)) in versions {
+            classes.insert(class_name, name.to_string());
+        }
+        Ok(classes)
+    }
+
+    fn read_central_directory(data: &[u8]) -> io::Result<HashMap<String, ZipEntry>> {
+        if data.len() < END_OF_CENTRAL_DIR_LEN {
+            return Err(invalid_data("not a ZIP file"));
+        }
+        // the end of central directory record is followed by a comment of up to 64 KiB
+        let last = data.len() - END_OF_CENTRAL_DIR_LEN;
+        let eocd = (last.saturating_sub(u16::MAX as usize)..=last)
+            .rev()
+            .find(|pos| LittleEndian::read_u32(&data[*pos..]) == END_OF_CENTRAL_DIR_SIG)
+            .ok_or_else(|| invalid_data("end of central directory not found"))?;
+        let entry_count = LittleEndian::read_u16(&data[eocd + 10..]) as usize;
+        let mut pos = LittleEndian::read_u32(&data[eocd + 16..]) as usize;
+        if entry_count == u16::MAX as usize || pos == u32::MAX as usize {
+            return Err(invalid_data("ZIP64 archives are not supported"));
+        }
+
+        let mut entries = HashMap::with_capacity(entry_count);
+        for _ in 0..entry_count {
+            let header = data
+                .get(pos..pos + CENTRAL_HEADER_LEN)
+                .ok_or_else(|| invalid_data("truncated central directory"))?;
+            if LittleEndian::read_u32(header) != CENTRAL_HEADER_SIG {
+                return Err(invalid_data("bad central directory header signature"));
+            }
+            let name_len = LittleEndian::read_u16(&header[28..]) as usize;
+            let extra_len = LittleEndian::read_u16(&header[30..]) as usize;
+            let comment_len = LittleEndian::read_u16(&header[32..]) as usize;
+            let name_start = pos + CENTRAL_HEADER_LEN;
+            let name = data
+                .get(name_start..name_start + name_len)
+                .ok_or_else(|| invalid_data("truncated central directory"))?;
+            let name = String::from_utf8_lossy(name).into_owned();
+            if !name.ends_with('/') {
+                entries.insert(
+                    name,
+                    ZipEntry {
+                        method: LittleEndian::read_u16(&header[10..]),
+                        compressed_size: LittleEndian::read_u32(&header[20..]) as usize,
+                        uncompressed_size: LittleEndian::read_u32(&header[24..]) as usize,
+                        local_header_offset: LittleEndian::read_u32(&header[42..]) as usize,
+                    },
+                );
+            }
+            pos = name_start + name_len + extra_len + comment_len;
+        }
+        Ok(entries)
+    }
+
+    fn slice(&self, start: usize, len: usize) -> io::Result<&[u8]> {
+        self.data
+            .get(start..start + len)
+            .ok_or_else(|| invalid_data("entry out of bounds"))
+    }
+}
+
+/// The main section of a JAR manifest.
+///
+/// https://docs.oracle.com/en/java/javase/25/docs/specs/jar/jar.html#jar-manifest
+#[derive(Debug, Default)]
+pub struct Manifest {
+    main_attributes: HashMap<String, String>,
+}
+
+impl Manifest {
+    pub fn parse(content: &str) -> Self {
+        let mut main_attributes = HashMap::new();
+        let mut current: Option<(String, String)> = None;
+        for line in content.lines() {
+            // a line starting with a single space continues the previous value
+            if let Some(continuation) = line.strip_prefix(' ') {
+                if let Some((_, value)) = current.as_mut() {
+                    value.push_str(continuation);
+                }
+                continue;
+            }
+            if let Some((name, value)) = current.take() {
+                main_attributes.insert(name.to_ascii_lowercase(), value);
+            }
+            // the main section ends at the first blank line
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                current = Some((name.trim().to_string(), value.trim_start().to_string()));
+            }
+        }
+        if let Some((name, value)) = current {
+            main_attributes.insert(name.to_ascii_lowercase(), value);
+        }
+        Self { main_attributes }
+    }
+
+    /// Attribute names are case-insensitive.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.main_attributes
+            .get(&name.to_ascii_lowercase())
+            .map(String::as_str)
+    }
+
+    pub fn main_class(&self) -> Option<&str> {
+        self.get("Main-Class")
+    }
+
+    /// `Class-Path` entries resolved against the directory of the JAR, in order.
+    pub fn class_path(&self, jar_path: &Path) -> Vec<PathBuf> {
+        let base = jar_path.parent().unwrap_or(Path::new(""));
+        self.get("Class-Path")
+            .map(|class_path| {
+                class_path
+                    .split_whitespace()
+                    .map(|entry| base.join(entry))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byteorder::WriteBytesExt;
+    use flate2::Compression;
+    use flate2::write::DeflateEncoder;
+    use std::io::Write;
+
+    // a ZIP file holding `entries` (name, method, content), CRCs are left 0 as they aren't checked
+    fn zip_bytes(entries: &[(&str, u16, &[u8])]) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut central = Vec::new();
+        for (name, method, content) in entries {
+            let stored = match *method {
+                METHOD_DEFLATED => {
+                    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+                    encoder.write_all(content).unwrap();
+                    encoder.finish().unwrap()
+                }
+                _ => content.to_vec(),
+            };
+            let local_header_offset = data.len() as u32;
+            data.write_u32::<LittleEndian>(LOCAL_HEADER_SIG).unwrap();
+            for field in [20, 0, *method, 0, 0] {
+                data.write_u16::<LittleEndian>(field).unwrap();
+            }
+            for field in [0, stored.len() as u32, content.len() as u32] {
+                data.write_u32::<LittleEndian>(field).unwrap();
+            }
+            data.write_u16::<LittleEndian>(name.len() as u16).unwrap();
+            data.write_u16::<LittleEndian>(0).unwrap();
+            data.extend_from_slice(name.as_bytes());
+            data.extend_from_slice(&stored);
+
+            central
+                .write_u32::<LittleEndian>(CENTRAL_HEADER_SIG)
+                .unwrap();
+            for field in [20, 20, 0, *method, 0, 0] {
+                central.write_u16::<LittleEndian>(field).unwrap();
+            }
+            for field in [0, stored.len() as u32, content.len() as u32] {
+                central.write_u32::<LittleEndian>(field).unwrap();
+            }
+            for field in [name.len() as u16, 0, 0, 0, 0] {
+                central.write_u16::<LittleEndian>(field).unwrap();
+            }
+            central.write_u32::<LittleEndian>(0).unwrap();
+            central
+                .write_u32::<LittleEndian>(local_header_offset)
+                .unwrap();
+            central.extend_from_slice(name.as_bytes());
+        }
+        let central_offset = data.len() as u32;
+        data.extend_from_slice(&central);
+        data.write_u32::<LittleEndian>(END_OF_CENTRAL_DIR_SIG)
+            .unwrap();
+        for field in [0, 0, entries.len() as u16, entries.len() as u16] {
+            data.write_u16::<LittleEndian>(field).unwrap();
+        }
+        data.write_u32::<LittleEndian>(central.len() as u32)
+            .unwrap();
+        data.write_u32::<LittleEndian>(central_offset).unwrap();
+        data.write_u16::<LittleEndian>(0).unwrap();
+        data
+    }
+
+    fn jar_file(name: &str, bytes: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("jar-{}-{name}.jar", std::process::id()));
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    fn open_error(name: &str, bytes: &[u8]) -> io::Error {
+        JarFile::open(jar_file(name, bytes)).unwrap_err()
+    }
+
+    #[test]
+    fn read_stored_and_deflated_entries() {
+        // given
+        let content = b"class file bytes, class file bytes, class file bytes".as_slice();
+        let path = jar_file(
+            "methods",
+            &zip_bytes(&[
+                ("a/Stored.class", METHOD_STORED, content),
+                ("a/Deflated.class", METHOD_DEFLATED, content),
+                ("a/", METHOD_STORED, b""),
+            ]),
+        );
+
+        // when
+        let jar = JarFile::open(&path).unwrap();
+
+        // then
+        assert_eq!(jar.read("a/Stored.class").unwrap().unwrap(), content);
+        assert_eq!(jar.read("a/Deflated.class").unwrap().unwrap(), content);
+        assert!(jar.read("a/Missing.class").unwrap().is_none());
+        let mut names = jar.names().collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["a/Deflated.class", "a/Stored.class"]);
+    }
+
+    #[test]
+    fn reject_unsupported_compression_method() {
+        // given
+        let path = jar_file("bzip2", &zip_bytes(&[("A.class", 12, b"bytes")]));
+        let jar = JarFile::open(&path).unwrap();
+
+        // when
+        let error = jar.read("A.class").unwrap_err();
+
+        // then
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            error.to_string(),
+            "unsupported compression method 12 for A.class"
+        );
+    }
+
+    #[test]
+    fn multi_release_entries_by_release() {
+        // given
+        let manifest = b"Manifest-Version: 1.0\r\nMulti-Release: true\r\n\r\n".as_slice();
+        let path = jar_file(
+            "multi-release",
+            &zip_bytes(&[
+                (MANIFEST_NAME, METHOD_STORED, manifest),
+                ("a/A.class", METHOD_STORED, b"root"),
+                ("META-INF/versions/9/a/A.class", METHOD_STORED, b"9"),
+                ("META-INF/versions/11/a/A.class", METHOD_STORED, b"11"),
+                ("META-INF/versions/17/a/B.class", METHOD_STORED, b"17"),
+                (
+                    "META-INF/versions/x/a/A.class",
+                    METHOD_STORED,
+                    b"bad version",
+                ),
+            ]),
+        );
+        let jar = JarFile::open(&path).unwrap();
+
+        for (release, a, b) in [
+            (8, "a/A.class", None),
+            (9, "META-INF/versions/9/a/A.class", None),
+            (10, "META-INF/versions/9/a/A.class", None),
+            (11, "META-INF/versions/11/a/A.class", None),
+            (
+                25,
+                "META-INF/versions/11/a/A.class",
+                Some("META-INF/versions/17/a/B.class"),
+            ),
+        ] {
+            // when
+            let classes = jar.class_entries(release).unwrap();
+
+            // then
+            assert_eq!(classes.get("a/A").map(String::as_str), Some(a), "{release}");
+            assert_eq!(classes.get("a/B").map(String::as_str), b, "{release}");
+            assert_eq!(classes.len(), 1 + b.iter().count(), "{release}");
+        }
+    }
+
+    #[test]
+    fn versioned_entries_are_ignored_without_multi_release() {
+        // given
+        let path = jar_file(
+            "single-release",
+            &zip_bytes(&[
+                ("a/A.class", METHOD_STORED, b"root"),
+                ("META-INF/versions/9/a/A.class", METHOD_STORED, b"9"),
+            ]),
+        );
+        let jar = JarFile::open(&path).unwrap();
+
+        // when
+        let classes = jar.class_entries(25).unwrap();
+
+        // then
+        assert_eq!(classes.len(), 1);
+        assert_eq!(classes["a/A"], "a/A.class");
+    }
+
+    #[test]
+    fn manifest_continuation_lines() {
+        // given
+        let content = "Manifest-Version: 1.0\r\n\
+            Main-Class: com.example.very.long.package.na\r\n me.Main\r\n\
+            Class-Path: lib/first.jar\r\n  lib/second.jar\r\n\
+            \r\n\
+            Name: com/example/\r\n\
+            Class-Path: ignored.jar\r\n";
+
+        // when
+        let manifest = Manifest::parse(content);
+
+        // then
+        assert_eq!(
+            manifest.main_class(),
+            Some("com.example.very.long.package.name.Main")
+        );
+        assert_eq!(
+            manifest.get("class-path"),
+            Some("lib/first.jar lib/second.jar")
+        );
+        assert_eq!(
+            manifest.class_path(Path::new("/app/app.jar")),
+            [
+                PathBuf::from("/app/lib/first.jar"),
+                PathBuf::from("/app/lib/second.jar")
+            ]
+        );
+        assert_eq!(manifest.get("Name"), None);
+    }
+
+    #[test]
+    fn reject_missing_end_of_central_directory() {
+        // given
+        let bytes = zip_bytes(&[("A.class", METHOD_STORED, b"bytes")]);
+
+        // when
+        let error = open_error("no-eocd", &bytes[..bytes.len() - 4]);
+
+        // then
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "end of central directory not found");
+    }
+
+    #[test]
+    fn reject_truncated_central_directory() {
+        // given
+        let mut bytes = zip_bytes(&[("A.class", METHOD_STORED, b"bytes")]);
+        // one more entry than the central directory holds, the last header runs past the end
+        let eocd = bytes.len() - END_OF_CENTRAL_DIR_LEN;
+        bytes[eocd + 10] = 2;
+
+        // when
+        let error = open_error("truncated", &bytes);
+
+        // then
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "truncated central directory");
+    }
+
+    #[test]
+    fn reject_corrupt_central_directory() {
+        // given
+        let mut bytes = zip_bytes(&[("A.class", METHOD_STORED, b"bytes")]);
+        let eocd = bytes.len() - END_OF_CENTRAL_DIR_LEN;
+        let central_offset = LittleEndian::read_u32(&bytes[eocd + 16..]) as usize;
+        bytes[central_offset] = 0;
+
+        // when
+        let error = open_error("corrupt", &bytes);
+
+        // then
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(error.to_string(), "bad central directory header signature");
+    }
+}
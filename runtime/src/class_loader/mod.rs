@@ -1,25 +1,51 @@
+use crate::class_loader::archive::{ClassArchive, ImageKey};
+use crate::class_loader::definition::{class_format_error, parse_class_file};
+use crate::class_loader::image::RuntimeImage;
 use crate::class_loader::system::SystemClassLoader;
 use crate::error::JvmError;
-use crate::{VmConfig, debug_log};
+use crate::{VmConfig, build_exception, debug_log};
+use jclass::ClassFile;
 use jimage::JImage;
 use std::path::PathBuf;
 //use toml::Value;
 //use toml_edit::Document;
 
+mod access;
+mod archive;
+pub(crate) mod definition;
+mod image;
+pub mod jar;
+mod resolution;
 mod system;
 
 // TODO: It is more like a stub for now, need to respect the doc
 
 #[derive(Debug, Clone)]
-struct ClassSource {
-    jmod_path: PathBuf,
-    entry_name: String,
+enum ClassSource {
+    Directory { root: PathBuf, entry_name: String },
+    // index into the loader's open JARs
+    Jar { jar: usize, entry_name: String },
+}
+
+/// A class the bootstrap loader found.
+pub enum ClassData {
+    /// The class file, still to be parsed.
+    Bytes(Vec<u8>),
+    /// Decoded from the class archive, it was parsed and checked before it was dumped.
+    Archived(ClassFile),
 }
 
 /// https://docs.oracle.com/javase/specs/jvms/se25/html/jvms-5.html#jvms-5.3.1
 
 pub struct ClassLoader {
-    jimage: JImage,
+    image: RuntimeImage,
+    // `--patch-module`, a module with the class path whose classes replace its own
+    patches: Vec<(String, SystemClassLoader)>,
+    // where the class archive goes and the image it is for, unless patches make it moot
+    archive_target: Option<(PathBuf, ImageKey)>,
+    archive: Option<ClassArchive>,
+    // `-Xbootclasspath/a:`, searched after the image
+    boot_append: SystemClassLoader,
     system: SystemClassLoader,
     //fixtures_path: PathBuf,
 }
@@ -27,35 +53,184 @@ pub struct ClassLoader {
 impl ClassLoader {
     pub fn new(vm_config: &VmConfig) -> Result<Self, JvmError> {
         debug_log!("Creating ClassLoader...");
-        let modules_path = &vm_config.home.join("lib").join("modules");
-        debug_log!("Loading JImage from path: {:?}", modules_path);
-        let jimage = JImage::new(modules_path);
+        let modules_path = vm_config.home.join("lib").join("modules");
+        let image = match &vm_config.exploded_modules {
+            Some(modules_path) => {
+                debug_log!("Indexing exploded modules from path: {:?}", modules_path);
+                RuntimeImage::exploded(modules_path)
+            }
+            None => {
+                debug_log!("Loading JImage from path: {:?}", modules_path);
+                RuntimeImage::JImage(JImage::new(&modules_path)?)
+            }
+        };
+        let patches = vm_config
+            .patch_modules
+            .iter()
+            .map(|(module, path)| {
+                debug_log!("Patching module {module} with: {path:?}");
+                Ok((
+                    module.clone(),
+                    SystemClassLoader::new(path, vm_config.feature_version())?,
+                ))
+            })
+            .collect::<Result<Vec<_>, JvmError>>()?;
+        let archive_target = match (&vm_config.class_archive, &image) {
+            (Some(path), RuntimeImage::JImage(jimage)) if patches.is_empty() => {
+                ImageKey::of(&modules_path, jimage)
+                    .ok()
+                    .map(|key| (path.clone(), key))
+            }
+            _ => None,
+        };
+        let archive = archive_target
+            .as_ref()
+            .and_then(|(path, key)| ClassArchive::open(path, *key));
+        debug_log!(
+            "Class archive {:?} is {}",
+            vm_config.class_archive,
+            if archive.is_some() {
+                "in use"
+            } else {
+                "not in use"
+            }
+        );
+        debug_log!(
+            "Appending to the boot class path: {:?}",
+            vm_config.boot_class_path_append
+        );
+        let boot_append = SystemClassLoader::new(
+            &vm_config.boot_class_path_append,
+            vm_config.feature_version(),
+        )?;
         debug_log!(
             "Loading SystemClassLoader from classpath: {:?}",
             vm_config.class_path
         );
-        let system_loader = SystemClassLoader::new(&vm_config.class_path)?;
+        let system_loader =
+            SystemClassLoader::new(&vm_config.class_path, vm_config.feature_version())?;
 
         //let fixtures_path = PathBuf::from("javap/tests/testdata/fixtures.toml");
 
         Ok(Self {
-            jimage,
+            image,
+            patches,
+            archive_target,
+            archive,
+            boot_append,
             system: system_loader,
             //fixtures_path,
         })
     }
 
+    /// The runtime image and the appended boot class path first, then the class path. Serves
+    /// both until the app class loader is up.
     #[hotpath::measure]
-    pub fn load(&self, name: &str) -> Result<Vec<u8>, JvmError> {
-        if let Some(bytes) = self.jimage.open_java_base_class(name) {
-            debug_log!("Bytecode of \"{name}\" found using JImage.");
+    pub fn load(&self, name: &str) -> Result<ClassData, JvmError> {
+        if let Some(data) = self.find_boot_class(name)? {
             //self.add_tested_class(name)?;
-            Ok(bytes.to_vec())
+            Ok(data)
         } else {
-            let bytes = self.system.find_class(name)?;
-            debug_log!("Bytecode of \"{name}\" found using SystemClassLoader.");
-            Ok(bytes)
+            self.load_from_class_path(name).map(ClassData::Bytes)
+        }
+    }
+
+    /// Any class of the runtime image, the bootstrap loader stands in for the platform loader.
+    pub fn has_boot_class(&self, name: &str) -> bool {
+        self.patch_of(name).is_some()
+            || self
+                .archive
+                .as_ref()
+                .is_some_and(|archive| archive.contains(name))
+            || self.image.contains_class(name)
+            || self.boot_append.contains(name)
+    }
+
+    pub fn load_boot(&self, name: &str) -> Result<ClassData, JvmError> {
+        self.find_boot_class(name)?
+            .ok_or_else(|| build_exception!(ClassNotFoundException, name.replace('/', ".")))
+    }
+
+    fn find_boot_class(&self, name: &str) -> Result<Option<ClassData>, JvmError> {
+        if let Some(patch) = self.patch_of(name) {
+            debug_log!("Bytecode of \"{name}\" found in --patch-module.");
+            return patch
+                .find_class(name)
+                .map(|bytes| Some(ClassData::Bytes(bytes)));
+        }
+        if let Some(archived) = self.archive.as_ref().and_then(|archive| archive.get(name)) {
+            debug_log!("Parsed \"{name}\" found in the class archive.");
+            // decoded straight from the mapping
+            return ClassFile::from_archived(archived)
+                .map(|cf| Some(ClassData::Archived(cf)))
+                .map_err(|e| class_format_error(e, name));
+        }
+        if let Some(bytes) = self.image.open_class(name)? {
+            debug_log!("Bytecode of \"{name}\" found in the runtime image.");
+            return Ok(Some(ClassData::Bytes(bytes)));
+        }
+        if self.boot_append.contains(name) {
+            debug_log!("Bytecode of \"{name}\" found in -Xbootclasspath/a.");
+            return self
+                .boot_append
+                .find_class(name)
+                .map(|bytes| Some(ClassData::Bytes(bytes)));
+        }
+        Ok(None)
+    }
+
+    /// Dumps the class archive with the boot classes `names` along with the ones it had, unless
+    /// they were all in it already.
+    pub fn dump_archive<'a>(&self, names: impl Iterator<Item = &'a str>) -> std::io::Result<()> {
+        let Some((path, key)) = &self.archive_target else {
+            return Ok(());
+        };
+        let archive = self.archive.as_ref();
+        let mut classes = names
+            .filter(|name| !archive.is_some_and(|archive| archive.contains(name)))
+            .filter_map(|name| {
+                // the class path stands in for the app class loader during startup
+                let bytes = self.image.open_class(name).ok()??;
+                let cf = parse_class_file(bytes, name).ok()?;
+                Some((name.as_bytes().to_vec(), cf.to_archived()))
+            })
+            .collect::<Vec<_>>();
+        if classes.is_empty() {
+            return Ok(());
+        }
+        debug_log!(
+            "Dumping {} new classes to class archive {path:?}",
+            classes.len()
+        );
+        classes.extend(
+            archive
+                .into_iter()
+                .flat_map(ClassArchive::entries)
+                .map(|(name, class_file)| (name.to_vec(), class_file.to_vec())),
+        );
+        ClassArchive::dump(path, *key, classes)
+    }
+
+    // the patch of the module with the package of `name` that has the class; a package the image
+    // does not know may be one a patch adds to its module
+    fn patch_of(&self, name: &str) -> Option<&SystemClassLoader> {
+        if self.patches.is_empty() {
+            return None;
         }
+        let module = name
+            .rfind('/')
+            .and_then(|i| self.image.package_module(&name[..i]));
+        self.patches
+            .iter()
+            .filter(|(patched, _)| module.as_ref().is_none_or(|module| module == patched))
+            .map(|(_, patch)| patch)
+            .find(|patch| patch.contains(name))
+    }
+
+    pub fn load_from_class_path(&self, name: &str) -> Result<Vec<u8>, JvmError> {
+        let bytes = self.system.find_class(name)?;
+        debug_log!("Bytecode of \"{name}\" found using SystemClassLoader.");
+        Ok(bytes)
     }
 
     /*
@@ -89,3 +264,82 @@ impl ClassLoader {
     }
      */
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byteorder::{BigEndian, WriteBytesExt};
+    use jimage::JImageWriter;
+
+    // `public class <name>` with no members, compiled for Java 8
+    fn class_bytes(name: &str) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.write_u32::<BigEndian>(0xCAFEBABE).unwrap();
+        bytes.write_u16::<BigEndian>(0).unwrap();
+        bytes.write_u16::<BigEndian>(52).unwrap();
+        bytes.write_u16::<BigEndian>(5).unwrap();
+        for (index, utf8) in [(1, name), (3, "java/lang/Object")] {
+            bytes.push(1);
+            bytes.write_u16::<BigEndian>(utf8.len() as u16).unwrap();
+            bytes.extend_from_slice(utf8.as_bytes());
+            bytes.push(7);
+            bytes.write_u16::<BigEndian>(index).unwrap();
+        }
+        // public super, this class, super class, then no interfaces, fields, methods or attributes
+        for field in [0x21, 2, 4, 0, 0, 0, 0] {
+            bytes.write_u16::<BigEndian>(field).unwrap();
+        }
+        bytes
+    }
+
+    fn vm_config(home: PathBuf, class_archive: PathBuf) -> VmConfig {
+        VmConfig {
+            home,
+            version: "25".to_string(),
+            main_class: String::new(),
+            class_path: Vec::new(),
+            boot_class_path_append: Vec::new(),
+            initial_heap_size: 0,
+            max_heap_size: 0,
+            frame_stack_size: 0,
+            jdwp_port: None,
+            scheduler: Default::default(),
+            deadlock_watchdog: None,
+            verify: Default::default(),
+            patch_modules: Vec::new(),
+            exploded_modules: None,
+            class_archive: Some(class_archive),
+        }
+    }
+
+    #[test]
+    fn dumped_boot_classes_are_loaded_from_the_archive() {
+        // given a runtime image with classes and no archive yet
+        let home =
+            std::env::temp_dir().join(format!("class-loader-test-{}-archive", std::process::id()));
+        std::fs::create_dir_all(home.join("lib")).unwrap();
+        let mut writer = JImageWriter::new();
+        for name in ["java/lang/Sample", "java/lang/Other"] {
+            writer.add("java.base", &format!("{name}.class"), class_bytes(name));
+        }
+        writer.write_to(home.join("lib").join("modules")).unwrap();
+        let config = vm_config(home.clone(), home.join("classes.jsa"));
+
+        // when the first run loads the class and dumps the archive
+        let first = ClassLoader::new(&config).unwrap();
+        let first_data = first.load_boot("java/lang/Sample").unwrap();
+        first
+            .dump_archive(std::iter::once("java/lang/Sample"))
+            .unwrap();
+        let second = ClassLoader::new(&config).unwrap();
+        let second_data = second.load_boot("java/lang/Sample").unwrap();
+        std::fs::remove_dir_all(&home).unwrap();
+
+        // then the first run parses the image bytes and the second does not
+        assert!(matches!(first_data, ClassData::Bytes(_)));
+        let ClassData::Archived(cf) = second_data else {
+            panic!("java/lang/Sample was not read from the archive");
+        };
+        assert_eq!(cf.this_class, 2);
+    }
+}
@@ -0,0 +1,217 @@
+use crate::class_loader::definition::{class_format_error, parse_class_file};
+use crate::error::JvmError;
+use crate::heap::HeapRef;
+use crate::interpreter::Interpreter;
+use crate::keys::{ClassId, MethodId, Symbol};
+use crate::thread::JavaThreadState;
+use crate::vm::Value;
+use crate::{VirtualMachine, build_exception, throw_exception};
+use jclass::ClassFile;
+
+impl VirtualMachine {
+    /// Class `name_sym` as `loader` sees it, `None` being the bootstrap loader. The bootstrap and
+    /// app class loaders are run by the method area, a user-defined one through its `loadClass`.
+    pub(crate) fn load_class_with(
+        &self,
+        thread: &mut JavaThreadState,
+        loader: Option<HeapRef>,
+        name_sym: Symbol,
+    ) -> Result<ClassId, JvmError> {
+        let (loaded, app_class_loader) = {
+            let ma = self.method_area_read();
+            (
+                ma.find_loaded_class(loader, name_sym),
+                ma.app_class_loader(),
+            )
+        };
+        if let Some(class_id) = loaded {
+            return Ok(class_id);
+        }
+        let Some(loader_ref) = loader.filter(|_| loader != app_class_loader) else {
+            return self
+                .method_area_write()
+                .get_class_id_or_load_with(loader, name_sym, thread.id);
+        };
+
+        let name = self.interner().resolve(&name_sym);
+        if let Some(element) = name.strip_prefix('[') {
+            // only the element class goes through the loader, array classes are made by the VM
+            let element = element
+                .strip_prefix('L')
+                .and_then(|element| element.strip_suffix(';'))
+                .or(element.starts_with('[').then_some(element));
+            if let Some(element) = element {
+                let element_sym = self.interner().get_or_intern(element);
+                self.load_class_with(thread, loader, element_sym)?;
+            }
+            return self
+                .method_area_write()
+                .get_class_id_or_load_with(loader, name_sym, thread.id);
+        }
+
+        let class_id = self.invoke_load_class(thread, loader_ref, name_sym)?;
+        self.method_area_write()
+            .record_initiating_loader(loader, name_sym, class_id);
+        Ok(class_id)
+    }
+
+    /// Resolves a class reference of the method `accessor_id`, with its class' defining loader,
+    /// and checks that the class is accessible to it.
+    pub(crate) fn resolve_class(
+        &self,
+        thread: &mut JavaThreadState,
+        accessor_id: &MethodId,
+        name_sym: Symbol,
+    ) -> Result<ClassId, JvmError> {
+        let (accessor, loader) = {
+            let ma = self.method_area_read();
+            let accessor = ma.get_method(accessor_id).class_id();
+            let accessor_class = ma.get_class(&accessor);
+            // a hidden class can only be found through itself
+            if accessor_class.get_name() == name_sym {
+                return Ok(accessor);
+            }
+            (accessor, accessor_class.get_loader())
+        };
+        let class_id = self
+            .load_class_with(thread, loader, name_sym)
+            .map_err(|e| self.not_found_as_no_class_def(e, name_sym))?;
+        self.check_class_access(accessor, class_id)?;
+        Ok(class_id)
+    }
+
+    /// Backs ClassLoader.defineClassN. `expected_name` is the binary name the caller passed, if
+    /// any.
+    pub(crate) fn define_class(
+        &self,
+        thread: &mut JavaThreadState,
+        loader: Option<HeapRef>,
+        expected_name: Option<&str>,
+        bytes: Vec<u8>,
+    ) -> Result<ClassId, JvmError> {
+        let expected_name = expected_name.map(|name| name.replace('.', "/"));
+        let name_hint = expected_name.as_deref().unwrap_or("<Unknown>");
+        let cf = parse_class_file(bytes, name_hint)?;
+        let name = cf
+            .cp
+            .get_class_name(&cf.this_class)
+            .map_err(|e| class_format_error(e, name_hint))?;
+        if expected_name.is_some() && name_hint != name {
+            throw_exception!(NoClassDefFoundError, "{name_hint} (wrong name: {name})")?
+        }
+        self.resolve_supertypes(thread, loader, &cf, name)?;
+        self.method_area_write().define_class(loader, cf, thread.id)
+    }
+
+    /// Backs Lookup.defineHiddenClass through ClassLoader.defineClass0. `loader` defines the class
+    /// but never finds it by name.
+    pub(crate) fn define_hidden_class(
+        &self,
+        thread: &mut JavaThreadState,
+        loader: Option<HeapRef>,
+        bytes: Vec<u8>,
+    ) -> Result<ClassId, JvmError> {
+        let cf = parse_class_file(bytes, "<Unknown>")?;
+        let name = cf
+            .cp
+            .get_class_name(&cf.this_class)
+            .map_err(|e| class_format_error(e, "<Unknown>"))?;
+        self.resolve_supertypes(thread, loader, &cf, name)?;
+        self.method_area_write()
+            .define_hidden_class(loader, cf, thread.id)
+    }
+
+    // the method area can't call into Java, so the superclass and the interfaces are resolved
+    // through the loader first
+    fn resolve_supertypes(
+        &self,
+        thread: &mut JavaThreadState,
+        loader: Option<HeapRef>,
+        cf: &ClassFile,
+        name: &str,
+    ) -> Result<(), JvmError> {
+        let class_format_error = |e| class_format_error(e, name);
+        let mut supertypes = Vec::with_capacity(cf.interfaces.len() + 1);
+        if let Some(super_name) = cf.get_super_class_name() {
+            supertypes.push(super_name.map_err(class_format_error)?);
+        }
+        for interface in &cf.interfaces {
+            supertypes.push(
+                cf.cp
+                    .get_class_name(interface)
+                    .map_err(class_format_error)?,
+            );
+        }
+        let name_sym = self.interner().get_or_intern(name);
+        self.method_area_write()
+            .begin_definition(loader, name_sym, thread.id)?;
+        let resolved = supertypes.into_iter().try_for_each(|supertype| {
+            let supertype_sym = self.interner().get_or_intern(supertype);
+            self.load_class_with(thread, loader, supertype_sym)
+                .map(|_| ())
+                .map_err(|e| self.not_found_as_no_class_def(e, supertype_sym))
+        });
+        self.method_area_write()
+            .end_definition(loader, name_sym, thread.id);
+        resolved
+    }
+
+    // JvmError::not_found_as_no_class_def, for a ClassNotFoundException a user-defined loader threw
+    // as well
+    fn not_found_as_no_class_def(&self, error: JvmError, name_sym: Symbol) -> JvmError {
+        let name = self.interner().resolve(&name_sym);
+        if let JvmError::JavaExceptionThrown(exception) = &error {
+            let is_not_found = self
+                .heap_read()
+                .get_class_id(*exception)
+                .is_ok_and(|class_id| {
+                    self.method_area_read().instance_of(
+                        class_id,
+                        None,
+                        self.br().java_lang_class_not_found_exception_sym,
+                    )
+                });
+            if is_not_found {
+                return build_exception!(NoClassDefFoundError, name.to_string());
+            }
+        }
+        error.not_found_as_no_class_def(name)
+    }
+
+    fn invoke_load_class(
+        &self,
+        thread: &mut JavaThreadState,
+        loader: HeapRef,
+        name_sym: Symbol,
+    ) -> Result<ClassId, JvmError> {
+        let name = self.interner().resolve(&name_sym);
+        let name_ref = self.heap_write().alloc_string(&name.replace('/', "."))?;
+        let load_class_method_id = {
+            let loader_class_id = self.heap_read().get_class_id(loader)?;
+            self.method_area_read()
+                .get_class(&loader_class_id)
+                .get_vtable_method_id(&self.br().class_loader_load_class_mk)?
+        };
+        let mirror = Interpreter::invoke_instance_method(
+            thread,
+            load_class_method_id,
+            self,
+            vec![Value::Ref(loader), Value::Ref(name_ref)],
+        )?
+        .map(|value| value.as_nullable_obj_ref())
+        .transpose()?
+        .flatten()
+        .ok_or_else(|| build_exception!(NoClassDefFoundError, name.to_string()))?;
+
+        let class_id = self.method_area_read().get_class_id_by_mirror(&mirror)?;
+        let loaded_name = self.method_area_read().get_class(&class_id).get_name();
+        if loaded_name != name_sym {
+            throw_exception!(
+                NoClassDefFoundError,
+                "{name} (wrong name: {})",
+                self.interner().resolve(&loaded_name)
+            )?
+        }
+        Ok(class_id)
+    }
+}
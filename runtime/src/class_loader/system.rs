@@ -1,67 +1,135 @@
 use crate::class_loader::ClassSource;
+use crate::class_loader::jar::JarFile;
 use crate::error::JvmError;
 use crate::{build_exception, debug_log};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::Read;
 use std::path::{Path, PathBuf};
+use tracing_log::log::debug;
 use walkdir::WalkDir;
 
 #[derive(Debug)]
 pub(super) struct SystemClassLoader {
     index: HashMap<String, ClassSource>,
+    jars: Vec<JarFile>,
 }
 
 impl SystemClassLoader {
-    pub fn new(path: &Vec<String>) -> Result<Self, JvmError> {
+    /// `release` is the Java feature version used to pick classes from multi-release JARs.
+    pub fn new(path: &Vec<String>, release: u32) -> Result<Self, JvmError> {
         debug_log!("Creating SystemClassLoader from classpath entries: {path:?}");
-        let mut index = HashMap::new();
+        let mut loader = Self {
+            index: HashMap::new(),
+            jars: Vec::new(),
+        };
+        let mut visited = HashSet::new();
 
         for entry in path {
-            let files_and_folders = WalkDir::new(entry);
-            let files_and_folders = files_and_folders.into_iter().collect::<Vec<_>>();
-            let java_classes: Vec<_> = files_and_folders
-                .into_iter()
-                .filter_map(Result::ok)
-                .map(|e| e.into_path())
-                .filter(|path| {
-                    path.is_file() && path.extension().map(|ext| ext == "class").unwrap_or(false)
-                })
-                .collect();
-            for class in java_classes {
-                let rel = class.strip_prefix(entry).unwrap_or(&class);
-                let rel_str = Self::path_to_forward_slash(rel);
-                if let Some(key) = Self::binary_name_from_rel(&rel_str) {
-                    index.entry(key).or_insert_with(|| ClassSource {
-                        jmod_path: PathBuf::from(entry),
-                        entry_name: rel.to_string_lossy().into_owned(),
-                    });
-                }
+            let entry = PathBuf::from(entry);
+            if entry.is_file() {
+                loader.add_jar(&entry, release, &mut visited);
+            } else {
+                loader.add_directory(&entry);
             }
         }
 
         debug_log!(
             "System classpath index prepared. Found {} classes.",
-            index.len()
+            loader.index.len()
         );
-        Ok(Self { index })
+        Ok(loader)
+    }
+
+    fn add_directory(&mut self, root: &Path) {
+        let java_classes: Vec<_> = WalkDir::new(root)
+            .into_iter()
+            .filter_map(Result::ok)
+            .map(|e| e.into_path())
+            .filter(|path| {
+                path.is_file() && path.extension().map(|ext| ext == "class").unwrap_or(false)
+            })
+            .collect();
+        for class in java_classes {
+            let rel = class.strip_prefix(root).unwrap_or(&class);
+            let rel_str = Self::path_to_forward_slash(rel);
+            if let Some(key) = Self::binary_name_from_rel(&rel_str) {
+                self.index
+                    .entry(key)
+                    .or_insert_with(|| ClassSource::Directory {
+                        root: root.to_path_buf(),
+                        entry_name: rel.to_string_lossy().into_owned(),
+                    });
+            }
+        }
+    }
+
+    // the JAR's own classes come first, then the ones from its manifest Class-Path, like
+    // URLClassPath does; unreadable JARs are skipped the same way
+    fn add_jar(&mut self, path: &Path, release: u32, visited: &mut HashSet<PathBuf>) {
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        if !visited.insert(canonical) {
+            return;
+        }
+        let jar = match JarFile::open(path) {
+            Ok(jar) => jar,
+            Err(e) => {
+                debug!("Skipping classpath entry {path:?}: {e}");
+                return;
+            }
+        };
+        let (classes, manifest) = match (jar.class_entries(release), jar.manifest()) {
+            (Ok(classes), Ok(manifest)) => (classes, manifest),
+            (Err(e), _) | (_, Err(e)) => {
+                debug!("Skipping classpath entry {path:?}: {e}");
+                return;
+            }
+        };
+
+        let jar_index = self.jars.len();
+        self.jars.push(jar);
+        for (key, entry_name) in classes {
+            self.index.entry(key).or_insert(ClassSource::Jar {
+                jar: jar_index,
+                entry_name,
+            });
+        }
+
+        for dependency in manifest
+            .map(|manifest| manifest.class_path(path))
+            .unwrap_or_default()
+        {
+            if dependency.is_file() {
+                self.add_jar(&dependency, release, visited);
+            } else {
+                self.add_directory(&dependency);
+            }
+        }
+    }
+
+    pub(crate) fn contains(&self, name: &str) -> bool {
+        self.index.contains_key(&Self::normalize_key(name))
     }
 
     #[hotpath::measure]
     pub(crate) fn find_class(&self, name: &str) -> Result<Vec<u8>, JvmError> {
         let key = Self::normalize_key(name);
-        let src = self
-            .index
-            .get(&key)
-            .ok_or_else(|| build_exception!(ClassNotFoundException, name.replace('/', ".")))?;
+        let not_found = || build_exception!(ClassNotFoundException, name.replace('/', "."));
+        let src = self.index.get(&key).ok_or_else(not_found)?;
 
-        let abs_path = src.jmod_path.join(&src.entry_name);
-        let mut file = File::open(&abs_path)
-            .map_err(|_| build_exception!(ClassNotFoundException, name.replace('/', ".")))?;
-        let mut buf = Vec::new();
-        file.read_to_end(&mut buf)
-            .map_err(|_| build_exception!(ClassNotFoundException, name.replace('/', ".")))?;
-        Ok(buf)
+        match src {
+            ClassSource::Directory { root, entry_name } => {
+                let mut file = File::open(root.join(entry_name)).map_err(|_| not_found())?;
+                let mut buf = Vec::new();
+                file.read_to_end(&mut buf).map_err(|_| not_found())?;
+                Ok(buf)
+            }
+            ClassSource::Jar { jar, entry_name } => self.jars[*jar]
+                .read(entry_name)
+                .ok()
+                .flatten()
+                .ok_or_else(not_found),
+        }
     }
 
     fn path_to_forward_slash(p: &Path) -> String {
@@ -4,6 +4,7 @@ use crate::rt::constant_pool::RuntimeConstantType;
 use common::descriptor::MethodDescriptor;
 use common::error::{InstructionErr, LinkageError, RuntimePoolError, TypeDescriptorErr};
 use common::utils::cursor::CursorError;
+use jimage::JImageError;
 use lasso::ThreadedRodeo;
 use std::fmt::Display;
 
@@ -35,6 +36,13 @@ pub enum JvmError {
     Todo(String),
     NotAJavaInstanceTodo(String),
     JavaException(JavaExceptionFromJvm),
+    JImage(JImageError),
+}
+
+impl From<JImageError> for JvmError {
+    fn from(value: JImageError) -> Self {
+        JvmError::JImage(value)
+    }
 }
 
 impl From<CursorError> for JvmError {
@@ -97,9 +105,24 @@ impl JvmError {
                 }
                 result
             }
+            JvmError::JImage(e) => e.to_string(),
             _ => format!("{:?}", self),
         }
     }
+
+    /// A class that another one depends on could not be found, which is a `NoClassDefFoundError`
+    /// naming it rather than a `ClassNotFoundException` (JVMS 5.3).
+    pub fn not_found_as_no_class_def(self, name: &str) -> Self {
+        match self {
+            JvmError::JavaException(e) if e.kind == JavaExceptionKind::ClassNotFoundException => {
+                JvmError::JavaException(JavaExceptionFromJvm::with_message(
+                    JavaExceptionKind::NoClassDefFoundError,
+                    name,
+                ))
+            }
+            e => e,
+        }
+    }
 }
 
 pub struct JavaExceptionReference {
@@ -160,6 +183,18 @@ pub enum JavaExceptionKind {
     IncompatibleClassChangeError,
     ClassFormatError,
     IOException,
+    IllegalMonitorStateException,
+    InterruptedException,
+    IllegalArgumentException,
+    NoClassDefFoundError,
+    LinkageError,
+    SecurityException,
+    UnsupportedClassVersionError,
+    ClassCircularityError,
+    VerifyError,
+    IllegalAccessError,
+    AbstractMethodError,
+    IllegalStateException,
 }
 
 impl JavaExceptionKind {
@@ -178,6 +213,18 @@ impl JavaExceptionKind {
             Self::IncompatibleClassChangeError => "java/lang/IncompatibleClassChangeError",
             Self::ClassFormatError => "java/lang/ClassFormatError",
             Self::IOException => "java/io/IOException",
+            Self::IllegalMonitorStateException => "java/lang/IllegalMonitorStateException",
+            Self::InterruptedException => "java/lang/InterruptedException",
+            Self::IllegalArgumentException => "java/lang/IllegalArgumentException",
+            Self::NoClassDefFoundError => "java/lang/NoClassDefFoundError",
+            Self::LinkageError => "java/lang/LinkageError",
+            Self::SecurityException => "java/lang/SecurityException",
+            Self::UnsupportedClassVersionError => "java/lang/UnsupportedClassVersionError",
+            Self::ClassCircularityError => "java/lang/ClassCircularityError",
+            Self::VerifyError => "java/lang/VerifyError",
+            Self::IllegalAccessError => "java/lang/IllegalAccessError",
+            Self::AbstractMethodError => "java/lang/AbstractMethodError",
+            Self::IllegalStateException => "java/lang/IllegalStateException",
         }
     }
 
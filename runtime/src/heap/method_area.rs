@@ -1,4 +1,5 @@
-use crate::class_loader::ClassLoader;
+use crate::class_loader::definition::{class_format_error, linking_error, parse_class_file};
+use crate::class_loader::{ClassData, ClassLoader};
 use crate::error::JvmError;
 use crate::heap::{Heap, HeapRef};
 use crate::jdwp::{ClassPrepareInfo, ClassStatus, DebugEvent, DebugState, TypeTag};
@@ -12,24 +13,38 @@ use crate::rt::constant_pool::RuntimeConstantPool;
 use crate::rt::field::InstanceField;
 use crate::rt::interface::InterfaceClass;
 use crate::rt::method::Method;
+use crate::rt::module::{ModuleRef, ModuleTable};
 use crate::rt::{ClassLike, JvmClass, PrimitiveClass};
 use crate::vm::Value;
 use crate::vm::bootstrap_registry::BootstrapRegistry;
-use crate::{MethodId, Symbol, VmConfig, debug_log, throw_exception};
+use crate::{MethodId, Symbol, VmConfig, build_exception, debug_log, throw_exception};
 use common::descriptor::MethodDescriptor;
-use common::error::{LinkageError, MethodDescriptorErr};
+use common::error::MethodDescriptorErr;
 use common::jtype::{AllocationType, JavaType, PrimitiveType};
 use jclass::ClassFile;
-use lasso::{Spur, ThreadedRodeo};
+use jclass::constant_pool::ConstantEntry;
+use lasso::ThreadedRodeo;
 use once_cell::sync::OnceCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, RwLock};
 
 pub struct MethodArea {
     debug_state: Arc<DebugState>,
     bootstrap_class_loader: ClassLoader,
-    class_name_to_index: HashMap<Spur, ClassId>,
+    // keyed by initiating loader (None for the bootstrap one) and name, the defining loader is
+    // one of the initiating loaders too
+    class_name_to_index: HashMap<(Option<HeapRef>, Symbol), ClassId>,
+    // ClassLoaders$AppClassLoader, until it is there the bootstrap loader serves the class path
+    app_class_loader: Option<HeapRef>,
+    // what ClassLoader.getSystemClassLoader returns once initPhase3 is done
+    system_class_loader: Option<HeapRef>,
+    // classes whose superclass and interfaces are being loaded and the thread loading them, that
+    // thread seeing one again is a cycle, another one defining it too is a duplicate definition
+    being_defined: HashSet<(Option<HeapRef>, Symbol, ThreadId)>,
     mirror_to_class_index: HashMap<HeapRef, ClassId>,
+    // defined through Lookup.defineHiddenClass, no loader knows them by name
+    hidden_classes: HashSet<ClassId>,
+    modules: ModuleTable,
     classes: Vec<JvmClass>,
     methods: Vec<Method>,
 
@@ -57,7 +72,12 @@ impl MethodArea {
             debug_state,
             bootstrap_class_loader,
             class_name_to_index: HashMap::new(),
+            app_class_loader: None,
+            system_class_loader: None,
+            being_defined: HashSet::new(),
             mirror_to_class_index: HashMap::new(),
+            hidden_classes: HashSet::new(),
+            modules: ModuleTable::default(),
             classes: Vec::with_capacity(1024),
             methods: Vec::with_capacity(16384),
             field_descriptors: Vec::with_capacity(2048),
@@ -119,12 +139,57 @@ impl MethodArea {
             let primitive_class =
                 JvmClass::Primitive(PrimitiveClass::new(name_sym, *primitive_type));
             let class_id = self.push_class(primitive_class);
-            self.class_name_to_index.insert(name_sym, class_id);
+            self.class_name_to_index.insert((None, name_sym), class_id);
         }
 
         Ok(())
     }
 
+    pub fn app_class_loader(&self) -> Option<HeapRef> {
+        self.app_class_loader
+    }
+
+    /// From now on class path classes are defined by `loader` instead of the bootstrap loader.
+    pub fn set_app_class_loader(&mut self, loader: HeapRef) {
+        self.app_class_loader = Some(loader);
+    }
+
+    pub fn system_class_loader(&self) -> Option<HeapRef> {
+        self.system_class_loader
+    }
+
+    pub fn set_system_class_loader(&mut self, loader: HeapRef) {
+        self.system_class_loader = Some(loader);
+    }
+
+    /// The class `loader` is an initiating loader of, if any.
+    pub fn find_loaded_class(&self, loader: Option<HeapRef>, name_sym: Symbol) -> Option<ClassId> {
+        self.class_name_to_index.get(&(loader, name_sym)).copied()
+    }
+
+    /// Dumps the class archive with the classes the bootstrap loader loaded.
+    pub fn dump_class_archive(&self) -> std::io::Result<()> {
+        let names = self
+            .class_name_to_index
+            .keys()
+            .filter(|(loader, _)| loader.is_none())
+            .map(|(_, name_sym)| self.interner.resolve(name_sym))
+            .filter(|name| !name.starts_with('['));
+        self.bootstrap_class_loader.dump_archive(names)
+    }
+
+    /// Records `loader` as an initiating loader of `class_id`, that is what it returned for the name.
+    pub fn record_initiating_loader(
+        &mut self,
+        loader: Option<HeapRef>,
+        name_sym: Symbol,
+        class_id: ClassId,
+    ) {
+        self.class_name_to_index
+            .entry((loader, name_sym))
+            .or_insert(class_id);
+    }
+
     pub fn classes(&self) -> &Vec<JvmClass> {
         &self.classes
     }
@@ -278,6 +343,80 @@ impl MethodArea {
         throw_exception!(NoSuchMethodError, method_key: key, class_sym: class_sym)
     }
 
+    /// The methods `key` names in `interfaces` that no other of them overrides, JVMS 5.4.3.3, in
+    /// load order. Static and private methods are not inherited and take no part.
+    pub fn maximally_specific_methods(
+        &self,
+        interfaces: &HashSet<ClassId>,
+        key: &MethodKey,
+    ) -> Vec<MethodId> {
+        let candidates = interfaces
+            .iter()
+            .filter_map(|interface_id| {
+                let method_id = *self
+                    .get_interface_class(interface_id)
+                    .ok()?
+                    .get_methods()
+                    .get(key)?;
+                let method = self.get_method(&method_id);
+                (!method.is_static() && !method.is_private()).then_some((*interface_id, method_id))
+            })
+            .collect::<Vec<_>>();
+        let mut maximally_specific = candidates
+            .iter()
+            .filter(|(interface_id, _)| {
+                !candidates.iter().any(|(other_id, _)| {
+                    other_id != interface_id
+                        && self
+                            .get_class(other_id)
+                            .get_interfaces()
+                            .is_ok_and(|supers| supers.contains(interface_id))
+                })
+            })
+            .copied()
+            .collect::<Vec<_>>();
+        maximally_specific.sort_by_key(|(interface_id, _)| *interface_id);
+        maximally_specific
+            .into_iter()
+            .map(|(_, method_id)| method_id)
+            .collect()
+    }
+
+    /// Interface method resolution, JVMS 5.4.3.4: a declared method, a public method of Object,
+    /// or a maximally-specific superinterface method, preferably the only non-abstract one.
+    pub fn resolve_interface_method_id(
+        &self,
+        interface_id: ClassId,
+        key: &MethodKey,
+    ) -> Option<MethodId> {
+        let interface = self.get_interface_class(&interface_id).ok()?;
+        if let Some(method_id) = interface.get_methods().get(key) {
+            return Some(*method_id);
+        }
+        let object_method_id = interface
+            .get_super()
+            .and_then(|object_id| {
+                self.get_instance_class(&object_id)
+                    .ok()?
+                    .get_special_method_id_opt(key)
+            })
+            .filter(|method_id| {
+                let method = self.get_method(method_id);
+                method.flags().is_public() && !method.is_static()
+            });
+        if object_method_id.is_some() {
+            return object_method_id;
+        }
+        let candidates = self.maximally_specific_methods(interface.get_interfaces().ok()?, key);
+        let mut defaults = candidates
+            .iter()
+            .filter(|method_id| !self.get_method(method_id).is_abstract());
+        match (defaults.next(), defaults.next()) {
+            (Some(method_id), None) => Some(*method_id),
+            _ => candidates.first().copied(),
+        }
+    }
+
     pub fn get_interface_class(&self, class_id: &ClassId) -> Result<&InterfaceClass, JvmError> {
         match self.get_class(class_id) {
             JvmClass::Interface(ic) => Ok(ic),
@@ -358,16 +497,40 @@ impl MethodArea {
 
     pub(crate) fn load_array_class(
         &mut self,
+        loader: Option<HeapRef>,
         name_sym: Symbol,
         thread_id: ThreadId,
     ) -> Result<ClassId, JvmError> {
-        if let Some(class_id) = self.class_name_to_index.get(&name_sym) {
-            return Ok(*class_id);
+        if let Some(class_id) = self.find_loaded_class(loader, name_sym) {
+            return Ok(class_id);
         }
         let type_descriptor_id = self.get_or_new_field_descriptor_id(name_sym)?;
         //TODO: avoid clone?
         let type_descriptor = self.get_field_descriptor(&type_descriptor_id).clone();
-        let name_str = self.interner.resolve(&name_sym);
+        let inner_type = type_descriptor.get_array_element_type().unwrap();
+        let element_class_id = match inner_type {
+            JavaType::Primitive(_) => None,
+            JavaType::Instance(inst) => {
+                let elem_sym = self.interner.get_or_intern(inst);
+                Some(self.get_class_id_or_load_with(loader, elem_sym, thread_id)?)
+            }
+            JavaType::Array(_) => {
+                let elem_sym = self.interner.get_or_intern(inner_type.as_descriptor());
+                Some(self.get_class_id_or_load_with(loader, elem_sym, thread_id)?)
+            }
+            _ => Err(JvmError::Todo(
+                "Array class with non-array or non-primitive type descriptor".to_string(),
+            ))?,
+        };
+
+        // an array class is defined by the defining loader of its element class
+        let element_loader =
+            element_class_id.and_then(|class_id| self.get_class(&class_id).get_loader());
+        if let Some(class_id) = self.find_loaded_class(element_loader, name_sym) {
+            self.record_initiating_loader(loader, name_sym, class_id);
+            return Ok(class_id);
+        }
+
         let obj_class_id = self.br().get_java_lang_object_id()?;
         let vtable = self
             .get_instance_class(&obj_class_id)?
@@ -378,51 +541,35 @@ impl MethodArea {
             .get_vtable_index()?
             .clone();
 
-        let class = {
-            let inner_type = type_descriptor.get_array_element_type().unwrap();
-            match inner_type {
-                JavaType::Primitive(prim) => {
-                    // TODO: there a 2 lazy fields, can use default to skip init here
-                    JvmClass::PrimitiveArray(PrimitiveArrayClass {
-                        name: name_sym,
-                        super_id: self.br().get_java_lang_object_id()?,
-                        element_class_id: OnceCell::new(),
-                        element_type: *prim,
-                        vtable,
-                        vtable_index,
-                        mirror_ref: OnceCell::new(),
-                    })
-                }
-                JavaType::Instance(inst) => JvmClass::InstanceArray(ObjectArrayClass {
+        let class = match (inner_type, element_class_id) {
+            (JavaType::Primitive(prim), _) => {
+                // TODO: there a 2 lazy fields, can use default to skip init here
+                JvmClass::PrimitiveArray(PrimitiveArrayClass {
                     name: name_sym,
-                    super_id: self.br().get_java_lang_object_id()?,
-                    element_class_id: self
-                        .get_class_id_or_load(self.interner.get_or_intern(inst), thread_id)?,
+                    super_id: obj_class_id,
+                    element_class_id: OnceCell::new(),
+                    element_type: *prim,
                     vtable,
                     vtable_index,
                     mirror_ref: OnceCell::new(),
-                }),
-                JavaType::Array(_) => {
-                    let elem_desc = inner_type.as_descriptor();
-                    let elem_sym = self.interner.get_or_intern(elem_desc);
-                    let element_class_id = self.get_class_id_or_load(elem_sym, thread_id)?;
-                    JvmClass::InstanceArray(ObjectArrayClass {
-                        name: name_sym,
-                        super_id: self.br().get_java_lang_object_id()?,
-                        element_class_id,
-                        vtable,
-                        vtable_index,
-                        mirror_ref: OnceCell::new(),
-                    })
-                }
-                _ => Err(JvmError::Todo(
-                    "Array class with non-array or non-primitive type descriptor".to_string(),
-                ))?,
+                })
             }
+            (_, Some(element_class_id)) => JvmClass::InstanceArray(ObjectArrayClass {
+                name: name_sym,
+                super_id: obj_class_id,
+                element_class_id,
+                loader: element_loader,
+                vtable,
+                vtable_index,
+                mirror_ref: OnceCell::new(),
+            }),
+            _ => unreachable!(),
         };
 
         let class_id = self.push_class(class);
-        self.class_name_to_index.insert(name_sym, class_id);
+        self.class_name_to_index
+            .insert((element_loader, name_sym), class_id);
+        self.record_initiating_loader(loader, name_sym, class_id);
 
         if let JvmClass::PrimitiveArray(prim_array) = self.get_class(&class_id) {
             prim_array.set_element_class_id(class_id)?;
@@ -479,49 +626,385 @@ impl MethodArea {
         false
     }
 
-    //TODO: probably need try to load?
-    pub fn instance_of(&self, this_class_id: ClassId, other_sym: Symbol) -> bool {
-        if let Some(&other_class_id) = self.class_name_to_index.get(&other_sym) {
-            self.is_subclass_of(this_class_id, other_class_id)
-        } else {
-            false
+    /// `other_sym` is looked up the way `loader` sees it. If `loader` has not loaded it, a
+    /// supertype with that name is the one `loader` would resolve anyway.
+    pub fn instance_of(
+        &self,
+        this_class_id: ClassId,
+        loader: Option<HeapRef>,
+        other_sym: Symbol,
+    ) -> bool {
+        match self.find_loaded_class(loader, other_sym) {
+            Some(other_class_id) => self.is_subclass_of(this_class_id, other_class_id),
+            None => self.has_supertype_named(this_class_id, other_sym),
         }
     }
 
+    fn has_supertype_named(&self, class_id: ClassId, name_sym: Symbol) -> bool {
+        let class = self.get_class(&class_id);
+        if class.get_name() == name_sym {
+            return true;
+        }
+        let Ok(class) = class.as_class_like() else {
+            return false;
+        };
+        if class
+            .get_super()
+            .is_some_and(|super_id| self.has_supertype_named(super_id, name_sym))
+        {
+            return true;
+        }
+        class.get_interfaces().is_ok_and(|interfaces| {
+            interfaces
+                .iter()
+                .any(|interface_id| self.has_supertype_named(*interface_id, name_sym))
+        })
+    }
+
     #[hotpath::measure]
-    fn load_class(&mut self, name_sym: Symbol, thread_id: ThreadId) -> Result<ClassId, JvmError> {
+    fn load_class(
+        &mut self,
+        loader: Option<HeapRef>,
+        name_sym: Symbol,
+        thread_id: ThreadId,
+    ) -> Result<ClassId, JvmError> {
         let data = {
             hotpath::measure_block!("load_class::read_raw_class", {
                 let name_str = self.interner.resolve(&name_sym);
-                if name_str.starts_with("[") {
-                    return self.load_array_class(name_sym, thread_id);
+                match (loader, self.app_class_loader) {
+                    (None, None) => self.bootstrap_class_loader.load(name_str)?,
+                    (None, Some(_)) => self.bootstrap_class_loader.load_boot(name_str)?,
+                    (Some(_), _) => ClassData::Bytes(
+                        self.bootstrap_class_loader.load_from_class_path(name_str)?,
+                    ),
                 }
-                self.bootstrap_class_loader.load(name_str)?
             })
         };
-        let cf = hotpath::measure_block!(
-            "load_class::parse_class_file",
-            ClassFile::try_from(data).map_err(LinkageError::from)?
-        );
-        let super_id = match cf.get_super_class_name() {
-            Some(super_name) => {
-                let super_name = super_name.unwrap();
-                let super_name_sym = self.interner.get_or_intern(super_name);
-                Some(self.get_class_id_or_load(super_name_sym, thread_id)?)
-            }
-            None => None,
+        let name = self.interner.resolve(&name_sym);
+        let cf = match data {
+            // the archive has it parsed, checked and keyed by its name already
+            ClassData::Archived(cf) => cf,
+            ClassData::Bytes(bytes) => hotpath::measure_block!(
+                "load_class::parse_class_file",
+                parse_class_file(bytes, name)?
+            ),
         };
+        let actual_name = cf
+            .cp
+            .get_class_name(&cf.this_class)
+            .map_err(|e| class_format_error(e, name))?;
+        if actual_name != name {
+            throw_exception!(NoClassDefFoundError, "{name} (wrong name: {actual_name})")?
+        }
+        self.define_class(loader, cf, thread_id)
+    }
+
+    /// Marks `name_sym` as being defined by `loader` on `thread_id` until `end_definition`. The
+    /// same thread running into it again before that means it is its own supertype.
+    pub(crate) fn begin_definition(
+        &mut self,
+        loader: Option<HeapRef>,
+        name_sym: Symbol,
+        thread_id: ThreadId,
+    ) -> Result<(), JvmError> {
+        if !self.being_defined.insert((loader, name_sym, thread_id)) {
+            throw_exception!(
+                ClassCircularityError,
+                "{}",
+                self.interner.resolve(&name_sym)
+            )?
+        }
+        Ok(())
+    }
+
+    pub(crate) fn end_definition(
+        &mut self,
+        loader: Option<HeapRef>,
+        name_sym: Symbol,
+        thread_id: ThreadId,
+    ) {
+        self.being_defined.remove(&(loader, name_sym, thread_id));
+    }
+
+    /// Creates the class `cf` with `loader` as its defining loader. The superclass and the
+    /// interfaces have to be loadable by `loader` from here, for a user-defined loader it means
+    /// they were resolved through it already.
+    pub(crate) fn define_class(
+        &mut self,
+        loader: Option<HeapRef>,
+        cf: ClassFile,
+        thread_id: ThreadId,
+    ) -> Result<ClassId, JvmError> {
+        let name = cf
+            .cp
+            .get_class_name(&cf.this_class)
+            .map_err(|e| class_format_error(e, "<Unknown>"))?;
+        let name_sym = self.interner.get_or_intern(name);
+        if self.find_loaded_class(loader, name_sym).is_some() {
+            throw_exception!(
+                LinkageError,
+                "attempted duplicate class definition for {}",
+                name.replace('/', ".")
+            )?
+        }
+
+        self.begin_definition(loader, name_sym, thread_id)?;
+        let res = self.create_class(loader, cf, name_sym, thread_id);
+        self.end_definition(loader, name_sym, thread_id);
+        let class_id = res?;
+        self.class_name_to_index
+            .insert((loader, name_sym), class_id);
+        Ok(class_id)
+    }
+
+    /// Creates the hidden class `cf`, JVMS 5.3.5 without recording `loader` as its initiating
+    /// loader. Like HotSpot the name gets a unique `+0x...` suffix, shown as `/0x...`.
+    pub(crate) fn define_hidden_class(
+        &mut self,
+        loader: Option<HeapRef>,
+        mut cf: ClassFile,
+        thread_id: ThreadId,
+    ) -> Result<ClassId, JvmError> {
+        let name_index = cf
+            .cp
+            .get_class(&cf.this_class)
+            .map_err(|e| class_format_error(e, "<Unknown>"))?;
+        let name = cf
+            .cp
+            .get_utf8(&name_index)
+            .map_err(|e| class_format_error(e, "<Unknown>"))?;
+        let hidden_name = format!("{name}+0x{:016x}", self.hidden_classes.len() + 1);
+        // the class refers to itself by the new name as well, through a constant of its own as the
+        // old one may be shared with other constants
+        let hidden_name_index = u16::try_from(cf.cp.inner.len()).map_err(|_| {
+            build_exception!(ClassFormatError, "Too many constants in class file {name}")
+        })?;
+        cf.cp.inner.push(ConstantEntry::Utf8(hidden_name.clone()));
+        cf.cp.inner[cf.this_class as usize] = ConstantEntry::Class(hidden_name_index);
+        let name_sym = self.interner.get_or_intern(hidden_name);
+        let class_id = self.create_class(loader, cf, name_sym, thread_id)?;
+        self.hidden_classes.insert(class_id);
+        Ok(class_id)
+    }
+
+    pub fn is_hidden(&self, class_id: &ClassId) -> bool {
+        self.hidden_classes.contains(class_id)
+    }
+
+    /// The binary name of the class, with the `/` of a hidden class' suffix, JLS 13.1.
+    pub fn external_name(&self, class_id: &ClassId) -> String {
+        let name = self
+            .interner
+            .resolve(&self.get_class(class_id).get_name())
+            .replace('/', ".");
+        match name.rfind('+') {
+            Some(suffix) if self.is_hidden(class_id) => {
+                format!("{}/{}", &name[..suffix], &name[suffix + 1..])
+            }
+            _ => name,
+        }
+    }
+
+    pub fn modules(&self) -> &ModuleTable {
+        &self.modules
+    }
+
+    pub fn modules_mut(&mut self) -> &mut ModuleTable {
+        &mut self.modules
+    }
+
+    /// The package of the class in internal form, `None` for the unnamed package. Arrays are in
+    /// the package of their element class.
+    pub fn package_of(&self, class_id: &ClassId) -> Option<Symbol> {
+        let class_id = self.bottom_element_class_id(*class_id);
+        let name = self.interner.resolve(&self.get_class(&class_id).get_name());
+        // a package nobody interned has no module either
+        name.rfind('/')
+            .and_then(|slash| self.interner.get(&name[..slash]))
+    }
+
+    /// The module of the class, `None` for the bootstrap loader's classes until java.base is
+    /// defined, like HotSpot's fixup list. Primitive types are in java.base.
+    pub fn module_of(&self, class_id: &ClassId) -> Option<ModuleRef> {
+        let class_id = self.bottom_element_class_id(*class_id);
+        let class = self.get_class(&class_id);
+        if class.is_primitive() || class.is_primitive_array() {
+            return self.modules.java_base().map(ModuleRef::Named);
+        }
+        let loader = class.get_loader();
+        if loader.is_none() && self.modules.java_base().is_none() {
+            return None;
+        }
+        let named = self.package_of(&class_id).and_then(|package| {
+            self.modules.package_module(loader, package).or_else(|| {
+                // the bootstrap loader defines the classes of the platform modules too
+                let platform_loader = self.modules.platform_loader().filter(|_| loader.is_none());
+                self.modules.package_module(Some(platform_loader?), package)
+            })
+        });
+        Some(named.map_or(ModuleRef::Unnamed { loader }, ModuleRef::Named))
+    }
+
+    fn bottom_element_class_id(&self, mut class_id: ClassId) -> ClassId {
+        while let Some(element) = self.get_class(&class_id).get_array_element_class_id() {
+            class_id = element;
+        }
+        class_id
+    }
+
+    /// The `java.lang.Module` of `module`, null for the bootstrap loader's unnamed module until
+    /// BootLoader hands it over.
+    pub fn module_object(&self, module: ModuleRef, heap: &Heap) -> Result<Value, JvmError> {
+        match module {
+            ModuleRef::Named(module_ref) => Ok(Value::Ref(module_ref)),
+            ModuleRef::Unnamed { loader: None } => {
+                Ok(self.modules.boot_unnamed().map_or(Value::Null, Value::Ref))
+            }
+            ModuleRef::Unnamed {
+                loader: Some(loader),
+            } => {
+                let loader_class_id = heap.get_class_id(loader)?;
+                let unnamed_module_offset = self
+                    .get_instance_field(
+                        &loader_class_id,
+                        &self.br().class_loader_unnamed_module_fk,
+                    )?
+                    .offset;
+                heap.read_field(loader, unnamed_module_offset, AllocationType::Reference)
+            }
+        }
+    }
+
+    /// Sets Class.module of the bootstrap loader's mirrors that were created before their module
+    /// was known, HotSpot's patch_javabase_entries.
+    pub fn patch_boot_mirror_modules(&self, heap: &RwLock<Heap>) -> Result<(), JvmError> {
+        let class_class_id = self.br().get_java_lang_class_id()?;
+        let module_offset = self
+            .get_instance_class(&class_class_id)?
+            .get_instance_field(&self.br().class_module_fk)?
+            .offset;
+        let mut heap = heap.write().unwrap();
+        for (index, class) in self.classes.iter().enumerate() {
+            let Some(mirror_ref) = class.get_mirror_ref() else {
+                continue;
+            };
+            let class_id = ClassId::from_usize(index + 1);
+            let Some(module) = self.module_of(&class_id) else {
+                continue;
+            };
+            if heap.read_field(mirror_ref, module_offset, AllocationType::Reference)? != Value::Null
+            {
+                continue;
+            }
+            let module = self.module_object(module, &heap)?;
+            heap.write_field(mirror_ref, module_offset, module, AllocationType::Reference)?;
+        }
+        Ok(())
+    }
+
+    fn create_class(
+        &mut self,
+        loader: Option<HeapRef>,
+        cf: ClassFile,
+        name_sym: Symbol,
+        thread_id: ThreadId,
+    ) -> Result<ClassId, JvmError> {
+        let super_id = self.resolve_super_class(loader, &cf, name_sym, thread_id)?;
+        let interner = self.interner.clone();
+        let name = interner.resolve(&name_sym);
         let class_id = hotpath::measure_block!("load_class::load_and_link_class", {
             if cf.access_flags.is_interface() {
-                InterfaceClass::load_and_link(cf, self, super_id, thread_id)?
+                InterfaceClass::load_and_link(cf, self, super_id, loader, thread_id)
+                    .map_err(|e| linking_error(e, name))?
             } else {
-                InstanceClass::load_and_link(cf, self, super_id, thread_id)?
+                InstanceClass::load_and_link(cf, self, super_id, loader, thread_id)
+                    .map_err(|e| linking_error(e, name))?
             }
         });
-        self.class_name_to_index.insert(name_sym, class_id);
+        self.send_class_prepare_event(class_id, name_sym, thread_id);
         Ok(class_id)
     }
 
+    // JVMS 5.3.5 step 3, the superclass has to be a non-final class and only Object has none
+    fn resolve_super_class(
+        &mut self,
+        loader: Option<HeapRef>,
+        cf: &ClassFile,
+        name_sym: Symbol,
+        thread_id: ThreadId,
+    ) -> Result<Option<ClassId>, JvmError> {
+        let interner = self.interner.clone();
+        let name = interner.resolve(&name_sym);
+        let Some(super_name) = cf.get_super_class_name() else {
+            if name_sym == self.br().java_lang_object_sym {
+                return Ok(None);
+            }
+            throw_exception!(
+                ClassFormatError,
+                "Invalid superclass index 0 in class file {name}"
+            )?
+        };
+        let super_name = super_name.map_err(|e| class_format_error(e, name))?;
+        let super_name_sym = self.interner.get_or_intern(super_name);
+        if cf.access_flags.is_interface() && super_name_sym != self.br().java_lang_object_sym {
+            throw_exception!(
+                ClassFormatError,
+                "Interfaces must have java.lang.Object as superclass in class file {name}"
+            )?
+        }
+
+        let super_id = self
+            .get_class_id_or_load_with(loader, super_name_sym, thread_id)
+            .map_err(|e| e.not_found_as_no_class_def(super_name))?;
+        let super_class = self.get_class(&super_id);
+        if super_class.is_interface() {
+            throw_exception!(
+                IncompatibleClassChangeError,
+                "class {} has interface {} as super class",
+                name.replace('/', "."),
+                super_name.replace('/', ".")
+            )?
+        }
+        if super_class
+            .as_class_like()
+            .is_ok_and(|class| class.flags().is_final())
+        {
+            throw_exception!(
+                IncompatibleClassChangeError,
+                "class {} cannot inherit from final class {}",
+                name.replace('/', "."),
+                super_name.replace('/', ".")
+            )?
+        }
+        Ok(Some(super_id))
+    }
+
+    /// Loads the direct superinterface `interface_sym` of the class `this_id` that is being linked.
+    pub(crate) fn load_super_interface(
+        &mut self,
+        loader: Option<HeapRef>,
+        this_id: ClassId,
+        interface_sym: Symbol,
+        thread_id: ThreadId,
+    ) -> Result<ClassId, JvmError> {
+        let interner = self.interner.clone();
+        let interface_name = interner.resolve(&interface_sym);
+        let interface_id = self
+            .get_class_id_or_load_with(loader, interface_sym, thread_id)
+            .map_err(|e| e.not_found_as_no_class_def(interface_name))?;
+        if !self.get_class(&interface_id).is_interface() {
+            throw_exception!(
+                IncompatibleClassChangeError,
+                "class {} can not implement {}, because it is not an interface",
+                interner
+                    .resolve(&self.get_class(&this_id).get_name())
+                    .replace('/', "."),
+                interface_name.replace('/', ".")
+            )?
+        }
+        Ok(interface_id)
+    }
+
     fn get_class_type_tag(&self, class_id: &ClassId) -> TypeTag {
         let class = self.get_class(class_id);
         if class.is_array() {
@@ -536,38 +1019,75 @@ impl MethodArea {
         }
     }
 
-    #[hotpath::measure]
+    /// Loads with the bootstrap loader.
     pub fn get_class_id_or_load(
         &mut self,
         name_sym: Symbol,
         thread_id: ThreadId,
+    ) -> Result<ClassId, JvmError> {
+        self.get_class_id_or_load_with(None, name_sym, thread_id)
+    }
+
+    /// Loads `name_sym` with `loader`. Only the bootstrap and the app class loader are run here,
+    /// a user-defined loader only knows the classes it was an initiating loader for so far.
+    #[hotpath::measure]
+    pub fn get_class_id_or_load_with(
+        &mut self,
+        loader: Option<HeapRef>,
+        name_sym: Symbol,
+        thread_id: ThreadId,
     ) -> Result<ClassId, JvmError> {
         hotpath::measure_block!("get_class_id_or_load::cache_lookup", {
-            if let Some(class_id) = self.class_name_to_index.get(&name_sym) {
-                return Ok(*class_id);
+            if let Some(class_id) = self.find_loaded_class(loader, name_sym) {
+                return Ok(class_id);
             }
         });
-        let class_id = self.load_class(name_sym, thread_id)?;
-        if self.debug_state.should_check() {
-            let name_str = self.interner.resolve(&name_sym);
-            if let Some(matched) = self.debug_state.matches_class_prepare(name_str) {
-                for request_id in matched {
-                    println!("Sending ClassPrepare event for class {}", name_str);
-                    self.debug_state
-                        .send_event(DebugEvent::ClassPrepare(ClassPrepareInfo {
-                            request_id,
-                            thread_id,
-                            ref_type_tag: self.get_class_type_tag(&class_id),
-                            type_id: class_id,
-                            signature: format!("L{};", name_str),
-                            status: ClassStatus::Prepared, // Todo: hardcoded for now
-                        }))
+        if self.interner.resolve(&name_sym).starts_with('[') {
+            return self.load_array_class(loader, name_sym, thread_id);
+        }
+        let class_id = match loader {
+            None => self.load_class(None, name_sym, thread_id)?,
+            Some(_) if loader == self.app_class_loader => {
+                // parent first, the platform loader sees no more than the bootstrap one for now
+                let name_str = self.interner.resolve(&name_sym);
+                if let Some(class_id) = self.find_loaded_class(None, name_sym) {
+                    class_id
+                } else if self.bootstrap_class_loader.has_boot_class(name_str) {
+                    self.load_class(None, name_sym, thread_id)?
+                } else {
+                    self.load_class(loader, name_sym, thread_id)?
                 }
             }
-        }
+            Some(_) => throw_exception!(
+                NoClassDefFoundError,
+                self.interner.resolve(&name_sym).to_string()
+            )?,
+        };
+        self.record_initiating_loader(loader, name_sym, class_id);
         Ok(class_id)
     }
 
+    fn send_class_prepare_event(&self, class_id: ClassId, name_sym: Symbol, thread_id: ThreadId) {
+        if !self.debug_state.should_check() {
+            return;
+        }
+        let name_str = self.interner.resolve(&name_sym);
+        if let Some(matched) = self.debug_state.matches_class_prepare(name_str) {
+            for request_id in matched {
+                println!("Sending ClassPrepare event for class {}", name_str);
+                self.debug_state
+                    .send_event(DebugEvent::ClassPrepare(ClassPrepareInfo {
+                        request_id,
+                        thread_id,
+                        ref_type_tag: self.get_class_type_tag(&class_id),
+                        type_id: class_id,
+                        signature: format!("L{};", name_str),
+                        status: ClassStatus::Prepared, // Todo: hardcoded for now
+                    }))
+            }
+        }
+    }
+
     pub fn get_class_id_by_mirror(&self, mirror: &HeapRef) -> Result<ClassId, JvmError> {
         self.mirror_to_class_index
             .get(mirror)
@@ -604,9 +1124,43 @@ impl MethodArea {
                 AllocationType::Boolean,
             )?;
         }
+        self.set_mirror_loader_and_module(mirror_ref, class_id, heap)?;
         self.mirror_to_class_index.insert(mirror_ref, class_id);
         let target_class = self.get_class(&class_id);
         target_class.set_mirror_ref(mirror_ref)?;
         Ok(mirror_ref)
     }
+
+    // Class.classLoader and Class.module, the latter stays null for the bootstrap loader's classes
+    // until java.base is defined
+    fn set_mirror_loader_and_module(
+        &self,
+        mirror_ref: HeapRef,
+        class_id: ClassId,
+        heap: &RwLock<Heap>,
+    ) -> Result<(), JvmError> {
+        let class_class_id = self.br().get_java_lang_class_id()?;
+        let class_class = self.get_instance_class(&class_class_id)?;
+        let loader_offset = class_class
+            .get_instance_field(&self.br().class_class_loader_fk)?
+            .offset;
+        let module_offset = class_class
+            .get_instance_field(&self.br().class_module_fk)?
+            .offset;
+
+        let mut heap = heap.write().unwrap();
+        if let Some(loader) = self.get_class(&class_id).get_loader() {
+            heap.write_field(
+                mirror_ref,
+                loader_offset,
+                Value::Ref(loader),
+                AllocationType::Reference,
+            )?;
+        }
+        if let Some(module) = self.module_of(&class_id) {
+            let module = self.module_object(module, &heap)?;
+            heap.write_field(mirror_ref, module_offset, module, AllocationType::Reference)?;
+        }
+        Ok(())
+    }
 }
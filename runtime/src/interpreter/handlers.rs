@@ -2,15 +2,19 @@ use crate::error::JvmError;
 use crate::heap::method_area::MethodArea;
 use crate::heap::{Heap, HeapRef};
 use crate::interpreter::Interpreter;
-use crate::keys::{ClassId, FieldKey, MethodKey};
+use crate::keys::{ClassId, FieldDescriptorId, FieldKey, MethodId, MethodKey, Symbol};
 use crate::rt::constant_pool::RuntimeConstant;
+use crate::rt::{ClassLike, JvmClass};
 use crate::thread::JavaThreadState;
 use crate::vm::Value;
-use crate::{VirtualMachine, throw_exception};
+use crate::{VirtualMachine, build_exception, throw_exception};
 use jclass::prelude::{ArrayType, LookupSwitchData, TableSwitchData};
 use std::cmp::Ordering;
 use tracing_log::log::warn;
 
+// the abstract flag of classes and methods
+const ACC_ABSTRACT: i32 = 0x0400;
+
 fn branch16(bci: usize, off: i16) -> usize {
     ((bci as isize) + (off as isize)) as usize
 }
@@ -18,6 +22,157 @@ fn branch32(bci: usize, off: i32) -> usize {
     ((bci as isize) + (off as isize)) as usize
 }
 
+// field resolution, JVMS 5.4.3.2, for getfield and putfield: the offset and the descriptor
+fn resolve_instance_field(
+    thread: &mut JavaThreadState,
+    vm: &VirtualMachine,
+    accessor_id: MethodId,
+    idx: u16,
+) -> Result<(usize, FieldDescriptorId), JvmError> {
+    let field_view = vm
+        .method_area_read()
+        .get_cp_by_method_id(&accessor_id)?
+        .get_field_view(&idx, vm.interner())?;
+    let field_key: FieldKey = field_view.name_and_type.into();
+    let target_class_id = vm.resolve_class(thread, &accessor_id, field_view.class_sym)?;
+    let target_field = *vm
+        .method_area_read()
+        .get_instance_field(&target_class_id, &field_key)?;
+    vm.check_field_access(
+        thread,
+        &accessor_id,
+        target_class_id,
+        target_field.declaring_class,
+        &field_key,
+        target_field.flags,
+    )?;
+    Ok((target_field.offset, target_field.descriptor_id))
+}
+
+// field resolution for getstatic and putstatic: the referenced class, the declaring one and the key
+fn resolve_static_field(
+    thread: &mut JavaThreadState,
+    vm: &VirtualMachine,
+    accessor_id: MethodId,
+    idx: u16,
+) -> Result<(ClassId, ClassId, FieldKey), JvmError> {
+    let target_field_view = vm
+        .method_area_read()
+        .get_cp_by_method_id(&accessor_id)?
+        .get_field_view(&idx, vm.interner())?;
+    let target_class_id = vm.resolve_class(thread, &accessor_id, target_field_view.class_sym)?;
+    let field_key: FieldKey = target_field_view.name_and_type.into();
+    let (actual_static_field_class_id, flags) = {
+        let ma = vm.method_area_read();
+        let actual_static_field_class_id =
+            ma.resolve_static_field_actual_class_id(target_class_id, &field_key)?;
+        let flags = ma
+            .get_class_like(&actual_static_field_class_id)?
+            .get_static_field_flags(&field_key)?;
+        (actual_static_field_class_id, flags)
+    };
+    vm.check_field_access(
+        thread,
+        &accessor_id,
+        target_class_id,
+        actual_static_field_class_id,
+        &field_key,
+        flags,
+    )?;
+    Ok((target_class_id, actual_static_field_class_id, field_key))
+}
+
+// method resolution, JVMS 5.4.3.3 and 5.4.3.4, for invokevirtual and invokeinterface, which
+// select the method from the receiver. Arrays pretend their clone() is public and resolve nothing
+fn resolve_method_ref(
+    thread: &mut JavaThreadState,
+    vm: &VirtualMachine,
+    accessor_id: MethodId,
+    class_sym: Symbol,
+    method_key: &MethodKey,
+) -> Result<(ClassId, Option<MethodId>), JvmError> {
+    let resolved_class_id = vm.resolve_class(thread, &accessor_id, class_sym)?;
+    let resolved_method_id = {
+        let ma = vm.method_area_read();
+        match ma.get_class(&resolved_class_id) {
+            JvmClass::Instance(class) => class.get_special_method_id_opt(method_key),
+            JvmClass::Interface(_) => ma.resolve_interface_method_id(resolved_class_id, method_key),
+            _ => None,
+        }
+    };
+    if let Some(method_id) = resolved_method_id {
+        vm.check_method_access(thread, &accessor_id, resolved_class_id, method_id)?;
+    }
+    Ok((resolved_class_id, resolved_method_id))
+}
+
+// HotSpot's LinkResolver::throw_abstract_method_error
+fn abstract_method_error(
+    vm: &VirtualMachine,
+    receiver_class_id: ClassId,
+    resolved_method_id: MethodId,
+) -> JvmError {
+    let ma = vm.method_area_read();
+    let external_name = |class_id: ClassId| {
+        vm.interner()
+            .resolve(&ma.get_class(&class_id).get_name())
+            .replace('/', ".")
+    };
+    let method = ma.get_method(&resolved_method_id);
+    let resolved_class_id = method.class_id();
+    let resolved_class = ma.get_class(&resolved_class_id);
+    let kind = if resolved_class.is_interface() {
+        "interface"
+    } else if resolved_class.get_raw_flags() & ACC_ABSTRACT != 0 {
+        "abstract class"
+    } else {
+        "class"
+    };
+    let descriptor = ma.get_method_descriptor(&method.descriptor_id());
+    build_exception!(
+        AbstractMethodError,
+        "Receiver class {} does not define or inherit an implementation of the resolved method '{}{} {}({})' of {} {}.",
+        external_name(receiver_class_id),
+        if method.is_abstract() {
+            "abstract "
+        } else {
+            ""
+        },
+        descriptor.ret,
+        vm.interner().resolve(&method.name),
+        descriptor
+            .params
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", "),
+        kind,
+        external_name(resolved_class_id)
+    )
+}
+
+// the methods an invokevirtual of a conflicting default selects, JVMS 5.4.6
+fn default_conflict_error(vm: &VirtualMachine, conflict: &[MethodId]) -> JvmError {
+    let ma = vm.method_area_read();
+    let methods = conflict
+        .iter()
+        .map(|method_id| {
+            let method = ma.get_method(method_id);
+            format!(
+                "{}.{}",
+                vm.interner()
+                    .resolve(&ma.get_class(&method.class_id()).get_name()),
+                vm.interner().resolve(&method.name)
+            )
+        })
+        .collect::<Vec<_>>();
+    build_exception!(
+        IncompatibleClassChangeError,
+        "Conflicting default methods: {}",
+        methods.join(" ")
+    )
+}
+
 #[inline]
 pub(super) fn handle_athrow(thread: &mut JavaThreadState) -> Result<(), JvmError> {
     let exception_ref = thread.stack.pop_obj_val()?;
@@ -176,9 +331,7 @@ pub(super) fn handle_anewarray(
         .method_area_read()
         .get_cp_by_method_id(&cur_frame_method_id)?
         .get_class_sym(&idx, vm.interner())?;
-    let target_array_class_id = vm
-        .method_area_write()
-        .get_class_id_or_load(target_array_sym, thread.id)?;
+    let target_array_class_id = vm.resolve_class(thread, &cur_frame_method_id, target_array_sym)?;
     let array_ref = vm
         .heap_write()
         .alloc_object_array(target_array_class_id, size)?;
@@ -519,19 +672,8 @@ pub(super) fn handle_getfield(
 ) -> Result<(), JvmError> {
     let target_obj_ref = thread.stack.pop_obj_val()?;
     let cur_frame_method_id = thread.stack.cur_java_frame()?.method_id();
-    let field_view = vm
-        .method_area_read()
-        .get_cp_by_method_id(&cur_frame_method_id)?
-        .get_field_view(&idx, vm.interner())?;
-    let target_class_id = vm
-        .method_area_write()
-        .get_class_id_or_load(field_view.class_sym, thread.id)?;
-    let (target_field_offset, target_field_descriptor_id) = {
-        let ma = vm.method_area_read();
-        let target_field =
-            ma.get_instance_field(&target_class_id, &field_view.name_and_type.into())?;
-        (target_field.offset, target_field.descriptor_id)
-    };
+    let (target_field_offset, target_field_descriptor_id) =
+        resolve_instance_field(thread, vm, cur_frame_method_id, idx)?;
     let value = vm.heap_read().read_field(
         target_obj_ref,
         target_field_offset,
@@ -549,18 +691,9 @@ pub(super) fn handle_getstatic(
     idx: u16,
 ) -> Result<(), JvmError> {
     let cur_frame_method_id = thread.stack.cur_java_frame()?.method_id();
-    let target_field_view = vm
-        .method_area_read()
-        .get_cp_by_method_id(&cur_frame_method_id)?
-        .get_field_view(&idx, vm.interner())?;
-    let target_class_id = vm
-        .method_area_write()
-        .get_class_id_or_load(target_field_view.class_sym, thread.id)?;
+    let (target_class_id, actual_static_field_class_id, field_key) =
+        resolve_static_field(thread, vm, cur_frame_method_id, idx)?;
     Interpreter::ensure_initialized(thread, Some(target_class_id), vm)?;
-    let field_key: FieldKey = target_field_view.name_and_type.into();
-    let actual_static_field_class_id = vm
-        .method_area_read()
-        .resolve_static_field_actual_class_id(target_class_id, &field_key)?;
     let value = vm
         .method_area_read()
         .get_static_field_value(&actual_static_field_class_id, &field_key)?;
@@ -987,6 +1120,13 @@ pub(super) fn handle_invokevirtual(
         .get_cp_by_method_id(&cur_frame_method_id)?
         .get_method_view(&idx, vm.interner())?;
     let method_key: MethodKey = target_method_view.name_and_type.into();
+    let (_, resolved_method_id) = resolve_method_ref(
+        thread,
+        vm,
+        cur_frame_method_id,
+        target_method_view.class_sym,
+        &method_key,
+    )?;
 
     let target_method_desc_id = vm
         .method_area_write()
@@ -1002,14 +1142,45 @@ pub(super) fn handle_invokevirtual(
     let object_ref = thread.stack.peek_operand_at(arg_count - 1)?.as_obj_ref()?;
     let actual_class_id = vm.heap_read().get_class_id(object_ref)?;
 
-    let target_method_id = vm
-        .method_area_read()
-        .get_class(&actual_class_id)
-        .get_vtable_method_id(&method_key)?;
+    let target_method_id =
+        select_virtual_method(vm, actual_class_id, resolved_method_id, &method_key)?;
     let args = Interpreter::prepare_method_args(thread, target_method_id, vm)?;
     Interpreter::invoke_method_internal(thread, target_method_id, args, vm)
 }
 
+// method selection, JVMS 5.4.6; private methods are not overridden
+fn select_virtual_method(
+    vm: &VirtualMachine,
+    actual_class_id: ClassId,
+    resolved_method_id: Option<MethodId>,
+    method_key: &MethodKey,
+) -> Result<MethodId, JvmError> {
+    let ma = vm.method_area_read();
+    if let Some(resolved_method_id) = resolved_method_id
+        && ma.get_method(&resolved_method_id).is_private()
+    {
+        return Ok(resolved_method_id);
+    }
+    let class = ma.get_class(&actual_class_id);
+    if let JvmClass::Instance(instance) = class
+        && let Some(conflict) = instance.get_default_conflict(method_key)
+    {
+        let conflict = conflict.to_vec();
+        drop(ma);
+        return Err(default_conflict_error(vm, &conflict));
+    }
+    let target_method_id = class.get_vtable_method_id(method_key)?;
+    if ma.get_method(&target_method_id).is_abstract() {
+        drop(ma);
+        return Err(abstract_method_error(
+            vm,
+            actual_class_id,
+            resolved_method_id.unwrap_or(target_method_id),
+        ));
+    }
+    Ok(target_method_id)
+}
+
 #[inline]
 pub(super) fn handle_instanceof(
     thread: &mut JavaThreadState,
@@ -1025,9 +1196,13 @@ pub(super) fn handle_instanceof(
     let obj_ref = thread.stack.pop_nullable_ref_val()?;
     if let Some(obj_ref) = obj_ref {
         let target_class = vm.heap_read().get_class_id(obj_ref)?;
-        let res = vm
-            .method_area_read()
-            .instance_of(target_class, class_name_sym);
+        let res = {
+            let ma = vm.method_area_read();
+            let loader = ma
+                .get_class(&ma.get_method(&cur_frame_method_id).class_id())
+                .get_loader();
+            ma.instance_of(target_class, loader, class_name_sym)
+        };
         thread
             .stack
             .push_operand(Value::Integer(if res { 1 } else { 0 }))
@@ -1289,9 +1464,7 @@ pub(super) fn handle_ldc_ldcw_ldc2w(
             RuntimeConstant::Class(class_entry) => {
                 let class_name_sym = class_entry.get_name_sym()?;
                 drop(ma);
-                let class_id = vm
-                    .method_area_write()
-                    .get_class_id_or_load(class_name_sym, thread.id)?;
+                let class_id = vm.resolve_class(thread, &cur_method_id, class_name_sym)?;
                 Value::Ref(
                     vm.method_area_write()
                         .get_mirror_ref_or_create(class_id, &vm.heap)?,
@@ -1319,9 +1492,7 @@ pub(super) fn handle_new(
         .method_area_read()
         .get_cp_by_method_id(&cur_frame_method_id)?
         .get_class_sym(&idx, vm.interner())?;
-    let target_class_id = vm
-        .method_area_write()
-        .get_class_id_or_load(target_class_name, thread.id)?;
+    let target_class_id = vm.resolve_class(thread, &cur_frame_method_id, target_class_name)?;
     Interpreter::ensure_initialized(thread, Some(target_class_id), vm)?;
     let instance_ref = vm.heap_write().alloc_instance(
         vm.method_area_read()
@@ -1343,6 +1514,7 @@ pub(super) fn handle_newarray(
         throw_exception!(NegativeArraySizeException, size.to_string())?
     }
     let class_id = vm.method_area_write().load_array_class(
+        None,
         vm.interner().get_or_intern(array_type.descriptor()),
         thread.id,
     )?;
@@ -1367,19 +1539,8 @@ pub(super) fn handle_putfield(
     let value = thread.stack.pop_operand()?;
     let target_obj_ref = thread.stack.pop_obj_val()?;
     let cur_frame_method_id = thread.stack.cur_java_frame()?.method_id();
-    let field_view = vm
-        .method_area_read()
-        .get_cp_by_method_id(&cur_frame_method_id)?
-        .get_field_view(&idx, vm.interner())?;
-    let target_class_id = vm
-        .method_area_write()
-        .get_class_id_or_load(field_view.class_sym, thread.id)?;
-    let (target_field_offset, target_field_descriptor_id) = {
-        let ma = vm.method_area_read();
-        let target_field =
-            ma.get_instance_field(&target_class_id, &field_view.name_and_type.into())?;
-        (target_field.offset, target_field.descriptor_id)
-    };
+    let (target_field_offset, target_field_descriptor_id) =
+        resolve_instance_field(thread, vm, cur_frame_method_id, idx)?;
     vm.heap_write().write_field(
         target_obj_ref,
         target_field_offset,
@@ -1398,18 +1559,9 @@ pub(super) fn handle_putstatic(
 ) -> Result<(), JvmError> {
     let value = thread.stack.pop_operand()?;
     let cur_frame_method_id = thread.stack.cur_java_frame()?.method_id();
-    let target_field_view = vm
-        .method_area_read()
-        .get_cp_by_method_id(&cur_frame_method_id)?
-        .get_field_view(&idx, vm.interner())?;
-    let target_class_id = vm
-        .method_area_write()
-        .get_class_id_or_load(target_field_view.class_sym, thread.id)?;
+    let (target_class_id, actual_static_field_class_id, field_key) =
+        resolve_static_field(thread, vm, cur_frame_method_id, idx)?;
     Interpreter::ensure_initialized(thread, Some(target_class_id), vm)?;
-    let field_key: FieldKey = target_field_view.name_and_type.into();
-    let actual_static_field_class_id = vm
-        .method_area_read()
-        .resolve_static_field_actual_class_id(target_class_id, &field_key)?;
     vm.method_area_read()
         .get_class_like(&actual_static_field_class_id)?
         .set_static_field_value(&field_key, value)
@@ -1442,17 +1594,78 @@ pub(super) fn handle_invokeinterface(
             let _ = thread.stack.pop_operand()?;
         }
     } else {
+        let method_key: MethodKey = target_method_view.name_and_type.into();
+        let (resolved_class_id, resolved_method_id) = resolve_method_ref(
+            thread,
+            vm,
+            cur_frame_method_id,
+            target_method_view.class_sym,
+            &method_key,
+        )?;
         let target_class_id = vm.heap_read().get_class_id(object_ref)?;
-        let target_method_id = vm
-            .method_area_read()
-            .get_instance_class(&target_class_id)?
-            .get_interface_method_id(&target_method_view.name_and_type.into())?;
+        let target_method_id = select_interface_method(
+            vm,
+            target_class_id,
+            resolved_class_id,
+            resolved_method_id,
+            &method_key,
+        )?;
         let args = Interpreter::prepare_method_args(thread, target_method_id, vm)?;
         Interpreter::invoke_method_internal(thread, target_method_id, args, vm)?;
     };
     Ok(())
 }
 
+// method selection for invokeinterface, JVMS 5.4.6, where a conflict among defaults leaves no
+// implementation
+fn select_interface_method(
+    vm: &VirtualMachine,
+    receiver_class_id: ClassId,
+    resolved_class_id: ClassId,
+    resolved_method_id: Option<MethodId>,
+    method_key: &MethodKey,
+) -> Result<MethodId, JvmError> {
+    let ma = vm.method_area_read();
+    if !ma.is_assignable_from(resolved_class_id, receiver_class_id) {
+        let external_name = |class_id: ClassId| {
+            vm.interner()
+                .resolve(&ma.get_class(&class_id).get_name())
+                .replace('/', ".")
+        };
+        return Err(build_exception!(
+            IncompatibleClassChangeError,
+            "Class {} does not implement the requested interface {}",
+            external_name(receiver_class_id),
+            external_name(resolved_class_id)
+        ));
+    }
+    if let Some(resolved_method_id) = resolved_method_id
+        && ma.get_method(&resolved_method_id).is_private()
+    {
+        return Ok(resolved_method_id);
+    }
+    let receiver = ma.get_instance_class(&receiver_class_id)?;
+    let target_method_id = receiver.get_interface_method_id(method_key).ok();
+    let selected = target_method_id.filter(|method_id| {
+        receiver.get_default_conflict(method_key).is_none()
+            && !ma.get_method(method_id).is_abstract()
+    });
+    match (selected, resolved_method_id.or(target_method_id)) {
+        (Some(method_id), _) => Ok(method_id),
+        (None, Some(resolved_method_id)) => {
+            drop(ma);
+            Err(abstract_method_error(
+                vm,
+                receiver_class_id,
+                resolved_method_id,
+            ))
+        }
+        (None, None) => {
+            throw_exception!(NoSuchMethodError, method_key: *method_key, class_sym: ma.get_class(&resolved_class_id).get_name())
+        }
+    }
+}
+
 #[inline]
 pub(super) fn handle_invokespecial(
     thread: &mut JavaThreadState,
@@ -1463,18 +1676,75 @@ pub(super) fn handle_invokespecial(
     let target_method_view = vm
         .method_area_read()
         .get_cp_by_method_id(&cur_frame_method_id)?
-        .get_method_view(&idx, vm.interner())?;
-    let target_class_id = vm
-        .method_area_write()
-        .get_class_id_or_load(target_method_view.class_sym, thread.id)?;
-    let target_method_id = vm
-        .method_area_read()
-        .get_instance_class(&target_class_id)?
-        .get_special_method_id(&target_method_view.name_and_type.into())?;
+        .get_method_or_interface_method_view(&idx, vm.interner())?;
+    let target_class_id =
+        vm.resolve_class(thread, &cur_frame_method_id, target_method_view.class_sym)?;
+    let target_method_id = select_special_method(
+        vm,
+        target_class_id,
+        &target_method_view.name_and_type.into(),
+    )?;
+    vm.check_method_access(
+        thread,
+        &cur_frame_method_id,
+        target_class_id,
+        target_method_id,
+    )?;
     let args = Interpreter::prepare_method_args(thread, target_method_id, vm)?;
     Interpreter::invoke_method_internal(thread, target_method_id, args, vm)
 }
 
+// an interface's own method, one of Object, or else the only non-abstract maximally-specific
+// superinterface method, like for Iface.super.m(), JVMS 6.5 invokespecial
+fn select_special_method(
+    vm: &VirtualMachine,
+    class_id: ClassId,
+    method_key: &MethodKey,
+) -> Result<MethodId, JvmError> {
+    let ma = vm.method_area_read();
+    let interface = match ma.get_class(&class_id) {
+        JvmClass::Interface(interface) => interface,
+        _ => {
+            return ma
+                .get_instance_class(&class_id)?
+                .get_special_method_id(method_key);
+        }
+    };
+    if let Some(method_id) = ma.resolve_interface_method_id(class_id, method_key)
+        && ma.get_method(&method_id).class_id() == class_id
+    {
+        return Ok(method_id);
+    }
+    if let Some(object_id) = interface.get_super()
+        && let Some(method_id) = ma
+            .get_instance_class(&object_id)?
+            .get_special_method_id_opt(method_key)
+    {
+        return Ok(method_id);
+    }
+    let candidates = ma.maximally_specific_methods(interface.get_interfaces()?, method_key);
+    let defaults = candidates
+        .iter()
+        .copied()
+        .filter(|method_id| !ma.get_method(method_id).is_abstract())
+        .collect::<Vec<_>>();
+    match (defaults.as_slice(), candidates.first()) {
+        ([method_id], _) => Ok(*method_id),
+        ([], Some(&method_id)) => {
+            drop(ma);
+            Err(abstract_method_error(vm, class_id, method_id))
+        }
+        ([], None) => {
+            throw_exception!(NoSuchMethodError, method_key: *method_key, class_sym: ma.get_class(&class_id).get_name())
+        }
+        (conflict, _) => {
+            let conflict = conflict.to_vec();
+            drop(ma);
+            Err(default_conflict_error(vm, &conflict))
+        }
+    }
+}
+
 #[inline]
 pub(super) fn handle_invokestatic(
     thread: &mut JavaThreadState,
@@ -1486,13 +1756,18 @@ pub(super) fn handle_invokestatic(
         .method_area_read()
         .get_cp_by_method_id(&cur_frame_method_id)?
         .get_method_or_interface_method_view(&idx, vm.interner())?;
-    let target_class_id = vm
-        .method_area_write()
-        .get_class_id_or_load(target_method_view.class_sym, thread.id)?;
-    Interpreter::ensure_initialized(thread, Some(target_class_id), vm)?;
+    let target_class_id =
+        vm.resolve_class(thread, &cur_frame_method_id, target_method_view.class_sym)?;
     let target_method_id = vm
         .method_area_read()
         .get_static_method_id(&target_class_id, target_method_view.name_and_type.into())?;
+    vm.check_method_access(
+        thread,
+        &cur_frame_method_id,
+        target_class_id,
+        target_method_id,
+    )?;
+    Interpreter::ensure_initialized(thread, Some(target_class_id), vm)?;
     let args = Interpreter::prepare_method_args(thread, target_method_id, vm)?;
     Interpreter::invoke_static_method(thread, target_method_id, vm, args)
 }
@@ -1703,15 +1978,22 @@ pub(super) fn handle_tableswitch(
 }
 
 #[inline]
-pub(super) fn handle_monitorenter(thread: &mut JavaThreadState) -> Result<(), JvmError> {
-    let _obj = thread.stack.pop_obj_val()?;
-    Ok(())
+pub(super) fn handle_monitorenter(
+    thread: &mut JavaThreadState,
+    vm: &VirtualMachine,
+) -> Result<(), JvmError> {
+    let obj = thread.stack.pop_obj_val()?;
+    let frame_index = thread.stack.frames().len() - 1;
+    vm.monitor_enter(thread, obj, frame_index)
 }
 
 #[inline]
-pub(super) fn handle_monitorexit(thread: &mut JavaThreadState) -> Result<(), JvmError> {
-    let _obj = thread.stack.pop_obj_val()?;
-    Ok(())
+pub(super) fn handle_monitorexit(
+    thread: &mut JavaThreadState,
+    vm: &VirtualMachine,
+) -> Result<(), JvmError> {
+    let obj = thread.stack.pop_obj_val()?;
+    vm.monitor_exit(thread, obj)
 }
 #[inline]
 pub(super) fn handle_multianewarray(
@@ -1727,8 +2009,7 @@ pub(super) fn handle_multianewarray(
             .method_area_read()
             .get_cp_by_method_id(&cur_frame_method_id)?
             .get_class_sym(&idx, vm.interner())?;
-        vm.method_area_write()
-            .get_class_id_or_load(class_name_sym, thread.id)?
+        vm.resolve_class(thread, &cur_frame_method_id, class_name_sym)?
     };
     let mut dimensions = (0..dimensions)
         .map(|_| thread.stack.pop_int_val())
@@ -2,12 +2,16 @@ use crate::error::JvmError;
 use crate::heap::HeapRef;
 use crate::interpreter::handlers::*;
 use crate::interpreter::return_handlers::*;
-use crate::keys::{ClassId, FieldKey};
+use crate::keys::ClassId;
 use crate::rt::{ClassLike, JvmClass};
 use crate::thread::JavaThreadState;
+use crate::thread::class_init::InitStep;
 use crate::vm::Value;
 use crate::vm::stack::{FrameType, JavaFrame, NativeFrame};
-use crate::{MethodId, VirtualMachine, build_exception, debug_log_instruction, error_log_method};
+use crate::{
+    MethodId, VirtualMachine, build_exception, debug_log_instruction, error_log_method,
+    throw_exception,
+};
 use jclass::attribute::method::ExceptionTableEntry;
 use jclass::prelude::Instruction;
 use std::ops::ControlFlow;
@@ -203,8 +207,8 @@ impl Interpreter {
             Instruction::Sastore => handle_sastore(thread, vm)?,
             Instruction::Sipush(value) => handle_sipush(thread, value)?,
             Instruction::TableSwitch(switch) => handle_tableswitch(thread, switch)?,
-            Instruction::Monitorenter => handle_monitorenter(thread)?,
-            Instruction::Monitorexit => handle_monitorexit(thread)?,
+            Instruction::Monitorenter => handle_monitorenter(thread, vm)?,
+            Instruction::Monitorexit => handle_monitorexit(thread, vm)?,
             Instruction::Multianewarray(idx, dimensions) => {
                 handle_multianewarray(thread, vm, idx, dimensions)?
             }
@@ -286,9 +290,11 @@ impl Interpreter {
             .get_cp_by_method_id(method_id)?
             .get_class_sym(&catch_type, vm.interner())?;
 
-        Ok(vm
-            .method_area_read()
-            .instance_of(exception_class_id, catch_type_sym))
+        let ma = vm.method_area_read();
+        let loader = ma
+            .get_class(&ma.get_method(method_id).class_id())
+            .get_loader();
+        Ok(ma.instance_of(exception_class_id, loader, catch_type_sym))
     }
 
     fn find_exception_handler(
@@ -328,24 +334,27 @@ impl Interpreter {
             // SAFETY: code_ptr is valid as long as method exists in method area (always)
             // need to use pointer to avoid borrow checker issues
             let code = unsafe { &*code_ptr };
+            if vm.scheduler.tick() {
+                vm.threads.publish_stack(thread);
+                vm.scheduler.yield_now(thread.id);
+            }
             let pc = thread.stack.pc()?;
             let instruction = Instruction::new_at(code, pc)?;
+            let is_branch = instruction.is_branch();
 
             match Self::interpret_instruction(thread, instruction, vm) {
                 Ok(flow) => {
                     if let ControlFlow::Break(res) = flow {
                         return Ok(res);
                     }
+                    // backward branch, a loop must not be able to hold off a safepoint
+                    if is_branch && thread.stack.pc()? <= pc {
+                        vm.safepoint_poll(thread);
+                    }
                 }
                 Err(e) => {
-                    let java_exception = match e {
-                        JvmError::JavaException(exception) => {
-                            vm.map_rust_error_to_java_exception(thread, exception)
-                        }
-                        JvmError::JavaExceptionThrown(exception_ref) => Ok(exception_ref),
-                        // TODO: this errors are not mapped yet or happened during mapping to java exception
-                        e => Err(e),
-                    }?;
+                    // TODO: other errors are not mapped yet or happened during mapping to java exception
+                    let java_exception = vm.exception_ref_of(thread, e)?;
                     if thread.stack.cur_frame()?.is_native() {
                         thread.stack.pop_native_frame()?;
                     }
@@ -434,15 +443,42 @@ impl Interpreter {
         args: Vec<Value>,
         vm: &VirtualMachine,
     ) -> Result<Option<Value>, JvmError> {
-        let is_native = {
+        vm.safepoint_poll(thread);
+        let (is_native, is_synchronized, is_static, class_id) = {
             let ma = vm.method_area_read();
-            ma.get_method(&method_id).is_native()
+            let method = ma.get_method(&method_id);
+            (
+                method.is_native(),
+                method.is_synchronized(),
+                method.is_static(),
+                method.class_id(),
+            )
         };
-        if is_native {
+        let monitor = if !is_synchronized {
+            None
+        } else if is_static {
+            Some(
+                vm.method_area_write()
+                    .get_mirror_ref_or_create(class_id, &vm.heap)?,
+            )
+        } else {
+            Some(args[0].as_obj_ref()?)
+        };
+        if let Some(monitor) = monitor {
+            // owned by the frame the method is about to push
+            let frame_index = thread.stack.frames().len();
+            vm.monitor_enter(thread, monitor, frame_index)?;
+        }
+        let res = if is_native {
             Self::invoke_native_method(thread, method_id, args, vm)
         } else {
             Self::invoke_java_method(thread, method_id, args, vm)
+        };
+        if let Some(monitor) = monitor {
+            vm.monitor_exit(thread, monitor)?;
         }
+        vm.safepoint_poll(thread);
+        res
     }
 
     fn invoke_method_internal(
@@ -458,17 +494,39 @@ impl Interpreter {
         Ok(())
     }
 
-    fn interface_needs_initialization(
-        interface_id: ClassId,
+    // the superinterfaces initialized with a class, JVMS 5.5: those declaring non-abstract,
+    // non-static methods, each one after its own superinterfaces
+    fn collect_interfaces_to_initialize(
+        class_id: ClassId,
         vm: &VirtualMachine,
-    ) -> Result<bool, JvmError> {
-        let has_clinit = {
-            let ma = vm.method_area_read();
-            let interface = ma.get_interface_class(&interface_id)?;
-            interface.has_clinit()
-        };
-
-        Ok(has_clinit) //TODO: || interface.has_non_constant_static_fields()?
+        interfaces: &mut Vec<ClassId>,
+    ) -> Result<(), JvmError> {
+        let mut direct_interfaces = vm
+            .method_area_read()
+            .get_class_like(&class_id)?
+            .get_direct_interfaces()?
+            .iter()
+            .copied()
+            .collect::<Vec<_>>();
+        // the order of the interfaces array, as far as the load order tells
+        direct_interfaces.sort();
+        for interface_id in direct_interfaces {
+            Self::collect_interfaces_to_initialize(interface_id, vm, interfaces)?;
+            let declares_default_methods = {
+                let ma = vm.method_area_read();
+                ma.get_interface_class(&interface_id)?
+                    .get_methods()
+                    .values()
+                    .any(|method_id| {
+                        let method = ma.get_method(method_id);
+                        !method.is_abstract() && !method.is_static()
+                    })
+            };
+            if declares_default_methods && !interfaces.contains(&interface_id) {
+                interfaces.push(interface_id);
+            }
+        }
+        Ok(())
     }
 
     fn run_clinit_if_exists(
@@ -485,6 +543,8 @@ impl Interpreter {
         Ok(())
     }
 
+    /// Initializes `class_id` the way JVMS 5.5 describes it: one thread runs the initialization
+    /// while others asking for it block, and a failed initialization leaves the class erroneous.
     pub fn ensure_initialized(
         thread: &mut JavaThreadState,
         class_id: Option<ClassId>,
@@ -493,28 +553,53 @@ impl Interpreter {
         let Some(class_id) = class_id else {
             return Ok(());
         };
+        vm.link_class(thread, class_id)?;
 
-        {
-            let ma = vm.method_area_read();
-            let class = ma.get_class_like(&class_id)?;
-
-            if class.is_initialized_or_initializing() {
-                return Ok(());
+        loop {
+            let step = {
+                let ma = vm.method_area_read();
+                vm.class_inits
+                    .begin(ma.get_class_like(&class_id)?, class_id, thread.id)
+            };
+            match step {
+                InitStep::Run => break,
+                InitStep::Done => return Ok(()),
+                InitStep::Wait => {
+                    vm.scheduler
+                        .block_until(thread.id, None, || !vm.class_inits.is_in_progress(class_id));
+                }
+                InitStep::Erroneous => {
+                    let name = vm.method_area_read().get_class(&class_id).get_name();
+                    throw_exception!(
+                        NoClassDefFoundError,
+                        "Could not initialize class {}",
+                        vm.interner().resolve(&name).replace('/', ".")
+                    )?
+                }
             }
-
-            class.set_initializing();
         }
 
-        let (is_instance, is_interface) = {
-            let ma = vm.method_area_read();
-            let jvm_class = ma.get_class(&class_id);
-            match jvm_class {
-                JvmClass::Instance(_) => (true, false),
-                JvmClass::Interface(_) => (false, true),
-                _ => (false, false),
-            }
-        };
+        let res = Self::initialize_class(thread, class_id, vm);
+        vm.class_inits.finish(
+            vm.method_area_read().get_class_like(&class_id)?,
+            class_id,
+            res.is_ok(),
+        );
+        vm.scheduler.notify_all();
+        res
+    }
 
+    // JVMS 5.5 steps 7 to 11, run by the thread that owns the initialization
+    fn initialize_class(
+        thread: &mut JavaThreadState,
+        class_id: ClassId,
+        vm: &VirtualMachine,
+    ) -> Result<(), JvmError> {
+        // interfaces do not initialize their superinterfaces
+        let is_instance = matches!(
+            vm.method_area_read().get_class(&class_id),
+            JvmClass::Instance(_)
+        );
         if is_instance {
             let super_id = {
                 let ma = vm.method_area_read();
@@ -524,54 +609,18 @@ impl Interpreter {
             if let Some(super_id) = super_id {
                 Self::ensure_initialized(thread, Some(super_id), vm)?;
             }
-            let interfaces = vm
-                .method_area_read()
-                .get_instance_class(&class_id)?
-                .get_interfaces()?
-                .clone(); // TODO: avoid clone?
+            let mut interfaces = Vec::new();
+            Self::collect_interfaces_to_initialize(class_id, vm, &mut interfaces)?;
             for interface_id in interfaces {
-                if Self::interface_needs_initialization(interface_id, vm)? {
-                    Self::ensure_initialized(thread, Some(interface_id), vm)?;
-                }
+                Self::ensure_initialized(thread, Some(interface_id), vm)?;
             }
-
-            Self::run_clinit_if_exists(thread, class_id, vm)?;
-
-            let cur_class_name = vm.method_area_read().get_instance_class(&class_id)?.name();
-
-            //TODO: stub
-            if vm.interner().resolve(&cur_class_name) == "jdk/internal/access/SharedSecrets" {
-                warn!(
-                    "TODO: Stub: Setting jdk/internal/access/SharedSecrets javaLangRefAccess to non-null value, to avoid NPEs"
-                );
-                let ref_access_fk = FieldKey {
-                    name: vm.interner().get_or_intern("javaLangRefAccess"),
-                    desc: vm
-                        .interner()
-                        .get_or_intern("Ljdk/internal/access/JavaLangRefAccess;"),
-                };
-                vm.method_area_read()
-                    .get_instance_class(&class_id)?
-                    .set_static_field_value(&ref_access_fk, Value::Ref(0))?;
-            }
-        } else if is_interface {
-            let interfaces = vm
-                .method_area_read()
-                .get_interface_class(&class_id)?
-                .get_interfaces()?
-                .clone(); // TODO: avoid clone?
-            for super_interface_id in interfaces {
-                if Self::interface_needs_initialization(super_interface_id, vm)? {
-                    Self::ensure_initialized(thread, Some(super_interface_id), vm)?;
-                }
-            }
-
-            Self::run_clinit_if_exists(thread, class_id, vm)?;
         }
 
-        vm.method_area_read()
-            .get_class_like(&class_id)?
-            .set_initialized();
+        if let Err(e) = Self::run_clinit_if_exists(thread, class_id, vm) {
+            return Err(JvmError::JavaExceptionThrown(
+                vm.exception_in_initializer_error(thread, e)?,
+            ));
+        }
         Ok(())
     }
 
@@ -596,4 +645,17 @@ impl Interpreter {
         Self::invoke_method_internal(thread, method_id, args, vm)?;
         Ok(())
     }
+
+    /// Like `invoke_static_method`, but hands the return value to the caller instead of pushing it
+    /// onto the caller's frame.
+    pub fn invoke_static_method_for_result(
+        thread: &mut JavaThreadState,
+        method_id: MethodId,
+        vm: &VirtualMachine,
+        args: Vec<Value>,
+    ) -> Result<Option<Value>, JvmError> {
+        let class_id = vm.method_area_read().get_method(&method_id).class_id();
+        Self::ensure_initialized(thread, Some(class_id), vm)?;
+        Self::invoke_method_core(thread, method_id, args, vm)
+    }
 }
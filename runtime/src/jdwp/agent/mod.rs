@@ -208,6 +208,10 @@ fn handle_command(
             debug.add_event_request(event_request);
             Ok(event_id.0.to_be_bytes().to_vec())
         }
+        JdwpCommand::VmSuspend => {
+            debug.suspend_all(None);
+            Ok(vec![])
+        }
         JdwpCommand::VmResume => {
             debug.resume_all();
             return Ok(None);
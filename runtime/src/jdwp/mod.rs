@@ -1,10 +1,11 @@
 use crate::jdwp::agent::command::{EventModifier, EventRequest};
 use crate::jdwp::class_matcher::ClassPatternMatcher;
 use crate::keys::{ClassId, MethodId, ThreadId};
+use crate::thread::safepoint::Safepoint;
 use dashmap::DashMap;
 use num_enum::TryFromPrimitive;
 use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
-use std::sync::{Condvar, Mutex, RwLock};
+use std::sync::{Arc, Condvar, Mutex, RwLock};
 use tokio::sync::mpsc::UnboundedSender;
 
 pub mod agent;
@@ -86,9 +87,7 @@ pub struct BreakpointLocation {
 }
 
 pub struct DebugState {
-    pub suspended: AtomicBool,
-    pub suspend_lock: Mutex<()>,
-    pub resume_signal: Condvar,
+    pub safepoint: Arc<Safepoint>,
 
     pub breakpoints: DashMap<BreakpointLocation, u32>,
     pub suspend_policies: DashMap<EventRequestId, SuspendPolicy>,
@@ -104,11 +103,9 @@ pub struct DebugState {
 }
 
 impl DebugState {
-    pub fn new(event_tx: UnboundedSender<DebugEvent>) -> Self {
+    pub fn new(event_tx: UnboundedSender<DebugEvent>, safepoint: Arc<Safepoint>) -> Self {
         Self {
-            suspended: AtomicBool::new(false),
-            suspend_lock: Mutex::new(()),
-            resume_signal: Condvar::new(),
+            safepoint,
             breakpoints: DashMap::new(),
             suspend_policies: DashMap::new(),
             class_prepare_events: RwLock::new(ClassPatternMatcher::new()),
@@ -126,26 +123,17 @@ impl DebugState {
     }
 
     pub fn resume_all(&self) {
-        self.suspended.store(false, Ordering::SeqCst);
-        self.resume_signal.notify_all();
+        self.safepoint.resume_all();
     }
 
-    pub fn suspend_all(&self) {
-        self.suspended.store(true, Ordering::SeqCst);
+    // returns once every thread except the requester is stopped at a safepoint or blocked
+    pub fn suspend_all(&self, requester: Option<ThreadId>) {
+        self.safepoint.suspend_all(requester);
     }
 
-    pub fn wait_if_suspended(&self) {
-        if !self.suspended.load(Ordering::Relaxed) {
-            return;
-        }
-
-        println!("Thread suspending");
-
-        let guard = self.suspend_lock.lock().unwrap();
-        let mut guard = guard;
-        while self.suspended.load(Ordering::SeqCst) {
-            guard = self.resume_signal.wait(guard).unwrap();
-        }
+    // for threads that are not executing bytecode, the rest stop at their safepoint polls
+    pub fn wait_if_suspended(&self, thread_id: ThreadId) {
+        self.safepoint.poll(thread_id);
     }
 
     pub fn wait_until_connected(&self) {
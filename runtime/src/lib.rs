@@ -6,13 +6,20 @@ use crate::jdwp::agent::start_jdwp_agent;
 use crate::jdwp::{DebugEvent, DebugState};
 use crate::keys::{MethodId, MethodKey, Symbol, ThreadId};
 use crate::native::NativeRegistry;
-use crate::thread::JavaThreadState;
+use crate::thread::class_init::ClassInitTable;
+use crate::thread::deadlock::start_deadlock_watchdog;
+use crate::thread::dump::start_signal_dispatcher;
+use crate::thread::monitor::MonitorTable;
+use crate::thread::safepoint::{Safepoint, SafepointState};
+use crate::thread::scheduler::Scheduler;
+use crate::thread::{JavaThreadState, ThreadEntry, ThreadRegistry, ThreadStatus};
 use crate::vm::Value;
 use crate::vm::bootstrap_registry::BootstrapRegistry;
-use crate::vm::stack::FrameStack;
+use common::jtype::AllocationType;
 use lasso::ThreadedRodeo;
 use std::path::PathBuf;
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, RwLock, Weak};
+use std::time::Duration;
 use tokio::sync::mpsc::unbounded_channel;
 
 mod class_loader;
@@ -25,29 +32,73 @@ pub mod log_traces;
 mod native;
 pub mod rt;
 mod thread;
+mod verifier;
 mod vm;
 
+pub use crate::class_loader::jar::{JarFile, Manifest};
+pub use crate::thread::scheduler::SchedulerMode;
+pub use crate::verifier::VerifyMode;
+
+/// The Java release whose class library the VM runs.
+pub const SUPPORTED_FEATURE_VERSION: u32 = 25;
+
 #[derive(Debug, Clone)]
 pub struct VmConfig {
     pub home: PathBuf,
     pub version: String,
     pub main_class: String,
     pub class_path: Vec<String>,
+    /// `-Xbootclasspath/a:`, directories and JARs whose classes the bootstrap loader defines
+    /// when the runtime image does not have them.
+    pub boot_class_path_append: Vec<String>,
     pub initial_heap_size: usize,
     pub max_heap_size: usize,
     pub frame_stack_size: usize,
     pub jdwp_port: Option<u16>,
+    pub scheduler: SchedulerMode,
+    pub deadlock_watchdog: Option<Duration>,
+    pub verify: VerifyMode,
+    /// `--patch-module`, a module name with the directories and JARs whose classes replace its
+    /// own.
+    pub patch_modules: Vec<(String, Vec<String>)>,
+    /// The `modules` directory of an exploded JDK build, read instead of `lib/modules`.
+    pub exploded_modules: Option<PathBuf>,
+    /// The archive the boot classes are read from when it matches the runtime image, and dumped
+    /// to with the classes of the run when it does not or misses some.
+    pub class_archive: Option<PathBuf>,
 }
 
 //TODO: make it better
 impl VmConfig {
-    pub fn validate(&self) {
-        if self.version != "25.0.1" {
-            panic!(
-                "Unsupported Java version: {}. Only 25.0.1 is supported.",
-                self.version
-            );
+    /// The feature release, e.g. 25 for "25.0.1".
+    pub fn feature_version(&self) -> u32 {
+        self.version
+            .split(['.', '-', '+'])
+            .next()
+            .and_then(|feature| feature.parse().ok())
+            .unwrap_or(0)
+    }
+
+    /// Whether the JDK at `home` is one the VM can run: any build of the supported feature
+    /// release, with a runtime image it can read.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.feature_version() != SUPPORTED_FEATURE_VERSION {
+            return Err(format!(
+                "Unsupported Java version {} in {}, only Java {SUPPORTED_FEATURE_VERSION} is supported.",
+                self.version,
+                self.home.display()
+            ));
+        }
+        if self.exploded_modules.is_none() {
+            let modules_path = self.home.join("lib").join("modules");
+            jimage::JImage::new(&modules_path).map_err(|e| {
+                format!(
+                    "Cannot use the runtime image {}: {e}",
+                    modules_path.display()
+                )
+            })?;
         }
+        Ok(())
     }
 }
 
@@ -59,6 +110,13 @@ pub struct VirtualMachine {
     string_interner: Arc<ThreadedRodeo>,
     br: Arc<BootstrapRegistry>,
     debug_state: Arc<DebugState>,
+    threads: ThreadRegistry,
+    scheduler: Scheduler,
+    safepoint: Arc<Safepoint>,
+    monitors: MonitorTable,
+    class_inits: ClassInitTable,
+    // needed to hand an owned VM to the OS threads started from Thread.start0
+    this: Weak<VirtualMachine>,
 }
 
 impl VirtualMachine {
@@ -66,9 +124,10 @@ impl VirtualMachine {
         config: VmConfig,
         string_interner: Arc<ThreadedRodeo>,
     ) -> Result<(Arc<Self>, JavaThreadState), ()> {
-        config.validate();
+        config.validate().map_err(|e| eprintln!("Error: {e}"))?;
         let (event_tx, event_rx) = unbounded_channel();
-        let debug_state = Arc::new(DebugState::new(event_tx));
+        let safepoint = Arc::new(Safepoint::new());
+        let debug_state = Arc::new(DebugState::new(event_tx, safepoint.clone()));
         let (method_area, br) =
             MethodArea::init(&config, string_interner.clone(), debug_state.clone()).map_err(
                 |e| {
@@ -83,7 +142,12 @@ impl VirtualMachine {
 
         let native_registry = NativeRegistry::new(string_interner.clone());
 
-        let vm = Arc::new(Self {
+        let threads = ThreadRegistry::new();
+        let main_thread_id = threads.next_thread_id();
+        let scheduler = Scheduler::new(config.scheduler, main_thread_id, safepoint.clone());
+        safepoint.register(main_thread_id, SafepointState::InJava);
+
+        let vm = Arc::new_cyclic(|this| Self {
             config,
             native_registry,
             string_interner: string_interner.clone(),
@@ -91,24 +155,35 @@ impl VirtualMachine {
             heap: RwLock::new(heap),
             br,
             debug_state: debug_state.clone(),
+            threads,
+            scheduler,
+            safepoint,
+            monitors: MonitorTable::new(),
+            class_inits: ClassInitTable::new(),
+            this: this.clone(),
         });
 
         #[cfg(feature = "log-runtime-traces")]
         log_traces::debug::init(&vm);
 
+        start_signal_dispatcher(&vm);
+        if let Some(interval) = vm.config.deadlock_watchdog {
+            start_deadlock_watchdog(&vm, interval);
+        }
+
         if let Some(jdwp_port) = vm.config.jdwp_port {
             start_jdwp_agent(vm.clone(), debug_state.clone(), event_rx, jdwp_port);
             debug_state.send_event(DebugEvent::VMStart);
-            debug_state.suspend_all(); //TODO: I assume always suspended at start (suspend=y)
+            debug_state.suspend_all(Some(main_thread_id)); //TODO: I assume always suspended at start (suspend=y)
 
             debug_state.wait_until_connected();
         }
 
         //TODO: I guess hotspot puts it just before main method invocation
         // that's why I don't stop in debugger in initPhase1 etc..
-        debug_state.wait_if_suspended();
+        debug_state.wait_if_suspended(main_thread_id);
 
-        let mut main_thread = vm.create_main_thread().map_err(|e| {
+        let mut main_thread = vm.create_main_thread(main_thread_id).map_err(|e| {
             eprintln!("Error: Could not initialize JVM.");
             eprintln!("Caused by: {}", e.into_pretty_string(&string_interner));
         })?;
@@ -133,6 +208,19 @@ impl VirtualMachine {
              */
         })?;
 
+        vm.initialize_app_class_loader(&mut main_thread)
+            .map_err(|e| {
+                eprintln!("Error: Could not initialize JVM.");
+                eprintln!("Caused by: {}", e.into_pretty_string(&string_interner));
+            })?;
+
+        // initPhase3 reports nothing itself, HotSpot prints whatever it threw
+        vm.initialize_system_class_loader(&mut main_thread)
+            .map_err(|e| {
+                eprintln!("Error occurred during initialization of VM");
+                vm.print_stack_trace(&mut main_thread, e);
+            })?;
+
         Ok((vm, main_thread))
     }
 
@@ -175,10 +263,16 @@ impl VirtualMachine {
                 Value::Ref(main_thread.name),
             ],
         )?;
+        self.set_thread_status(main_thread.thread_obj, ThreadStatus::Runnable)?;
+        self.set_thread_eetop(main_thread.thread_obj, main_thread.id.as_usize() as i64)?;
+        self.threads.add(
+            main_thread.id,
+            ThreadEntry::new(false, main_thread.thread_obj, main_thread.name),
+        );
         Ok(())
     }
 
-    fn create_main_thread(&self) -> Result<JavaThreadState, JvmError> {
+    fn create_main_thread(&self, id: ThreadId) -> Result<JavaThreadState, JvmError> {
         let thread_class_id = self.br().get_java_lang_thread_id()?;
         let thread_instance_size = self
             .method_area_read()
@@ -190,16 +284,121 @@ impl VirtualMachine {
         let main_string_ref = self
             .heap_write()
             .get_str_from_pool_or_new(self.br().main_sym)?;
-        let thread = JavaThreadState {
-            id: ThreadId::from_index(0), // TODO: hardcoded for main thread
-            thread_obj: main_thread_ref,
-            group_obj: 0,
-            name: main_string_ref,
-            stack: FrameStack::new(&self.config),
-        };
+        let thread = JavaThreadState::new(id, main_thread_ref, main_string_ref, &self.config);
         Ok(thread)
     }
 
+    /// Backs Thread.start0: registers the thread and spawns the OS thread that runs it.
+    fn start_java_thread(&self, thread_obj: HeapRef) -> Result<(), JvmError> {
+        let vm = self
+            .this
+            .upgrade()
+            .ok_or(JvmError::Todo("VM is already destroyed".to_string()))?;
+        let id = self.threads.next_thread_id();
+        let daemon = self.is_daemon_thread(thread_obj)?;
+        let name = {
+            let thread_class_id = self.br().get_java_lang_thread_id()?;
+            let offset = self
+                .method_area_read()
+                .get_instance_class(&thread_class_id)?
+                .get_instance_field(&self.br().thread_name_fk)?
+                .offset;
+            self.heap_read()
+                .read_field(thread_obj, offset, AllocationType::Reference)?
+                .as_obj_ref()?
+        };
+        let os_thread_name = self.heap_read().get_rust_string_from_java_string(name)?;
+
+        self.set_thread_status(thread_obj, ThreadStatus::Runnable)?;
+        self.set_thread_eetop(thread_obj, id.as_usize() as i64)?;
+        self.threads
+            .add(id, ThreadEntry::new(daemon, thread_obj, name));
+        self.safepoint.register(id, SafepointState::Blocked);
+        self.scheduler.register(id);
+
+        let thread = JavaThreadState::new(id, thread_obj, name, &self.config);
+        std::thread::Builder::new()
+            .name(os_thread_name)
+            .spawn(move || vm.run_java_thread(thread))
+            .map_err(|e| {
+                self.threads.remove(id);
+                self.safepoint.unregister(id);
+                self.scheduler.detach(id);
+                JvmError::Todo(format!("unable to create native thread: {e}"))
+            })?;
+        Ok(())
+    }
+
+    fn run_java_thread(&self, mut thread: JavaThreadState) {
+        self.scheduler.attach(thread.id);
+        self.safepoint.leave_safe_region(thread.id);
+
+        let run_method_id = self
+            .heap_read()
+            .get_class_id(thread.thread_obj)
+            .and_then(|class_id| {
+                self.method_area_read()
+                    .get_class(&class_id)
+                    .get_vtable_method_id(&self.br().thread_run_mk)
+            });
+        let thread_obj = thread.thread_obj;
+        let res = run_method_id.and_then(|run_method_id| {
+            Interpreter::invoke_instance_method(
+                &mut thread,
+                run_method_id,
+                self,
+                vec![Value::Ref(thread_obj)],
+            )
+        });
+        if let Err(e) = res {
+            self.unhandled_exception(&mut thread, e);
+        }
+
+        if let Err(e) = self.exit_java_thread(&mut thread) {
+            eprintln!(
+                "Error: Could not terminate thread: {}",
+                e.into_pretty_string(&self.string_interner)
+            );
+        }
+        self.threads.remove(thread.id);
+        self.safepoint.unregister(thread.id);
+        self.scheduler.detach(thread.id);
+    }
+
+    // Thread.exit() and then what HotSpot calls ensure_join: mark the thread dead and wake joiners
+    fn exit_java_thread(&self, thread: &mut JavaThreadState) -> Result<(), JvmError> {
+        let thread_class_id = self.br().get_java_lang_thread_id()?;
+        let exit_method_id = self
+            .method_area_read()
+            .get_instance_class(&thread_class_id)?
+            .get_special_method_id(&self.br().thread_exit_mk)?;
+        if let Err(e) = Interpreter::invoke_instance_method(
+            thread,
+            exit_method_id,
+            self,
+            vec![Value::Ref(thread.thread_obj)],
+        ) {
+            self.unhandled_exception(thread, e);
+        }
+
+        self.threads.publish_stack(thread);
+        self.monitors
+            .enter(&self.scheduler, thread.id, thread.thread_obj)?;
+        self.set_thread_status(thread.thread_obj, ThreadStatus::Terminated)?;
+        self.set_thread_eetop(thread.thread_obj, 0)?;
+        self.monitors.notify_all(thread.id, thread.thread_obj)?;
+        self.monitors
+            .exit(&self.scheduler, thread.id, thread.thread_obj)
+    }
+
+    // what HotSpot does in DestroyJavaVM
+    fn wait_for_non_daemon_threads(&self, main_thread: &JavaThreadState) {
+        self.threads.publish_stack(main_thread);
+        self.scheduler.block_until(main_thread.id, None, || {
+            !self.threads.has_non_daemon_threads_except(main_thread.id)
+        });
+    }
+
     fn create_system_thread_group(
         &self,
         main_thread: &mut JavaThreadState,
@@ -266,7 +465,13 @@ impl VirtualMachine {
 
         let init_phase1_method_key = self.br().system_init_phase1_mk;
         let init_phase2_method_key = self.br().system_init_phase2_mk;
-        let init_phase3_method_key = self.br().system_init_phase3_mk;
+
+        // HotSpot gets here through java.lang.ref.Finalizer: Reference's <clinit> hands
+        // JavaLangRefAccess to SharedSecrets, which initPhase1 relies on
+        let reference_class_id = self
+            .method_area_write()
+            .get_class_id_or_load(self.br().java_lang_ref_reference_sym, thread.id)?;
+        Interpreter::ensure_initialized(thread, Some(reference_class_id), self)?;
 
         // Run initPhase1
 
@@ -277,25 +482,115 @@ impl VirtualMachine {
 
         Interpreter::invoke_static_method(thread, init_phase1_method_id, self, vec![])?;
 
-        // Run initPhase2
-        /*
-               let init_phase2_method_id = self
-                   .method_area
-                   .get_instance_class(&system_class_id)?
-                   .get_special_method_id(&init_phase2_method_key)?;
+        // Run initPhase2, ModuleBootstrap.boot defines the boot layer. Failures are reported by
+        // initPhase2 itself, HotSpot then exits without a message of its own
 
-               Interpreter::invoke_static_method(
-                   thread_id,
-                   init_phase2_method_id,
-                   self,
-                   vec![Value::Integer(1), Value::Integer(1)],
-               )?;
+        let init_phase2_method_id = self
+            .method_area_read()
+            .get_instance_class(&system_class_id)?
+            .get_special_method_id(&init_phase2_method_key)?;
 
-        */
+        let result = Interpreter::invoke_static_method_for_result(
+            thread,
+            init_phase2_method_id,
+            self,
+            vec![Value::Integer(1), Value::Integer(1)],
+        )?;
+        if result != Some(Value::Integer(0)) {
+            return Err(JvmError::Todo(
+                "System.initPhase2 failed to initialize the boot layer".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
 
+    // the built-in app class loader, from now on the class path is no longer served by the
+    // bootstrap loader
+    fn initialize_app_class_loader(&self, thread: &mut JavaThreadState) -> Result<(), JvmError> {
+        let class_loaders_id = self
+            .method_area_write()
+            .get_class_id_or_load(self.br().jdk_internal_loader_class_loaders_sym, thread.id)?;
+        let app_class_loader_method_id = self.method_area_read().get_static_method_id(
+            &class_loaders_id,
+            self.br().class_loaders_app_class_loader_mk,
+        )?;
+        let app_class_loader = Interpreter::invoke_static_method_for_result(
+            thread,
+            app_class_loader_method_id,
+            self,
+            vec![],
+        )?
+        .ok_or(JvmError::Todo(
+            "ClassLoaders.appClassLoader returned nothing".to_string(),
+        ))?
+        .as_obj_ref()?;
+        self.method_area_write()
+            .set_app_class_loader(app_class_loader);
         Ok(())
     }
 
+    // initPhase3 creates the system class loader, which is the app class loader unless
+    // java.system.class.loader names another one, and brings the VM to init level 4
+    fn initialize_system_class_loader(&self, thread: &mut JavaThreadState) -> Result<(), JvmError> {
+        let system_class_id = self.br().get_java_lang_system_id()?;
+        let init_phase3_method_id = self
+            .method_area_read()
+            .get_instance_class(&system_class_id)?
+            .get_special_method_id(&self.br().system_init_phase3_mk)?;
+        Interpreter::invoke_static_method(thread, init_phase3_method_id, self, vec![])?;
+
+        let class_loader_id = self
+            .method_area_write()
+            .get_class_id_or_load(self.br().java_lang_class_loader_sym, thread.id)?;
+        let get_system_class_loader_method_id = self.method_area_read().get_static_method_id(
+            &class_loader_id,
+            self.br().class_loader_get_system_class_loader_mk,
+        )?;
+        let system_class_loader = Interpreter::invoke_static_method_for_result(
+            thread,
+            get_system_class_loader_method_id,
+            self,
+            vec![],
+        )?
+        .ok_or(JvmError::Todo(
+            "ClassLoader.getSystemClassLoader returned nothing".to_string(),
+        ))?
+        .as_obj_ref()?;
+        self.method_area_write()
+            .set_system_class_loader(system_class_loader);
+        Ok(())
+    }
+
+    fn print_stack_trace(&self, thread: &mut JavaThreadState, error: JvmError) {
+        let exception = match self.exception_ref_of(thread, error) {
+            Ok(exception) => exception,
+            Err(e) => {
+                eprintln!("{}", e.into_pretty_string(self.interner()));
+                return;
+            }
+        };
+        let print_stack_trace = self
+            .heap_read()
+            .get_class_id(exception)
+            .and_then(|class_id| {
+                self.method_area_read()
+                    .get_class(&class_id)
+                    .get_vtable_method_id(&self.br().print_stack_trace_mk)
+            })
+            .and_then(|method_id| {
+                Interpreter::invoke_instance_method(
+                    thread,
+                    method_id,
+                    self,
+                    vec![Value::Ref(exception)],
+                )
+            });
+        if let Err(e) = print_stack_trace {
+            eprintln!("{}", e.into_pretty_string(self.interner()));
+        }
+    }
+
     // TODO: refactor and improve error handling. ideally can't fail
     //TODO: exception arg should be actually JvmError, like any error
     fn map_rust_error_to_java_exception(
@@ -339,6 +634,70 @@ impl VirtualMachine {
         Ok(instance)
     }
 
+    /// The Java object behind `error`, allocated first if the VM raised it natively.
+    pub(crate) fn exception_ref_of(
+        &self,
+        thread: &mut JavaThreadState,
+        error: JvmError,
+    ) -> Result<HeapRef, JvmError> {
+        match error {
+            JvmError::JavaException(exception) => {
+                self.map_rust_error_to_java_exception(thread, exception)
+            }
+            JvmError::JavaExceptionThrown(exception_ref) => Ok(exception_ref),
+            e => Err(e),
+        }
+    }
+
+    /// JVMS 5.5: a `<clinit>` completing abruptly with anything but an `Error` is rethrown as an
+    /// `ExceptionInInitializerError` carrying it.
+    pub(crate) fn exception_in_initializer_error(
+        &self,
+        thread: &mut JavaThreadState,
+        error: JvmError,
+    ) -> Result<HeapRef, JvmError> {
+        let exception = self.exception_ref_of(thread, error)?;
+        let class_id = self.heap_read().get_class_id(exception)?;
+        if self
+            .method_area_read()
+            .instance_of(class_id, None, self.br().java_lang_error_sym)
+        {
+            return Ok(exception);
+        }
+        self.new_throwable_with_cause(
+            thread,
+            self.br().java_lang_exception_in_initializer_error_sym,
+            exception,
+        )
+    }
+
+    fn new_throwable_with_cause(
+        &self,
+        thread: &mut JavaThreadState,
+        class_sym: Symbol,
+        cause: HeapRef,
+    ) -> Result<HeapRef, JvmError> {
+        let class_id = self
+            .method_area_write()
+            .get_class_id_or_load(class_sym, thread.id)?;
+        let (method_id, instance_size) = {
+            let ma = self.method_area_read();
+            let class = ma.get_instance_class(&class_id)?;
+            (
+                class.get_special_method_id(&self.br().throwable_cause_constructor_mk)?,
+                class.get_instance_size()?,
+            )
+        };
+        let instance = self.heap_write().alloc_instance(instance_size, class_id)?;
+        Interpreter::invoke_instance_method(
+            thread,
+            method_id,
+            self,
+            vec![Value::Ref(instance), Value::Ref(cause)],
+        )?;
+        Ok(instance)
+    }
+
     //TODO: exception should be allocated on java heap at this point, and be a reference
     //TODO: get rid of unwrap, need to understand how to handle errors here properly
     fn unhandled_exception(&self, thread: &mut JavaThreadState, exception: JvmError) {
@@ -379,6 +738,24 @@ impl VirtualMachine {
         }
     }
 
+    /// Stops every Java thread but `requester` at a safepoint and runs `op` while they are stopped.
+    /// `requester` is `None` when called from a thread that doesn't run Java code.
+    pub fn handshake<R, F>(&self, requester: Option<ThreadId>, op: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        self.safepoint.handshake(requester, op)
+    }
+
+    /// Stops `thread` at a safepoint if one is requested, publishing its stack for the operation.
+    #[inline]
+    pub(crate) fn safepoint_poll(&self, thread: &JavaThreadState) {
+        if self.safepoint.is_requested() {
+            self.threads.publish_stack(thread);
+            self.safepoint.poll(thread.id);
+        }
+    }
+
     pub fn interner(&self) -> &ThreadedRodeo {
         &self.string_interner
     }
@@ -428,9 +805,10 @@ pub fn start(config: VmConfig) -> Result<(), ()> {
     log_traces::debug::init(&vm);
 
     let main_class_sym = vm.string_interner.get_or_intern(&vm.config.main_class);
+    // LauncherHelper.loadMainClass: Class.forName(mainClass, false, ClassLoader.getSystemClassLoader())
+    let system_class_loader = vm.method_area_read().system_class_loader();
     let main_class_id = vm
-        .method_area_write()
-        .get_class_id_or_load(main_class_sym, main_thread.id)
+        .load_class_with(&mut main_thread, system_class_loader, main_class_sym)
         .map_err(|e| {
             eprintln!(
                 "Error: Could not find or load main class {}",
@@ -449,11 +827,15 @@ pub fn start(config: VmConfig) -> Result<(), ()> {
 
     // TODO: it works more or less correctly, but should be improved
     let res = Interpreter::invoke_static_method(&mut main_thread, main_method_id, &mut vm, vec![]);
-    vm.debug_state.send_event(DebugEvent::VMDeath);
+    let is_ok = res.is_ok();
     if let Err(e) = res {
         vm.unhandled_exception(&mut main_thread, e);
-        Err(())
-    } else {
-        Ok(())
     }
+    vm.wait_for_non_daemon_threads(&main_thread);
+    if let Err(e) = vm.method_area_read().dump_class_archive() {
+        eprintln!("Warning: Could not write the class archive: {e}");
+    }
+    vm.threads.remove(main_thread.id);
+    vm.debug_state.send_event(DebugEvent::VMDeath);
+    if is_ok { Ok(()) } else { Err(()) }
 }
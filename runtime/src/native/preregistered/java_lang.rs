@@ -1,11 +1,12 @@
 use crate::keys::{ClassId, FullyQualifiedMethodKey};
 use crate::native::{NativeRegistry, NativeRet};
-use crate::thread::JavaThreadState;
+use crate::thread::{JavaThreadState, ThreadStatus};
 use crate::vm::Value;
 use crate::vm::stack::FrameType;
 use crate::{MethodId, VirtualMachine, throw_exception};
 use common::jtype::AllocationType;
 use jclass::prelude::ArrayType;
+use std::time::Duration;
 use tracing_log::log::debug;
 
 pub(super) fn do_register_java_lang_preregistered_natives(native_registry: &mut NativeRegistry) {
@@ -84,6 +85,24 @@ pub(super) fn do_register_java_lang_preregistered_natives(native_registry: &mut
         ),
         java_lang_object_notify_all,
     );
+    native_registry.register(
+        FullyQualifiedMethodKey::new_with_str(
+            "java/lang/Object",
+            "notify",
+            "()V",
+            &native_registry.string_interner,
+        ),
+        java_lang_object_notify,
+    );
+    native_registry.register(
+        FullyQualifiedMethodKey::new_with_str(
+            "java/lang/Object",
+            "wait0",
+            "(J)V",
+            &native_registry.string_interner,
+        ),
+        java_lang_object_wait_0,
+    );
     native_registry.register(
         FullyQualifiedMethodKey::new_with_str(
             "java/lang/StackTraceElement",
@@ -190,12 +209,16 @@ fn java_lang_object_get_class(
         //TODO: refactor and rethink how I handle array classes and their mirrors
         //right now I put on heap for arrays the class id of the element type, but the mirror has to be of the array type
         if vm.heap_read().is_array(object_ref)? {
-            let class_name_sym = vm.method_area_read().get_class(&class_id).get_name();
+            let (class_name_sym, loader) = {
+                let ma = vm.method_area_read();
+                let class = ma.get_class(&class_id);
+                (class.get_name(), class.get_loader())
+            };
             let raw_name = vm.interner().resolve(&class_name_sym);
             let array_name = format!("[L{};", raw_name);
             let array_class_name_sym = vm.interner().get_or_intern(&array_name);
             vm.method_area_write()
-                .load_array_class(array_class_name_sym, thread.id)?
+                .load_array_class(loader, array_class_name_sym, thread.id)?
         } else {
             class_id
         }
@@ -242,14 +265,14 @@ fn java_lang_throwable_fill_in_stack_trace(
                 .get_method(&frame.method_id())
                 .class_id();
             !vm.method_area_read()
-                .instance_of(class_id, vm.br().java_lang_throwable_sym)
+                .instance_of(class_id, None, vm.br().java_lang_throwable_sym)
         })
         .cloned() // TODO: very bad clone
         .collect();
     frames.reverse();
-    let int_arr_class = vm
-        .method_area_write()
-        .load_array_class(vm.br().int_array_desc, thread.id)?;
+    let int_arr_class =
+        vm.method_area_write()
+            .load_array_class(None, vm.br().int_array_desc, thread.id)?;
     let class_id_array = vm.heap_write().alloc_primitive_array(
         int_arr_class,
         ArrayType::Int,
@@ -502,11 +525,57 @@ fn java_lang_stack_trace_element_init_stack_trace_elements(
 }
 
 fn java_lang_object_notify_all(
-    _vm: &VirtualMachine,
-    _thread: &mut JavaThreadState,
-    _args: &[Value],
+    vm: &VirtualMachine,
+    thread: &mut JavaThreadState,
+    args: &[Value],
 ) -> NativeRet {
-    debug!("TODO: Stub: java.lang.Object.notifyAll");
+    let obj = args[0].as_obj_ref()?;
+    vm.monitors.notify_all(thread.id, obj)?;
+    Ok(None)
+}
+
+fn java_lang_object_notify(
+    vm: &VirtualMachine,
+    thread: &mut JavaThreadState,
+    args: &[Value],
+) -> NativeRet {
+    let obj = args[0].as_obj_ref()?;
+    vm.monitors.notify(thread.id, obj)?;
+    Ok(None)
+}
+
+fn java_lang_object_wait_0(
+    vm: &VirtualMachine,
+    thread: &mut JavaThreadState,
+    args: &[Value],
+) -> NativeRet {
+    let obj = args[0].as_obj_ref()?;
+    let timeout_millis = args[1].as_long()?;
+    if timeout_millis < 0 {
+        throw_exception!(IllegalArgumentException, "timeout value is negative")?;
+    }
+    let timeout = (timeout_millis > 0).then(|| Duration::from_millis(timeout_millis as u64));
+    let thread_obj = thread.thread_obj;
+    if !vm.monitors.holds_lock(thread.id, obj) {
+        throw_exception!(IllegalMonitorStateException, "current thread is not owner")?;
+    }
+
+    let status = if timeout.is_some() {
+        ThreadStatus::InObjectWaitTimed
+    } else {
+        ThreadStatus::InObjectWait
+    };
+    let interrupted = vm.is_thread_interrupted(thread_obj)?
+        || vm.with_thread_status(thread, status, || {
+            vm.monitors
+                .wait(&vm.scheduler, thread.id, obj, timeout, || {
+                    vm.is_thread_interrupted(thread_obj).unwrap_or(false)
+                })
+        })??;
+    if interrupted {
+        vm.clear_thread_interrupted(thread_obj)?;
+        throw_exception!(InterruptedException)?;
+    }
     Ok(None)
 }
 
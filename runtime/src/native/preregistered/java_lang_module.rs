@@ -1,9 +1,14 @@
-use crate::VirtualMachine;
+use crate::error::JvmError;
+use crate::heap::HeapRef;
 use crate::keys::FullyQualifiedMethodKey;
 use crate::native::{NativeRegistry, NativeRet};
+use crate::rt::module::{ExportTarget, ModuleRef, NamedModule};
 use crate::thread::JavaThreadState;
 use crate::vm::Value;
-use tracing_log::log::debug;
+use crate::{VirtualMachine, build_exception, throw_exception};
+use common::jtype::AllocationType;
+
+const JAVA_BASE: &str = "java.base";
 
 pub(super) fn do_register_java_lang_module_preregistered_natives(
     native_registry: &mut NativeRegistry,
@@ -16,14 +21,405 @@ pub(super) fn do_register_java_lang_module_preregistered_natives(
             &native_registry.string_interner,
         ),
         java_lang_module_define_module_0,
-    )
+    );
+    native_registry.register(
+        FullyQualifiedMethodKey::new_with_str(
+            "java/lang/Module",
+            "addReads0",
+            "(Ljava/lang/Module;Ljava/lang/Module;)V",
+            &native_registry.string_interner,
+        ),
+        java_lang_module_add_reads_0,
+    );
+    native_registry.register(
+        FullyQualifiedMethodKey::new_with_str(
+            "java/lang/Module",
+            "addExports0",
+            "(Ljava/lang/Module;Ljava/lang/String;Ljava/lang/Module;)V",
+            &native_registry.string_interner,
+        ),
+        java_lang_module_add_exports_0,
+    );
+    native_registry.register(
+        FullyQualifiedMethodKey::new_with_str(
+            "java/lang/Module",
+            "addExportsToAll0",
+            "(Ljava/lang/Module;Ljava/lang/String;)V",
+            &native_registry.string_interner,
+        ),
+        java_lang_module_add_exports_to_all_0,
+    );
+    native_registry.register(
+        FullyQualifiedMethodKey::new_with_str(
+            "java/lang/Module",
+            "addExportsToAllUnnamed0",
+            "(Ljava/lang/Module;Ljava/lang/String;)V",
+            &native_registry.string_interner,
+        ),
+        java_lang_module_add_exports_to_all_unnamed_0,
+    );
+    native_registry.register(
+        FullyQualifiedMethodKey::new_with_str(
+            "jdk/internal/loader/BootLoader",
+            "setBootLoaderUnnamedModule0",
+            "(Ljava/lang/Module;)V",
+            &native_registry.string_interner,
+        ),
+        jdk_internal_loader_boot_loader_set_boot_loader_unnamed_module_0,
+    );
 }
 
+// HotSpot's Modules::define_module, java.base included
 fn java_lang_module_define_module_0(
-    _vm: &VirtualMachine,
+    vm: &VirtualMachine,
     _thread: &mut JavaThreadState,
-    _args: &[Value],
+    args: &[Value],
 ) -> NativeRet {
-    debug!("Stub: java/lang/Module.defineModule0()");
+    let module = args[0]
+        .as_nullable_obj_ref()?
+        .ok_or_else(|| build_exception!(NullPointerException, "Null module object"))?;
+    let (name, loader) = module_name_and_loader(vm, module)?;
+    let Some(name) = name else {
+        return Err(build_exception!(
+            IllegalArgumentException,
+            "Module name cannot be null"
+        ));
+    };
+    let is_open = args[1].as_int()? != 0;
+    let packages = package_names(vm, &args[4], &name)?;
+    if name == JAVA_BASE {
+        return define_java_base(vm, module, loader, &packages);
+    }
+
+    let is_platform = match loader {
+        Some(loader) => is_platform_loader(vm, loader)?,
+        None => false,
+    };
+    if let Some(loader) = loader
+        && !is_platform
+        && let Some(package) = packages
+            .iter()
+            .find(|package| *package == "java" || package.starts_with("java/"))
+    {
+        let ma = vm.method_area_read();
+        return Err(build_exception!(
+            IllegalArgumentException,
+            "Class loader (instance of): {} tried to define prohibited package name: {}",
+            vm.loader_name(&ma, loader),
+            package.replace('/', ".")
+        ));
+    }
+
+    let name_sym = vm.interner().get_or_intern(&name);
+    let package_syms = packages
+        .iter()
+        .map(|package| vm.interner().get_or_intern(package))
+        .collect::<Vec<_>>();
+    let mut ma = vm.method_area_write();
+    if ma.modules().find(loader, name_sym).is_some() {
+        return Err(build_exception!(
+            IllegalStateException,
+            "Module {name} is already defined"
+        ));
+    }
+    for (package, package_sym) in packages.iter().zip(&package_syms) {
+        if let Some(other) = ma.modules().package_module(loader, *package_sym) {
+            let other = ma
+                .modules()
+                .get(&other)
+                .map(|other| vm.interner().resolve(&other.name))
+                .unwrap_or_default();
+            return Err(build_exception!(
+                IllegalStateException,
+                "Package {package} for module {name} is already in another module, {other}, defined to the class loader"
+            ));
+        }
+    }
+    if is_platform && let Some(loader) = loader {
+        ma.modules_mut().set_platform_loader(loader);
+    }
+    ma.modules_mut().define(
+        module,
+        NamedModule::new(name_sym, loader, is_open),
+        &package_syms,
+        false,
+    );
+    Ok(None)
+}
+
+fn define_java_base(
+    vm: &VirtualMachine,
+    module: HeapRef,
+    loader: Option<HeapRef>,
+    packages: &[String],
+) -> NativeRet {
+    if loader.is_some() {
+        return Err(build_exception!(
+            IllegalArgumentException,
+            "Class loader must be the boot class loader"
+        ));
+    }
+    let package_syms = packages
+        .iter()
+        .map(|package| vm.interner().get_or_intern(package))
+        .collect::<Vec<_>>();
+    let mut ma = vm.method_area_write();
+    if ma.modules().java_base().is_some() {
+        return Err(build_exception!(
+            InternalError,
+            "Module {JAVA_BASE} is already defined"
+        ));
+    }
+    let name_sym = vm.interner().get_or_intern(JAVA_BASE);
+    ma.modules_mut().define(
+        module,
+        NamedModule::new(name_sym, None, false),
+        &package_syms,
+        true,
+    );
+    // Object.class.getModule() is java.base from now on, Module.defineModules relies on it
+    ma.patch_boot_mirror_modules(&vm.heap)?;
     Ok(None)
 }
+
+// HotSpot's add_reads_module, a null `to` stands for all unnamed modules
+fn java_lang_module_add_reads_0(
+    vm: &VirtualMachine,
+    _thread: &mut JavaThreadState,
+    args: &[Value],
+) -> NativeRet {
+    let from = args[0]
+        .as_nullable_obj_ref()?
+        .ok_or_else(|| build_exception!(NullPointerException, "from_module is null"))?;
+    let from_module = module_ref(vm, from)?;
+    let to_module = args[1]
+        .as_nullable_obj_ref()?
+        .map(|to| module_ref(vm, to))
+        .transpose()?;
+    if let ModuleRef::Named(from) = from_module
+        && Some(from_module) != to_module
+    {
+        vm.method_area_write()
+            .modules_mut()
+            .add_read(from, to_module);
+    }
+    Ok(None)
+}
+
+fn java_lang_module_add_exports_0(
+    vm: &VirtualMachine,
+    _thread: &mut JavaThreadState,
+    args: &[Value],
+) -> NativeRet {
+    let to = args[2]
+        .as_nullable_obj_ref()?
+        .ok_or_else(|| build_exception!(NullPointerException, "to_module is null"))?;
+    let to = module_ref(vm, to)?;
+    add_exports(
+        vm,
+        &args[0],
+        &args[1],
+        ExportTarget::Module(to),
+        "from_module",
+    )
+}
+
+fn java_lang_module_add_exports_to_all_0(
+    vm: &VirtualMachine,
+    _thread: &mut JavaThreadState,
+    args: &[Value],
+) -> NativeRet {
+    add_exports(vm, &args[0], &args[1], ExportTarget::All, "from_module")
+}
+
+fn java_lang_module_add_exports_to_all_unnamed_0(
+    vm: &VirtualMachine,
+    _thread: &mut JavaThreadState,
+    args: &[Value],
+) -> NativeRet {
+    add_exports(vm, &args[0], &args[1], ExportTarget::AllUnnamed, "module")
+}
+
+// HotSpot's add_module_exports, `role` is how its messages call the exporting module.
+// Unnamed and open modules export all their packages already.
+fn add_exports(
+    vm: &VirtualMachine,
+    module: &Value,
+    package: &Value,
+    to: ExportTarget,
+    role: &str,
+) -> NativeRet {
+    let module = module
+        .as_nullable_obj_ref()?
+        .ok_or_else(|| build_exception!(NullPointerException, "{role} is null"))?;
+    let package = package
+        .as_nullable_obj_ref()?
+        .ok_or_else(|| build_exception!(NullPointerException, "package is null"))?;
+    let ModuleRef::Named(module) = module_ref(vm, module)? else {
+        return Ok(None);
+    };
+    if matches!(to, ExportTarget::Module(ModuleRef::Named(to)) if to == module) {
+        return Ok(None);
+    }
+    let package = vm
+        .heap_read()
+        .get_rust_string_from_java_string(package)?
+        .replace('.', "/");
+    let mut ma = vm.method_area_write();
+    let Some(named) = ma.modules().get(&module) else {
+        return Ok(None);
+    };
+    if named.is_open {
+        return Ok(None);
+    }
+    let (name, loader) = (vm.interner().resolve(&named.name), named.loader);
+    let package_sym = vm.interner().get(&package);
+    let owner = package_sym.and_then(|package| ma.modules().package_module(loader, package));
+    match owner {
+        None => throw_exception!(
+            IllegalArgumentException,
+            "Package {package} not found in {role} {name}"
+        ),
+        Some(owner) if owner != module => {
+            let owner = ma
+                .modules()
+                .get(&owner)
+                .map(|owner| vm.interner().resolve(&owner.name))
+                .unwrap_or_default();
+            throw_exception!(
+                IllegalArgumentException,
+                "Package: {package} found in module {owner}, not in {role}: {name}"
+            )
+        }
+        Some(_) => {
+            let package_sym = package_sym.expect("the package has a module");
+            ma.modules_mut().export(loader, package_sym, to);
+            Ok(None)
+        }
+    }
+}
+
+// HotSpot's set_bootloader_unnamed_module, where the bootstrap loader's classes outside the
+// named modules are
+fn jdk_internal_loader_boot_loader_set_boot_loader_unnamed_module_0(
+    vm: &VirtualMachine,
+    _thread: &mut JavaThreadState,
+    args: &[Value],
+) -> NativeRet {
+    let module = args[0]
+        .as_nullable_obj_ref()?
+        .ok_or_else(|| build_exception!(NullPointerException, "Null module object"))?;
+    let (name, loader) = module_name_and_loader(vm, module)?;
+    if name.is_some() {
+        return Err(build_exception!(
+            IllegalArgumentException,
+            "boot loader's unnamed module's java.lang.Module has a name"
+        ));
+    }
+    if loader.is_some() {
+        return Err(build_exception!(
+            IllegalArgumentException,
+            "Class loader must be the boot class loader"
+        ));
+    }
+    let mut ma = vm.method_area_write();
+    ma.modules_mut().set_boot_unnamed(module);
+    ma.patch_boot_mirror_modules(&vm.heap)?;
+    Ok(None)
+}
+
+// HotSpot's get_module_entry, a module that was never defined is the unnamed module of its loader
+fn module_ref(vm: &VirtualMachine, module: HeapRef) -> Result<ModuleRef, JvmError> {
+    let (_, loader) = module_name_and_loader(vm, module)?;
+    if vm.method_area_read().modules().get(&module).is_some() {
+        Ok(ModuleRef::Named(module))
+    } else {
+        Ok(ModuleRef::Unnamed { loader })
+    }
+}
+
+// Module.name and Module.loader
+fn module_name_and_loader(
+    vm: &VirtualMachine,
+    module: HeapRef,
+) -> Result<(Option<String>, Option<HeapRef>), JvmError> {
+    let ma = vm.method_area_read();
+    let heap = vm.heap_read();
+    let class_id = heap.get_class_id(module)?;
+    if ma.get_class(&class_id).get_name() != vm.br().java_lang_module_sym {
+        return Err(build_exception!(
+            IllegalArgumentException,
+            "module is not an instance of type java.lang.Module"
+        ));
+    }
+    let name_offset = ma
+        .get_instance_field(&class_id, &vm.br().module_name_fk)?
+        .offset;
+    let loader_offset = ma
+        .get_instance_field(&class_id, &vm.br().module_loader_fk)?
+        .offset;
+    let name = heap
+        .read_field(module, name_offset, AllocationType::Reference)?
+        .as_nullable_obj_ref()?
+        .map(|name| heap.get_rust_string_from_java_string(name))
+        .transpose()?;
+    let loader = heap
+        .read_field(module, loader_offset, AllocationType::Reference)?
+        .as_nullable_obj_ref()?;
+    Ok((name, loader))
+}
+
+// the package names of defineModule0 in internal form, checked like HotSpot's
+// verify_package_name
+fn package_names(
+    vm: &VirtualMachine,
+    packages: &Value,
+    module_name: &str,
+) -> Result<Vec<String>, JvmError> {
+    let Some(packages) = packages.as_nullable_obj_ref()? else {
+        return Ok(Vec::new());
+    };
+    let heap = vm.heap_read();
+    let mut names = Vec::new();
+    for index in 0..heap.get_array_length(packages)? {
+        let Some(package) = heap
+            .read_array_element(packages, index)?
+            .as_nullable_obj_ref()?
+        else {
+            return Err(build_exception!(
+                IllegalArgumentException,
+                "Bad package name"
+            ));
+        };
+        let package_class_id = heap.get_class_id(package)?;
+        if package_class_id != vm.br().get_java_lang_string_id()? {
+            return Err(build_exception!(
+                IllegalArgumentException,
+                "Bad package name"
+            ));
+        }
+        let package = heap
+            .get_rust_string_from_java_string(package)?
+            .replace('.', "/");
+        let is_valid = !package.is_empty()
+            && !package.starts_with('/')
+            && !package.ends_with('/')
+            && !package.contains("//")
+            && !package.contains([';', '[']);
+        if !is_valid {
+            return Err(build_exception!(
+                IllegalArgumentException,
+                "Invalid package name: {package} for module: {module_name}"
+            ));
+        }
+        names.push(package);
+    }
+    Ok(names)
+}
+
+fn is_platform_loader(vm: &VirtualMachine, loader: HeapRef) -> Result<bool, JvmError> {
+    let ma = vm.method_area_read();
+    let loader_class_id = vm.heap_read().get_class_id(loader)?;
+    Ok(ma.get_class(&loader_class_id).get_name()
+        == vm.br().jdk_internal_loader_platform_class_loader_sym)
+}
@@ -142,29 +142,48 @@ fn jdk_internal_util_system_props_raw_vm_properties(
     let string_class = vm
         .method_area_write()
         .get_class_id_or_load(string_class_sym, thread.id)?;
+    let mut properties = vec![
+        (
+            "java.home".to_string(),
+            vm.config.home.to_str().unwrap().to_string(),
+        ),
+        (
+            "sun.nio.PageAlignDirectMemory".to_string(),
+            "false".to_string(),
+        ),
+        (
+            "java.class.path".to_string(),
+            vm.config.class_path.join(":"),
+        ),
+    ];
+    // read by ClassLoaders to find the resources of the appended boot class path
+    if !vm.config.boot_class_path_append.is_empty() {
+        properties.push((
+            "jdk.boot.class.path.append".to_string(),
+            vm.config.boot_class_path_append.join(":"),
+        ));
+    }
+    // read by ModuleBootstrap to patch the modules of the boot layer
+    for (i, (module, path)) in vm.config.patch_modules.iter().enumerate() {
+        properties.push((
+            format!("jdk.module.patch.{i}"),
+            format!("{module}={}", path.join(":")),
+        ));
+    }
     //TODO: same here, it needs a registry for common interned strings
-    let h = vm.heap_write().alloc_object_array(string_class, 4)?;
-    let java_home_key = vm
-        .heap_write()
-        .get_str_from_pool_or_new(vm.interner().get_or_intern("java.home"))?;
-    let java_home_value = vm.heap_write().get_str_from_pool_or_new(
-        vm.interner()
-            .get_or_intern(vm.config.home.to_str().unwrap()),
-    )?;
-    let sun_page_align_stub = vm
-        .heap_write()
-        .get_str_from_pool_or_new(vm.interner().get_or_intern("sun.nio.PageAlignDirectMemory"))?;
-    let false_str = vm
+    let h = vm
         .heap_write()
-        .get_str_from_pool_or_new(vm.interner().get_or_intern("false"))?;
-    vm.heap_write()
-        .write_array_element(h, 0, Value::Ref(java_home_key))?;
-    vm.heap_write()
-        .write_array_element(h, 1, Value::Ref(java_home_value))?;
-    vm.heap_write()
-        .write_array_element(h, 2, Value::Ref(sun_page_align_stub))?;
-    vm.heap_write()
-        .write_array_element(h, 3, Value::Ref(false_str))?;
+        .alloc_object_array(string_class, properties.len() as i32 * 2)?;
+    for (i, (key, value)) in (0..).zip(&properties) {
+        let key = vm
+            .heap_write()
+            .get_str_from_pool_or_new(vm.interner().get_or_intern(key))?;
+        let value = vm.heap_write().alloc_string(value)?;
+        vm.heap_write()
+            .write_array_element(h, i * 2, Value::Ref(key))?;
+        vm.heap_write()
+            .write_array_element(h, i * 2 + 1, Value::Ref(value))?;
+    }
     Ok(Some(Value::Ref(h)))
 }
 
@@ -1,9 +1,10 @@
 use crate::error::JvmError;
+use crate::interpreter::Interpreter;
 use crate::keys::FullyQualifiedMethodKey;
 use crate::native::NativeRet;
 use crate::thread::JavaThreadState;
 use crate::vm::Value;
-use crate::{ThreadId, VirtualMachine};
+use crate::{ThreadId, VirtualMachine, throw_exception};
 use common::jtype::AllocationType;
 use tracing_log::log::debug;
 
@@ -62,6 +63,36 @@ pub(super) fn java_lang_class_register_natives(
         java_lang_class_is_array,
     );
 
+    vm.native_registry.register(
+        FullyQualifiedMethodKey::new_with_str(
+            "java/lang/Class",
+            "isHidden",
+            "()Z",
+            &vm.string_interner,
+        ),
+        java_lang_class_is_hidden,
+    );
+
+    vm.native_registry.register(
+        FullyQualifiedMethodKey::new_with_str(
+            "java/lang/Class",
+            "isInstance",
+            "(Ljava/lang/Object;)Z",
+            &vm.string_interner,
+        ),
+        java_lang_class_is_instance,
+    );
+
+    vm.native_registry.register(
+        FullyQualifiedMethodKey::new_with_str(
+            "java/lang/Class",
+            "getNestHost0",
+            "()Ljava/lang/Class;",
+            &vm.string_interner,
+        ),
+        java_lang_class_get_nest_host_0,
+    );
+
     vm.native_registry.register(
         FullyQualifiedMethodKey::new_with_str(
             "java/lang/Class",
@@ -89,10 +120,56 @@ pub(super) fn java_lang_class_register_natives(
         ),
         java_lang_class_is_assignable_from,
     );
+    // newer Class.java versions dropped the trailing caller class
+    for desc in [
+        "(Ljava/lang/String;ZLjava/lang/ClassLoader;Ljava/lang/Class;)Ljava/lang/Class;",
+        "(Ljava/lang/String;ZLjava/lang/ClassLoader;)Ljava/lang/Class;",
+    ] {
+        vm.native_registry.register(
+            FullyQualifiedMethodKey::new_with_str(
+                "java/lang/Class",
+                "forName0",
+                desc,
+                &vm.string_interner,
+            ),
+            java_lang_class_for_name_0,
+        );
+    }
 
     Ok(None)
 }
 
+fn java_lang_class_for_name_0(
+    vm: &VirtualMachine,
+    thread: &mut JavaThreadState,
+    args: &[Value],
+) -> NativeRet {
+    let Some(name_ref) = args[0].as_nullable_obj_ref()? else {
+        throw_exception!(NullPointerException)?
+    };
+    let name = vm.heap_read().get_rust_string_from_java_string(name_ref)?;
+    let initialize = args[1].as_int()? != 0;
+    let loader = args[2].as_nullable_obj_ref()?;
+
+    // binary names use dots, `java/lang/String` must not find java.lang.String
+    if name.is_empty() || name.contains('/') {
+        throw_exception!(ClassNotFoundException, "{name}")?
+    }
+    let name_sym = vm.interner().get_or_intern(name.replace('.', "/"));
+    let class_id = vm.load_class_with(thread, loader, name_sym)?;
+    if vm.method_area_read().get_class(&class_id).is_primitive() {
+        throw_exception!(ClassNotFoundException, "{name}")?
+    }
+    if initialize {
+        Interpreter::ensure_initialized(thread, Some(class_id), vm)?;
+    }
+
+    let mirror = vm
+        .method_area_write()
+        .get_mirror_ref_or_create(class_id, &vm.heap)?;
+    Ok(Some(Value::Ref(mirror)))
+}
+
 fn java_lang_class_is_assignable_from(
     vm: &VirtualMachine,
     _thread: &mut JavaThreadState,
@@ -143,6 +220,62 @@ fn java_lang_class_is_interface(
     Ok(Some(Value::Integer(if is_interface { 1 } else { 0 })))
 }
 
+fn java_lang_class_is_hidden(
+    vm: &VirtualMachine,
+    _thread: &mut JavaThreadState,
+    args: &[Value],
+) -> NativeRet {
+    let mirror_ref = args
+        .first()
+        .ok_or(JvmError::Todo(
+            "java.lang.Class.isHidden: missing 0 argument".to_string(),
+        ))?
+        .as_obj_ref()?;
+    let ma = vm.method_area_read();
+    let target_class_id = ma.get_class_id_by_mirror(&mirror_ref)?;
+    Ok(Some(Value::Integer(ma.is_hidden(&target_class_id) as i32)))
+}
+
+fn java_lang_class_is_instance(
+    vm: &VirtualMachine,
+    _thread: &mut JavaThreadState,
+    args: &[Value],
+) -> NativeRet {
+    let mirror_ref = args[0].as_obj_ref()?;
+    let Some(object) = args[1].as_nullable_obj_ref()? else {
+        return Ok(Some(Value::Integer(0)));
+    };
+    let object_class_id = vm.heap_read().get_class_id(object)?;
+    let ma = vm.method_area_read();
+    let this_class_id = ma.get_class_id_by_mirror(&mirror_ref)?;
+    let is_instance = ma.is_assignable_from(this_class_id, object_class_id);
+    Ok(Some(Value::Integer(is_instance as i32)))
+}
+
+// a class whose nest host fails to validate is its own host, JVMS 5.4.4
+fn java_lang_class_get_nest_host_0(
+    vm: &VirtualMachine,
+    thread: &mut JavaThreadState,
+    args: &[Value],
+) -> NativeRet {
+    let mirror_ref = args[0].as_obj_ref()?;
+    let class_id = vm.method_area_read().get_class_id_by_mirror(&mirror_ref)?;
+    let host = if vm
+        .method_area_read()
+        .get_class(&class_id)
+        .as_class_like()
+        .is_ok()
+    {
+        vm.nest_host(thread, class_id)?
+    } else {
+        class_id
+    };
+    let host_mirror = vm
+        .method_area_write()
+        .get_mirror_ref_or_create(host, &vm.heap)?;
+    Ok(Some(Value::Ref(host_mirror)))
+}
+
 fn java_lang_class_is_array(
     vm: &VirtualMachine,
     _thread: &mut JavaThreadState,
@@ -215,15 +348,25 @@ fn java_lang_class_init_class_name(
     let class_class_id = vm.br.get_java_lang_class_id()?;
     let class_name_fk = vm.br.class_name_fk;
     let target_class_id = vm.method_area_read().get_class_id_by_mirror(&mirror_ref)?;
-    let name_sym = vm.method_area_read().get_class(&target_class_id).get_name();
-    let name_ref = vm
-        .heap_write()
-        .alloc_string_from_interned_with_char_mapping(
-            name_sym,
-            Some(&|c| {
-                if c == '/' { '.' } else { c }
-            }),
-        )?;
+    let (name_sym, is_hidden) = {
+        let ma = vm.method_area_read();
+        (
+            ma.get_class(&target_class_id).get_name(),
+            ma.is_hidden(&target_class_id),
+        )
+    };
+    let name_ref = if is_hidden {
+        let name = vm.method_area_read().external_name(&target_class_id);
+        vm.heap_write().alloc_string(&name)?
+    } else {
+        vm.heap_write()
+            .alloc_string_from_interned_with_char_mapping(
+                name_sym,
+                Some(&|c| {
+                    if c == '/' { '.' } else { c }
+                }),
+            )?
+    };
     let name_field_offset = {
         let ma = vm.method_area_read();
         ma.get_instance_field(&class_class_id, &class_name_fk)?
@@ -1,22 +1,285 @@
+use crate::error::{JavaExceptionKind, JvmError};
+use crate::heap::HeapRef;
+use crate::interpreter::Interpreter;
+use crate::keys::{ClassId, FullyQualifiedMethodKey};
 use crate::native::NativeRet;
 use crate::thread::JavaThreadState;
 use crate::vm::Value;
-use crate::{ThreadId, VirtualMachine};
+use crate::{VirtualMachine, build_exception};
+use common::jtype::AllocationType;
+use jclass::prelude::ArrayType;
+use tracing_log::log::debug;
+
+// java.lang.invoke.MethodHandleNatives.Constants.NESTMATE_CLASS and HIDDEN_CLASS
+const NESTMATE_CLASS: i32 = 0x1;
+const HIDDEN_CLASS: i32 = 0x2;
 
 pub(super) fn java_lang_class_loader_register_natives(
     vm: &VirtualMachine,
     _thread: &mut JavaThreadState,
     _args: &[Value],
 ) -> NativeRet {
+    vm.native_registry.register(
+        FullyQualifiedMethodKey::new_with_str(
+            "java/lang/ClassLoader",
+            "defineClass0",
+            "(Ljava/lang/ClassLoader;Ljava/lang/Class;Ljava/lang/String;[BIILjava/security/ProtectionDomain;ZILjava/lang/Object;)Ljava/lang/Class;",
+            &vm.string_interner,
+        ),
+        java_lang_class_loader_define_class_0,
+    );
+    vm.native_registry.register(
+        FullyQualifiedMethodKey::new_with_str(
+            "java/lang/ClassLoader",
+            "defineClass1",
+            "(Ljava/lang/ClassLoader;Ljava/lang/String;[BIILjava/security/ProtectionDomain;Ljava/lang/String;)Ljava/lang/Class;",
+            &vm.string_interner,
+        ),
+        java_lang_class_loader_define_class_1,
+    );
+    vm.native_registry.register(
+        FullyQualifiedMethodKey::new_with_str(
+            "java/lang/ClassLoader",
+            "findLoadedClass0",
+            "(Ljava/lang/String;)Ljava/lang/Class;",
+            &vm.string_interner,
+        ),
+        java_lang_class_loader_find_loaded_class_0,
+    );
+    vm.native_registry.register(
+        FullyQualifiedMethodKey::new_with_str(
+            "java/lang/ClassLoader",
+            "findBootstrapClass",
+            "(Ljava/lang/String;)Ljava/lang/Class;",
+            &vm.string_interner,
+        ),
+        java_lang_class_loader_find_bootstrap_class,
+    );
+    vm.native_registry.register(
+        FullyQualifiedMethodKey::new_with_str(
+            "java/lang/ClassLoader",
+            "retrieveDirectives",
+            "()Ljava/lang/AssertionStatusDirectives;",
+            &vm.string_interner,
+        ),
+        java_lang_class_loader_retrieve_directives,
+    );
     Ok(None)
 }
 
-/*
-fn java_lang_class_loader_(
+fn java_lang_class_loader_define_class_0(
+    vm: &VirtualMachine,
+    thread: &mut JavaThreadState,
+    args: &[Value],
+) -> NativeRet {
+    let loader = args[0].as_nullable_obj_ref()?;
+    let name = java_string_arg(vm, &args[2])?;
+    let bytes = byte_array_range(
+        vm,
+        args[3].as_obj_ref()?,
+        args[4].as_int()?,
+        args[5].as_int()?,
+    )?;
+    let initialize = args[7].as_int()? != 0;
+    let flags = args[8].as_int()?;
+
+    let class_id = if flags & HIDDEN_CLASS != 0 {
+        let lookup = args[1].as_obj_ref()?;
+        define_hidden_class(vm, thread, loader, lookup, bytes, flags, args[9])?
+    } else {
+        vm.define_class(thread, loader, name.as_deref(), bytes)?
+    };
+    if initialize {
+        Interpreter::ensure_initialized(thread, Some(class_id), vm)?;
+    }
+    mirror_of(vm, class_id)
+}
+
+// a nestmate joins the nest of the lookup class, JVMS 5.4.4
+fn define_hidden_class(
+    vm: &VirtualMachine,
+    thread: &mut JavaThreadState,
+    loader: Option<HeapRef>,
+    lookup: HeapRef,
+    bytes: Vec<u8>,
+    flags: i32,
+    class_data: Value,
+) -> Result<ClassId, JvmError> {
+    let class_id = vm.define_hidden_class(thread, loader, bytes)?;
+    if flags & NESTMATE_CLASS != 0 {
+        let lookup_class_id = vm.method_area_read().get_class_id_by_mirror(&lookup)?;
+        let host = vm.nest_host(thread, lookup_class_id)?;
+        vm.method_area_read()
+            .get_class_like(&class_id)?
+            .set_nest_host(host, None);
+    }
+    let mirror = vm
+        .method_area_write()
+        .get_mirror_ref_or_create(class_id, &vm.heap)?;
+    set_class_data(vm, mirror, class_data)?;
+    Ok(class_id)
+}
+
+// Class.classData, what MethodHandles.classData hands out
+fn set_class_data(vm: &VirtualMachine, mirror: HeapRef, class_data: Value) -> Result<(), JvmError> {
+    let offset = vm
+        .method_area_read()
+        .get_instance_field(
+            &vm.br().get_java_lang_class_id()?,
+            &vm.br().class_class_data_fk,
+        )?
+        .offset;
+    vm.heap_write()
+        .write_field(mirror, offset, class_data, AllocationType::Reference)
+}
+
+fn java_lang_class_loader_define_class_1(
+    vm: &VirtualMachine,
+    thread: &mut JavaThreadState,
+    args: &[Value],
+) -> NativeRet {
+    let loader = args[0].as_nullable_obj_ref()?;
+    let name = java_string_arg(vm, &args[1])?;
+    let bytes = byte_array_range(
+        vm,
+        args[2].as_obj_ref()?,
+        args[3].as_int()?,
+        args[4].as_int()?,
+    )?;
+
+    let class_id = vm.define_class(thread, loader, name.as_deref(), bytes)?;
+    mirror_of(vm, class_id)
+}
+
+fn java_lang_class_loader_find_loaded_class_0(
+    vm: &VirtualMachine,
+    _thread: &mut JavaThreadState,
+    args: &[Value],
+) -> NativeRet {
+    let loader = args[0].as_obj_ref()?;
+    let Some(name) = java_string_arg(vm, &args[1])? else {
+        return Ok(Some(Value::Null));
+    };
+    if name.starts_with('[') {
+        return Ok(Some(Value::Null));
+    }
+    let name_sym = vm.interner().get_or_intern(name.replace('.', "/"));
+
+    // only a lookup, BuiltinClassLoader defines class path classes itself through defineClass1
+    let class_id = vm
+        .method_area_read()
+        .find_loaded_class(Some(loader), name_sym);
+    match class_id {
+        Some(class_id) => mirror_of(vm, class_id),
+        None => Ok(Some(Value::Null)),
+    }
+}
+
+fn java_lang_class_loader_find_bootstrap_class(
+    vm: &VirtualMachine,
+    thread: &mut JavaThreadState,
+    args: &[Value],
+) -> NativeRet {
+    let Some(name) = java_string_arg(vm, &args[0])? else {
+        return Ok(Some(Value::Null));
+    };
+    let name_sym = vm.interner().get_or_intern(name.replace('.', "/"));
+    match not_found_as_none(vm.load_class_with(thread, None, name_sym))? {
+        Some(class_id) => mirror_of(vm, class_id),
+        None => Ok(Some(Value::Null)),
+    }
+}
+
+fn java_lang_class_loader_retrieve_directives(
     vm: &VirtualMachine,
     thread: &mut JavaThreadState,
     _args: &[Value],
 ) -> NativeRet {
-    Ok(Some(Value::Ref(vm.get_thread(&thread_id).thread_obj)))
+    debug!("TODO: Stub: java.lang.ClassLoader.retrieveDirectives, no -ea/-da options yet");
+    let directives_class_id = vm
+        .method_area_write()
+        .get_class_id_or_load(vm.br().java_lang_assertion_status_directives_sym, thread.id)?;
+    let string_class_id = vm.br().get_java_lang_string_id()?;
+    let boolean_array_class_id = vm
+        .method_area_write()
+        .get_class_id_or_load(vm.interner().get_or_intern("[Z"), thread.id)?;
+    let instance_size = vm
+        .method_area_read()
+        .get_instance_class(&directives_class_id)?
+        .get_instance_size()?;
+    let directives = vm
+        .heap_write()
+        .alloc_instance(instance_size, directives_class_id)?;
+
+    for (name, is_boolean_array) in [
+        ("classes", false),
+        ("classEnabled", true),
+        ("packages", false),
+        ("packageEnabled", true),
+    ] {
+        let offset = vm
+            .method_area_read()
+            .get_instance_class(&directives_class_id)?
+            .get_instance_field_by_name(&vm.interner().get_or_intern(name))?
+            .offset;
+        let array = if is_boolean_array {
+            vm.heap_write()
+                .alloc_primitive_array(boolean_array_class_id, ArrayType::Boolean, 0)?
+        } else {
+            vm.heap_write().alloc_object_array(string_class_id, 0)?
+        };
+        vm.heap_write().write_field(
+            directives,
+            offset,
+            Value::Ref(array),
+            AllocationType::Reference,
+        )?;
+    }
+    Ok(Some(Value::Ref(directives)))
+}
+
+fn java_string_arg(vm: &VirtualMachine, arg: &Value) -> Result<Option<String>, JvmError> {
+    arg.as_nullable_obj_ref()?
+        .map(|name| vm.heap_read().get_rust_string_from_java_string(name))
+        .transpose()
+}
+
+fn byte_array_range(
+    vm: &VirtualMachine,
+    array: HeapRef,
+    off: i32,
+    len: i32,
+) -> Result<Vec<u8>, JvmError> {
+    let heap = vm.heap_read();
+    let bytes = heap.get_byte_array_slice(array)?;
+    let range = usize::try_from(off)
+        .ok()
+        .zip(usize::try_from(len).ok())
+        .map(|(off, len)| off..off.saturating_add(len))
+        .filter(|range| range.end <= bytes.len())
+        .ok_or_else(|| {
+            build_exception!(
+                ArrayIndexOutOfBoundsException,
+                "Range [{off}, {off} + {len}) out of bounds for length {}",
+                bytes.len()
+            )
+        })?;
+    Ok(bytes[range].iter().map(|b| *b as u8).collect())
+}
+
+fn not_found_as_none(res: Result<ClassId, JvmError>) -> Result<Option<ClassId>, JvmError> {
+    match res {
+        Ok(class_id) => Ok(Some(class_id)),
+        Err(JvmError::JavaException(e)) if e.kind == JavaExceptionKind::ClassNotFoundException => {
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+fn mirror_of(vm: &VirtualMachine, class_id: ClassId) -> NativeRet {
+    let mirror = vm
+        .method_area_write()
+        .get_mirror_ref_or_create(class_id, &vm.heap)?;
+    Ok(Some(Value::Ref(mirror)))
 }
- */
@@ -59,12 +59,11 @@ pub(super) fn java_lang_system_register_natives(
 }
 
 fn java_lang_system_nano_time(
-    _vm: &VirtualMachine,
+    vm: &VirtualMachine,
     _thread: &mut JavaThreadState,
     _args: &[Value],
 ) -> NativeRet {
-    debug!("TODO: Stub: java.lang.System.nanoTime");
-    Ok(Some(Value::Long(0)))
+    Ok(Some(Value::Long(vm.scheduler.nano_time())))
 }
 
 fn java_lang_system_set_out_0(
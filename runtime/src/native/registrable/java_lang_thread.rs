@@ -1,8 +1,13 @@
+use crate::heap::Heap;
 use crate::keys::FullyQualifiedMethodKey;
 use crate::native::NativeRet;
-use crate::thread::JavaThreadState;
+use crate::thread::{JavaThreadState, ThreadStatus};
 use crate::vm::Value;
-use crate::{ThreadId, VirtualMachine};
+use crate::{VirtualMachine, throw_exception};
+use common::jtype::AllocationType;
+use jclass::prelude::ArrayType;
+use std::time::Duration;
+use tracing_log::log::debug;
 
 pub(super) fn java_lang_thread_register_natives(
     vm: &VirtualMachine,
@@ -18,6 +23,78 @@ pub(super) fn java_lang_thread_register_natives(
         ),
         java_lang_thread_current_thread,
     );
+    vm.native_registry.register(
+        FullyQualifiedMethodKey::new_with_str(
+            "java/lang/Thread",
+            "start0",
+            "()V",
+            &vm.string_interner,
+        ),
+        java_lang_thread_start_0,
+    );
+    vm.native_registry.register(
+        FullyQualifiedMethodKey::new_with_str(
+            "java/lang/Thread",
+            "yield0",
+            "()V",
+            &vm.string_interner,
+        ),
+        java_lang_thread_yield_0,
+    );
+    vm.native_registry.register(
+        FullyQualifiedMethodKey::new_with_str(
+            "java/lang/Thread",
+            "sleepNanos0",
+            "(J)V",
+            &vm.string_interner,
+        ),
+        java_lang_thread_sleep_nanos_0,
+    );
+    vm.native_registry.register(
+        FullyQualifiedMethodKey::new_with_str(
+            "java/lang/Thread",
+            "interrupt0",
+            "()V",
+            &vm.string_interner,
+        ),
+        java_lang_thread_interrupt_0,
+    );
+    vm.native_registry.register(
+        FullyQualifiedMethodKey::new_with_str(
+            "java/lang/Thread",
+            "holdsLock",
+            "(Ljava/lang/Object;)Z",
+            &vm.string_interner,
+        ),
+        java_lang_thread_holds_lock,
+    );
+    vm.native_registry.register(
+        FullyQualifiedMethodKey::new_with_str(
+            "java/lang/Thread",
+            "setPriority0",
+            "(I)V",
+            &vm.string_interner,
+        ),
+        java_lang_thread_set_priority_0,
+    );
+    vm.native_registry.register(
+        FullyQualifiedMethodKey::new_with_str(
+            "java/lang/Thread",
+            "getNextThreadIdOffset",
+            "()J",
+            &vm.string_interner,
+        ),
+        java_lang_thread_get_next_thread_id_offset,
+    );
+    vm.native_registry.register(
+        FullyQualifiedMethodKey::new_with_str(
+            "java/lang/Thread",
+            "ensureMaterializedForStackWalk",
+            "(Ljava/lang/Object;)V",
+            &vm.string_interner,
+        ),
+        java_lang_thread_ensure_materialized_for_stack_walk,
+    );
     Ok(None)
 }
 
@@ -28,3 +105,108 @@ fn java_lang_thread_current_thread(
 ) -> NativeRet {
     Ok(Some(Value::Ref(thread.thread_obj)))
 }
+
+fn java_lang_thread_start_0(
+    vm: &VirtualMachine,
+    _thread: &mut JavaThreadState,
+    args: &[Value],
+) -> NativeRet {
+    let thread_obj = args[0].as_obj_ref()?;
+    vm.start_java_thread(thread_obj)?;
+    Ok(None)
+}
+
+fn java_lang_thread_yield_0(
+    vm: &VirtualMachine,
+    thread: &mut JavaThreadState,
+    _args: &[Value],
+) -> NativeRet {
+    vm.threads.publish_stack(thread);
+    vm.scheduler.yield_now(thread.id);
+    Ok(None)
+}
+
+fn java_lang_thread_sleep_nanos_0(
+    vm: &VirtualMachine,
+    thread: &mut JavaThreadState,
+    args: &[Value],
+) -> NativeRet {
+    let nanos = args[0].as_long()?;
+    let thread_obj = thread.thread_obj;
+    let interrupted = vm.with_thread_status(thread, ThreadStatus::Sleeping, || {
+        vm.scheduler.block_until(
+            thread.id,
+            Some(Duration::from_nanos(nanos.max(0) as u64)),
+            || vm.is_thread_interrupted(thread_obj).unwrap_or(false),
+        )
+    })?;
+    if interrupted {
+        vm.clear_thread_interrupted(thread_obj)?;
+        throw_exception!(InterruptedException, "sleep interrupted")?;
+    }
+    Ok(None)
+}
+
+fn java_lang_thread_interrupt_0(
+    vm: &VirtualMachine,
+    _thread: &mut JavaThreadState,
+    _args: &[Value],
+) -> NativeRet {
+    // the interrupted field is already set by Thread.interrupt, only need to wake the target up
+    vm.scheduler.notify_all();
+    Ok(None)
+}
+
+fn java_lang_thread_holds_lock(
+    vm: &VirtualMachine,
+    thread: &mut JavaThreadState,
+    args: &[Value],
+) -> NativeRet {
+    let obj = args[0].as_obj_ref()?;
+    let holds = vm.monitors.holds_lock(thread.id, obj);
+    Ok(Some(Value::Integer(holds as i32)))
+}
+
+fn java_lang_thread_set_priority_0(
+    _vm: &VirtualMachine,
+    _thread: &mut JavaThreadState,
+    _args: &[Value],
+) -> NativeRet {
+    debug!("TODO: Stub: java.lang.Thread.setPriority0");
+    Ok(None)
+}
+
+/// Thread.ThreadIdentifiers reads and bumps the counter with Unsafe and a null base, so the
+/// "address" returned here is a heap offset of a long[1] allocated for that purpose.
+fn java_lang_thread_get_next_thread_id_offset(
+    vm: &VirtualMachine,
+    thread: &mut JavaThreadState,
+    _args: &[Value],
+) -> NativeRet {
+    let address = vm.threads.next_tid_address().get_or_try_init(|| {
+        let long_array_sym = vm.interner().get_or_intern("[J");
+        let long_array_class_id =
+            vm.method_area_write()
+                .load_array_class(None, long_array_sym, thread.id)?;
+        let counter =
+            vm.heap_write()
+                .alloc_primitive_array(long_array_class_id, ArrayType::Long, 1)?;
+        // main is tid 1 (the primordial thread)
+        vm.heap_write().write_field(
+            counter,
+            Heap::ARRAY_ELEMENTS_OFFSET,
+            Value::Long(2),
+            AllocationType::Long,
+        )?;
+        Ok::<_, crate::error::JvmError>(counter + Heap::ARRAY_ELEMENTS_OFFSET)
+    })?;
+    Ok(Some(Value::Long(*address as i64)))
+}
+
+fn java_lang_thread_ensure_materialized_for_stack_walk(
+    _vm: &VirtualMachine,
+    _thread: &mut JavaThreadState,
+    _args: &[Value],
+) -> NativeRet {
+    Ok(None)
+}
@@ -1,12 +1,13 @@
 use crate::error::JvmError;
-use crate::heap::Heap;
+use crate::heap::{Heap, HeapRef};
 use crate::interpreter::Interpreter;
 use crate::keys::FullyQualifiedMethodKey;
 use crate::native::NativeRet;
-use crate::thread::JavaThreadState;
+use crate::thread::{JavaThreadState, ThreadStatus};
 use crate::vm::Value;
 use crate::{ThreadId, VirtualMachine};
 use common::jtype::AllocationType;
+use std::time::Duration;
 use tracing_log::log::debug;
 
 pub(super) fn jdk_internal_misc_unsafe_register_natives(
@@ -117,21 +118,21 @@ pub(super) fn jdk_internal_misc_unsafe_register_natives(
     );
     vm.native_registry.register(
         FullyQualifiedMethodKey::new_with_str(
-            "jdk/internal/loader/BootLoader",
-            "setBootLoaderUnnamedModule0",
-            "(Ljava/lang/Module;)V",
+            "jdk/internal/misc/Unsafe",
+            "getLong",
+            "(Ljava/lang/Object;J)J",
             &vm.string_interner,
         ),
-        jdk_internal_loader_boot_loader_set_boot_loader_unnamed_module_0,
+        jdk_internal_misc_unsafe_get_long,
     );
     vm.native_registry.register(
         FullyQualifiedMethodKey::new_with_str(
             "jdk/internal/misc/Unsafe",
-            "getLong",
+            "getLongVolatile",
             "(Ljava/lang/Object;J)J",
             &vm.string_interner,
         ),
-        jdk_internal_misc_unsafe_get_long,
+        jdk_internal_misc_unsafe_get_long_volatile,
     );
 
     vm.native_registry.register(
@@ -143,22 +144,120 @@ pub(super) fn jdk_internal_misc_unsafe_register_natives(
         ),
         jdk_internal_misc_unsafe_get_int,
     );
+    // getAndAdd*, getAndSet* and the weak CAS variants are plain Java loops over compareAndSet*,
+    // so the atomicity of everything in java.util.concurrent comes down to the natives below
+    vm.native_registry.register(
+        FullyQualifiedMethodKey::new_with_str(
+            "jdk/internal/misc/Unsafe",
+            "compareAndExchangeInt",
+            "(Ljava/lang/Object;JII)I",
+            &vm.string_interner,
+        ),
+        jdk_internal_misc_unsafe_compare_and_exchange_int,
+    );
+    vm.native_registry.register(
+        FullyQualifiedMethodKey::new_with_str(
+            "jdk/internal/misc/Unsafe",
+            "compareAndExchangeLong",
+            "(Ljava/lang/Object;JJJ)J",
+            &vm.string_interner,
+        ),
+        jdk_internal_misc_unsafe_compare_and_exchange_long,
+    );
+    vm.native_registry.register(
+        FullyQualifiedMethodKey::new_with_str(
+            "jdk/internal/misc/Unsafe",
+            "compareAndExchangeReference",
+            "(Ljava/lang/Object;JLjava/lang/Object;Ljava/lang/Object;)Ljava/lang/Object;",
+            &vm.string_interner,
+        ),
+        jdk_internal_misc_unsafe_compare_and_exchange_reference,
+    );
+    vm.native_registry.register(
+        FullyQualifiedMethodKey::new_with_str(
+            "jdk/internal/misc/Unsafe",
+            "getReference",
+            "(Ljava/lang/Object;J)Ljava/lang/Object;",
+            &vm.string_interner,
+        ),
+        jdk_internal_misc_unsafe_get_reference_volatile,
+    );
+    vm.native_registry.register(
+        FullyQualifiedMethodKey::new_with_str(
+            "jdk/internal/misc/Unsafe",
+            "putReference",
+            "(Ljava/lang/Object;JLjava/lang/Object;)V",
+            &vm.string_interner,
+        ),
+        jdk_internal_misc_unsafe_put_reference_volatile,
+    );
+    vm.native_registry.register(
+        FullyQualifiedMethodKey::new_with_str(
+            "jdk/internal/misc/Unsafe",
+            "putReferenceVolatile",
+            "(Ljava/lang/Object;JLjava/lang/Object;)V",
+            &vm.string_interner,
+        ),
+        jdk_internal_misc_unsafe_put_reference_volatile,
+    );
+    vm.native_registry.register(
+        FullyQualifiedMethodKey::new_with_str(
+            "jdk/internal/misc/Unsafe",
+            "putInt",
+            "(Ljava/lang/Object;JI)V",
+            &vm.string_interner,
+        ),
+        jdk_internal_misc_unsafe_put_int_volatile,
+    );
+    vm.native_registry.register(
+        FullyQualifiedMethodKey::new_with_str(
+            "jdk/internal/misc/Unsafe",
+            "putIntVolatile",
+            "(Ljava/lang/Object;JI)V",
+            &vm.string_interner,
+        ),
+        jdk_internal_misc_unsafe_put_int_volatile,
+    );
+    vm.native_registry.register(
+        FullyQualifiedMethodKey::new_with_str(
+            "jdk/internal/misc/Unsafe",
+            "putLong",
+            "(Ljava/lang/Object;JJ)V",
+            &vm.string_interner,
+        ),
+        jdk_internal_misc_unsafe_put_long_volatile,
+    );
+    vm.native_registry.register(
+        FullyQualifiedMethodKey::new_with_str(
+            "jdk/internal/misc/Unsafe",
+            "putLongVolatile",
+            "(Ljava/lang/Object;JJ)V",
+            &vm.string_interner,
+        ),
+        jdk_internal_misc_unsafe_put_long_volatile,
+    );
+    vm.native_registry.register(
+        FullyQualifiedMethodKey::new_with_str(
+            "jdk/internal/misc/Unsafe",
+            "park",
+            "(ZJ)V",
+            &vm.string_interner,
+        ),
+        jdk_internal_misc_unsafe_park,
+    );
+    vm.native_registry.register(
+        FullyQualifiedMethodKey::new_with_str(
+            "jdk/internal/misc/Unsafe",
+            "unpark",
+            "(Ljava/lang/Object;)V",
+            &vm.string_interner,
+        ),
+        jdk_internal_misc_unsafe_unpark,
+    );
 
     Ok(None)
 }
 
-fn jdk_internal_loader_boot_loader_set_boot_loader_unnamed_module_0(
-    _vm: &VirtualMachine,
-    _thread: &mut JavaThreadState,
-    _args: &[Value],
-) -> NativeRet {
-    debug!("TODO: Stub: jdk.internal.loader.BootLoader.setBootLoaderUnnamedModule0");
-    // TODO: this is unnamed module handling
-    // should take arg 0 (Module instance) and set internally it in somewhere in jvm
-    // and then this instance should be returned when .getModule() for bootstrap classes is called
-    Ok(None)
-}
-
 fn jdk_internal_misc_unsafe_ensure_class_initialized_0(
     vm: &VirtualMachine,
     thread: &mut JavaThreadState,
@@ -176,11 +275,11 @@ fn jdk_internal_misc_unsafe_get_int_volatile(
     args: &[Value],
 ) -> NativeRet {
     debug!("TODO: Stub: jdk.internal.misc.Unsafe.getIntVolatile");
-    let base = args[1].as_obj_ref()?;
-    let off = args[2].as_long()?;
+    let base = unsafe_base(&args[1])?;
+    let offset = unsafe_offset(&args[2])?;
     let value = vm
         .heap_read()
-        .read_field(base, off as usize, AllocationType::Int)?
+        .read_field(base, offset, AllocationType::Int)?
         .as_int()?;
     Ok(Some(Value::Integer(value)))
 }
@@ -190,11 +289,41 @@ fn jdk_internal_misc_unsafe_get_long(
     _thread: &mut JavaThreadState,
     args: &[Value],
 ) -> NativeRet {
-    let base = args[1].as_obj_ref()?;
-    let off = args[2].as_long()?;
+    let base = unsafe_base(&args[1])?;
+    let offset = unsafe_offset(&args[2])?;
+    let value = vm
+        .heap_read()
+        .read_field(base, offset, AllocationType::Long)?
+        .as_long()?;
+    Ok(Some(Value::Long(value)))
+}
+
+// A null base means the offset is an absolute address, which in this VM is just a heap offset
+fn unsafe_base(value: &Value) -> Result<HeapRef, JvmError> {
+    Ok(value.as_nullable_obj_ref()?.unwrap_or(0))
+}
+
+fn unsafe_offset(value: &Value) -> Result<usize, JvmError> {
+    let offset = value.as_long()?;
+    usize::try_from(offset).map_err(|_| JvmError::Todo(format!("Unsafe: negative offset {offset}")))
+}
+
+// Checks that a reference argument is an object or null and passes it through
+fn unsafe_reference(value: &Value) -> Result<Value, JvmError> {
+    value.as_nullable_obj_ref()?;
+    Ok(*value)
+}
+
+fn jdk_internal_misc_unsafe_get_long_volatile(
+    vm: &VirtualMachine,
+    _thread: &mut JavaThreadState,
+    args: &[Value],
+) -> NativeRet {
+    let base = unsafe_base(&args[1])?;
+    let offset = unsafe_offset(&args[2])?;
     let value = vm
         .heap_read()
-        .read_field(base, off as usize, AllocationType::Long)?
+        .read_field(base, offset, AllocationType::Long)?
         .as_long()?;
     Ok(Some(Value::Long(value)))
 }
@@ -204,11 +333,11 @@ fn jdk_internal_misc_unsafe_get_int(
     _thread: &mut JavaThreadState,
     args: &[Value],
 ) -> NativeRet {
-    let base = args[1].as_obj_ref()?;
-    let off = args[2].as_long()?;
+    let base = unsafe_base(&args[1])?;
+    let offset = unsafe_offset(&args[2])?;
     let value = vm
         .heap_read()
-        .read_field(base, off as usize, AllocationType::Int)?
+        .read_field(base, offset, AllocationType::Int)?
         .as_int()?;
     Ok(Some(Value::Integer(value)))
 }
@@ -227,37 +356,21 @@ fn jdk_internal_misc_unsafe_compare_and_set_int(
     _thread: &mut JavaThreadState,
     args: &[Value],
 ) -> NativeRet {
-    let object = match args[1] {
-        Value::Ref(h) => h,
-        _ => panic!("compareAndSetInt: expected object"),
-    };
-    let offset = match args[2] {
-        Value::Long(l) if l >= 0 => l as usize,
-        _ => panic!("compareAndSetInt: expected non-negative offset"),
-    };
-    let expected = match args[3] {
-        Value::Integer(i) => i,
-        _ => panic!("compareAndSetInt: expected int expected"),
-    };
-    let new_value = match args[4] {
-        Value::Integer(i) => i,
-        _ => panic!("compareAndSetInt: expected int new_value"),
-    };
-
-    let current = vm
-        .heap_read()
-        .read_field(object, offset, AllocationType::Int)?;
-    if current == Value::Integer(expected) {
-        vm.heap_write().write_field(
-            object,
-            offset,
-            Value::Integer(new_value),
-            AllocationType::Int,
-        )?;
-        Ok(Some(Value::Integer(1)))
-    } else {
-        Ok(Some(Value::Integer(0)))
-    }
+    let base = unsafe_base(&args[1])?;
+    let offset = unsafe_offset(&args[2])?;
+    let expected = args[3].as_int()?;
+    let new_value = args[4].as_int()?;
+    let current = compare_and_exchange(
+        vm,
+        base,
+        offset,
+        AllocationType::Int,
+        Value::Integer(expected),
+        Value::Integer(new_value),
+    )?;
+    Ok(Some(Value::Integer(
+        (current == Value::Integer(expected)) as i32,
+    )))
 }
 
 fn jdk_internal_misc_unsafe_compare_and_set_long(
@@ -265,41 +378,21 @@ fn jdk_internal_misc_unsafe_compare_and_set_long(
     _thread: &mut JavaThreadState,
     args: &[Value],
 ) -> NativeRet {
-    debug!("TODO: Stub: jdk.internal.misc.Unsafe.compareAndSetLong");
-    let object = match args[1] {
-        Value::Ref(h) => h,
-        _ => panic!("jdk.internal.misc.Unsafe.compareAndSetLong: expected object"),
-    };
-    let offset = match args[2] {
-        Value::Long(l) if l >= 0 => l as usize,
-        _ => panic!("jdk.internal.misc.Unsafe.compareAndSetLong: expected non-negative offset"),
-    };
-    let expected = match args[3] {
-        Value::Long(l) => l,
-        _ => panic!("jdk.internal.misc.Unsafe.compareAndSetLong: expected long expected value"),
-    };
-    let new_value = match args[4] {
-        Value::Long(l) => l,
-        _ => panic!("jdk.internal.misc.Unsafe.compareAndSetLong: expected long new value"),
-    };
-    let object_field_value = vm
-        .heap_read()
-        .read_field(object, offset, AllocationType::Long)?;
-    if let Value::Long(current_value) = object_field_value {
-        if current_value == expected {
-            vm.heap_write().write_field(
-                object,
-                offset,
-                Value::Long(new_value),
-                AllocationType::Long,
-            )?;
-            Ok(Some(Value::Integer(1)))
-        } else {
-            Ok(Some(Value::Integer(0)))
-        }
-    } else {
-        panic!("jdk.internal.misc.Unsafe.compareAndSetLong: field at offset is not long");
-    }
+    let base = unsafe_base(&args[1])?;
+    let offset = unsafe_offset(&args[2])?;
+    let expected = args[3].as_long()?;
+    let new_value = args[4].as_long()?;
+    let current = compare_and_exchange(
+        vm,
+        base,
+        offset,
+        AllocationType::Long,
+        Value::Long(expected),
+        Value::Long(new_value),
+    )?;
+    Ok(Some(Value::Integer(
+        (current == Value::Long(expected)) as i32,
+    )))
 }
 
 fn jdk_internal_misc_unsafe_get_reference_volatile(
@@ -308,19 +401,11 @@ fn jdk_internal_misc_unsafe_get_reference_volatile(
     args: &[Value],
 ) -> NativeRet {
     debug!("TODO: Stub: jdk.internal.misc.Unsafe.getReferenceVolatile");
-    let base = match &args[1] {
-        Value::Ref(h) => *h,
-        Value::Null => panic!("Unsafe.getReferenceVolatile base is null"),
-        _ => panic!("Unsafe.getReferenceVolatile expects an object base"),
-    };
-
-    let off = match args[2] {
-        Value::Long(x) => x,
-        _ => panic!("Unsafe.getReferenceVolatile expects a long offset"),
-    };
+    let base = unsafe_base(&args[1])?;
+    let offset = unsafe_offset(&args[2])?;
     Ok(Some(vm.heap_read().read_field(
         base,
-        off as usize,
+        offset,
         AllocationType::Reference,
     )?))
 }
@@ -395,61 +480,202 @@ fn jdk_internal_misc_unsafe_compare_and_set_reference(
     _thread: &mut JavaThreadState,
     args: &[Value],
 ) -> NativeRet {
-    let object = match args[1] {
-        Value::Ref(h) => h,
-        _ => panic!("compareAndSetReference: expected object"),
-    };
-    let offset = match args[2] {
-        Value::Long(l) if l >= 0 => l as usize,
-        _ => panic!("compareAndSetReference: expected non-negative offset"),
-    };
-    let expected = match args[3] {
-        v @ (Value::Ref(_) | Value::Null) => v,
-        _ => panic!("compareAndSetReference: expected object or null"),
-    };
-    let new_value = match args[4] {
-        Value::Ref(h) => h,
-        _ => panic!("compareAndSetReference: expected object"),
-    };
+    let base = unsafe_base(&args[1])?;
+    let offset = unsafe_offset(&args[2])?;
+    let expected = unsafe_reference(&args[3])?;
+    let new_value = unsafe_reference(&args[4])?;
+    let current = compare_and_exchange(
+        vm,
+        base,
+        offset,
+        AllocationType::Reference,
+        expected,
+        new_value,
+    )?;
+    Ok(Some(Value::Integer((current == expected) as i32)))
+}
 
-    let current = vm
-        .heap_read()
-        .read_field(object, offset, AllocationType::Reference)?;
+// The read and the write happen under one heap write lock, so no other heap access can
+// interleave with them. Returns the value that was there before.
+fn compare_and_exchange(
+    vm: &VirtualMachine,
+    base: HeapRef,
+    offset: usize,
+    field_type: AllocationType,
+    expected: Value,
+    new_value: Value,
+) -> Result<Value, JvmError> {
+    let mut heap = vm.heap_write();
+    let current = heap.read_field(base, offset, field_type)?;
     if current == expected {
-        vm.heap_write().write_field(
-            object,
-            offset,
-            Value::Ref(new_value),
-            AllocationType::Reference,
-        )?;
-        Ok(Some(Value::Integer(1)))
-    } else {
-        Ok(Some(Value::Integer(0)))
+        heap.write_field(base, offset, new_value, field_type)?;
     }
+    Ok(current)
 }
 
-fn jdk_internal_misc_unsafe_put_byte(
+fn jdk_internal_misc_unsafe_compare_and_exchange_int(
+    vm: &VirtualMachine,
+    _thread: &mut JavaThreadState,
+    args: &[Value],
+) -> NativeRet {
+    let base = unsafe_base(&args[1])?;
+    let offset = unsafe_offset(&args[2])?;
+    let expected = args[3].as_int()?;
+    let new_value = args[4].as_int()?;
+    let current = compare_and_exchange(
+        vm,
+        base,
+        offset,
+        AllocationType::Int,
+        Value::Integer(expected),
+        Value::Integer(new_value),
+    )?;
+    Ok(Some(current))
+}
+
+fn jdk_internal_misc_unsafe_compare_and_exchange_long(
     vm: &VirtualMachine,
     _thread: &mut JavaThreadState,
     args: &[Value],
 ) -> NativeRet {
-    let object = args
-        .get(1)
-        .ok_or(JvmError::Todo("putByte: missing 1 argument".to_string()))?
-        .as_obj_ref()?;
-    let offset = match args
-        .get(2)
-        .ok_or(JvmError::Todo("putByte: missing 2 argument".to_string()))?
-    {
-        Value::Long(l) if *l >= 0 => *l as usize,
-        _ => panic!("putByte: expected non-negative offset"),
+    let base = unsafe_base(&args[1])?;
+    let offset = unsafe_offset(&args[2])?;
+    let expected = args[3].as_long()?;
+    let new_value = args[4].as_long()?;
+    let current = compare_and_exchange(
+        vm,
+        base,
+        offset,
+        AllocationType::Long,
+        Value::Long(expected),
+        Value::Long(new_value),
+    )?;
+    Ok(Some(current))
+}
+
+fn jdk_internal_misc_unsafe_compare_and_exchange_reference(
+    vm: &VirtualMachine,
+    _thread: &mut JavaThreadState,
+    args: &[Value],
+) -> NativeRet {
+    let base = unsafe_base(&args[1])?;
+    let offset = unsafe_offset(&args[2])?;
+    let current = compare_and_exchange(
+        vm,
+        base,
+        offset,
+        AllocationType::Reference,
+        unsafe_reference(&args[3])?,
+        unsafe_reference(&args[4])?,
+    )?;
+    Ok(Some(current))
+}
+
+fn jdk_internal_misc_unsafe_put_reference_volatile(
+    vm: &VirtualMachine,
+    _thread: &mut JavaThreadState,
+    args: &[Value],
+) -> NativeRet {
+    let base = unsafe_base(&args[1])?;
+    let offset = unsafe_offset(&args[2])?;
+    let value = unsafe_reference(&args[3])?;
+    vm.heap_write()
+        .write_field(base, offset, value, AllocationType::Reference)?;
+    Ok(None)
+}
+
+fn jdk_internal_misc_unsafe_put_int_volatile(
+    vm: &VirtualMachine,
+    _thread: &mut JavaThreadState,
+    args: &[Value],
+) -> NativeRet {
+    let base = unsafe_base(&args[1])?;
+    let offset = unsafe_offset(&args[2])?;
+    let value = args[3].as_int()?;
+    vm.heap_write()
+        .write_field(base, offset, Value::Integer(value), AllocationType::Int)?;
+    Ok(None)
+}
+
+fn jdk_internal_misc_unsafe_put_long_volatile(
+    vm: &VirtualMachine,
+    _thread: &mut JavaThreadState,
+    args: &[Value],
+) -> NativeRet {
+    let base = unsafe_base(&args[1])?;
+    let offset = unsafe_offset(&args[2])?;
+    let value = args[3].as_long()?;
+    vm.heap_write()
+        .write_field(base, offset, Value::Long(value), AllocationType::Long)?;
+    Ok(None)
+}
+
+fn jdk_internal_misc_unsafe_park(
+    vm: &VirtualMachine,
+    thread: &mut JavaThreadState,
+    args: &[Value],
+) -> NativeRet {
+    let is_absolute = args[1].as_int()? != 0;
+    let time = args[2].as_long()?;
+    if vm.threads.take_permit(thread.id) {
+        return Ok(None);
+    }
+    // absolute deadlines are milliseconds since the epoch, relative ones are nanoseconds
+    // and 0 means no timeout
+    let timeout = if is_absolute {
+        let now = vm.scheduler.current_time_millis();
+        if time <= now {
+            return Ok(None);
+        }
+        Some(Duration::from_millis((time - now) as u64))
+    } else if time < 0 {
+        return Ok(None);
+    } else if time == 0 {
+        None
+    } else {
+        Some(Duration::from_nanos(time as u64))
     };
-    let value = args
-        .get(3)
-        .ok_or(JvmError::Todo("putByte: missing 3 argument".to_string()))?
-        .as_int()?;
+    let thread_obj = thread.thread_obj;
+    let status = if timeout.is_some() {
+        ThreadStatus::ParkedTimed
+    } else {
+        ThreadStatus::Parked
+    };
+    // returns early on interrupt but leaves the interrupt status alone, unlike sleep and wait
+    vm.with_thread_status(thread, status, || {
+        vm.scheduler.block_until(thread.id, timeout, || {
+            vm.threads.take_permit(thread.id)
+                || vm.is_thread_interrupted(thread_obj).unwrap_or(false)
+        })
+    })?;
+    Ok(None)
+}
 
+fn jdk_internal_misc_unsafe_unpark(
+    vm: &VirtualMachine,
+    _thread: &mut JavaThreadState,
+    args: &[Value],
+) -> NativeRet {
+    let Some(thread_obj) = args[1].as_nullable_obj_ref()? else {
+        return Ok(None);
+    };
+    // unparking a thread that isn't started yet or already terminated has no effect
+    if let Some(thread_id) = vm.get_live_thread_id(thread_obj)? {
+        vm.threads.unpark(thread_id);
+        vm.scheduler.notify_all();
+    }
+    Ok(None)
+}
+
+fn jdk_internal_misc_unsafe_put_byte(
+    vm: &VirtualMachine,
+    _thread: &mut JavaThreadState,
+    args: &[Value],
+) -> NativeRet {
+    let base = unsafe_base(&args[1])?;
+    let offset = unsafe_offset(&args[2])?;
+    let value = args[3].as_int()?;
     vm.heap_write()
-        .write_field(object, offset, Value::Integer(value), AllocationType::Byte)?;
+        .write_field(base, offset, Value::Integer(value), AllocationType::Byte)?;
     Ok(None)
 }
@@ -46,6 +46,8 @@ pub struct ObjectArrayClass {
     pub name: Symbol,
     pub super_id: ClassId,
     pub element_class_id: ClassId,
+    // the element class' defining loader
+    pub loader: Option<HeapRef>,
     pub vtable: Vec<MethodId>,
     pub vtable_index: HashMap<MethodKey, u16>,
     pub(crate) mirror_ref: OnceCell<HeapRef>,
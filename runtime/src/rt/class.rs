@@ -1,4 +1,5 @@
 use crate::error::JvmError;
+use crate::heap::HeapRef;
 use crate::heap::method_area::MethodArea;
 use crate::keys::{ClassId, FieldKey, MethodKey, ThreadId};
 use crate::rt::constant_pool::RuntimeConstantPool;
@@ -25,6 +26,8 @@ pub struct InstanceClass {
     pub vtable: OnceCell<Vec<MethodId>>,
     pub vtable_index: OnceCell<HashMap<MethodKey, u16>>,
     pub itable: OnceCell<HashMap<MethodKey, MethodId>>,
+    // the non-abstract maximally-specific methods of keys that have more than one
+    default_conflicts: OnceCell<HashMap<MethodKey, Vec<MethodId>>>,
 
     // TODO: review if we need both offset maps
     pub instance_fields: OnceCell<Vec<InstanceField>>,
@@ -42,6 +45,7 @@ impl InstanceClass {
         cp: RuntimeConstantPool,
         this_class: u16,
         attributes: Vec<ClassAttribute>,
+        loader: Option<HeapRef>,
     ) -> Result<ClassId, JvmError> {
         let name = cp.get_class_sym(&this_class, method_area.interner())?;
 
@@ -54,13 +58,19 @@ impl InstanceClass {
             }
         }
 
+        let base = BaseClass::new(name, flags, super_id, source_file, loader).with_nest(
+            &cp,
+            &attributes,
+            method_area.interner(),
+        )?;
         let class = JvmClass::Instance(Box::new(Self {
-            base: BaseClass::new(name, flags, super_id, source_file),
+            base,
             cp,
             declared_method_index: OnceCell::new(),
             vtable: OnceCell::new(),
             vtable_index: OnceCell::new(),
             itable: OnceCell::new(),
+            default_conflicts: OnceCell::new(),
             instance_fields: OnceCell::new(),
             instance_fields_offset_map: OnceCell::new(),
             instance_fields_name_offset_map: OnceCell::new(),
@@ -89,6 +99,7 @@ impl InstanceClass {
             })
             .transpose()?
             .unwrap_or_default();
+        let mut method_ids = Vec::with_capacity(methods.len());
 
         for method in methods {
             let method_key = {
@@ -112,6 +123,7 @@ impl InstanceClass {
             let is_constructor = method_key.name == method_area.br().init_sym
                 || method_key.name == method_area.br().clinit_sym;
             let method_id = method_area.push_method(method);
+            method_ids.push(method_id);
 
             // TODO: need to think about private as well. Private methods should not be in vtable
             // but it can be called with invokevirtual from the same class...
@@ -136,6 +148,7 @@ impl InstanceClass {
 
         let this = method_area.get_instance_class(&this_id)?;
         this.set_declared_methods(declared_index)?;
+        this.base.set_method_ids(method_ids)?;
         Ok((vtable, vtable_index))
     }
 
@@ -224,6 +237,7 @@ impl InstanceClass {
         this_id: ClassId,
         super_id: Option<ClassId>,
         method_area: &mut MethodArea,
+        loader: Option<HeapRef>,
         thread_id: ThreadId,
     ) -> Result<(), JvmError> {
         let mut interface_ids = super_id
@@ -237,18 +251,16 @@ impl InstanceClass {
         for interface in interfaces {
             let cp = &method_area.get_instance_class(&this_id)?.cp;
             let interface_name = cp.get_class_sym(&interface, method_area.interner())?;
-            let interface_id = method_area.get_class_id_or_load(interface_name, thread_id)?;
+            let interface_id =
+                method_area.load_super_interface(loader, this_id, interface_name, thread_id)?;
             interface_ids.insert(interface_id);
             direct_interfaces.insert(interface_id);
-
-            /* TODO: probably need to handle superinterfaces as well
-                something like:
-                if let Ok(interface_class) = method_area.get_interface_class(&interface_id) {
-                for super_interface_id in interface_class.get_super_interfaces() {
-                    interface_ids.insert(*super_interface_id);
-                }
-            }
-                 */
+            // superinterfaces are implemented too, JLS 8.1.5
+            interface_ids.extend(
+                method_area
+                    .get_interface_class(&interface_id)?
+                    .get_interfaces()?,
+            );
         }
         let this = method_area.get_instance_class(&this_id)?;
         this.base.set_interfaces(interface_ids)?;
@@ -256,65 +268,70 @@ impl InstanceClass {
         Ok(())
     }
 
+    // JVMS 5.4.6: a method of the class or of a superclass is selected over the ones of its
+    // interfaces, otherwise the only non-abstract maximally-specific superinterface method
     fn link_itable_and_vtable(
         this_id: ClassId,
-        super_id: Option<ClassId>,
-        method_area: &mut MethodArea,
+        method_area: &MethodArea,
         mut vtable: Vec<MethodId>,
         mut vtable_index: HashMap<MethodKey, u16>,
     ) -> Result<(), JvmError> {
-        let mut itable = super_id
-            .map(|id| method_area.get_instance_class(&id))
-            .transpose()?
-            .map(|class| class.get_itable().cloned())
-            .transpose()?
-            .unwrap_or_default();
+        let this = method_area.get_instance_class(&this_id)?;
+        let interfaces = this.base.get_interfaces()?;
+        let mut interface_method_keys = HashSet::new();
+        for interface_id in interfaces {
+            for (method_key, method_id) in
+                method_area.get_interface_class(interface_id)?.get_methods()
+            {
+                let method = method_area.get_method(method_id);
+                if !method.is_static() && !method.is_private() {
+                    interface_method_keys.insert(*method_key);
+                }
+            }
+        }
 
-        for interface in method_area
-            .get_instance_class(&this_id)?
-            .base
-            .get_interfaces()?
-        {
-            let interface_class = method_area.get_interface_class(interface)?;
-            let interface_methods = interface_class.get_methods();
-            for (method_key, method_id) in interface_methods {
-                let impl_method_id = match vtable_index.get(method_key) {
-                    Some(&idx) => vtable[idx as usize],
-                    None => {
-                        // not in vtable yet
-                        if !method_area.get_method(method_id).is_abstract() {
-                            // Default method - add to vtable
-                            let idx = vtable.len() as u16;
-                            vtable_index.insert(*method_key, idx);
-                            vtable.push(*method_id);
-                            *method_id
-                        } else {
-                            // Abstract - class must be abstract or it's an error
-                            if !method_area
-                                .get_instance_class(&this_id)?
-                                .base
-                                .flags
-                                .is_abstract()
-                            {
-                                return Err(JvmError::Todo(format!(
-                                    "Concrete class {} does not implement interface method {} {}",
-                                    method_area.interner().resolve(
-                                        &method_area.get_instance_class(&this_id)?.base.name
-                                    ),
-                                    method_area.interner().resolve(&method_key.name),
-                                    method_area.interner().resolve(&method_key.desc)
-                                )));
-                            }
-                            continue;
-                        }
+        let mut itable = HashMap::new();
+        let mut default_conflicts = HashMap::new();
+        for method_key in interface_method_keys {
+            let class_method_id = vtable_index
+                .get(&method_key)
+                .map(|&idx| vtable[idx as usize])
+                .filter(|method_id| {
+                    let class_id = method_area.get_method(method_id).class_id();
+                    !method_area.get_class(&class_id).is_interface()
+                });
+            let selected = match class_method_id {
+                Some(method_id) => method_id,
+                None => {
+                    let candidates =
+                        method_area.maximally_specific_methods(interfaces, &method_key);
+                    let defaults = candidates
+                        .iter()
+                        .copied()
+                        .filter(|method_id| !method_area.get_method(method_id).is_abstract())
+                        .collect::<Vec<_>>();
+                    // an abstract method is left selected, invoking it throws AbstractMethodError
+                    let Some(selected) = defaults.first().or(candidates.first()).copied() else {
+                        continue;
+                    };
+                    if defaults.len() > 1 {
+                        default_conflicts.insert(method_key, defaults);
                     }
-                };
-                itable.insert(*method_key, impl_method_id);
+                    selected
+                }
+            };
+            itable.insert(method_key, selected);
+            match vtable_index.get(&method_key) {
+                Some(&idx) => vtable[idx as usize] = selected,
+                None => {
+                    vtable_index.insert(method_key, vtable.len() as u16);
+                    vtable.push(selected);
+                }
             }
         }
 
-        let this = method_area.get_instance_class(&this_id)?;
         this.set_itable(itable)?;
+        this.set_default_conflicts(default_conflicts)?;
         this.set_vtable(vtable)?;
         this.set_vtable_index(vtable_index)?;
         Ok(())
@@ -337,6 +354,7 @@ impl InstanceClass {
         mut cf: ClassFile,
         method_area: &mut MethodArea,
         super_id: Option<ClassId>,
+        loader: Option<HeapRef>,
         thread_id: ThreadId,
     ) -> Result<ClassId, JvmError> {
         let runtime_cp = Self::prepare_cp(cf.cp, &mut cf.attributes);
@@ -347,16 +365,25 @@ impl InstanceClass {
             runtime_cp,
             cf.this_class,
             cf.attributes,
+            loader,
         )?;
+        method_area
+            .get_instance_class(&this_id)?
+            .base
+            .set_major_version(cf.major_version)?;
 
         Self::link_fields(cf.fields, this_id, super_id, method_area)?;
         let (vtable, vtable_index) =
             Self::prepare_methods(cf.methods, this_id, super_id, method_area)?;
-        Self::link_interfaces(cf.interfaces, this_id, super_id, method_area, thread_id)?;
-        Self::link_itable_and_vtable(this_id, super_id, method_area, vtable, vtable_index)?;
-
-        let this = method_area.get_instance_class(&this_id)?;
-        this.set_linked();
+        Self::link_interfaces(
+            cf.interfaces,
+            this_id,
+            super_id,
+            method_area,
+            loader,
+            thread_id,
+        )?;
+        Self::link_itable_and_vtable(this_id, method_area, vtable, vtable_index)?;
         Ok(this_id)
     }
 
@@ -418,6 +445,12 @@ impl InstanceClass {
         None
     }
 
+    /// The default methods `key` selects from unrelated interfaces, JVMS 5.4.6. Invoking it
+    /// throws IncompatibleClassChangeError.
+    pub fn get_default_conflict(&self, key: &MethodKey) -> Option<&[MethodId]> {
+        self.default_conflicts.get()?.get(key).map(Vec::as_slice)
+    }
+
     // Internal getters and setters for "lazy" initialized fields
     // mostly because I need to know this class id during linking
 
@@ -533,6 +566,15 @@ impl InstanceClass {
             .set(itable)
             .map_err(|_| JvmError::Todo("Itable already initialized".to_string()))
     }
+
+    fn set_default_conflicts(
+        &self,
+        default_conflicts: HashMap<MethodKey, Vec<MethodId>>,
+    ) -> Result<(), JvmError> {
+        self.default_conflicts
+            .set(default_conflicts)
+            .map_err(|_| JvmError::Todo("Default conflicts already initialized".to_string()))
+    }
 }
 
 impl ClassLike for InstanceClass {
@@ -1,5 +1,6 @@
 use crate::MethodId;
 use crate::error::JvmError;
+use crate::heap::HeapRef;
 use crate::heap::method_area::MethodArea;
 use crate::keys::{ClassId, FieldKey, MethodKey, ThreadId};
 use crate::rt::constant_pool::RuntimeConstantPool;
@@ -29,12 +30,19 @@ impl InterfaceClass {
         method_area: &mut MethodArea,
         super_id: Option<ClassId>,
         this_class: u16,
+        attributes: &[ClassAttribute],
+        loader: Option<HeapRef>,
     ) -> Result<ClassId, JvmError> {
         let name = cp.get_class_sym(&this_class, method_area.interner())?;
 
         //TODO: source file name? etc
+        let base = BaseClass::new(name, flags, super_id, None, loader).with_nest(
+            &cp,
+            attributes,
+            method_area.interner(),
+        )?;
         let class = JvmClass::Interface(Box::new(Self {
-            base: BaseClass::new(name, flags, super_id, None),
+            base,
             cp,
             methods: OnceCell::new(),
         }));
@@ -48,6 +56,7 @@ impl InterfaceClass {
         method_area: &mut MethodArea,
     ) -> Result<(), JvmError> {
         let mut declared_index = HashMap::new();
+        let mut method_ids = Vec::with_capacity(methods.len());
         for method in methods {
             // TODO: can be extracted to a common function
             let method_key = {
@@ -68,6 +77,7 @@ impl InterfaceClass {
                 method_key.desc,
             );
             let method_id = method_area.push_method(method);
+            method_ids.push(method_id);
             if method_key.name == method_area.br().clinit_sym {
                 method_area
                     .get_interface_class(&this_id)?
@@ -80,6 +90,7 @@ impl InterfaceClass {
 
         let this = method_area.get_interface_class(&this_id)?;
         this.set_methods(declared_index);
+        this.base.set_method_ids(method_ids)?;
 
         Ok(())
     }
@@ -121,6 +132,7 @@ impl InterfaceClass {
         this_id: ClassId,
         super_id: Option<ClassId>,
         method_area: &mut MethodArea,
+        loader: Option<HeapRef>,
         thread_id: ThreadId,
     ) -> Result<(), JvmError> {
         let mut interface_ids = super_id
@@ -134,18 +146,15 @@ impl InterfaceClass {
         for interface in interfaces {
             let cp = &method_area.get_interface_class(&this_id)?.cp;
             let interface_name = cp.get_class_sym(&interface, method_area.interner())?;
-            let interface_id = method_area.get_class_id_or_load(interface_name, thread_id)?;
+            let interface_id =
+                method_area.load_super_interface(loader, this_id, interface_name, thread_id)?;
             interface_ids.insert(interface_id);
             direct_interfaces.insert(interface_id);
-
-            /* TODO: probably need to handle superinterfaces as well
-                something like:
-                if let Ok(interface_class) = method_area.get_interface_class(&interface_id) {
-                for super_interface_id in interface_class.get_super_interfaces() {
-                    interface_ids.insert(*super_interface_id);
-                }
-            }
-                 */
+            interface_ids.extend(
+                method_area
+                    .get_interface_class(&interface_id)?
+                    .get_interfaces()?,
+            );
         }
         let this = method_area.get_interface_class(&this_id)?;
         this.base.set_interfaces(interface_ids)?;
@@ -170,14 +179,34 @@ impl InterfaceClass {
         mut cf: ClassFile,
         method_area: &mut MethodArea,
         super_id: Option<ClassId>,
+        loader: Option<HeapRef>,
         thread_id: ThreadId,
     ) -> Result<ClassId, JvmError> {
         let cp = Self::prepare_cp(cf.cp, &mut cf.attributes);
-        let this_id = Self::load(cf.access_flags, cp, method_area, super_id, cf.this_class)?;
+        let this_id = Self::load(
+            cf.access_flags,
+            cp,
+            method_area,
+            super_id,
+            cf.this_class,
+            &cf.attributes,
+            loader,
+        )?;
+        method_area
+            .get_interface_class(&this_id)?
+            .base
+            .set_major_version(cf.major_version)?;
 
         Self::link_methods(cf.methods, this_id, method_area)?;
         Self::link_fields(cf.fields, this_id, method_area)?;
-        Self::link_interfaces(cf.interfaces, this_id, super_id, method_area, thread_id)?;
+        Self::link_interfaces(
+            cf.interfaces,
+            this_id,
+            super_id,
+            method_area,
+            loader,
+            thread_id,
+        )?;
 
         Ok(this_id)
     }
@@ -10,6 +10,7 @@ use jclass::flags::MethodFlags;
 use jclass::prelude::MethodInfo;
 use std::cell::OnceCell;
 
+#[derive(Clone)]
 pub struct CodeBody {
     pub code: Box<[u8]>,
     max_stack: u16,
@@ -17,6 +18,21 @@ pub struct CodeBody {
     // TODO: Create a dedicated struct? (now struct from jclass)
     line_numbers: Option<Vec<LineNumberEntry>>,
     pub exception_table: Vec<ExceptionTableEntry>,
+    stack_map_table: Option<Vec<StackMapFrame>>,
+}
+
+impl CodeBody {
+    pub fn max_stack(&self) -> u16 {
+        self.max_stack
+    }
+
+    pub fn max_locals(&self) -> u16 {
+        self.max_locals
+    }
+
+    pub fn stack_map_table(&self) -> Option<&[StackMapFrame]> {
+        self.stack_map_table.as_deref()
+    }
 }
 
 pub enum MethodBody {
@@ -72,6 +88,10 @@ impl Method {
         self.class_id
     }
 
+    pub fn flags(&self) -> MethodFlags {
+        self.flags
+    }
+
     pub fn is_static(&self) -> bool {
         self.flags.is_static()
     }
@@ -80,14 +100,29 @@ impl Method {
         self.flags.is_abstract()
     }
 
+    pub fn is_private(&self) -> bool {
+        self.flags.is_private()
+    }
+
     pub fn is_native(&self) -> bool {
         self.flags.is_native()
     }
 
+    pub fn is_synchronized(&self) -> bool {
+        self.flags.is_synchronized()
+    }
+
     pub fn descriptor_id(&self) -> MethodDescriptorId {
         self.descriptor_id
     }
 
+    pub fn get_code_body(&self) -> Option<&CodeBody> {
+        match &self.body {
+            MethodBody::Interpreted(code_body) => Some(code_body),
+            _ => None,
+        }
+    }
+
     pub fn get_frame_attributes(&self) -> Result<(u16, u16), JvmError> {
         match &self.body {
             MethodBody::Interpreted(code_body) => {
@@ -187,6 +222,7 @@ impl TryFrom<CodeAttribute> for CodeBody {
             max_locals: code_attr.max_locals,
             line_numbers: all_line_numbers,
             exception_table,
+            stack_map_table: stack_map_table.into_inner(),
         })
     }
 }
@@ -10,7 +10,9 @@ use crate::vm::Value;
 use crate::{MethodId, Symbol};
 use common::jtype::PrimitiveType;
 use itertools::Either;
-use jclass::flags::ClassFlags;
+use jclass::attribute::ClassAttribute;
+use jclass::flags::{ClassFlags, FieldFlags};
+use lasso::ThreadedRodeo;
 use once_cell::sync::OnceCell;
 use std::collections::{HashMap, HashSet};
 use std::fmt::Display;
@@ -22,6 +24,7 @@ pub mod constant_pool;
 pub mod field;
 pub mod interface;
 pub mod method;
+pub mod module;
 
 pub trait ClassLike {
     fn base(&self) -> &BaseClass;
@@ -60,6 +63,10 @@ pub trait ClassLike {
         self.base().source_file
     }
 
+    fn get_loader(&self) -> Option<HeapRef> {
+        self.base().loader
+    }
+
     fn has_static_field(&self, field_key: &FieldKey) -> Result<bool, JvmError> {
         self.base()
             .get_static_fields()
@@ -85,6 +92,14 @@ pub trait ClassLike {
         Ok(*static_field.value.read().unwrap())
     }
 
+    fn get_static_field_flags(&self, field_key: &FieldKey) -> Result<FieldFlags, JvmError> {
+        self.base()
+            .get_static_fields()?
+            .get(field_key)
+            .map(|static_field| static_field.flags)
+            .ok_or(JvmError::Todo("No such field".to_string()))
+    }
+
     fn get_interfaces(&self) -> Result<&HashSet<ClassId>, JvmError> {
         self.base().get_interfaces()
     }
@@ -93,14 +108,55 @@ pub trait ClassLike {
         self.base().get_direct_interfaces()
     }
 
+    /// Every method of the class file, `<clinit>` included, in declaration order.
+    fn get_method_ids(&self) -> Result<&[MethodId], JvmError> {
+        self.base().get_method_ids()
+    }
+
+    fn get_major_version(&self) -> Result<u16, JvmError> {
+        self.base().get_major_version()
+    }
+
+    /// The class named by the NestHost attribute, JVMS 4.7.28.
+    fn get_nest_host_name(&self) -> Option<Symbol> {
+        self.base().nest_host_name
+    }
+
+    /// Whether the NestMembers attribute lists `name`, JVMS 4.7.29.
+    fn has_nest_member_named(&self, name: Symbol) -> bool {
+        self.base().nest_member_names.contains(&name)
+    }
+
+    /// The validated nest host, once an access check needed it.
+    fn get_nest_host(&self) -> Option<ClassId> {
+        self.base().nest_host.get().copied()
+    }
+
+    /// Why the NestHost attribute was rejected, for the messages of failed private accesses.
+    fn get_nest_host_error(&self) -> Option<&str> {
+        self.base().nest_host_error.get().map(String::as_str)
+    }
+
+    fn set_nest_host(&self, nest_host: ClassId, error: Option<String>) {
+        if self.base().nest_host.set(nest_host).is_ok()
+            && let Some(error) = error
+        {
+            let _ = self.base().nest_host_error.set(error);
+        }
+    }
+
+    // only moves a loaded class on, another thread may be initializing it already
     fn set_linked(&self) {
-        self.base()
-            .state
-            .store(ClassState::Linked as u8, Ordering::Release);
+        let _ = self.base().state.compare_exchange(
+            ClassState::Loaded as u8,
+            ClassState::Linked as u8,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        );
     }
 
-    fn is_initializing(&self) -> bool {
-        self.base().state.load(Ordering::Acquire) == ClassState::Initializing as u8
+    fn init_state(&self) -> ClassState {
+        ClassState::from(self.base().state.load(Ordering::Acquire))
     }
 
     fn set_initializing(&self) {
@@ -115,9 +171,10 @@ pub trait ClassLike {
             .store(ClassState::Initialized as u8, Ordering::Release);
     }
 
-    fn is_initialized_or_initializing(&self) -> bool {
-        let state = self.base().state.load(Ordering::Acquire);
-        state == ClassState::Initialized as u8 || state == ClassState::Initializing as u8
+    fn set_erroneous(&self) {
+        self.base()
+            .state
+            .store(ClassState::Erroneous as u8, Ordering::Release);
     }
 }
 
@@ -131,7 +188,15 @@ pub struct BaseClass {
     direct_interfaces: OnceCell<HashSet<ClassId>>,
     static_fields: OnceCell<HashMap<FieldKey, StaticField>>,
     clinit: OnceCell<MethodId>,
+    method_ids: OnceCell<Vec<MethodId>>,
+    major_version: OnceCell<u16>,
     source_file: Option<Symbol>,
+    // defining loader, None for the bootstrap one
+    loader: Option<HeapRef>,
+    nest_host_name: Option<Symbol>,
+    nest_member_names: Vec<Symbol>,
+    nest_host: OnceCell<ClassId>,
+    nest_host_error: OnceCell<String>,
 }
 
 impl BaseClass {
@@ -140,21 +205,53 @@ impl BaseClass {
         flags: ClassFlags,
         super_id: Option<ClassId>,
         source_file: Option<Symbol>,
+        loader: Option<HeapRef>,
     ) -> Self {
         Self {
             name,
             flags,
             super_id,
             source_file,
+            loader,
             state: AtomicU8::new(ClassState::Loaded as u8),
             mirror_ref: OnceCell::new(),
             interfaces: OnceCell::new(),
             direct_interfaces: OnceCell::new(),
             static_fields: OnceCell::new(),
             clinit: OnceCell::new(),
+            method_ids: OnceCell::new(),
+            major_version: OnceCell::new(),
+            nest_host_name: None,
+            nest_member_names: Vec::new(),
+            nest_host: OnceCell::new(),
+            nest_host_error: OnceCell::new(),
         }
     }
 
+    /// Takes the nest of the class from its NestHost and NestMembers attributes.
+    pub fn with_nest(
+        mut self,
+        cp: &RuntimeConstantPool,
+        attributes: &[ClassAttribute],
+        interner: &ThreadedRodeo,
+    ) -> Result<Self, JvmError> {
+        for attr in attributes {
+            match attr {
+                ClassAttribute::NestHost(host) => {
+                    self.nest_host_name = Some(cp.get_class_sym(host, interner)?);
+                }
+                ClassAttribute::NestMembers(members) => {
+                    self.nest_member_names = members
+                        .iter()
+                        .map(|member| cp.get_class_sym(member, interner))
+                        .collect::<Result<_, _>>()?;
+                }
+                _ => {}
+            }
+        }
+        Ok(self)
+    }
+
     // Internal getters and setters for "lazy" initialized fields
     // mostly because I need to know this class id during linking
 
@@ -164,6 +261,31 @@ impl BaseClass {
             .map_err(|_| JvmError::Todo("BaseClass clinit already set".to_string()))
     }
 
+    fn set_method_ids(&self, method_ids: Vec<MethodId>) -> Result<(), JvmError> {
+        self.method_ids
+            .set(method_ids)
+            .map_err(|_| JvmError::Todo("BaseClass method_ids already set".to_string()))
+    }
+
+    fn get_method_ids(&self) -> Result<&[MethodId], JvmError> {
+        self.method_ids
+            .get()
+            .map(Vec::as_slice)
+            .ok_or(JvmError::Todo("BaseClass method_ids not set".to_string()))
+    }
+
+    fn set_major_version(&self, major_version: u16) -> Result<(), JvmError> {
+        self.major_version
+            .set(major_version)
+            .map_err(|_| JvmError::Todo("BaseClass major_version already set".to_string()))
+    }
+
+    fn get_major_version(&self) -> Result<u16, JvmError> {
+        self.major_version.get().copied().ok_or(JvmError::Todo(
+            "BaseClass major_version not set".to_string(),
+        ))
+    }
+
     fn get_interfaces(&self) -> Result<&HashSet<ClassId>, JvmError> {
         self.interfaces
             .get()
@@ -212,6 +334,7 @@ pub enum ClassState {
     Linked = 1,       // Verified, prepared
     Initializing = 2, // <clinit> in progress
     Initialized = 3,  // <clinit> executed
+    Erroneous = 4,    // <clinit> or a supertype's initialization failed
 }
 
 impl From<u8> for ClassState {
@@ -221,6 +344,7 @@ impl From<u8> for ClassState {
             1 => ClassState::Linked,
             2 => ClassState::Initializing,
             3 => ClassState::Initialized,
+            4 => ClassState::Erroneous,
             _ => unreachable!(),
         }
     }
@@ -394,6 +518,16 @@ impl JvmClass {
         }
     }
 
+    /// The defining loader, `None` for the bootstrap one.
+    pub fn get_loader(&self) -> Option<HeapRef> {
+        match self {
+            JvmClass::Instance(ic) => ic.get_loader(),
+            JvmClass::Interface(i) => i.get_loader(),
+            JvmClass::InstanceArray(oac) => oac.loader,
+            JvmClass::PrimitiveArray(_) | JvmClass::Primitive(_) => None,
+        }
+    }
+
     pub fn is_primitive(&self) -> bool {
         matches!(self, JvmClass::Primitive(_))
     }
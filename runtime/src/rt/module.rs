@@ -0,0 +1,190 @@
+use crate::heap::HeapRef;
+use crate::keys::Symbol;
+use std::collections::{HashMap, HashSet};
+
+/// The module a class is in. Unnamed modules are never defined to the VM, each loader has one
+/// and they are told apart by the loader.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ModuleRef {
+    Named(HeapRef),
+    Unnamed { loader: Option<HeapRef> },
+}
+
+impl ModuleRef {
+    pub fn is_named(&self) -> bool {
+        matches!(self, ModuleRef::Named(_))
+    }
+}
+
+/// Where `Module.addExports0` and friends export a package to.
+#[derive(Clone, Copy, Debug)]
+pub enum ExportTarget {
+    All,
+    AllUnnamed,
+    Module(ModuleRef),
+}
+
+/// A module defined through `Module.defineModule0`, HotSpot's ModuleEntry.
+#[derive(Debug)]
+pub struct NamedModule {
+    pub name: Symbol,
+    pub loader: Option<HeapRef>,
+    pub is_open: bool,
+    reads: HashSet<ModuleRef>,
+    reads_all_unnamed: bool,
+}
+
+impl NamedModule {
+    pub fn new(name: Symbol, loader: Option<HeapRef>, is_open: bool) -> Self {
+        Self {
+            name,
+            loader,
+            is_open,
+            reads: HashSet::new(),
+            reads_all_unnamed: false,
+        }
+    }
+}
+
+// HotSpot's PackageEntry of a named module
+#[derive(Debug)]
+struct ModulePackage {
+    module: HeapRef,
+    exported_to_all: bool,
+    exported_to_all_unnamed: bool,
+    exported_to: HashSet<ModuleRef>,
+}
+
+/// The named modules with their packages, reads and exports, keyed by their `java.lang.Module`.
+#[derive(Debug, Default)]
+pub struct ModuleTable {
+    modules: HashMap<HeapRef, NamedModule>,
+    // keyed by defining loader and package name in internal form
+    packages: HashMap<(Option<HeapRef>, Symbol), ModulePackage>,
+    java_base: Option<HeapRef>,
+    // BootLoader.UNNAMED_MODULE
+    boot_unnamed: Option<HeapRef>,
+    // ClassLoaders.PLATFORM_LOADER, known once it defines its first module
+    platform_loader: Option<HeapRef>,
+}
+
+impl ModuleTable {
+    pub fn java_base(&self) -> Option<HeapRef> {
+        self.java_base
+    }
+
+    pub fn boot_unnamed(&self) -> Option<HeapRef> {
+        self.boot_unnamed
+    }
+
+    pub fn set_boot_unnamed(&mut self, module: HeapRef) {
+        self.boot_unnamed = Some(module);
+    }
+
+    pub fn platform_loader(&self) -> Option<HeapRef> {
+        self.platform_loader
+    }
+
+    pub fn set_platform_loader(&mut self, loader: HeapRef) {
+        self.platform_loader = Some(loader);
+    }
+
+    pub fn get(&self, module: &HeapRef) -> Option<&NamedModule> {
+        self.modules.get(module)
+    }
+
+    pub fn find(&self, loader: Option<HeapRef>, name: Symbol) -> Option<HeapRef> {
+        self.modules
+            .iter()
+            .find(|(_, module)| module.loader == loader && module.name == name)
+            .map(|(module_ref, _)| *module_ref)
+    }
+
+    /// The named module that has `package` in `loader`.
+    pub fn package_module(&self, loader: Option<HeapRef>, package: Symbol) -> Option<HeapRef> {
+        self.packages
+            .get(&(loader, package))
+            .map(|package| package.module)
+    }
+
+    /// Defines `module` with its packages, the caller has checked none of them is defined yet.
+    pub fn define(
+        &mut self,
+        module_ref: HeapRef,
+        module: NamedModule,
+        packages: &[Symbol],
+        is_java_base: bool,
+    ) {
+        for package in packages {
+            self.packages.insert(
+                (module.loader, *package),
+                ModulePackage {
+                    module: module_ref,
+                    exported_to_all: false,
+                    exported_to_all_unnamed: false,
+                    exported_to: HashSet::new(),
+                },
+            );
+        }
+        if is_java_base {
+            self.java_base = Some(module_ref);
+        }
+        self.modules.insert(module_ref, module);
+    }
+
+    /// Unnamed modules read every module already, `None` makes `from` read all of them.
+    pub fn add_read(&mut self, from: HeapRef, to: Option<ModuleRef>) {
+        if let Some(module) = self.modules.get_mut(&from) {
+            match to {
+                None => module.reads_all_unnamed = true,
+                Some(to) => {
+                    module.reads.insert(to);
+                }
+            }
+        }
+    }
+
+    pub fn can_read(&self, from: ModuleRef, to: ModuleRef) -> bool {
+        let ModuleRef::Named(from_ref) = from else {
+            return true;
+        };
+        // every module reads java.base
+        if from == to
+            || self
+                .java_base
+                .is_some_and(|base| to == ModuleRef::Named(base))
+        {
+            return true;
+        }
+        self.modules.get(&from_ref).is_some_and(|module| {
+            module.reads.contains(&to) || (!to.is_named() && module.reads_all_unnamed)
+        })
+    }
+
+    pub fn export(&mut self, loader: Option<HeapRef>, package: Symbol, to: ExportTarget) {
+        let Some(package) = self.packages.get_mut(&(loader, package)) else {
+            return;
+        };
+        match to {
+            ExportTarget::All => package.exported_to_all = true,
+            ExportTarget::AllUnnamed => package.exported_to_all_unnamed = true,
+            ExportTarget::Module(module) => {
+                package.exported_to.insert(module);
+            }
+        }
+    }
+
+    /// Whether the module of `package` in `loader` exports it to `to`, HotSpot's
+    /// PackageEntry::is_qexported_to. Open modules export all their packages.
+    pub fn is_exported_to(&self, loader: Option<HeapRef>, package: Symbol, to: ModuleRef) -> bool {
+        let Some(package) = self.packages.get(&(loader, package)) else {
+            return true;
+        };
+        self.modules
+            .get(&package.module)
+            .is_some_and(|module| module.is_open)
+            || package.exported_to_all
+            || (package.exported_to_all_unnamed && !to.is_named())
+            || package.exported_to.contains(&to)
+    }
+}
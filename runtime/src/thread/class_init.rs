@@ -0,0 +1,72 @@
+use crate::keys::{ClassId, ThreadId};
+use crate::rt::{ClassLike, ClassState};
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+
+/// What a thread asking for the initialization of a class has to do next, JVMS 5.5 steps 2 to 6.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InitStep {
+    /// The caller initializes the class now and reports the outcome to [`ClassInitTable::finish`].
+    Run,
+    /// Initialized already, or being initialized by the caller itself further up its stack.
+    Done,
+    /// Another thread is initializing the class, block and ask again.
+    Wait,
+    /// An earlier initialization attempt failed.
+    Erroneous,
+}
+
+/// The thread running the initialization of each class that is being initialized.
+///
+/// The class' own state tells whether it is initialized, this table tells by whom it is being
+/// initialized. Both change under the same lock, so two threads can never both start initializing
+/// a class. Blocking goes through the [`Scheduler`](crate::thread::scheduler::Scheduler), like for
+/// monitors.
+#[derive(Default)]
+pub struct ClassInitTable {
+    in_progress: Mutex<HashMap<ClassId, ThreadId>>,
+}
+
+impl ClassInitTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn begin(&self, class: &dyn ClassLike, class_id: ClassId, thread_id: ThreadId) -> InitStep {
+        let mut in_progress = self.lock();
+        match class.init_state() {
+            ClassState::Initialized => return InitStep::Done,
+            ClassState::Erroneous => return InitStep::Erroneous,
+            _ => {}
+        }
+        match in_progress.get(&class_id) {
+            Some(owner) if *owner == thread_id => InitStep::Done,
+            Some(_) => InitStep::Wait,
+            None => {
+                in_progress.insert(class_id, thread_id);
+                class.set_initializing();
+                InitStep::Run
+            }
+        }
+    }
+
+    /// Records the outcome of an initialization started by [`ClassInitTable::begin`]. Threads
+    /// waiting for it have to be woken through the scheduler afterwards.
+    pub fn finish(&self, class: &dyn ClassLike, class_id: ClassId, succeeded: bool) {
+        let mut in_progress = self.lock();
+        if succeeded {
+            class.set_initialized();
+        } else {
+            class.set_erroneous();
+        }
+        in_progress.remove(&class_id);
+    }
+
+    pub fn is_in_progress(&self, class_id: ClassId) -> bool {
+        self.lock().contains_key(&class_id)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<ClassId, ThreadId>> {
+        self.in_progress.lock().unwrap()
+    }
+}
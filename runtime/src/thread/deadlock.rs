@@ -0,0 +1,126 @@
+use crate::VirtualMachine;
+use crate::error::JvmError;
+use crate::keys::ThreadId;
+use crate::thread::ThreadInfo;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::sync::Arc;
+use std::time::Duration;
+
+impl VirtualMachine {
+    /// HotSpot's "Found one Java-level deadlock" report for every cycle of threads blocked on
+    /// each other's monitors, or `None` if there is none. `requester` is the calling Java thread,
+    /// if any.
+    pub fn find_deadlocks(&self, requester: Option<ThreadId>) -> Result<Option<String>, JvmError> {
+        self.handshake(requester, || {
+            let mut out = String::new();
+            self.write_deadlocks(&mut out)?;
+            Ok((!out.is_empty()).then(|| out.trim_start().to_string()))
+        })
+    }
+
+    /// Must be called inside a handshake, the stacks of the deadlocked threads are printed too.
+    pub(super) fn write_deadlocks(&self, out: &mut String) -> Result<(), JvmError> {
+        let cycles = self.monitors.find_deadlocks();
+        if cycles.is_empty() {
+            return Ok(());
+        }
+        let threads = self
+            .threads
+            .snapshot()
+            .into_iter()
+            .map(|info| (info.id, info))
+            .collect::<HashMap<_, _>>();
+
+        for cycle in &cycles {
+            out.push_str("\nFound one Java-level deadlock:\n=============================\n");
+            for blocked in cycle {
+                let obj = blocked.obj;
+                let _ = writeln!(
+                    out,
+                    "\"{}\":",
+                    self.deadlocked_thread_name(&threads, blocked.thread_id)?
+                );
+                let _ = writeln!(
+                    out,
+                    "  waiting to lock monitor {obj:#018x} (object {obj:#018x}, a {}),",
+                    self.class_name_of(obj)?
+                );
+                let _ = writeln!(
+                    out,
+                    "  which is held by \"{}\"",
+                    self.deadlocked_thread_name(&threads, blocked.owner)?
+                );
+                out.push('\n');
+            }
+
+            out.push_str("Java stack information for the threads listed above:\n");
+            out.push_str("===================================================\n");
+            for blocked in cycle {
+                let Some(info) = threads.get(&blocked.thread_id) else {
+                    continue;
+                };
+                let name = self
+                    .heap_read()
+                    .get_rust_string_from_java_string(info.name)?;
+                let _ = writeln!(out, "\"{name}\":");
+                self.write_stack(out, info)?;
+            }
+        }
+
+        let plural = if cycles.len() == 1 { "" } else { "s" };
+        let _ = writeln!(out, "\nFound {} deadlock{plural}.", cycles.len());
+        Ok(())
+    }
+
+    fn deadlocked_thread_name(
+        &self,
+        threads: &HashMap<ThreadId, ThreadInfo>,
+        thread_id: ThreadId,
+    ) -> Result<String, JvmError> {
+        match threads.get(&thread_id) {
+            Some(info) => self.heap_read().get_rust_string_from_java_string(info.name),
+            None => Ok(format!("<thread {}>", thread_id.as_usize())),
+        }
+    }
+}
+
+/// Checks for deadlocks every `interval` and prints the report once per newly deadlocked set of
+/// threads.
+pub(crate) fn start_deadlock_watchdog(vm: &Arc<VirtualMachine>, interval: Duration) {
+    let vm = Arc::downgrade(vm);
+    let res = std::thread::Builder::new()
+        .name("Deadlock Watchdog".to_string())
+        .spawn(move || {
+            let mut reported = HashSet::new();
+            loop {
+                std::thread::sleep(interval);
+                let Some(vm) = vm.upgrade() else {
+                    return;
+                };
+                // the cheap check first, the report needs a safepoint
+                let deadlocked = vm
+                    .monitors
+                    .find_deadlocks()
+                    .into_iter()
+                    .flatten()
+                    .map(|blocked| blocked.thread_id)
+                    .collect::<HashSet<_>>();
+                if deadlocked.is_subset(&reported) {
+                    continue;
+                }
+                match vm.find_deadlocks(None) {
+                    Ok(Some(report)) => println!("{report}"),
+                    Ok(None) => {}
+                    Err(e) => eprintln!(
+                        "Warning: Could not report deadlock: {}",
+                        e.into_pretty_string(vm.interner())
+                    ),
+                }
+                reported.extend(deadlocked);
+            }
+        });
+    if let Err(e) = res {
+        eprintln!("Warning: Could not start deadlock watchdog: {e}");
+    }
+}
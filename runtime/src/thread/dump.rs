@@ -0,0 +1,162 @@
+use crate::VirtualMachine;
+use crate::error::JvmError;
+use crate::heap::HeapRef;
+use crate::keys::ThreadId;
+use crate::thread::{FrameSnapshot, ThreadInfo, ThreadStatus};
+use std::fmt::Write;
+use std::sync::Arc;
+
+impl VirtualMachine {
+    /// jstack-style dump of every Java thread, taken while all of them are stopped at a
+    /// safepoint. `requester` is the calling Java thread, if any.
+    pub fn thread_dump(&self, requester: Option<ThreadId>) -> String {
+        self.handshake(requester, || {
+            let mut out = format!("Full thread dump lagertha-vm ({}):\n", self.config.version);
+            for info in self.threads.snapshot() {
+                out.push('\n');
+                if let Err(e) = self.write_thread(&mut out, &info) {
+                    self.write_dump_error(&mut out, e);
+                }
+            }
+            if let Err(e) = self.write_deadlocks(&mut out) {
+                self.write_dump_error(&mut out, e);
+            }
+            out
+        })
+    }
+
+    pub fn print_thread_dump(&self, requester: Option<ThreadId>) {
+        println!("{}", self.thread_dump(requester));
+    }
+
+    fn write_thread(&self, out: &mut String, info: &ThreadInfo) -> Result<(), JvmError> {
+        let name = self
+            .heap_read()
+            .get_rust_string_from_java_string(info.name)?;
+        let tid = self.get_thread_tid(info.thread_obj)?;
+        let priority = self.get_thread_priority(info.thread_obj)?;
+        let status = self.get_thread_status(info.thread_obj)?;
+        let daemon = if info.daemon { " daemon" } else { "" };
+        let _ = writeln!(out, "\"{name}\" #{tid}{daemon} prio={priority}");
+        let _ = writeln!(out, "   java.lang.Thread.State: {}", status.description());
+        self.write_stack(out, info)
+    }
+
+    pub(super) fn write_stack(&self, out: &mut String, info: &ThreadInfo) -> Result<(), JvmError> {
+        let status = self.get_thread_status(info.thread_obj)?;
+        let frames = &info.stack.frames;
+        for (frame_index, frame) in frames.iter().enumerate().rev() {
+            let _ = writeln!(out, "\tat {}", self.describe_frame(frame));
+            if frame_index + 1 == frames.len() {
+                self.write_blocked_on(out, info, status)?;
+            }
+            for (_, obj) in info
+                .stack
+                .locked_monitors
+                .iter()
+                .rev()
+                .filter(|(index, _)| *index == frame_index)
+            {
+                let _ = writeln!(out, "\t- locked {}", self.describe_object(*obj)?);
+            }
+        }
+        Ok(())
+    }
+
+    fn write_dump_error(&self, out: &mut String, e: JvmError) {
+        let _ = writeln!(
+            out,
+            "\t<unable to dump: {}>",
+            e.into_pretty_string(self.interner())
+        );
+    }
+
+    fn write_blocked_on(
+        &self,
+        out: &mut String,
+        info: &ThreadInfo,
+        status: ThreadStatus,
+    ) -> Result<(), JvmError> {
+        if let Some(obj) = self.monitors.contended_object(info.id) {
+            let _ = writeln!(out, "\t- waiting to lock {}", self.describe_object(obj)?);
+        } else if let Some(obj) = self.monitors.waiting_on(info.id) {
+            let _ = writeln!(out, "\t- waiting on {}", self.describe_object(obj)?);
+        } else if matches!(status, ThreadStatus::Parked | ThreadStatus::ParkedTimed)
+            && let Some(blocker) = self.get_thread_park_blocker(info.thread_obj)?
+        {
+            let _ = writeln!(
+                out,
+                "\t- parking to wait for  {}",
+                self.describe_object(blocker)?
+            );
+        }
+        Ok(())
+    }
+
+    // java.lang.Thread.sleep(Thread.java:509)
+    fn describe_frame(&self, frame: &FrameSnapshot) -> String {
+        let ma = self.method_area_read();
+        let method = ma.get_method(&frame.method_id);
+        let class = ma.get_class(&method.class_id());
+        let class_name = self.symbol_to_pretty_string(class.get_name());
+        let method_name = self.interner().resolve(&method.name);
+        let location = match frame.pc {
+            None => "Native Method".to_string(),
+            Some(pc) => match (
+                class.get_source_file(),
+                method.get_line_number_by_cp(pc as i32),
+            ) {
+                (Some(source), Some(line)) => {
+                    format!("{}:{line}", self.interner().resolve(&source))
+                }
+                (Some(source), None) => self.interner().resolve(&source).to_string(),
+                (None, _) => "Unknown Source".to_string(),
+            },
+        };
+        format!("{class_name}.{method_name}({location})")
+    }
+
+    // <0x00000000000012f0> (a java.lang.Object)
+    pub(super) fn describe_object(&self, obj: HeapRef) -> Result<String, JvmError> {
+        Ok(format!("<{obj:#018x}> (a {})", self.class_name_of(obj)?))
+    }
+
+    pub(super) fn class_name_of(&self, obj: HeapRef) -> Result<String, JvmError> {
+        let class_id = self.heap_read().get_class_id(obj)?;
+        Ok(self.symbol_to_pretty_string(self.method_area_read().get_class(&class_id).get_name()))
+    }
+}
+
+/// Prints a thread dump on every SIGQUIT, like `kill -3` does for HotSpot.
+///
+/// SIGQUIT is blocked here and received synchronously by a dedicated thread, so this must run
+/// before any other thread is spawned to have them inherit the signal mask.
+pub(crate) fn start_signal_dispatcher(vm: &Arc<VirtualMachine>) {
+    // SAFETY: plain libc calls on a locally owned signal set
+    let set = unsafe {
+        let mut set: libc::sigset_t = std::mem::zeroed();
+        libc::sigemptyset(&mut set);
+        libc::sigaddset(&mut set, libc::SIGQUIT);
+        libc::pthread_sigmask(libc::SIG_BLOCK, &set, std::ptr::null_mut());
+        set
+    };
+    let vm = Arc::downgrade(vm);
+    let res = std::thread::Builder::new()
+        .name("Signal Dispatcher".to_string())
+        .spawn(move || {
+            loop {
+                let mut signal = 0;
+                // SAFETY: `set` outlives the call
+                if unsafe { libc::sigwait(&set, &mut signal) } != 0 {
+                    return;
+                }
+                let Some(vm) = vm.upgrade() else {
+                    return;
+                };
+                vm.print_thread_dump(None);
+            }
+        });
+    if let Err(e) = res {
+        eprintln!("Warning: Could not start signal dispatcher: {e}");
+    }
+}
@@ -1,6 +1,22 @@
+use crate::error::JvmError;
 use crate::heap::HeapRef;
-use crate::keys::ThreadId;
-use crate::vm::stack::FrameStack;
+use crate::keys::{FieldKey, MethodId, ThreadId};
+use crate::vm::Value;
+use crate::vm::stack::{FrameStack, FrameType};
+use crate::{VirtualMachine, VmConfig};
+use common::jtype::AllocationType;
+use num_enum::TryFromPrimitive;
+use once_cell::sync::OnceCell;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+pub mod class_init;
+pub mod deadlock;
+pub mod dump;
+pub mod monitor;
+pub mod safepoint;
+pub mod scheduler;
 
 pub struct JavaThreadState {
     pub id: ThreadId,
@@ -8,4 +24,390 @@ pub struct JavaThreadState {
     pub group_obj: HeapRef, // TODO: Once cell?
     pub name: HeapRef,
     pub stack: FrameStack,
+    // monitors entered by this thread and the index of the frame that entered them
+    pub locked_monitors: Vec<(usize, HeapRef)>,
+}
+
+impl JavaThreadState {
+    pub fn new(id: ThreadId, thread_obj: HeapRef, name: HeapRef, config: &VmConfig) -> Self {
+        Self {
+            id,
+            thread_obj,
+            group_obj: 0,
+            name,
+            stack: FrameStack::new(config),
+            locked_monitors: Vec::new(),
+        }
+    }
+}
+
+// java.lang.Thread.State as encoded in Thread.FieldHolder.threadStatus (JVMTI thread state bits)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive)]
+#[repr(i32)]
+pub enum ThreadStatus {
+    New = 0x0000,
+    Runnable = 0x0005,
+    Sleeping = 0x00e1,
+    InObjectWait = 0x0191,
+    InObjectWaitTimed = 0x01a1,
+    Parked = 0x0291,
+    ParkedTimed = 0x02a1,
+    BlockedOnMonitorEnter = 0x0401,
+    Terminated = 0x0002,
+}
+
+impl ThreadStatus {
+    // as printed by jstack
+    pub fn description(self) -> &'static str {
+        match self {
+            ThreadStatus::New => "NEW",
+            ThreadStatus::Runnable => "RUNNABLE",
+            ThreadStatus::Sleeping => "TIMED_WAITING (sleeping)",
+            ThreadStatus::InObjectWait => "WAITING (on object monitor)",
+            ThreadStatus::InObjectWaitTimed => "TIMED_WAITING (on object monitor)",
+            ThreadStatus::Parked => "WAITING (parking)",
+            ThreadStatus::ParkedTimed => "TIMED_WAITING (parking)",
+            ThreadStatus::BlockedOnMonitorEnter => "BLOCKED (on object monitor)",
+            ThreadStatus::Terminated => "TERMINATED",
+        }
+    }
+}
+
+/// What a thread dump shows of a thread: its frames and the monitors they hold.
+///
+/// Published by the owning thread right before it may become safe (blocking in the scheduler or
+/// stopping at a poll), so it is current for as long as the thread stays safe.
+#[derive(Clone, Default)]
+pub struct StackSnapshot {
+    pub frames: Vec<FrameSnapshot>,
+    pub locked_monitors: Vec<(usize, HeapRef)>,
+}
+
+#[derive(Clone, Copy)]
+pub struct FrameSnapshot {
+    pub method_id: MethodId,
+    // None for native frames
+    pub pc: Option<usize>,
+}
+
+impl StackSnapshot {
+    fn of(thread: &JavaThreadState) -> Self {
+        let frames = thread
+            .stack
+            .frames()
+            .iter()
+            .map(|frame| FrameSnapshot {
+                method_id: frame.method_id(),
+                pc: match frame {
+                    FrameType::JavaFrame(java_frame) => Some(java_frame.pc()),
+                    FrameType::NativeFrame(_) => None,
+                },
+            })
+            .collect();
+        Self {
+            frames,
+            locked_monitors: thread.locked_monitors.clone(),
+        }
+    }
+}
+
+pub struct ThreadEntry {
+    pub daemon: bool,
+    pub thread_obj: HeapRef,
+    pub name: HeapRef,
+    // LockSupport permit, at most one
+    permit: bool,
+    // empty until the thread publishes its stack for the first time
+    stack: StackSnapshot,
+}
+
+impl ThreadEntry {
+    pub fn new(daemon: bool, thread_obj: HeapRef, name: HeapRef) -> Self {
+        Self {
+            daemon,
+            thread_obj,
+            name,
+            permit: false,
+            stack: StackSnapshot::default(),
+        }
+    }
+}
+
+/// A copy of a [`ThreadEntry`] that can be inspected without holding the registry lock.
+pub struct ThreadInfo {
+    pub id: ThreadId,
+    pub daemon: bool,
+    pub thread_obj: HeapRef,
+    pub name: HeapRef,
+    pub stack: StackSnapshot,
+}
+
+/// Every live Java thread, the main thread included.
+pub struct ThreadRegistry {
+    next_index: AtomicUsize,
+    threads: Mutex<BTreeMap<ThreadId, ThreadEntry>>,
+    // "native" address of the counter behind Thread.ThreadIdentifiers, see getNextThreadIdOffset
+    next_tid_address: OnceCell<usize>,
+}
+
+impl ThreadRegistry {
+    pub fn new() -> Self {
+        Self {
+            next_index: AtomicUsize::new(0),
+            threads: Mutex::new(BTreeMap::new()),
+            next_tid_address: OnceCell::new(),
+        }
+    }
+
+    pub fn next_thread_id(&self) -> ThreadId {
+        ThreadId::from_index(self.next_index.fetch_add(1, Ordering::Relaxed))
+    }
+
+    pub fn add(&self, id: ThreadId, entry: ThreadEntry) {
+        self.threads.lock().unwrap().insert(id, entry);
+    }
+
+    pub fn remove(&self, id: ThreadId) {
+        self.threads.lock().unwrap().remove(&id);
+    }
+
+    /// Makes the current stack of `thread` visible to thread dumps.
+    pub fn publish_stack(&self, thread: &JavaThreadState) {
+        let stack = StackSnapshot::of(thread);
+        if let Some(entry) = self.threads.lock().unwrap().get_mut(&thread.id) {
+            entry.stack = stack;
+        }
+    }
+
+    pub fn snapshot(&self) -> Vec<ThreadInfo> {
+        self.threads
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, entry)| ThreadInfo {
+                id: *id,
+                daemon: entry.daemon,
+                thread_obj: entry.thread_obj,
+                name: entry.name,
+                stack: entry.stack.clone(),
+            })
+            .collect()
+    }
+
+    pub fn has_non_daemon_threads_except(&self, id: ThreadId) -> bool {
+        self.threads
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|(thread_id, entry)| *thread_id != id && !entry.daemon)
+    }
+
+    /// Makes the permit of `id` available, the caller has to wake the thread up.
+    pub fn unpark(&self, id: ThreadId) {
+        if let Some(entry) = self.threads.lock().unwrap().get_mut(&id) {
+            entry.permit = true;
+        }
+    }
+
+    /// Consumes the permit of `id` if it is available.
+    pub fn take_permit(&self, id: ThreadId) -> bool {
+        self.threads
+            .lock()
+            .unwrap()
+            .get_mut(&id)
+            .is_some_and(|entry| std::mem::take(&mut entry.permit))
+    }
+
+    pub fn next_tid_address(&self) -> &OnceCell<usize> {
+        &self.next_tid_address
+    }
+}
+
+impl Default for ThreadRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Accessors for the java.lang.Thread fields the VM maintains
+impl VirtualMachine {
+    fn thread_field_offset(&self, field_key: &FieldKey) -> Result<usize, JvmError> {
+        let thread_class_id = self.br().get_java_lang_thread_id()?;
+        Ok(self
+            .method_area_read()
+            .get_instance_class(&thread_class_id)?
+            .get_instance_field(field_key)?
+            .offset)
+    }
+
+    fn thread_holder_field_offset(
+        &self,
+        holder: HeapRef,
+        field_key: &FieldKey,
+    ) -> Result<usize, JvmError> {
+        let holder_class_id = self.heap_read().get_class_id(holder)?;
+        Ok(self
+            .method_area_read()
+            .get_instance_class(&holder_class_id)?
+            .get_instance_field(field_key)?
+            .offset)
+    }
+
+    fn thread_holder(&self, thread_obj: HeapRef) -> Result<HeapRef, JvmError> {
+        let offset = self.thread_field_offset(&self.br().thread_holder_fk)?;
+        self.heap_read()
+            .read_field(thread_obj, offset, AllocationType::Reference)?
+            .as_obj_ref()
+    }
+
+    pub(crate) fn get_thread_status(&self, thread_obj: HeapRef) -> Result<ThreadStatus, JvmError> {
+        let holder = self.thread_holder(thread_obj)?;
+        let offset = self.thread_holder_field_offset(holder, &self.br().thread_status_fk)?;
+        let status = self
+            .heap_read()
+            .read_field(holder, offset, AllocationType::Int)?
+            .as_int()?;
+        ThreadStatus::try_from(status)
+            .map_err(|_| JvmError::Todo(format!("Unknown thread status: {status:#x}")))
+    }
+
+    /// Runs the blocking operation `f` with the thread status set to `status`.
+    pub(crate) fn with_thread_status<R, F>(
+        &self,
+        thread: &JavaThreadState,
+        status: ThreadStatus,
+        f: F,
+    ) -> Result<R, JvmError>
+    where
+        F: FnOnce() -> R,
+    {
+        self.set_thread_status(thread.thread_obj, status)?;
+        self.threads.publish_stack(thread);
+        let res = f();
+        self.set_thread_status(thread.thread_obj, ThreadStatus::Runnable)?;
+        Ok(res)
+    }
+
+    /// Enters the monitor of `obj` on behalf of the frame at `frame_index`.
+    pub(crate) fn monitor_enter(
+        &self,
+        thread: &mut JavaThreadState,
+        obj: HeapRef,
+        frame_index: usize,
+    ) -> Result<(), JvmError> {
+        if !self.monitors.try_enter(thread.id, obj) {
+            let thread_id = thread.id;
+            self.with_thread_status(thread, ThreadStatus::BlockedOnMonitorEnter, || {
+                self.monitors.enter(&self.scheduler, thread_id, obj)
+            })??;
+        }
+        thread.locked_monitors.push((frame_index, obj));
+        Ok(())
+    }
+
+    pub(crate) fn monitor_exit(
+        &self,
+        thread: &mut JavaThreadState,
+        obj: HeapRef,
+    ) -> Result<(), JvmError> {
+        self.monitors.exit(&self.scheduler, thread.id, obj)?;
+        if let Some(pos) = thread
+            .locked_monitors
+            .iter()
+            .rposition(|(_, locked)| *locked == obj)
+        {
+            thread.locked_monitors.remove(pos);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn set_thread_status(
+        &self,
+        thread_obj: HeapRef,
+        status: ThreadStatus,
+    ) -> Result<(), JvmError> {
+        let holder = self.thread_holder(thread_obj)?;
+        let offset = self.thread_holder_field_offset(holder, &self.br().thread_status_fk)?;
+        self.heap_write().write_field(
+            holder,
+            offset,
+            Value::Integer(status as i32),
+            AllocationType::Int,
+        )
+    }
+
+    pub(crate) fn get_thread_priority(&self, thread_obj: HeapRef) -> Result<i32, JvmError> {
+        let holder = self.thread_holder(thread_obj)?;
+        let offset = self.thread_holder_field_offset(holder, &self.br().thread_priority_fk)?;
+        self.heap_read()
+            .read_field(holder, offset, AllocationType::Int)?
+            .as_int()
+    }
+
+    // the Java level thread id, Thread.threadId()
+    pub(crate) fn get_thread_tid(&self, thread_obj: HeapRef) -> Result<i64, JvmError> {
+        let offset = self.thread_field_offset(&self.br().thread_tid_fk)?;
+        self.heap_read()
+            .read_field(thread_obj, offset, AllocationType::Long)?
+            .as_long()
+    }
+
+    pub(crate) fn get_thread_park_blocker(
+        &self,
+        thread_obj: HeapRef,
+    ) -> Result<Option<HeapRef>, JvmError> {
+        let offset = self.thread_field_offset(&self.br().thread_park_blocker_fk)?;
+        self.heap_read()
+            .read_field(thread_obj, offset, AllocationType::Reference)?
+            .as_nullable_obj_ref()
+    }
+
+    pub(crate) fn is_daemon_thread(&self, thread_obj: HeapRef) -> Result<bool, JvmError> {
+        let holder = self.thread_holder(thread_obj)?;
+        let offset = self.thread_holder_field_offset(holder, &self.br().thread_daemon_fk)?;
+        Ok(self
+            .heap_read()
+            .read_field(holder, offset, AllocationType::Boolean)?
+            .as_int()?
+            != 0)
+    }
+
+    // eetop is the address of the native thread in HotSpot, Thread.isAlive() only checks it for 0
+    pub(crate) fn set_thread_eetop(&self, thread_obj: HeapRef, eetop: i64) -> Result<(), JvmError> {
+        let offset = self.thread_field_offset(&self.br().thread_eetop_fk)?;
+        self.heap_write()
+            .write_field(thread_obj, offset, Value::Long(eetop), AllocationType::Long)
+    }
+
+    /// The id of a started thread that hasn't terminated yet, see `set_thread_eetop`.
+    pub(crate) fn get_live_thread_id(
+        &self,
+        thread_obj: HeapRef,
+    ) -> Result<Option<ThreadId>, JvmError> {
+        let offset = self.thread_field_offset(&self.br().thread_eetop_fk)?;
+        let eetop = self
+            .heap_read()
+            .read_field(thread_obj, offset, AllocationType::Long)?
+            .as_long()?;
+        Ok((eetop != 0).then(|| ThreadId::from_usize(eetop as usize)))
+    }
+
+    pub(crate) fn is_thread_interrupted(&self, thread_obj: HeapRef) -> Result<bool, JvmError> {
+        let offset = self.thread_field_offset(&self.br().thread_interrupted_fk)?;
+        Ok(self
+            .heap_read()
+            .read_field(thread_obj, offset, AllocationType::Boolean)?
+            .as_int()?
+            != 0)
+    }
+
+    pub(crate) fn clear_thread_interrupted(&self, thread_obj: HeapRef) -> Result<(), JvmError> {
+        let offset = self.thread_field_offset(&self.br().thread_interrupted_fk)?;
+        self.heap_write().write_field(
+            thread_obj,
+            offset,
+            Value::Integer(0),
+            AllocationType::Boolean,
+        )
+    }
 }
@@ -0,0 +1,318 @@
+use crate::error::JvmError;
+use crate::heap::HeapRef;
+use crate::keys::ThreadId;
+use crate::thread::scheduler::Scheduler;
+use crate::throw_exception;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+#[derive(Default)]
+struct ObjectMonitor {
+    owner: Option<ThreadId>,
+    recursions: u32,
+    // threads inside Object.wait, in arrival order
+    wait_set: Vec<ThreadId>,
+}
+
+impl ObjectMonitor {
+    fn is_unused(&self) -> bool {
+        self.owner.is_none() && self.wait_set.is_empty()
+    }
+}
+
+/// One edge of the waits-for graph: `thread_id` is blocked on the monitor of `obj`, which is
+/// owned by `owner`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockedOnMonitor {
+    pub thread_id: ThreadId,
+    pub obj: HeapRef,
+    pub owner: ThreadId,
+}
+
+#[derive(Default)]
+struct Monitors {
+    by_object: HashMap<HeapRef, ObjectMonitor>,
+    // threads blocked in monitorenter and the object they are trying to lock
+    contended: HashMap<ThreadId, HeapRef>,
+}
+
+impl Monitors {
+    /// Takes the monitor of `obj` if it is free or already owned by `thread_id`. On success the
+    /// thread stops being contended in the same step, so it never shows up as waiting for itself.
+    fn acquire(&mut self, thread_id: ThreadId, obj: HeapRef, recursions: u32) -> bool {
+        let monitor = self.by_object.entry(obj).or_default();
+        let acquired = match monitor.owner {
+            None => {
+                monitor.owner = Some(thread_id);
+                monitor.recursions = recursions;
+                true
+            }
+            Some(owner) if owner == thread_id => {
+                monitor.recursions += recursions;
+                true
+            }
+            Some(_) => false,
+        };
+        if acquired {
+            self.contended.remove(&thread_id);
+        }
+        acquired
+    }
+}
+
+/// Object monitors, inflated lazily on first use and dropped again once nobody owns or waits on
+/// them. All blocking goes through the [`Scheduler`], so the same code serves native and green
+/// threads.
+#[derive(Default)]
+pub struct MonitorTable {
+    monitors: Mutex<Monitors>,
+}
+
+impl MonitorTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn enter(
+        &self,
+        scheduler: &Scheduler,
+        thread_id: ThreadId,
+        obj: HeapRef,
+    ) -> Result<(), JvmError> {
+        self.enter_with_recursions(scheduler, thread_id, obj, 1);
+        Ok(())
+    }
+
+    pub fn exit(
+        &self,
+        scheduler: &Scheduler,
+        thread_id: ThreadId,
+        obj: HeapRef,
+    ) -> Result<(), JvmError> {
+        let released = {
+            let mut monitors = self.lock();
+            let Some(monitor) = monitors.by_object.get_mut(&obj) else {
+                return throw_exception!(IllegalMonitorStateException);
+            };
+            if monitor.owner != Some(thread_id) {
+                return throw_exception!(IllegalMonitorStateException);
+            }
+            monitor.recursions -= 1;
+            if monitor.recursions == 0 {
+                monitor.owner = None;
+                if monitor.is_unused() {
+                    monitors.by_object.remove(&obj);
+                }
+                true
+            } else {
+                false
+            }
+        };
+        if released {
+            scheduler.notify_all();
+        }
+        Ok(())
+    }
+
+    /// Implements `Object.wait`. Returns `true` if the wait ended because `is_interrupted` turned
+    /// true; the monitor is re-acquired in every case.
+    pub fn wait<F>(
+        &self,
+        scheduler: &Scheduler,
+        thread_id: ThreadId,
+        obj: HeapRef,
+        timeout: Option<Duration>,
+        mut is_interrupted: F,
+    ) -> Result<bool, JvmError>
+    where
+        F: FnMut() -> bool,
+    {
+        let saved_recursions = {
+            let mut monitors = self.lock();
+            let Some(monitor) = monitors.by_object.get_mut(&obj) else {
+                return throw_exception!(
+                    IllegalMonitorStateException,
+                    "current thread is not owner"
+                );
+            };
+            if monitor.owner != Some(thread_id) {
+                return throw_exception!(
+                    IllegalMonitorStateException,
+                    "current thread is not owner"
+                );
+            }
+            let saved_recursions = monitor.recursions;
+            monitor.owner = None;
+            monitor.recursions = 0;
+            monitor.wait_set.push(thread_id);
+            saved_recursions
+        };
+        scheduler.notify_all();
+
+        let mut interrupted = false;
+        scheduler.block_until(thread_id, timeout, || {
+            interrupted = is_interrupted();
+            interrupted || !self.is_in_wait_set(thread_id, obj)
+        });
+
+        if let Some(monitor) = self.lock().by_object.get_mut(&obj) {
+            monitor.wait_set.retain(|id| *id != thread_id);
+        }
+        self.enter_with_recursions(scheduler, thread_id, obj, saved_recursions);
+        Ok(interrupted)
+    }
+
+    pub fn notify(&self, thread_id: ThreadId, obj: HeapRef) -> Result<(), JvmError> {
+        self.notify_waiters(thread_id, obj, false)
+    }
+
+    pub fn notify_all(&self, thread_id: ThreadId, obj: HeapRef) -> Result<(), JvmError> {
+        self.notify_waiters(thread_id, obj, true)
+    }
+
+    /// Enters the monitor only if that doesn't require blocking.
+    pub fn try_enter(&self, thread_id: ThreadId, obj: HeapRef) -> bool {
+        self.try_enter_with_recursions(thread_id, obj, 1)
+    }
+
+    /// The object whose monitor `thread_id` is blocked on in `monitorenter`.
+    pub fn contended_object(&self, thread_id: ThreadId) -> Option<HeapRef> {
+        self.lock().contended.get(&thread_id).copied()
+    }
+
+    /// The object `thread_id` is waiting on in `Object.wait`.
+    pub fn waiting_on(&self, thread_id: ThreadId) -> Option<HeapRef> {
+        self.lock()
+            .by_object
+            .iter()
+            .find(|(_, monitor)| monitor.wait_set.contains(&thread_id))
+            .map(|(obj, _)| *obj)
+    }
+
+    /// Cycles in the waits-for graph of threads blocked in `monitorenter` (including threads
+    /// re-acquiring a monitor after `Object.wait`). None of these threads can ever proceed.
+    ///
+    /// Every thread waits for at most one monitor, so following the edges from each thread either
+    /// ends at a running thread or runs into a cycle. Each cycle starts at its lowest thread id.
+    pub fn find_deadlocks(&self) -> Vec<Vec<BlockedOnMonitor>> {
+        let waits_for = {
+            let monitors = self.lock();
+            monitors
+                .contended
+                .iter()
+                .filter_map(|(thread_id, obj)| {
+                    let owner = monitors.by_object.get(obj)?.owner?;
+                    if owner == *thread_id {
+                        return None;
+                    }
+                    Some((
+                        *thread_id,
+                        BlockedOnMonitor {
+                            thread_id: *thread_id,
+                            obj: *obj,
+                            owner,
+                        },
+                    ))
+                })
+                .collect::<HashMap<_, _>>()
+        };
+
+        let mut starts = waits_for.keys().copied().collect::<Vec<_>>();
+        starts.sort();
+        let mut visited = HashSet::new();
+        let mut cycles = Vec::new();
+        for start in starts {
+            let mut path = Vec::new();
+            let mut cur = start;
+            while visited.insert(cur) {
+                let Some(edge) = waits_for.get(&cur) else {
+                    break;
+                };
+                path.push(*edge);
+                cur = edge.owner;
+            }
+            // the walk stopped at a thread seen before, it's a new cycle only if that thread is
+            // on the current path
+            if let Some(pos) = path.iter().position(|edge| edge.thread_id == cur) {
+                let mut cycle = path.split_off(pos);
+                let min = (0..cycle.len())
+                    .min_by_key(|i| cycle[*i].thread_id)
+                    .unwrap_or(0);
+                cycle.rotate_left(min);
+                cycles.push(cycle);
+            }
+        }
+        cycles
+    }
+
+    pub fn holds_lock(&self, thread_id: ThreadId, obj: HeapRef) -> bool {
+        self.lock()
+            .by_object
+            .get(&obj)
+            .is_some_and(|monitor| monitor.owner == Some(thread_id))
+    }
+
+    fn notify_waiters(&self, thread_id: ThreadId, obj: HeapRef, all: bool) -> Result<(), JvmError> {
+        {
+            let mut monitors = self.lock();
+            let Some(monitor) = monitors
+                .by_object
+                .get_mut(&obj)
+                .filter(|monitor| monitor.owner == Some(thread_id))
+            else {
+                return throw_exception!(
+                    IllegalMonitorStateException,
+                    "current thread is not owner"
+                );
+            };
+            if all {
+                monitor.wait_set.clear();
+            } else if !monitor.wait_set.is_empty() {
+                monitor.wait_set.remove(0);
+            }
+        }
+        // no wake-up here: notified threads can't proceed before we release the monitor,
+        // and `exit` wakes them
+        Ok(())
+    }
+
+    fn enter_with_recursions(
+        &self,
+        scheduler: &Scheduler,
+        thread_id: ThreadId,
+        obj: HeapRef,
+        recursions: u32,
+    ) {
+        {
+            let mut monitors = self.lock();
+            if monitors.acquire(thread_id, obj, recursions) {
+                return;
+            }
+            monitors.contended.insert(thread_id, obj);
+        }
+        scheduler.block_until(thread_id, None, || {
+            self.try_enter_with_recursions(thread_id, obj, recursions)
+        });
+    }
+
+    fn try_enter_with_recursions(
+        &self,
+        thread_id: ThreadId,
+        obj: HeapRef,
+        recursions: u32,
+    ) -> bool {
+        self.lock().acquire(thread_id, obj, recursions)
+    }
+
+    fn is_in_wait_set(&self, thread_id: ThreadId, obj: HeapRef) -> bool {
+        self.lock()
+            .by_object
+            .get(&obj)
+            .is_some_and(|monitor| monitor.wait_set.contains(&thread_id))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Monitors> {
+        self.monitors.lock().unwrap()
+    }
+}
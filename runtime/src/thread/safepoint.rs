@@ -0,0 +1,194 @@
+use crate::keys::ThreadId;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex, MutexGuard};
+
+/// Where a thread is with respect to a safepoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SafepointState {
+    /// Executing bytecode, will stop at the next poll.
+    InJava,
+    /// Blocked in the scheduler (monitors, `Object.wait`, sleeping, parking, waiting for a turn)
+    /// or not started yet. Doesn't touch the Java state until it leaves the safe region.
+    Blocked,
+    /// Stopped at a poll.
+    Stopped,
+}
+
+struct SafepointInner {
+    threads: HashMap<ThreadId, SafepointState>,
+    // running handshakes plus one for a debugger suspend
+    pending: u32,
+    suspended: bool,
+}
+
+impl SafepointInner {
+    fn all_safe_except(&self, requester: Option<ThreadId>) -> bool {
+        self.threads
+            .iter()
+            .all(|(id, state)| Some(*id) == requester || *state != SafepointState::InJava)
+    }
+}
+
+/// Brings every Java thread to a known bytecode boundary.
+///
+/// The interpreter polls at method entry/exit and backward branches; once a safepoint is
+/// requested, threads in Java stop at their next poll, while threads blocked in the scheduler are
+/// already safe and are held back when they try to return to Java.
+pub struct Safepoint {
+    // fast path for the polls, mirrors `pending > 0`
+    requested: AtomicBool,
+    inner: Mutex<SafepointInner>,
+    changed: Condvar,
+    // handshake operations run one at a time even if their safepoints overlap
+    operation: Mutex<()>,
+}
+
+impl Safepoint {
+    pub fn new() -> Self {
+        Self {
+            requested: AtomicBool::new(false),
+            inner: Mutex::new(SafepointInner {
+                threads: HashMap::new(),
+                pending: 0,
+                suspended: false,
+            }),
+            changed: Condvar::new(),
+            operation: Mutex::new(()),
+        }
+    }
+
+    pub fn register(&self, thread_id: ThreadId, state: SafepointState) {
+        self.lock().threads.insert(thread_id, state);
+        self.changed.notify_all();
+    }
+
+    pub fn unregister(&self, thread_id: ThreadId) {
+        self.lock().threads.remove(&thread_id);
+        self.changed.notify_all();
+    }
+
+    #[inline]
+    pub fn is_requested(&self) -> bool {
+        self.requested.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    pub fn poll(&self, thread_id: ThreadId) {
+        if self.is_requested() {
+            self.stop(thread_id);
+        }
+    }
+
+    pub fn enter_safe_region(&self, thread_id: ThreadId) {
+        self.set_state(&mut self.lock(), thread_id, SafepointState::Blocked);
+    }
+
+    /// Returns to Java, waiting for a safepoint in progress to finish first.
+    pub fn leave_safe_region(&self, thread_id: ThreadId) {
+        let mut inner = self.wait_while_pending(self.lock());
+        self.set_state(&mut inner, thread_id, SafepointState::InJava);
+    }
+
+    /// Stops all threads but the requester, runs `op` and resumes them.
+    pub fn handshake<R, F>(&self, requester: Option<ThreadId>, op: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        if let Some(requester) = requester {
+            self.enter_safe_region(requester);
+        }
+        {
+            let mut inner = self.lock();
+            inner.pending += 1;
+            self.requested.store(true, Ordering::Release);
+            drop(self.wait_until_safe(inner, requester));
+        }
+        let res = {
+            let _operation = self.operation.lock().unwrap();
+            op()
+        };
+        self.release(|inner| inner.pending -= 1);
+        if let Some(requester) = requester {
+            self.leave_safe_region(requester);
+        }
+        res
+    }
+
+    /// Stops all threads but the requester until [`Safepoint::resume_all`] is called.
+    pub fn suspend_all(&self, requester: Option<ThreadId>) {
+        let mut inner = self.lock();
+        if !inner.suspended {
+            inner.suspended = true;
+            inner.pending += 1;
+            self.requested.store(true, Ordering::Release);
+        }
+        drop(self.wait_until_safe(inner, requester));
+    }
+
+    pub fn resume_all(&self) {
+        self.release(|inner| {
+            if inner.suspended {
+                inner.suspended = false;
+                inner.pending -= 1;
+            }
+        });
+    }
+
+    #[cold]
+    fn stop(&self, thread_id: ThreadId) {
+        let mut inner = self.lock();
+        self.set_state(&mut inner, thread_id, SafepointState::Stopped);
+        let mut inner = self.wait_while_pending(inner);
+        self.set_state(&mut inner, thread_id, SafepointState::InJava);
+    }
+
+    fn release<F>(&self, update: F)
+    where
+        F: FnOnce(&mut SafepointInner),
+    {
+        let mut inner = self.lock();
+        update(&mut inner);
+        self.requested.store(inner.pending > 0, Ordering::Release);
+        drop(inner);
+        self.changed.notify_all();
+    }
+
+    fn set_state(&self, inner: &mut SafepointInner, thread_id: ThreadId, state: SafepointState) {
+        if let Some(cur) = inner.threads.get_mut(&thread_id) {
+            *cur = state;
+        }
+        self.changed.notify_all();
+    }
+
+    fn wait_until_safe<'a>(
+        &self,
+        mut inner: MutexGuard<'a, SafepointInner>,
+        requester: Option<ThreadId>,
+    ) -> MutexGuard<'a, SafepointInner> {
+        while !inner.all_safe_except(requester) {
+            inner = self.changed.wait(inner).unwrap();
+        }
+        inner
+    }
+
+    fn wait_while_pending<'a>(
+        &self,
+        mut inner: MutexGuard<'a, SafepointInner>,
+    ) -> MutexGuard<'a, SafepointInner> {
+        while inner.pending > 0 {
+            inner = self.changed.wait(inner).unwrap();
+        }
+        inner
+    }
+
+    fn lock(&self) -> MutexGuard<'_, SafepointInner> {
+        self.inner.lock().unwrap()
+    }
+}
+
+impl Default for Safepoint {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,387 @@
+use crate::keys::ThreadId;
+use crate::thread::safepoint::Safepoint;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// How Java threads are mapped onto the host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SchedulerMode {
+    /// Every Java thread runs freely on its own OS thread.
+    #[default]
+    Native,
+    /// Only one Java thread runs at a time and the switches are driven by a seeded PRNG,
+    /// so the same seed always produces the same interleaving.
+    Green { seed: u64, quantum: u32 },
+}
+
+/// Decides which Java thread may run and provides the single blocking primitive used by
+/// monitors, `Object.wait`, `Thread.sleep` and parking.
+///
+/// Blocking code never waits on its own OS primitive, it calls [`Scheduler::block_until`] with a
+/// condition and whoever changes the state behind that condition calls [`Scheduler::notify_all`].
+/// A thread blocked here is in a safe region, see [`Safepoint`].
+pub enum Scheduler {
+    Native(NativeScheduler),
+    Green(GreenScheduler),
+}
+
+impl Scheduler {
+    pub fn new(mode: SchedulerMode, main_thread_id: ThreadId, safepoint: Arc<Safepoint>) -> Self {
+        match mode {
+            SchedulerMode::Native => Scheduler::Native(NativeScheduler::new(safepoint)),
+            SchedulerMode::Green { seed, quantum } => Scheduler::Green(GreenScheduler::new(
+                seed,
+                quantum,
+                main_thread_id,
+                safepoint,
+            )),
+        }
+    }
+
+    /// Called by the parent thread before the OS thread of `thread_id` is spawned.
+    pub fn register(&self, thread_id: ThreadId) {
+        if let Scheduler::Green(green) = self {
+            green.register(thread_id);
+        }
+    }
+
+    /// Called by a freshly spawned thread before it executes any bytecode.
+    pub fn attach(&self, thread_id: ThreadId) {
+        if let Scheduler::Green(green) = self {
+            green.attach(thread_id);
+        }
+    }
+
+    /// Called by a terminating thread after it executed its last bytecode.
+    pub fn detach(&self, thread_id: ThreadId) {
+        match self {
+            Scheduler::Native(native) => native.notify_all(),
+            Scheduler::Green(green) => green.detach(thread_id),
+        }
+    }
+
+    /// Counts one bytecode of the running thread, returns true once its quantum is used up and it
+    /// has to [`Scheduler::yield_now`].
+    #[inline]
+    pub fn tick(&self) -> bool {
+        match self {
+            Scheduler::Native(_) => false,
+            Scheduler::Green(green) => green.tick(),
+        }
+    }
+
+    pub fn yield_now(&self, thread_id: ThreadId) {
+        match self {
+            Scheduler::Native(_) => std::thread::yield_now(),
+            Scheduler::Green(green) => green.switch(thread_id, GreenStatus::Runnable),
+        }
+    }
+
+    /// Blocks `thread_id` until `ready` returns true or `timeout` elapses.
+    /// Returns `false` if the timeout elapsed first.
+    pub fn block_until<F>(&self, thread_id: ThreadId, timeout: Option<Duration>, ready: F) -> bool
+    where
+        F: FnMut() -> bool,
+    {
+        match self {
+            Scheduler::Native(native) => native.block_until(thread_id, timeout, ready),
+            Scheduler::Green(green) => green.block_until(thread_id, timeout, ready),
+        }
+    }
+
+    /// Milliseconds since the epoch. Green threads only see the virtual time, which starts at the
+    /// epoch, so absolute deadlines are as reproducible as relative ones.
+    pub fn current_time_millis(&self) -> i64 {
+        match self {
+            Scheduler::Native(_) => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as i64,
+            Scheduler::Green(green) => (green.lock_state().clock / 1_000_000) as i64,
+        }
+    }
+
+    /// Nanoseconds since an arbitrary origin, never going backwards. Green threads see the virtual
+    /// time here as well, so timed waits measured with it expire when the scheduler says so.
+    pub fn nano_time(&self) -> i64 {
+        match self {
+            Scheduler::Native(native) => native.origin.elapsed().as_nanos() as i64,
+            Scheduler::Green(green) => green.lock_state().clock as i64,
+        }
+    }
+
+    /// Wakes every blocked thread so it can re-check its condition.
+    pub fn notify_all(&self) {
+        match self {
+            Scheduler::Native(native) => native.notify_all(),
+            Scheduler::Green(green) => green.notify_all(),
+        }
+    }
+}
+
+pub struct NativeScheduler {
+    lock: Mutex<()>,
+    state_changed: Condvar,
+    safepoint: Arc<Safepoint>,
+    // origin of nano_time
+    origin: Instant,
+}
+
+impl NativeScheduler {
+    fn new(safepoint: Arc<Safepoint>) -> Self {
+        Self {
+            lock: Mutex::new(()),
+            state_changed: Condvar::new(),
+            safepoint,
+            origin: Instant::now(),
+        }
+    }
+
+    fn block_until<F>(&self, thread_id: ThreadId, timeout: Option<Duration>, ready: F) -> bool
+    where
+        F: FnMut() -> bool,
+    {
+        self.safepoint.enter_safe_region(thread_id);
+        let res = self.wait_until(timeout, ready);
+        self.safepoint.leave_safe_region(thread_id);
+        res
+    }
+
+    // `ready` is evaluated under `lock` and notifiers take `lock` after changing the state,
+    // so a notification can't slip in between the check and the wait
+    fn wait_until<F>(&self, timeout: Option<Duration>, mut ready: F) -> bool
+    where
+        F: FnMut() -> bool,
+    {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut guard = self.lock.lock().unwrap();
+        loop {
+            if ready() {
+                return true;
+            }
+            match deadline {
+                None => guard = self.state_changed.wait(guard).unwrap(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return false;
+                    }
+                    guard = self
+                        .state_changed
+                        .wait_timeout(guard, deadline - now)
+                        .unwrap()
+                        .0;
+                }
+            }
+        }
+    }
+
+    fn notify_all(&self) {
+        let _guard = self.lock.lock().unwrap();
+        self.state_changed.notify_all();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GreenStatus {
+    Runnable,
+    // deadline is in virtual nanoseconds
+    Blocked { deadline: Option<u64> },
+}
+
+struct GreenState {
+    current: Option<ThreadId>,
+    // BTreeMap to keep the candidate order stable between runs
+    threads: BTreeMap<ThreadId, GreenStatus>,
+    rng: SplitMix64,
+    // virtual time: one nanosecond per executed bytecode, jumps forward when every thread sleeps
+    clock: u64,
+}
+
+impl GreenState {
+    fn pick_next(&mut self) -> Option<ThreadId> {
+        let mut runnable = self.runnable();
+        if runnable.is_empty() {
+            let earliest_deadline = self
+                .threads
+                .values()
+                .filter_map(|status| match status {
+                    GreenStatus::Blocked { deadline } => *deadline,
+                    GreenStatus::Runnable => None,
+                })
+                .min()?;
+            self.clock = self.clock.max(earliest_deadline);
+            for status in self.threads.values_mut() {
+                if let GreenStatus::Blocked {
+                    deadline: Some(deadline),
+                } = status
+                    && *deadline <= self.clock
+                {
+                    *status = GreenStatus::Runnable;
+                }
+            }
+            runnable = self.runnable();
+        }
+        let pos = (self.rng.next_u64() % runnable.len() as u64) as usize;
+        Some(runnable[pos])
+    }
+
+    fn runnable(&self) -> Vec<ThreadId> {
+        self.threads
+            .iter()
+            .filter(|(_, status)| **status == GreenStatus::Runnable)
+            .map(|(id, _)| *id)
+            .collect()
+    }
+}
+
+/// Runs Java threads one at a time.
+///
+/// The interpreter is recursive, so every green thread still keeps its own native stack (and OS
+/// thread to hold it), but only the thread holding the baton (`current`) executes; all others
+/// are parked on `turn`. Every scheduling decision is taken by the baton holder, which makes the
+/// interleaving a pure function of the seed and the program.
+pub struct GreenScheduler {
+    quantum: u32,
+    // bytecodes the current thread may still execute, only touched by the baton holder
+    budget: AtomicU32,
+    state: Mutex<GreenState>,
+    turn: Condvar,
+    safepoint: Arc<Safepoint>,
+}
+
+impl GreenScheduler {
+    fn new(seed: u64, quantum: u32, main_thread_id: ThreadId, safepoint: Arc<Safepoint>) -> Self {
+        let quantum = quantum.max(1);
+        let mut threads = BTreeMap::new();
+        threads.insert(main_thread_id, GreenStatus::Runnable);
+        Self {
+            quantum,
+            budget: AtomicU32::new(quantum),
+            state: Mutex::new(GreenState {
+                current: Some(main_thread_id),
+                threads,
+                rng: SplitMix64::new(seed),
+                clock: 0,
+            }),
+            turn: Condvar::new(),
+            safepoint,
+        }
+    }
+
+    fn register(&self, thread_id: ThreadId) {
+        self.lock_state()
+            .threads
+            .insert(thread_id, GreenStatus::Runnable);
+    }
+
+    fn attach(&self, thread_id: ThreadId) {
+        let state = self.lock_state();
+        self.wait_for_turn(state, thread_id);
+    }
+
+    fn detach(&self, thread_id: ThreadId) {
+        let mut state = self.lock_state();
+        self.consume_budget(&mut state);
+        state.threads.remove(&thread_id);
+        self.hand_over(&mut state);
+    }
+
+    #[inline]
+    fn tick(&self) -> bool {
+        let left = self.budget.load(Ordering::Relaxed);
+        if left <= 1 {
+            return true;
+        }
+        self.budget.store(left - 1, Ordering::Relaxed);
+        false
+    }
+
+    fn block_until<F>(&self, thread_id: ThreadId, timeout: Option<Duration>, mut ready: F) -> bool
+    where
+        F: FnMut() -> bool,
+    {
+        let deadline = timeout.map(|timeout| {
+            self.lock_state()
+                .clock
+                .saturating_add(timeout.as_nanos().min(u64::MAX as u128) as u64)
+        });
+        loop {
+            if ready() {
+                return true;
+            }
+            if let Some(deadline) = deadline
+                && self.lock_state().clock >= deadline
+            {
+                return false;
+            }
+            self.switch(thread_id, GreenStatus::Blocked { deadline });
+        }
+    }
+
+    fn notify_all(&self) {
+        let mut state = self.lock_state();
+        for status in state.threads.values_mut() {
+            *status = GreenStatus::Runnable;
+        }
+    }
+
+    /// Gives up the baton and waits until the scheduler picks `thread_id` again.
+    fn switch(&self, thread_id: ThreadId, status: GreenStatus) {
+        let mut state = self.lock_state();
+        self.consume_budget(&mut state);
+        if let Some(cur) = state.threads.get_mut(&thread_id) {
+            *cur = status;
+        }
+        self.hand_over(&mut state);
+        self.wait_for_turn(state, thread_id);
+    }
+
+    fn consume_budget(&self, state: &mut GreenState) {
+        let left = self.budget.load(Ordering::Relaxed).min(self.quantum);
+        state.clock += u64::from(self.quantum - left);
+    }
+
+    fn hand_over(&self, state: &mut GreenState) {
+        // None means every thread is blocked forever, exactly what a deadlocked JVM would do
+        state.current = state.pick_next();
+        self.budget.store(self.quantum, Ordering::Relaxed);
+        self.turn.notify_all();
+    }
+
+    // threads waiting for the baton are in a safe region, the baton holder stops at its next poll
+    fn wait_for_turn(&self, mut state: MutexGuard<'_, GreenState>, thread_id: ThreadId) {
+        if state.current == Some(thread_id) {
+            return;
+        }
+        self.safepoint.enter_safe_region(thread_id);
+        while state.current != Some(thread_id) {
+            state = self.turn.wait(state).unwrap();
+        }
+        drop(state);
+        self.safepoint.leave_safe_region(thread_id);
+    }
+
+    fn lock_state(&self) -> MutexGuard<'_, GreenState> {
+        self.state.lock().unwrap()
+    }
+}
+
+// https://prng.di.unimi.it/splitmix64.c
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
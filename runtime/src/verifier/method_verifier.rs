@@ -0,0 +1,1275 @@
+use crate::error::JvmError;
+use crate::rt::constant_pool::RuntimeConstantType;
+use crate::verifier::types::{Frame, VType};
+use crate::verifier::{ClassEnvironment, MethodToVerify};
+use crate::{Symbol, build_exception};
+use common::descriptor::MethodDescriptor;
+use common::jtype::{JavaType, ReturnType};
+use jclass::prelude::Instruction;
+use lasso::ThreadedRodeo;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Display;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum InvokeKind {
+    Virtual,
+    Special,
+    Static,
+    Interface(u8),
+    Dynamic,
+}
+
+// the class, name and descriptor of a field or method reference
+#[derive(Clone, Copy)]
+struct MemberRef {
+    class: Symbol,
+    name: Symbol,
+    desc: Symbol,
+}
+
+pub(super) struct WellKnown {
+    pub object: Symbol,
+    pub throwable: Symbol,
+    string: Symbol,
+    class: Symbol,
+    method_type: Symbol,
+    method_handle: Symbol,
+    cloneable: Symbol,
+    serializable: Symbol,
+    init: Symbol,
+    clinit: Symbol,
+}
+
+impl WellKnown {
+    fn new(interner: &ThreadedRodeo) -> Self {
+        Self {
+            object: interner.get_or_intern("java/lang/Object"),
+            throwable: interner.get_or_intern("java/lang/Throwable"),
+            string: interner.get_or_intern("java/lang/String"),
+            class: interner.get_or_intern("java/lang/Class"),
+            method_type: interner.get_or_intern("java/lang/invoke/MethodType"),
+            method_handle: interner.get_or_intern("java/lang/invoke/MethodHandle"),
+            cloneable: interner.get_or_intern("java/lang/Cloneable"),
+            serializable: interner.get_or_intern("java/io/Serializable"),
+            init: interner.get_or_intern("<init>"),
+            clinit: interner.get_or_intern("<clinit>"),
+        }
+    }
+}
+
+/// The abstract interpretation of a method's instructions both verifiers share. They differ in
+/// where the frames at branch targets come from, the `StackMapTable` or a data-flow analysis.
+pub(super) struct MethodVerifier<'a, E> {
+    pub env: &'a mut E,
+    pub interner: &'a ThreadedRodeo,
+    pub method: &'a MethodToVerify,
+    pub names: WellKnown,
+    pub instructions: BTreeMap<u16, Instruction>,
+    /// The frames of the `StackMapTable`, `None` when they are inferred.
+    pub stack_map: Option<HashMap<u16, Frame>>,
+    /// Branch targets of the last instruction with the frame they are reached with, collected
+    /// while inferring.
+    pub successors: Vec<(u16, Frame)>,
+    pub initial_locals: Vec<VType>,
+    return_type: Option<VType>,
+    max_stack: usize,
+    pub max_locals: usize,
+    // offset of the instruction being checked, for the error messages
+    pub pc: Option<u16>,
+}
+
+impl<'a, E: ClassEnvironment> MethodVerifier<'a, E> {
+    pub fn new(
+        env: &'a mut E,
+        interner: &'a ThreadedRodeo,
+        method: &'a MethodToVerify,
+    ) -> Result<Self, JvmError> {
+        let names = WellKnown::new(interner);
+        let mut verifier = Self {
+            env,
+            interner,
+            method,
+            names,
+            instructions: BTreeMap::new(),
+            stack_map: None,
+            successors: Vec::new(),
+            initial_locals: Vec::new(),
+            return_type: None,
+            max_stack: usize::from(method.code.max_stack()),
+            max_locals: usize::from(method.code.max_locals()),
+            pc: None,
+        };
+
+        let descriptor = verifier.method_descriptor(method.desc)?;
+        if !method.is_static {
+            verifier.initial_locals.push(
+                if method.name == verifier.names.init && method.class_name != verifier.names.object
+                {
+                    VType::UninitializedThis
+                } else {
+                    VType::Reference(method.class_name)
+                },
+            );
+        }
+        verifier.initial_locals.extend(
+            descriptor
+                .params
+                .iter()
+                .map(|param| VType::from_java_type(param, interner)),
+        );
+        verifier.return_type = match &descriptor.ret {
+            ReturnType::Void => None,
+            ReturnType::Type(java_type) => Some(VType::from_java_type(java_type, interner)),
+        };
+
+        verifier.decode()?;
+        verifier.check_exception_table()?;
+        Ok(verifier)
+    }
+
+    fn decode(&mut self) -> Result<(), JvmError> {
+        let code = &self.method.code.code;
+        let mut pc = 0;
+        while pc < code.len() {
+            let instruction = Instruction::new_at(code, pc)
+                .map_err(|e| self.error(format!("Bad instruction at {pc}"), format!("{e}")))?;
+            let next = pc + usize::from(instruction.byte_size());
+            self.instructions.insert(pc as u16, instruction);
+            pc = next;
+        }
+        Ok(())
+    }
+
+    pub(super) fn expand(&self, locals: &[VType], stack: &[VType]) -> Result<Frame, JvmError> {
+        let mut frame = Frame {
+            locals: Vec::with_capacity(self.max_locals),
+            stack: Vec::with_capacity(stack.len()),
+            this_uninit: locals.contains(&VType::UninitializedThis),
+        };
+        for (slots, types) in [(&mut frame.locals, locals), (&mut frame.stack, stack)] {
+            for vtype in types {
+                slots.push(*vtype);
+                if vtype.is_category2() {
+                    slots.push(VType::Top);
+                }
+            }
+        }
+        if frame.locals.len() > self.max_locals {
+            return Err(match self.pc {
+                None => self.error(
+                    "Arguments can't fit into locals",
+                    format!("Locals size {} exceeds max_locals", frame.locals.len()),
+                ),
+                Some(_) => self.error(
+                    "StackMapTable error: local size exceeds max_locals",
+                    "Error exists in the stackmap",
+                ),
+            });
+        }
+        if frame.stack.len() > self.max_stack {
+            return Err(self.error(
+                "StackMapTable error: stack size exceeds max_stack",
+                "Error exists in the stackmap",
+            ));
+        }
+        frame.locals.resize(self.max_locals, VType::Top);
+        Ok(frame)
+    }
+
+    // JVMS 4.7.3 constraints HotSpot checks while parsing the class file
+    fn check_exception_table(&self) -> Result<(), JvmError> {
+        let code_length = self.method.code.code.len();
+        let class_name = self.interner.resolve(&self.method.class_name);
+        let is_instruction = |pc: u16| self.instructions.contains_key(&pc);
+        for entry in &self.method.code.exception_table {
+            let end_ok = usize::from(entry.end_pc) == code_length || is_instruction(entry.end_pc);
+            if entry.start_pc >= entry.end_pc || !is_instruction(entry.start_pc) || !end_ok {
+                return Err(build_exception!(
+                    ClassFormatError,
+                    "Illegal exception table range in class file {class_name}"
+                ));
+            }
+            if !is_instruction(entry.handler_pc) {
+                return Err(build_exception!(
+                    ClassFormatError,
+                    "Illegal exception table handler in class file {class_name}"
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub(super) fn is_assignable(&mut self, from: VType, to: VType) -> Result<bool, JvmError> {
+        if from == to {
+            return Ok(true);
+        }
+        match (from, to) {
+            (_, VType::Top) => Ok(true),
+            (VType::Null, VType::Reference(_)) => Ok(true),
+            (VType::Reference(from), VType::Reference(to)) => {
+                self.is_assignable_reference(from, to)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    // JVMS isJavaAssignable, interfaces are treated like Object
+    pub(super) fn is_assignable_reference(
+        &mut self,
+        from: Symbol,
+        to: Symbol,
+    ) -> Result<bool, JvmError> {
+        if from == to || to == self.names.object {
+            return Ok(true);
+        }
+        let from_name = self.interner.resolve(&from);
+        let to_name = self.interner.resolve(&to);
+        if let Some(to_component) = to_name.strip_prefix('[') {
+            let Some(from_component) = from_name.strip_prefix('[') else {
+                return Ok(false);
+            };
+            return match (
+                Self::reference_component(to_component),
+                Self::reference_component(from_component),
+            ) {
+                (Some(to), Some(from)) => {
+                    let (from, to) = (
+                        self.interner.get_or_intern(from),
+                        self.interner.get_or_intern(to),
+                    );
+                    self.is_assignable_reference(from, to)
+                }
+                _ => Ok(from_component == to_component),
+            };
+        }
+        if from_name.starts_with('[') {
+            return Ok(to == self.names.cloneable || to == self.names.serializable);
+        }
+        if self.env.is_interface(to)? {
+            return Ok(true);
+        }
+        let mut current = from;
+        while let Some(super_name) = self.env.super_class(current)? {
+            if super_name == to {
+                return Ok(true);
+            }
+            current = super_name;
+        }
+        Ok(false)
+    }
+
+    // the class name or descriptor of a reference array component, None for primitives
+    pub(super) fn reference_component(component: &str) -> Option<&str> {
+        if component.starts_with('[') {
+            return Some(component);
+        }
+        component
+            .strip_prefix('L')
+            .and_then(|name| name.strip_suffix(';'))
+    }
+
+    pub(super) fn writes_local(instruction: &Instruction) -> bool {
+        matches!(
+            instruction,
+            Instruction::Istore(_)
+                | Instruction::Istore0
+                | Instruction::Istore1
+                | Instruction::Istore2
+                | Instruction::Istore3
+                | Instruction::Lstore(_)
+                | Instruction::Lstore0
+                | Instruction::Lstore1
+                | Instruction::Lstore2
+                | Instruction::Lstore3
+                | Instruction::Fstore(_)
+                | Instruction::Fstore0
+                | Instruction::Fstore1
+                | Instruction::Fstore2
+                | Instruction::Fstore3
+                | Instruction::Dstore(_)
+                | Instruction::Dstore0
+                | Instruction::Dstore1
+                | Instruction::Dstore2
+                | Instruction::Dstore3
+                | Instruction::Astore(_)
+                | Instruction::Astore0
+                | Instruction::Astore1
+                | Instruction::Astore2
+                | Instruction::Astore3
+                | Instruction::InvokeSpecial(_)
+        )
+    }
+
+    // returns whether the next instruction can be reached from this one
+    pub(super) fn execute(
+        &mut self,
+        instruction: &Instruction,
+        pc: u16,
+        frame: &mut Frame,
+    ) -> Result<bool, JvmError> {
+        use VType::{Double, Float, Integer, Long};
+
+        match instruction {
+            Instruction::Nop => {}
+            Instruction::AconstNull => self.push(frame, VType::Null)?,
+            Instruction::IconstM1
+            | Instruction::Iconst0
+            | Instruction::Iconst1
+            | Instruction::Iconst2
+            | Instruction::Iconst3
+            | Instruction::Iconst4
+            | Instruction::Iconst5
+            | Instruction::Bipush(_)
+            | Instruction::Sipush(_) => self.push(frame, Integer)?,
+            Instruction::Lconst0 | Instruction::Lconst1 => self.push(frame, Long)?,
+            Instruction::Fconst0 | Instruction::Fconst1 | Instruction::Fconst2 => {
+                self.push(frame, Float)?
+            }
+            Instruction::Dconst0 | Instruction::Dconst1 => self.push(frame, Double)?,
+            Instruction::Ldc(index) | Instruction::LdcW(index) => {
+                let vtype = match self.constant_type(*index)? {
+                    RuntimeConstantType::Integer => Integer,
+                    RuntimeConstantType::Float => Float,
+                    RuntimeConstantType::String => VType::Reference(self.names.string),
+                    RuntimeConstantType::Class => VType::Reference(self.names.class),
+                    RuntimeConstantType::MethodType => VType::Reference(self.names.method_type),
+                    RuntimeConstantType::MethodHandle => VType::Reference(self.names.method_handle),
+                    _ => return Err(self.illegal_constant(*index)),
+                };
+                self.push(frame, vtype)?
+            }
+            Instruction::Ldc2W(index) => {
+                let vtype = match self.constant_type(*index)? {
+                    RuntimeConstantType::Long => Long,
+                    RuntimeConstantType::Double => Double,
+                    _ => return Err(self.illegal_constant(*index)),
+                };
+                self.push(frame, vtype)?
+            }
+
+            Instruction::Iload(n) => self.load(frame, usize::from(*n), Integer)?,
+            Instruction::Iload0 => self.load(frame, 0, Integer)?,
+            Instruction::Iload1 => self.load(frame, 1, Integer)?,
+            Instruction::Iload2 => self.load(frame, 2, Integer)?,
+            Instruction::Iload3 => self.load(frame, 3, Integer)?,
+            Instruction::Lload(n) => self.load(frame, usize::from(*n), Long)?,
+            Instruction::Lload0 => self.load(frame, 0, Long)?,
+            Instruction::Lload1 => self.load(frame, 1, Long)?,
+            Instruction::Lload2 => self.load(frame, 2, Long)?,
+            Instruction::Lload3 => self.load(frame, 3, Long)?,
+            Instruction::Fload(n) => self.load(frame, usize::from(*n), Float)?,
+            Instruction::Fload0 => self.load(frame, 0, Float)?,
+            Instruction::Fload1 => self.load(frame, 1, Float)?,
+            Instruction::Fload2 => self.load(frame, 2, Float)?,
+            Instruction::Fload3 => self.load(frame, 3, Float)?,
+            Instruction::Dload(n) => self.load(frame, usize::from(*n), Double)?,
+            Instruction::Dload0 => self.load(frame, 0, Double)?,
+            Instruction::Dload1 => self.load(frame, 1, Double)?,
+            Instruction::Dload2 => self.load(frame, 2, Double)?,
+            Instruction::Dload3 => self.load(frame, 3, Double)?,
+            Instruction::Aload(n) => self.load_reference(frame, usize::from(*n))?,
+            Instruction::Aload0 => self.load_reference(frame, 0)?,
+            Instruction::Aload1 => self.load_reference(frame, 1)?,
+            Instruction::Aload2 => self.load_reference(frame, 2)?,
+            Instruction::Aload3 => self.load_reference(frame, 3)?,
+
+            Instruction::Istore(n) => self.store(frame, usize::from(*n), Integer)?,
+            Instruction::Istore0 => self.store(frame, 0, Integer)?,
+            Instruction::Istore1 => self.store(frame, 1, Integer)?,
+            Instruction::Istore2 => self.store(frame, 2, Integer)?,
+            Instruction::Istore3 => self.store(frame, 3, Integer)?,
+            Instruction::Lstore(n) => self.store(frame, usize::from(*n), Long)?,
+            Instruction::Lstore0 => self.store(frame, 0, Long)?,
+            Instruction::Lstore1 => self.store(frame, 1, Long)?,
+            Instruction::Lstore2 => self.store(frame, 2, Long)?,
+            Instruction::Lstore3 => self.store(frame, 3, Long)?,
+            Instruction::Fstore(n) => self.store(frame, usize::from(*n), Float)?,
+            Instruction::Fstore0 => self.store(frame, 0, Float)?,
+            Instruction::Fstore1 => self.store(frame, 1, Float)?,
+            Instruction::Fstore2 => self.store(frame, 2, Float)?,
+            Instruction::Fstore3 => self.store(frame, 3, Float)?,
+            Instruction::Dstore(n) => self.store(frame, usize::from(*n), Double)?,
+            Instruction::Dstore0 => self.store(frame, 0, Double)?,
+            Instruction::Dstore1 => self.store(frame, 1, Double)?,
+            Instruction::Dstore2 => self.store(frame, 2, Double)?,
+            Instruction::Dstore3 => self.store(frame, 3, Double)?,
+            Instruction::Astore(n) => self.store_reference(frame, usize::from(*n))?,
+            Instruction::Astore0 => self.store_reference(frame, 0)?,
+            Instruction::Astore1 => self.store_reference(frame, 1)?,
+            Instruction::Astore2 => self.store_reference(frame, 2)?,
+            Instruction::Astore3 => self.store_reference(frame, 3)?,
+            Instruction::Iinc(n, _) => self.check_local(frame, usize::from(*n), Integer)?,
+
+            Instruction::Iaload => self.array_load(frame, &["[I"], Integer)?,
+            Instruction::Baload => self.array_load(frame, &["[B", "[Z"], Integer)?,
+            Instruction::Caload => self.array_load(frame, &["[C"], Integer)?,
+            Instruction::Saload => self.array_load(frame, &["[S"], Integer)?,
+            Instruction::Laload => self.array_load(frame, &["[J"], Long)?,
+            Instruction::Faload => self.array_load(frame, &["[F"], Float)?,
+            Instruction::Daload => self.array_load(frame, &["[D"], Double)?,
+            Instruction::Aaload => {
+                self.pop(frame, Integer)?;
+                let component = match self.pop_array(frame)? {
+                    VType::Reference(array) => {
+                        let name = self.interner.resolve(&array);
+                        match Self::reference_component(&name[1..]) {
+                            Some(component) => {
+                                VType::Reference(self.interner.get_or_intern(component))
+                            }
+                            None => return Err(self.bad_array(VType::Reference(array))),
+                        }
+                    }
+                    other => other,
+                };
+                self.push(frame, component)?
+            }
+            Instruction::Iastore => self.array_store(frame, &["[I"], Integer)?,
+            Instruction::Bastore => self.array_store(frame, &["[B", "[Z"], Integer)?,
+            Instruction::Castore => self.array_store(frame, &["[C"], Integer)?,
+            Instruction::Sastore => self.array_store(frame, &["[S"], Integer)?,
+            Instruction::Lastore => self.array_store(frame, &["[J"], Long)?,
+            Instruction::Fastore => self.array_store(frame, &["[F"], Float)?,
+            Instruction::Dastore => self.array_store(frame, &["[D"], Double)?,
+            Instruction::Aastore => {
+                self.pop(frame, VType::Reference(self.names.object))?;
+                self.pop(frame, Integer)?;
+                if let VType::Reference(array) = self.pop_array(frame)? {
+                    let name = self.interner.resolve(&array);
+                    if Self::reference_component(&name[1..]).is_none() {
+                        return Err(self.bad_array(VType::Reference(array)));
+                    }
+                }
+            }
+
+            Instruction::Pop => self.stack_shuffle(frame, 1, &[1], |stack| {
+                stack.pop();
+            })?,
+            Instruction::Pop2 => self.stack_shuffle(frame, 2, &[2], |stack| {
+                stack.truncate(stack.len() - 2);
+            })?,
+            Instruction::Dup => self.duplicate(frame, 1, 1)?,
+            Instruction::DupX1 => self.duplicate(frame, 1, 2)?,
+            Instruction::DupX2 => self.duplicate(frame, 1, 3)?,
+            Instruction::Dup2 => self.duplicate(frame, 2, 2)?,
+            Instruction::Dup2X1 => self.duplicate(frame, 2, 3)?,
+            Instruction::Dup2X2 => self.duplicate(frame, 2, 4)?,
+            Instruction::Swap => self.stack_shuffle(frame, 2, &[1, 2], |stack| {
+                let len = stack.len();
+                stack.swap(len - 1, len - 2);
+            })?,
+
+            Instruction::Iadd
+            | Instruction::Isub
+            | Instruction::Imul
+            | Instruction::Idiv
+            | Instruction::Irem
+            | Instruction::Iand
+            | Instruction::Ior
+            | Instruction::Ixor
+            | Instruction::Ishl
+            | Instruction::Ishr
+            | Instruction::Iushr => self.binary(frame, Integer, Integer, Integer)?,
+            Instruction::Ladd
+            | Instruction::Lsub
+            | Instruction::Lmul
+            | Instruction::Ldiv
+            | Instruction::Lrem
+            | Instruction::Land
+            | Instruction::Lor
+            | Instruction::Lxor => self.binary(frame, Long, Long, Long)?,
+            Instruction::Lshl | Instruction::Lshr | Instruction::Lushr => {
+                self.binary(frame, Long, Integer, Long)?
+            }
+            Instruction::Fadd
+            | Instruction::Fsub
+            | Instruction::Fmul
+            | Instruction::Fdiv
+            | Instruction::Frem => self.binary(frame, Float, Float, Float)?,
+            Instruction::Dadd
+            | Instruction::Dsub
+            | Instruction::Dmul
+            | Instruction::Ddiv
+            | Instruction::Drem => self.binary(frame, Double, Double, Double)?,
+            Instruction::Lcmp => self.binary(frame, Long, Long, Integer)?,
+            Instruction::Fcmpl | Instruction::Fcmpg => self.binary(frame, Float, Float, Integer)?,
+            Instruction::Dcmpl | Instruction::Dcmpg => {
+                self.binary(frame, Double, Double, Integer)?
+            }
+            Instruction::Ineg | Instruction::I2b | Instruction::I2c | Instruction::I2s => {
+                self.unary(frame, Integer, Integer)?
+            }
+            Instruction::Lneg => self.unary(frame, Long, Long)?,
+            Instruction::Fneg => self.unary(frame, Float, Float)?,
+            Instruction::Dneg => self.unary(frame, Double, Double)?,
+            Instruction::I2l => self.unary(frame, Integer, Long)?,
+            Instruction::I2f => self.unary(frame, Integer, Float)?,
+            Instruction::I2d => self.unary(frame, Integer, Double)?,
+            Instruction::L2i => self.unary(frame, Long, Integer)?,
+            Instruction::L2f => self.unary(frame, Long, Float)?,
+            Instruction::L2d => self.unary(frame, Long, Double)?,
+            Instruction::F2i => self.unary(frame, Float, Integer)?,
+            Instruction::F2l => self.unary(frame, Float, Long)?,
+            Instruction::F2d => self.unary(frame, Float, Double)?,
+            Instruction::D2i => self.unary(frame, Double, Integer)?,
+            Instruction::D2l => self.unary(frame, Double, Long)?,
+            Instruction::D2f => self.unary(frame, Double, Float)?,
+
+            Instruction::IfEq(offset)
+            | Instruction::IfNe(offset)
+            | Instruction::IfLt(offset)
+            | Instruction::IfGe(offset)
+            | Instruction::IfGt(offset)
+            | Instruction::IfLe(offset) => {
+                self.pop(frame, Integer)?;
+                self.branch(frame, pc, i32::from(*offset))?
+            }
+            Instruction::IfIcmpeq(offset)
+            | Instruction::IfIcmpne(offset)
+            | Instruction::IfIcmplt(offset)
+            | Instruction::IfIcmpge(offset)
+            | Instruction::IfIcmpgt(offset)
+            | Instruction::IfIcmple(offset) => {
+                self.pop(frame, Integer)?;
+                self.pop(frame, Integer)?;
+                self.branch(frame, pc, i32::from(*offset))?
+            }
+            Instruction::IfAcmpEq(offset) | Instruction::IfAcmpNe(offset) => {
+                self.pop_reference(frame)?;
+                self.pop_reference(frame)?;
+                self.branch(frame, pc, i32::from(*offset))?
+            }
+            Instruction::Ifnull(offset) | Instruction::Ifnonnull(offset) => {
+                self.pop_reference(frame)?;
+                self.branch(frame, pc, i32::from(*offset))?
+            }
+            Instruction::Goto(offset) => {
+                self.branch(frame, pc, i32::from(*offset))?;
+                return Ok(false);
+            }
+            Instruction::GotoW(offset) => {
+                self.branch(frame, pc, *offset)?;
+                return Ok(false);
+            }
+            Instruction::TableSwitch(data) => {
+                if data.low > data.high {
+                    return Err(self.error(
+                        "low must be less than or equal to high in tableswitch",
+                        "Error exists in the bytecode",
+                    ));
+                }
+                self.pop(frame, Integer)?;
+                self.branch(frame, pc, data.default_offset)?;
+                for offset in &data.offsets {
+                    self.branch(frame, pc, *offset)?;
+                }
+                return Ok(false);
+            }
+            Instruction::Lookupswitch(data) => {
+                if data.pairs.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+                    return Err(self.error(
+                        "Bad lookupswitch instruction",
+                        "Error exists in the bytecode",
+                    ));
+                }
+                self.pop(frame, Integer)?;
+                self.branch(frame, pc, data.default_offset)?;
+                for (_, offset) in &data.pairs {
+                    self.branch(frame, pc, *offset)?;
+                }
+                return Ok(false);
+            }
+
+            Instruction::Ireturn => {
+                let value = self.pop(frame, Integer)?;
+                self.return_value(frame, value)?;
+                return Ok(false);
+            }
+            Instruction::Lreturn => {
+                let value = self.pop(frame, Long)?;
+                self.return_value(frame, value)?;
+                return Ok(false);
+            }
+            Instruction::Freturn => {
+                let value = self.pop(frame, Float)?;
+                self.return_value(frame, value)?;
+                return Ok(false);
+            }
+            Instruction::Dreturn => {
+                let value = self.pop(frame, Double)?;
+                self.return_value(frame, value)?;
+                return Ok(false);
+            }
+            Instruction::Areturn => {
+                let value = self.pop_reference(frame)?;
+                self.return_value(frame, value)?;
+                return Ok(false);
+            }
+            Instruction::Return => {
+                if self.return_type.is_some() {
+                    return Err(self.error(
+                        "Method expects a return value",
+                        "Error exists in the bytecode",
+                    ));
+                }
+                if frame.this_uninit {
+                    return Err(self.error(
+                        "Constructor must call super() or this() before return",
+                        "Error exists in the bytecode",
+                    ));
+                }
+                return Ok(false);
+            }
+            Instruction::Athrow => {
+                self.pop(frame, VType::Reference(self.names.throwable))?;
+                return Ok(false);
+            }
+
+            Instruction::Getstatic(index) => {
+                let (_, field_type) = self.field_ref(*index)?;
+                self.push(frame, field_type)?
+            }
+            Instruction::Putstatic(index) => {
+                let (_, field_type) = self.field_ref(*index)?;
+                self.pop(frame, field_type)?;
+            }
+            Instruction::Getfield(index) => {
+                let (field, field_type) = self.field_ref(*index)?;
+                let object = self.pop(frame, VType::Reference(field.class))?;
+                self.check_protected(frame, object, field, false, "getfield")?;
+                self.push(frame, field_type)?
+            }
+            Instruction::Putfield(index) => {
+                let (field, field_type) = self.field_ref(*index)?;
+                self.pop(frame, field_type)?;
+                // a constructor may set its own fields before calling super()
+                let is_own_field = frame.stack.last() == Some(&VType::UninitializedThis)
+                    && field.class == self.method.class_name;
+                if is_own_field {
+                    frame.stack.pop();
+                } else {
+                    let object = self.pop(frame, VType::Reference(field.class))?;
+                    self.check_protected(frame, object, field, false, "putfield")?;
+                }
+            }
+
+            Instruction::InvokeVirtual(index) => self.invoke(frame, *index, InvokeKind::Virtual)?,
+            Instruction::InvokeSpecial(index) => self.invoke(frame, *index, InvokeKind::Special)?,
+            Instruction::InvokeStatic(index) => self.invoke(frame, *index, InvokeKind::Static)?,
+            Instruction::InvokeInterface(index, count) => {
+                self.invoke(frame, *index, InvokeKind::Interface(*count))?
+            }
+            Instruction::InvokeDynamic(index) => self.invoke(frame, *index, InvokeKind::Dynamic)?,
+
+            Instruction::New(index) => {
+                let class_sym = self.class_sym(*index)?;
+                if self.interner.resolve(&class_sym).starts_with('[') {
+                    return Err(
+                        self.error("Illegal new instruction", "Error exists in the bytecode")
+                    );
+                }
+                let uninitialized = VType::Uninitialized(pc);
+                if frame.stack.contains(&uninitialized) {
+                    return Err(self.error(
+                        "Uninitialized object exists on backward branch",
+                        "Error exists in the bytecode",
+                    ));
+                }
+                frame.initialize(uninitialized, VType::Top);
+                self.push(frame, uninitialized)?
+            }
+            Instruction::Newarray(array_type) => {
+                self.pop(frame, Integer)?;
+                let array = self.interner.get_or_intern(array_type.descriptor());
+                self.push(frame, VType::Reference(array))?
+            }
+            Instruction::Anewarray(index) => {
+                self.pop(frame, Integer)?;
+                let component = self.class_sym(*index)?;
+                let component = self.interner.resolve(&component);
+                let array = if component.starts_with('[') {
+                    format!("[{component}")
+                } else {
+                    format!("[L{component};")
+                };
+                let array = self.interner.get_or_intern(array);
+                self.push(frame, VType::Reference(array))?
+            }
+            Instruction::Multianewarray(index, dimensions) => {
+                let array = self.class_sym(*index)?;
+                let array_dimensions = self
+                    .interner
+                    .resolve(&array)
+                    .chars()
+                    .take_while(|c| *c == '[')
+                    .count();
+                if *dimensions == 0 || usize::from(*dimensions) > array_dimensions {
+                    return Err(self.error(
+                        "Illegal dimension in multianewarray instruction",
+                        "Error exists in the bytecode",
+                    ));
+                }
+                for _ in 0..*dimensions {
+                    self.pop(frame, Integer)?;
+                }
+                self.push(frame, VType::Reference(array))?
+            }
+            Instruction::ArrayLength => {
+                self.pop_array(frame)?;
+                self.push(frame, Integer)?
+            }
+            Instruction::Checkcast(index) => {
+                self.pop_reference(frame)?;
+                let class_sym = self.class_sym(*index)?;
+                self.push(frame, VType::Reference(class_sym))?
+            }
+            Instruction::Instanceof(index) => {
+                self.pop_reference(frame)?;
+                self.class_sym(*index)?;
+                self.push(frame, Integer)?
+            }
+            Instruction::Monitorenter | Instruction::Monitorexit => {
+                self.pop_reference(frame)?;
+            }
+
+            // subroutines only exist for the old verifier, class files of version 50 and up
+            // must not use them
+            Instruction::Jsr(_)
+            | Instruction::JsrW(_)
+            | Instruction::Ret(_)
+            | Instruction::Breakpoint
+            | Instruction::Impdep1
+            | Instruction::Impdep2 => {
+                return Err(self.error("Bad instruction", "Error exists in the bytecode"));
+            }
+        }
+        Ok(true)
+    }
+
+    pub(super) fn push(&self, frame: &mut Frame, vtype: VType) -> Result<(), JvmError> {
+        let size = if vtype.is_category2() { 2 } else { 1 };
+        if frame.stack.len() + size > self.max_stack {
+            return Err(self.error("Operand stack overflow", "Exceeded max stack size."));
+        }
+        frame.stack.push(vtype);
+        if vtype.is_category2() {
+            frame.stack.push(VType::Top);
+        }
+        Ok(())
+    }
+
+    fn pop(&mut self, frame: &mut Frame, expected: VType) -> Result<VType, JvmError> {
+        let size = if expected.is_category2() { 2 } else { 1 };
+        let Some(index) = frame.stack.len().checked_sub(size) else {
+            return Err(self.underflow());
+        };
+        let value = frame.stack[index];
+        let matches = if expected.is_category2() {
+            value == expected && frame.stack[index + 1] == VType::Top
+        } else {
+            value != VType::Top && self.is_assignable(value, expected)?
+        };
+        if !matches {
+            return Err(self.error(
+                "Bad type on operand stack",
+                format!(
+                    "Type {} (current frame, stack[{index}]) is not assignable to {}",
+                    value.describe(self.interner),
+                    expected.describe(self.interner)
+                ),
+            ));
+        }
+        frame.stack.truncate(index);
+        Ok(value)
+    }
+
+    fn pop_reference(&mut self, frame: &mut Frame) -> Result<VType, JvmError> {
+        let Some(value) = frame.stack.last().copied() else {
+            return Err(self.underflow());
+        };
+        if !value.is_reference() {
+            return Err(self.error(
+                "Bad type on operand stack",
+                format!(
+                    "Type {} (current frame, stack[{}]) is not assignable to reference type",
+                    value.describe(self.interner),
+                    frame.stack.len() - 1
+                ),
+            ));
+        }
+        frame.stack.pop();
+        Ok(value)
+    }
+
+    // an array or null
+    fn pop_array(&mut self, frame: &mut Frame) -> Result<VType, JvmError> {
+        let value = self.pop_reference(frame)?;
+        match value {
+            VType::Null => Ok(value),
+            VType::Reference(name) if self.interner.resolve(&name).starts_with('[') => Ok(value),
+            other => Err(self.bad_array(other)),
+        }
+    }
+
+    fn array_load(
+        &mut self,
+        frame: &mut Frame,
+        arrays: &[&str],
+        component: VType,
+    ) -> Result<(), JvmError> {
+        self.pop(frame, VType::Integer)?;
+        self.check_array(frame, arrays)?;
+        self.push(frame, component)
+    }
+
+    fn array_store(
+        &mut self,
+        frame: &mut Frame,
+        arrays: &[&str],
+        component: VType,
+    ) -> Result<(), JvmError> {
+        self.pop(frame, component)?;
+        self.pop(frame, VType::Integer)?;
+        self.check_array(frame, arrays)
+    }
+
+    fn check_array(&mut self, frame: &mut Frame, arrays: &[&str]) -> Result<(), JvmError> {
+        match self.pop_array(frame)? {
+            VType::Reference(name) if !arrays.contains(&self.interner.resolve(&name)) => {
+                Err(self.bad_array(VType::Reference(name)))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // pops nothing, `boundaries` are the depths at which the stack is split and must not cut a
+    // long or a double in two
+    fn stack_shuffle(
+        &self,
+        frame: &mut Frame,
+        depth: usize,
+        boundaries: &[usize],
+        shuffle: impl FnOnce(&mut Vec<VType>),
+    ) -> Result<(), JvmError> {
+        let len = frame.stack.len();
+        if len < depth {
+            return Err(self.underflow());
+        }
+        for boundary in boundaries {
+            if frame.stack[len - boundary] == VType::Top {
+                return Err(self.error(
+                    "Bad type on operand stack",
+                    format!(
+                        "Type top (current frame, stack[{}]) is not assignable to category1 type",
+                        len - boundary
+                    ),
+                ));
+            }
+        }
+        shuffle(&mut frame.stack);
+        if frame.stack.len() > self.max_stack {
+            return Err(self.error("Operand stack overflow", "Exceeded max stack size."));
+        }
+        Ok(())
+    }
+
+    // copies the top `count` slots below the top `depth` ones
+    fn duplicate(&self, frame: &mut Frame, count: usize, depth: usize) -> Result<(), JvmError> {
+        self.stack_shuffle(frame, depth, &[count, depth], |stack| {
+            let len = stack.len();
+            let copied = stack[len - count..].to_vec();
+            stack.splice(len - depth..len - depth, copied);
+        })
+    }
+
+    fn binary(
+        &mut self,
+        frame: &mut Frame,
+        left: VType,
+        right: VType,
+        result: VType,
+    ) -> Result<(), JvmError> {
+        self.pop(frame, right)?;
+        self.pop(frame, left)?;
+        self.push(frame, result)
+    }
+
+    fn unary(&mut self, frame: &mut Frame, operand: VType, result: VType) -> Result<(), JvmError> {
+        self.pop(frame, operand)?;
+        self.push(frame, result)
+    }
+
+    fn load(&mut self, frame: &mut Frame, index: usize, expected: VType) -> Result<(), JvmError> {
+        self.check_local(frame, index, expected)?;
+        self.push(frame, expected)
+    }
+
+    fn check_local(
+        &mut self,
+        frame: &Frame,
+        index: usize,
+        expected: VType,
+    ) -> Result<(), JvmError> {
+        let size = if expected.is_category2() { 2 } else { 1 };
+        self.check_local_index(index + size - 1)?;
+        let value = frame.locals[index];
+        let matches = if expected.is_category2() {
+            value == expected && frame.locals[index + 1] == VType::Top
+        } else {
+            value != VType::Top && self.is_assignable(value, expected)?
+        };
+        if !matches {
+            return Err(self.bad_local(value, index, &expected.describe(self.interner)));
+        }
+        Ok(())
+    }
+
+    fn load_reference(&mut self, frame: &mut Frame, index: usize) -> Result<(), JvmError> {
+        self.check_local_index(index)?;
+        let value = frame.locals[index];
+        if !value.is_reference() {
+            return Err(self.bad_local(value, index, "reference type"));
+        }
+        self.push(frame, value)
+    }
+
+    fn store(&mut self, frame: &mut Frame, index: usize, vtype: VType) -> Result<(), JvmError> {
+        let value = self.pop(frame, vtype)?;
+        self.set_local(frame, index, value)
+    }
+
+    fn store_reference(&mut self, frame: &mut Frame, index: usize) -> Result<(), JvmError> {
+        // astore is how a subroutine saves its return address
+        let value = match frame.stack.last() {
+            Some(VType::ReturnAddress(_)) => frame.stack.pop().unwrap_or(VType::Top),
+            _ => self.pop_reference(frame)?,
+        };
+        self.set_local(frame, index, value)
+    }
+
+    fn set_local(&self, frame: &mut Frame, index: usize, value: VType) -> Result<(), JvmError> {
+        let size = if value.is_category2() { 2 } else { 1 };
+        self.check_local_index(index + size - 1)?;
+        frame.locals[index] = value;
+        if value.is_category2() {
+            frame.locals[index + 1] = VType::Top;
+        }
+        // a long or a double whose second half is overwritten is gone
+        if index > 0 && frame.locals[index - 1].is_category2() {
+            frame.locals[index - 1] = VType::Top;
+        }
+        Ok(())
+    }
+
+    fn check_local_index(&self, index: usize) -> Result<(), JvmError> {
+        if index >= self.max_locals {
+            return Err(self.error(
+                "Illegal local variable number",
+                format!("Local index {index} is invalid"),
+            ));
+        }
+        Ok(())
+    }
+
+    fn branch(&mut self, frame: &Frame, pc: u16, offset: i32) -> Result<(), JvmError> {
+        let target = i32::from(pc) + offset;
+        let Some(target) = u16::try_from(target)
+            .ok()
+            .filter(|target| self.instructions.contains_key(target))
+        else {
+            return Err(self.error(
+                "Illegal target of jump or branch",
+                format!("Target {target} is not an instruction"),
+            ));
+        };
+        if self.stack_map.is_some() {
+            return self.check_branch_target(frame, target);
+        }
+        self.successors.push((target, frame.clone()));
+        Ok(())
+    }
+
+    fn return_value(&mut self, frame: &Frame, value: VType) -> Result<(), JvmError> {
+        let Some(return_type) = self.return_type else {
+            return Err(self.error(
+                "Method does not expect a return value",
+                "Error exists in the bytecode",
+            ));
+        };
+        if !self.is_assignable(value, return_type)? {
+            return Err(self.error(
+                "Bad return type",
+                format!(
+                    "Type {} (current frame, stack[{}]) is not assignable to {}",
+                    value.describe(self.interner),
+                    frame.stack.len(),
+                    return_type.describe(self.interner)
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    fn invoke(&mut self, frame: &mut Frame, index: u16, kind: InvokeKind) -> Result<(), JvmError> {
+        let interner = self.interner;
+        let (class_sym, name_sym, desc_sym) = match kind {
+            InvokeKind::Dynamic => {
+                let nat = self
+                    .env
+                    .with_cp(|cp| Ok(cp.get_invoke_dynamic_view(&index, interner)?.nat_view))?;
+                (None, nat.name_sym, nat.descriptor_sym)
+            }
+            _ => {
+                let view = self.env.with_cp(|cp| match kind {
+                    InvokeKind::Virtual => cp.get_method_view(&index, interner),
+                    InvokeKind::Interface(_) => cp.get_interface_method_view(&index, interner),
+                    _ => cp.get_method_or_interface_method_view(&index, interner),
+                })?;
+                (
+                    Some(view.class_sym),
+                    view.name_and_type.name_sym,
+                    view.name_and_type.descriptor_sym,
+                )
+            }
+        };
+        let is_init = name_sym == self.names.init;
+        if name_sym == self.names.clinit || (is_init && kind != InvokeKind::Special) {
+            return Err(self.error(
+                "Illegal call to internal method",
+                "Error exists in the bytecode",
+            ));
+        }
+
+        let descriptor = self.method_descriptor(desc_sym)?;
+        let params = descriptor
+            .params
+            .iter()
+            .map(|param| VType::from_java_type(param, interner))
+            .collect::<Vec<_>>();
+        if let InvokeKind::Interface(count) = kind {
+            let slots = 1 + params
+                .iter()
+                .map(|param| if param.is_category2() { 2 } else { 1 })
+                .sum::<usize>();
+            if usize::from(count) != slots {
+                return Err(self.error(
+                    "Inconsistent args count operand in invokeinterface",
+                    "Error exists in the bytecode",
+                ));
+            }
+        }
+        for param in params.iter().rev() {
+            self.pop(frame, *param)?;
+        }
+
+        match (kind, class_sym) {
+            (InvokeKind::Special, Some(class_sym)) if is_init => {
+                self.initialize_object(frame, class_sym)?
+            }
+            (InvokeKind::Special, _) => {
+                self.pop(frame, VType::Reference(self.method.class_name))?;
+            }
+            (InvokeKind::Virtual, Some(class_sym)) => {
+                let object = self.pop(frame, VType::Reference(class_sym))?;
+                let method = MemberRef {
+                    class: class_sym,
+                    name: name_sym,
+                    desc: desc_sym,
+                };
+                self.check_protected(frame, object, method, true, "invokevirtual")?;
+            }
+            (InvokeKind::Interface(_), Some(class_sym)) => {
+                self.pop(frame, VType::Reference(class_sym))?;
+            }
+            _ => {}
+        }
+
+        if let ReturnType::Type(java_type) = &descriptor.ret {
+            self.push(frame, VType::from_java_type(java_type, interner))?;
+        }
+        Ok(())
+    }
+
+    // JVMS 4.10.1.8: a protected member of a superclass in another run-time package may only be
+    // used on an object of the current class or its subclasses
+    fn check_protected(
+        &mut self,
+        frame: &Frame,
+        object: VType,
+        member: MemberRef,
+        is_method: bool,
+        instruction: &str,
+    ) -> Result<(), JvmError> {
+        let current = VType::Reference(self.method.class_name);
+        if object == current || !self.is_superclass(member.class)? {
+            return Ok(());
+        }
+        if !self
+            .env
+            .is_protected_access(member.class, member.name, member.desc, is_method)?
+        {
+            return Ok(());
+        }
+        // arrays inherit the protected Object.clone() as a public method
+        let is_array = matches!(object, VType::Reference(name) if self.interner.resolve(&name).starts_with('['));
+        if (is_array && is_method && member.class == self.names.object)
+            || self.is_assignable(object, current)?
+        {
+            return Ok(());
+        }
+        Err(self.error(
+            format!("Bad access to protected data in {instruction}"),
+            format!(
+                "Type {} (current frame, stack[{}]) is not assignable to {}",
+                object.describe(self.interner),
+                frame.stack.len(),
+                current.describe(self.interner)
+            ),
+        ))
+    }
+
+    fn is_superclass(&mut self, name: Symbol) -> Result<bool, JvmError> {
+        let mut current = self.method.super_name;
+        while let Some(super_name) = current {
+            if super_name == name {
+                return Ok(true);
+            }
+            current = self.env.super_class(super_name)?;
+        }
+        Ok(false)
+    }
+
+    // invokespecial <init>, JVMS 4.10.1.9.invokespecial
+    fn initialize_object(&mut self, frame: &mut Frame, class_sym: Symbol) -> Result<(), JvmError> {
+        let depth = frame.stack.len();
+        let uninitialized = self.pop_reference(frame)?;
+        match uninitialized {
+            VType::UninitializedThis => {
+                if class_sym != self.method.class_name && Some(class_sym) != self.method.super_name
+                {
+                    return Err(
+                        self.error("Bad <init> method call", "Error exists in the bytecode")
+                    );
+                }
+                frame.initialize(uninitialized, VType::Reference(self.method.class_name));
+            }
+            VType::Uninitialized(offset) => {
+                let Some(Instruction::New(new_index)) = self.instructions.get(&offset).cloned()
+                else {
+                    return Err(self.error(
+                        "Expecting new instruction",
+                        format!("Offset {offset} is not a new instruction"),
+                    ));
+                };
+                if self.class_sym(new_index)? != class_sym {
+                    return Err(self.error(
+                        "Call to wrong <init> method",
+                        "Error exists in the bytecode",
+                    ));
+                }
+                frame.initialize(uninitialized, VType::Reference(class_sym));
+            }
+            other => {
+                return Err(self.error(
+                    "Bad operand type when invoking <init>",
+                    format!(
+                        "Type {} (current frame, stack[{}]) is not assignable to 'uninitialized'",
+                        other.describe(self.interner),
+                        depth - 1
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub(super) fn class_sym(&self, index: u16) -> Result<Symbol, JvmError> {
+        let interner = self.interner;
+        self.env.with_cp(|cp| cp.get_class_sym(&index, interner))
+    }
+
+    fn constant_type(&self, index: u16) -> Result<RuntimeConstantType, JvmError> {
+        let interner = self.interner;
+        self.env
+            .with_cp(|cp| Ok(cp.get_constant(&index, interner)?.get_type()))
+    }
+
+    fn field_ref(&self, index: u16) -> Result<(MemberRef, VType), JvmError> {
+        let interner = self.interner;
+        let view = self.env.with_cp(|cp| cp.get_field_view(&index, interner))?;
+        let descriptor = interner.resolve(&view.name_and_type.descriptor_sym);
+        let field_type = JavaType::try_from(descriptor).map_err(|e| {
+            self.error(
+                "Illegal field descriptor",
+                format!("{e} in descriptor {descriptor}"),
+            )
+        })?;
+        let field = MemberRef {
+            class: view.class_sym,
+            name: view.name_and_type.name_sym,
+            desc: view.name_and_type.descriptor_sym,
+        };
+        Ok((field, VType::from_java_type(&field_type, interner)))
+    }
+
+    fn method_descriptor(&self, desc: Symbol) -> Result<MethodDescriptor, JvmError> {
+        let desc = self.interner.resolve(&desc);
+        MethodDescriptor::try_from(desc)
+            .map_err(|e| self.error("Illegal method descriptor", format!("{e:?}")))
+    }
+
+    fn illegal_constant(&self, index: u16) -> JvmError {
+        self.error(
+            format!("Illegal type at constant pool entry {index}"),
+            "Error exists in the bytecode",
+        )
+    }
+
+    fn underflow(&self) -> JvmError {
+        self.error("Operand stack underflow", "Attempt to pop empty stack.")
+    }
+
+    fn bad_array(&self, value: VType) -> JvmError {
+        let name = self
+            .pc
+            .and_then(|pc| self.instructions.get(&pc))
+            .map(Instruction::get_name)
+            .unwrap_or_default();
+        self.error(
+            format!("Bad type on operand stack in {name}"),
+            format!(
+                "Type {} is not assignable to the array type of {name}",
+                value.describe(self.interner)
+            ),
+        )
+    }
+
+    fn bad_local(&self, value: VType, index: usize, expected: &str) -> JvmError {
+        self.error(
+            "Bad local variable type",
+            format!(
+                "Type {} (current frame, locals[{index}]) is not assignable to {expected}",
+                value.describe(self.interner)
+            ),
+        )
+    }
+
+    // HotSpot's layout, without the frame and bytecode dumps
+    pub(super) fn error(&self, headline: impl Display, reason: impl Display) -> JvmError {
+        let location = match self.pc {
+            Some(pc) => format!(
+                " @{pc}: {}",
+                self.instructions
+                    .get(&pc)
+                    .map_or("<invalid>", Instruction::get_name)
+            ),
+            None => String::new(),
+        };
+        build_exception!(
+            VerifyError,
+            "{headline}\nException Details:\n  Location:\n    {}.{}{}{location}\n  Reason:\n    {reason}",
+            self.interner.resolve(&self.method.class_name),
+            self.interner.resolve(&self.method.name),
+            self.interner.resolve(&self.method.desc)
+        )
+    }
+}
@@ -0,0 +1,259 @@
+use crate::VirtualMachine;
+use crate::error::{JavaExceptionKind, JvmError};
+use crate::heap::HeapRef;
+use crate::keys::{ClassId, FieldKey, MethodKey, Symbol};
+use crate::rt::constant_pool::RuntimeConstantPool;
+use crate::rt::method::CodeBody;
+use crate::rt::{ClassState, JvmClass};
+use crate::thread::JavaThreadState;
+use lasso::ThreadedRodeo;
+
+mod method_verifier;
+mod type_checker;
+mod type_inference;
+mod types;
+
+// class files older than this have no StackMapTable and are verified by type inference
+const TYPE_CHECKING_MIN_VERSION: u16 = 50;
+
+/// Which classes get verified, HotSpot's `-Xverify`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VerifyMode {
+    /// Every class except those of the bootstrap loader.
+    #[default]
+    Remote,
+    All,
+    /// Nothing, for trusted code.
+    None,
+}
+
+/// What the verifier needs to know about classes other than the method's own, JVMS 4.10.1.1.
+pub(crate) trait ClassEnvironment {
+    /// Runs `f` on the constant pool of the class being verified.
+    fn with_cp<R>(
+        &self,
+        f: impl FnOnce(&RuntimeConstantPool) -> Result<R, JvmError>,
+    ) -> Result<R, JvmError>;
+
+    fn super_class(&mut self, name: Symbol) -> Result<Option<Symbol>, JvmError>;
+
+    fn is_interface(&mut self, name: Symbol) -> Result<bool, JvmError>;
+
+    /// Whether the member found through `class_name` is protected and declared outside the
+    /// run-time package of the class being verified.
+    fn is_protected_access(
+        &mut self,
+        class_name: Symbol,
+        name: Symbol,
+        desc: Symbol,
+        is_method: bool,
+    ) -> Result<bool, JvmError>;
+}
+
+/// A method copied out of the method area, so no lock is held while classes get loaded.
+pub(crate) struct MethodToVerify {
+    pub class_name: Symbol,
+    pub super_name: Option<Symbol>,
+    pub name: Symbol,
+    pub desc: Symbol,
+    pub is_static: bool,
+    pub code: CodeBody,
+}
+
+struct VmClassEnvironment<'a> {
+    vm: &'a VirtualMachine,
+    thread: &'a mut JavaThreadState,
+    loader: Option<HeapRef>,
+    class_id: ClassId,
+}
+
+impl VmClassEnvironment<'_> {
+    fn load(&mut self, name: Symbol) -> Result<ClassId, JvmError> {
+        // a hidden class can only be found through itself
+        if self
+            .vm
+            .method_area_read()
+            .get_class(&self.class_id)
+            .get_name()
+            == name
+        {
+            return Ok(self.class_id);
+        }
+        self.vm.load_class_with(self.thread, self.loader, name)
+    }
+}
+
+impl ClassEnvironment for VmClassEnvironment<'_> {
+    fn with_cp<R>(
+        &self,
+        f: impl FnOnce(&RuntimeConstantPool) -> Result<R, JvmError>,
+    ) -> Result<R, JvmError> {
+        let ma = self.vm.method_area_read();
+        f(ma.get_class(&self.class_id).get_cp()?)
+    }
+
+    fn super_class(&mut self, name: Symbol) -> Result<Option<Symbol>, JvmError> {
+        let class_id = self.load(name)?;
+        let ma = self.vm.method_area_read();
+        Ok(ma
+            .get_class(&class_id)
+            .get_super_id()
+            .map(|super_id| ma.get_class(&super_id).get_name()))
+    }
+
+    fn is_interface(&mut self, name: Symbol) -> Result<bool, JvmError> {
+        let class_id = self.load(name)?;
+        Ok(self
+            .vm
+            .method_area_read()
+            .get_class(&class_id)
+            .is_interface())
+    }
+
+    fn is_protected_access(
+        &mut self,
+        class_name: Symbol,
+        name: Symbol,
+        desc: Symbol,
+        is_method: bool,
+    ) -> Result<bool, JvmError> {
+        let class_id = self.load(class_name)?;
+        let ma = self.vm.method_area_read();
+        let member = if is_method {
+            let JvmClass::Instance(class) = ma.get_class(&class_id) else {
+                return Ok(false);
+            };
+            class
+                .get_special_method_id_opt(&MethodKey { name, desc })
+                .map(|method_id| {
+                    let method = ma.get_method(&method_id);
+                    (method.class_id(), method.flags().is_protected())
+                })
+        } else {
+            let field_key = FieldKey { name, desc };
+            match ma.get_instance_field(&class_id, &field_key) {
+                Ok(field) => Some((field.declaring_class, field.flags.is_protected())),
+                Err(_) => ma
+                    .resolve_static_field_actual_class_id(class_id, &field_key)
+                    .ok()
+                    .and_then(|declaring| {
+                        let flags = ma
+                            .get_class_like(&declaring)
+                            .ok()?
+                            .get_static_field_flags(&field_key)
+                            .ok()?;
+                        Some((declaring, flags.is_protected()))
+                    }),
+            }
+        };
+        // unresolvable members fail later, at resolution
+        Ok(member.is_some_and(|(declaring, is_protected)| {
+            is_protected && !VirtualMachine::is_same_package(&ma, self.class_id, declaring)
+        }))
+    }
+}
+
+impl VirtualMachine {
+    /// Links `class_id` and its supertypes before their initialization, JVMS 5.4. Like HotSpot,
+    /// classes of the bootstrap loader are trusted unless [`VerifyMode::All`] is set.
+    pub(crate) fn link_class(
+        &self,
+        thread: &mut JavaThreadState,
+        class_id: ClassId,
+    ) -> Result<(), JvmError> {
+        let (supertypes, loader, major_version) = {
+            let ma = self.method_area_read();
+            let class = ma.get_class(&class_id);
+            if !matches!(class, JvmClass::Instance(_) | JvmClass::Interface(_)) {
+                return Ok(());
+            }
+            let class_like = class.as_class_like()?;
+            if class_like.init_state() != ClassState::Loaded {
+                return Ok(());
+            }
+            let supertypes = class_like
+                .get_super()
+                .into_iter()
+                .chain(class_like.get_direct_interfaces()?.iter().copied())
+                .collect::<Vec<_>>();
+            (
+                supertypes,
+                class_like.get_loader(),
+                class_like.get_major_version()?,
+            )
+        };
+        for supertype in supertypes {
+            self.link_class(thread, supertype)?;
+        }
+
+        let verify = match self.config.verify {
+            VerifyMode::Remote => loader.is_some(),
+            VerifyMode::All => true,
+            VerifyMode::None => false,
+        };
+        if verify {
+            let methods = self.methods_to_verify(class_id)?;
+            let mut env = VmClassEnvironment {
+                vm: self,
+                thread,
+                loader,
+                class_id,
+            };
+            for method in &methods {
+                Self::verify_method(&mut env, self.interner(), method, major_version)?;
+            }
+        }
+
+        self.method_area_read()
+            .get_class_like(&class_id)?
+            .set_linked();
+        Ok(())
+    }
+
+    // version 50 class files may still rely on the old verifier when their stack maps are wrong,
+    // HotSpot's FailOverToOldVerifier
+    fn verify_method<E: ClassEnvironment>(
+        env: &mut E,
+        interner: &ThreadedRodeo,
+        method: &MethodToVerify,
+        major_version: u16,
+    ) -> Result<(), JvmError> {
+        if major_version < TYPE_CHECKING_MIN_VERSION {
+            return type_inference::infer_method(env, interner, method);
+        }
+        match type_checker::verify_method(env, interner, method) {
+            Err(JvmError::JavaException(e))
+                if e.kind == JavaExceptionKind::VerifyError
+                    && major_version == TYPE_CHECKING_MIN_VERSION =>
+            {
+                type_inference::infer_method(env, interner, method)
+            }
+            result => result,
+        }
+    }
+
+    fn methods_to_verify(&self, class_id: ClassId) -> Result<Vec<MethodToVerify>, JvmError> {
+        let ma = self.method_area_read();
+        let class = ma.get_class(&class_id);
+        let class_name = class.get_name();
+        let super_name = class
+            .get_super_id()
+            .map(|super_id| ma.get_class(&super_id).get_name());
+        let mut methods = Vec::new();
+        for method_id in class.as_class_like()?.get_method_ids()? {
+            let method = ma.get_method(method_id);
+            let Some(code) = method.get_code_body() else {
+                continue;
+            };
+            methods.push(MethodToVerify {
+                class_name,
+                super_name,
+                name: method.name,
+                desc: method.desc,
+                is_static: method.is_static(),
+                code: code.clone(),
+            });
+        }
+        Ok(methods)
+    }
+}
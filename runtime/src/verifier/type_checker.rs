@@ -0,0 +1,280 @@
+use crate::error::JvmError;
+use crate::verifier::method_verifier::MethodVerifier;
+use crate::verifier::types::{Frame, VType};
+use crate::verifier::{ClassEnvironment, MethodToVerify};
+use jclass::attribute::method::code::{StackMapFrame, VerificationTypeInfo};
+use jclass::prelude::Instruction;
+use lasso::ThreadedRodeo;
+use std::collections::HashMap;
+
+/// Checks `method` against its `StackMapTable`, JVMS 4.10.1.
+pub(crate) fn verify_method<E: ClassEnvironment>(
+    env: &mut E,
+    interner: &ThreadedRodeo,
+    method: &MethodToVerify,
+) -> Result<(), JvmError> {
+    let mut verifier = MethodVerifier::new(env, interner, method)?;
+    verifier.build_stack_map()?;
+    verifier.run()
+}
+
+impl<E: ClassEnvironment> MethodVerifier<'_, E> {
+    fn run(&mut self) -> Result<(), JvmError> {
+        let mut frame = self.expand(&self.initial_locals.clone(), &[])?;
+        let mut falls_through = true;
+        let pcs = self.instructions.keys().copied().collect::<Vec<_>>();
+        for pc in pcs {
+            self.pc = Some(pc);
+            if let Some(map_frame) = self.map_frame(pc) {
+                if falls_through {
+                    self.check_frame(
+                        &frame,
+                        &map_frame,
+                        pc,
+                        "Instruction type does not match stack map",
+                    )?;
+                }
+                frame = map_frame;
+            } else if !falls_through {
+                return Err(self.error(
+                    "Expecting a stack map frame",
+                    "Expected stackmap frame at this location.",
+                ));
+            }
+
+            self.check_handlers(pc, &frame)?;
+            let instruction = self.instructions[&pc].clone();
+            falls_through = self.execute(&instruction, pc, &mut frame)?;
+            if Self::writes_local(&instruction) {
+                self.check_handlers(pc, &frame)?;
+            }
+        }
+        if falls_through {
+            self.pc = Some(self.method.code.code.len() as u16);
+            return Err(self.error(
+                "Control flow falls through code end",
+                "Error exists in the bytecode",
+            ));
+        }
+        Ok(())
+    }
+
+    // the table stores the locals of each frame relative to the previous one, starting from the
+    // method's arguments
+    fn build_stack_map(&mut self) -> Result<(), JvmError> {
+        let table = self.method.code.stack_map_table().unwrap_or_default();
+        let mut stack_map = HashMap::new();
+        let mut locals = self.initial_locals.clone();
+        let mut previous: Option<u16> = None;
+        for entry in table {
+            let (offset_delta, stack) = match entry {
+                StackMapFrame::Same { offset_delta }
+                | StackMapFrame::SameExtended { offset_delta } => (*offset_delta, Vec::new()),
+                StackMapFrame::SameLocals1StackItem {
+                    offset_delta,
+                    stack,
+                }
+                | StackMapFrame::SameLocals1StackItemExtended {
+                    offset_delta,
+                    stack,
+                } => (*offset_delta, vec![self.stack_map_type(stack)?]),
+                StackMapFrame::Chop { k, offset_delta } => {
+                    let Some(len) = locals.len().checked_sub(usize::from(*k)) else {
+                        return Err(self.error(
+                            "StackMapTable error: bad chop",
+                            "Chopped more locals than the frame has",
+                        ));
+                    };
+                    locals.truncate(len);
+                    (*offset_delta, Vec::new())
+                }
+                StackMapFrame::Append {
+                    offset_delta,
+                    locals: appended,
+                    ..
+                } => {
+                    for local in appended {
+                        locals.push(self.stack_map_type(local)?);
+                    }
+                    (*offset_delta, Vec::new())
+                }
+                StackMapFrame::Full {
+                    offset_delta,
+                    locals: full_locals,
+                    stack,
+                } => {
+                    locals = full_locals
+                        .iter()
+                        .map(|local| self.stack_map_type(local))
+                        .collect::<Result<_, _>>()?;
+                    let stack = stack
+                        .iter()
+                        .map(|item| self.stack_map_type(item))
+                        .collect::<Result<_, _>>()?;
+                    (*offset_delta, stack)
+                }
+            };
+            let offset = match previous {
+                None => u32::from(offset_delta),
+                Some(previous) => u32::from(previous) + u32::from(offset_delta) + 1,
+            };
+            let offset = u16::try_from(offset)
+                .ok()
+                .filter(|offset| self.instructions.contains_key(offset))
+                .ok_or_else(|| {
+                    self.error(
+                        "StackMapTable error: bad offset",
+                        format!("Frame offset {offset} is not an instruction"),
+                    )
+                })?;
+            let frame = self.expand(&locals, &stack)?;
+            stack_map.insert(offset, frame);
+            previous = Some(offset);
+        }
+        self.stack_map = Some(stack_map);
+        Ok(())
+    }
+
+    fn map_frame(&self, pc: u16) -> Option<Frame> {
+        self.stack_map
+            .as_ref()
+            .and_then(|stack_map| stack_map.get(&pc))
+            .cloned()
+    }
+
+    pub(super) fn check_branch_target(
+        &mut self,
+        frame: &Frame,
+        target: u16,
+    ) -> Result<(), JvmError> {
+        let Some(target_frame) = self.map_frame(target) else {
+            return Err(self.error(
+                format!("Expecting a stackmap frame at branch target {target}"),
+                "Expected stackmap frame at this location.",
+            ));
+        };
+        self.check_frame(
+            frame,
+            &target_frame,
+            target,
+            &format!("Inconsistent stackmap frames at branch target {target}"),
+        )
+    }
+
+    fn stack_map_type(&self, info: &VerificationTypeInfo) -> Result<VType, JvmError> {
+        Ok(match info {
+            VerificationTypeInfo::Top => VType::Top,
+            VerificationTypeInfo::Integer => VType::Integer,
+            VerificationTypeInfo::Float => VType::Float,
+            VerificationTypeInfo::Double => VType::Double,
+            VerificationTypeInfo::Long => VType::Long,
+            VerificationTypeInfo::Null => VType::Null,
+            VerificationTypeInfo::UninitializedThis => VType::UninitializedThis,
+            VerificationTypeInfo::Object(index) => VType::Reference(self.class_sym(*index)?),
+            VerificationTypeInfo::Uninitialized(offset) => {
+                if !matches!(self.instructions.get(offset), Some(Instruction::New(_))) {
+                    return Err(self.error(
+                        "StackMapTable error: bad uninitialized offset",
+                        format!("Offset {offset} is not a new instruction"),
+                    ));
+                }
+                VType::Uninitialized(*offset)
+            }
+        })
+    }
+
+    fn check_handlers(&mut self, pc: u16, frame: &Frame) -> Result<(), JvmError> {
+        let method = self.method;
+        for entry in &method.code.exception_table {
+            if pc < entry.start_pc || pc >= entry.end_pc {
+                continue;
+            }
+            let handler = entry.handler_pc;
+            let catch_type = if entry.catch_type == 0 {
+                self.names.throwable
+            } else {
+                let catch_type = self.class_sym(entry.catch_type)?;
+                if !self.is_assignable_reference(catch_type, self.names.throwable)? {
+                    return Err(self.error(
+                        format!(
+                            "Catch type is not a subclass of Throwable in exception handler {handler}"
+                        ),
+                        "Error exists in the bytecode",
+                    ));
+                }
+                catch_type
+            };
+            let Some(handler_frame) = self.map_frame(handler) else {
+                return Err(self.error(
+                    format!("Expecting a stackmap frame at branch target {handler}"),
+                    "Expected stackmap frame at this location.",
+                ));
+            };
+            let exception_frame = Frame {
+                locals: frame.locals.clone(),
+                stack: vec![VType::Reference(catch_type)],
+                this_uninit: frame.this_uninit,
+            };
+            self.check_frame(
+                &exception_frame,
+                &handler_frame,
+                handler,
+                &format!("Stack map does not match the one at exception handler {handler}"),
+            )?;
+        }
+        Ok(())
+    }
+
+    // like HotSpot, a mismatch is reported at the offset of the stack map frame
+    fn check_frame(
+        &mut self,
+        current: &Frame,
+        target: &Frame,
+        target_pc: u16,
+        headline: &str,
+    ) -> Result<(), JvmError> {
+        let pc = self.pc.replace(target_pc);
+        let result = self.is_frame_assignable(current, target, headline);
+        self.pc = pc;
+        result
+    }
+
+    // JVMS frameIsAssignable
+    fn is_frame_assignable(
+        &mut self,
+        current: &Frame,
+        target: &Frame,
+        headline: &str,
+    ) -> Result<(), JvmError> {
+        if current.stack.len() != target.stack.len() {
+            return Err(self.error(
+                headline,
+                "Current frame's stack size doesn't match stackmap.",
+            ));
+        }
+        for (kind, from, to) in [
+            ("locals", &current.locals, &target.locals),
+            ("stack", &current.stack, &target.stack),
+        ] {
+            for (i, (from, to)) in from.iter().zip(to.iter()).enumerate() {
+                if !self.is_assignable(*from, *to)? {
+                    return Err(self.error(
+                        headline,
+                        format!(
+                            "Type {} (current frame, {kind}[{i}]) is not assignable to {} (stack map, {kind}[{i}])",
+                            from.describe(self.interner),
+                            to.describe(self.interner)
+                        ),
+                    ));
+                }
+            }
+        }
+        if current.this_uninit && !target.this_uninit {
+            return Err(self.error(
+                headline,
+                "Current frame's flags are not assignable to stack map frame's.",
+            ));
+        }
+        Ok(())
+    }
+}
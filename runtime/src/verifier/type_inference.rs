@@ -0,0 +1,487 @@
+use crate::Symbol;
+use crate::error::JvmError;
+use crate::verifier::method_verifier::MethodVerifier;
+use crate::verifier::types::{Frame, VType};
+use crate::verifier::{ClassEnvironment, MethodToVerify};
+use jclass::prelude::Instruction;
+use lasso::ThreadedRodeo;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// Verifies `method` by inferring the frame of every instruction, JVMS 4.10.2, for class files
+/// without a `StackMapTable`.
+pub(crate) fn infer_method<E: ClassEnvironment>(
+    env: &mut E,
+    interner: &ThreadedRodeo,
+    method: &MethodToVerify,
+) -> Result<(), JvmError> {
+    let verifier = MethodVerifier::new(env, interner, method)?;
+    let subroutines = find_subroutines(&verifier.instructions, verifier.max_locals);
+    TypeInference {
+        verifier,
+        subroutines,
+        frames: HashMap::new(),
+        returns: HashMap::new(),
+        changed: BTreeSet::new(),
+    }
+    .run()
+}
+
+struct TypeInference<'a, E> {
+    verifier: MethodVerifier<'a, E>,
+    /// The locals each subroutine, keyed by its entry, may write, nested calls included.
+    subroutines: HashMap<u16, Vec<bool>>,
+    /// The frame before each instruction reached so far.
+    frames: HashMap<u16, Frame>,
+    /// The frames at the `ret`s of each subroutine, merged.
+    returns: HashMap<u16, Frame>,
+    changed: BTreeSet<u16>,
+}
+
+impl<E: ClassEnvironment> TypeInference<'_, E> {
+    fn run(mut self) -> Result<(), JvmError> {
+        let initial = self
+            .verifier
+            .expand(&self.verifier.initial_locals.clone(), &[])?;
+        let code_length = self.verifier.method.code.code.len();
+        if code_length == 0 {
+            self.verifier.pc = Some(0);
+            return Err(self.verifier.error(
+                "Control flow falls through code end",
+                "Error exists in the bytecode",
+            ));
+        }
+        self.merge_into(0, initial)?;
+
+        while let Some(pc) = self.changed.pop_first() {
+            self.verifier.pc = Some(pc);
+            let mut frame = self.frames[&pc].clone();
+            self.merge_handlers(pc, &frame)?;
+            let instruction = self.verifier.instructions[&pc].clone();
+            let next = usize::from(pc) + usize::from(instruction.byte_size());
+
+            let falls_through = match instruction {
+                Instruction::Jsr(offset) => self.call(pc, next, i32::from(offset), &frame)?,
+                Instruction::JsrW(offset) => self.call(pc, next, offset, &frame)?,
+                Instruction::Ret(index) => self.ret(usize::from(index), &frame)?,
+                _ => self.verifier.execute(&instruction, pc, &mut frame)?,
+            };
+            if MethodVerifier::<E>::writes_local(&instruction) {
+                self.merge_handlers(pc, &frame)?;
+            }
+            for (target, target_frame) in std::mem::take(&mut self.verifier.successors) {
+                self.merge_into(target, target_frame)?;
+            }
+            if falls_through {
+                if next >= code_length {
+                    self.verifier.pc = Some(code_length as u16);
+                    return Err(self.verifier.error(
+                        "Control flow falls through code end",
+                        "Error exists in the bytecode",
+                    ));
+                }
+                self.merge_into(next as u16, frame)?;
+            }
+        }
+        Ok(())
+    }
+
+    // jsr, the instruction after it is reached once the subroutine returns
+    fn call(&mut self, pc: u16, next: usize, offset: i32, frame: &Frame) -> Result<bool, JvmError> {
+        let entry = i32::from(pc) + offset;
+        let Some(entry) = u16::try_from(entry)
+            .ok()
+            .filter(|entry| self.verifier.instructions.contains_key(entry))
+        else {
+            return Err(self.verifier.error(
+                "Illegal target of jump or branch",
+                format!("Target {entry} is not an instruction"),
+            ));
+        };
+        let mut entry_frame = frame.clone();
+        self.verifier
+            .push(&mut entry_frame, VType::ReturnAddress(entry))?;
+        self.merge_into(entry, entry_frame)?;
+        if let Some(returned) = self.returns.get(&entry).cloned() {
+            let after = self.after_return(frame, &returned, entry);
+            self.merge_into(next as u16, after)?;
+        }
+        Ok(false)
+    }
+
+    fn ret(&mut self, index: usize, frame: &Frame) -> Result<bool, JvmError> {
+        let entry = match frame.locals.get(index) {
+            Some(VType::ReturnAddress(entry)) => *entry,
+            Some(other) => {
+                return Err(self.verifier.error(
+                    "Bad local variable type",
+                    format!(
+                        "Type {} (current frame, locals[{index}]) is not assignable to returnAddress",
+                        other.describe(self.verifier.interner)
+                    ),
+                ));
+            }
+            None => {
+                return Err(self.verifier.error(
+                    "Illegal local variable number",
+                    format!("Local index {index} is invalid"),
+                ));
+            }
+        };
+        let returned = match self.returns.get(&entry).cloned() {
+            Some(previous) => self.merge_frames(&previous, frame)?,
+            None => frame.clone(),
+        };
+        self.returns.insert(entry, returned.clone());
+
+        let callers = self
+            .verifier
+            .instructions
+            .iter()
+            .filter_map(|(pc, instruction)| match instruction {
+                Instruction::Jsr(offset) => Some((*pc, i32::from(*offset), 3)),
+                Instruction::JsrW(offset) => Some((*pc, *offset, 5)),
+                _ => None,
+            })
+            .filter(|(pc, offset, _)| i32::from(*pc) + offset == i32::from(entry))
+            .collect::<Vec<_>>();
+        for (pc, _, size) in callers {
+            let Some(call_frame) = self.frames.get(&pc).cloned() else {
+                continue;
+            };
+            let after = self.after_return(&call_frame, &returned, entry);
+            self.merge_into(pc + size, after)?;
+        }
+        Ok(false)
+    }
+
+    // locals the subroutine does not write keep their types from before the jsr
+    fn after_return(&self, call_frame: &Frame, returned: &Frame, entry: u16) -> Frame {
+        let written = &self.subroutines[&entry];
+        let mut locals = call_frame
+            .locals
+            .iter()
+            .zip(&returned.locals)
+            .zip(written)
+            .map(|((before, after), written)| if *written { *after } else { *before })
+            .collect::<Vec<_>>();
+        drop_split_values(&mut locals);
+        Frame {
+            locals,
+            stack: returned.stack.clone(),
+            this_uninit: returned.this_uninit,
+        }
+    }
+
+    fn merge_handlers(&mut self, pc: u16, frame: &Frame) -> Result<(), JvmError> {
+        let method = self.verifier.method;
+        for entry in &method.code.exception_table {
+            if pc < entry.start_pc || pc >= entry.end_pc {
+                continue;
+            }
+            let throwable = self.verifier.names.throwable;
+            let catch_type = if entry.catch_type == 0 {
+                throwable
+            } else {
+                let catch_type = self.verifier.class_sym(entry.catch_type)?;
+                if !self
+                    .verifier
+                    .is_assignable_reference(catch_type, throwable)?
+                {
+                    return Err(self.verifier.error(
+                        format!(
+                            "Catch type is not a subclass of Throwable in exception handler {}",
+                            entry.handler_pc
+                        ),
+                        "Error exists in the bytecode",
+                    ));
+                }
+                catch_type
+            };
+            let handler_frame = Frame {
+                locals: frame.locals.clone(),
+                stack: vec![VType::Reference(catch_type)],
+                this_uninit: frame.this_uninit,
+            };
+            self.merge_into(entry.handler_pc, handler_frame)?;
+        }
+        Ok(())
+    }
+
+    fn merge_into(&mut self, pc: u16, frame: Frame) -> Result<(), JvmError> {
+        let merged = match self.frames.get(&pc).cloned() {
+            None => frame,
+            Some(existing) => {
+                let merged = self.merge_frames(&existing, &frame)?;
+                if merged == existing {
+                    return Ok(());
+                }
+                merged
+            }
+        };
+        self.frames.insert(pc, merged);
+        self.changed.insert(pc);
+        Ok(())
+    }
+
+    // JVMS 4.10.2.2, locals that disagree become unusable, the stacks must agree
+    fn merge_frames(&mut self, existing: &Frame, incoming: &Frame) -> Result<Frame, JvmError> {
+        if existing.stack.len() != incoming.stack.len() {
+            return Err(self.verifier.error(
+                "Inconsistent stack height",
+                format!(
+                    "Stack height {} does not match {} of an earlier path",
+                    incoming.stack.len(),
+                    existing.stack.len()
+                ),
+            ));
+        }
+        let mut stack = Vec::with_capacity(existing.stack.len());
+        for (i, (a, b)) in existing.stack.iter().zip(&incoming.stack).enumerate() {
+            let merged = self.merge_types(*a, *b)?;
+            if merged == VType::Top && *a != VType::Top {
+                return Err(self.verifier.error(
+                    "Mismatched stack types",
+                    format!(
+                        "Type {} (current frame, stack[{i}]) does not match {}",
+                        b.describe(self.verifier.interner),
+                        a.describe(self.verifier.interner)
+                    ),
+                ));
+            }
+            stack.push(merged);
+        }
+        let mut locals = Vec::with_capacity(existing.locals.len());
+        for (a, b) in existing.locals.iter().zip(&incoming.locals) {
+            locals.push(self.merge_types(*a, *b)?);
+        }
+        drop_split_values(&mut locals);
+        Ok(Frame {
+            locals,
+            stack,
+            this_uninit: existing.this_uninit || incoming.this_uninit,
+        })
+    }
+
+    fn merge_types(&mut self, a: VType, b: VType) -> Result<VType, JvmError> {
+        Ok(match (a, b) {
+            _ if a == b => a,
+            (VType::Null, VType::Reference(_)) => b,
+            (VType::Reference(_), VType::Null) => a,
+            (VType::Reference(a), VType::Reference(b)) => {
+                VType::Reference(self.common_supertype(a, b)?)
+            }
+            _ => VType::Top,
+        })
+    }
+
+    // the first common superclass, with interfaces treated like Object
+    fn common_supertype(&mut self, a: Symbol, b: Symbol) -> Result<Symbol, JvmError> {
+        let object = self.verifier.names.object;
+        if a == b {
+            return Ok(a);
+        }
+        let interner = self.verifier.interner;
+        let (a_name, b_name) = (interner.resolve(&a), interner.resolve(&b));
+        if a_name.starts_with('[') || b_name.starts_with('[') {
+            let components = a_name
+                .strip_prefix('[')
+                .and_then(MethodVerifier::<E>::reference_component)
+                .zip(
+                    b_name
+                        .strip_prefix('[')
+                        .and_then(MethodVerifier::<E>::reference_component),
+                );
+            let Some((a_component, b_component)) = components else {
+                return Ok(object);
+            };
+            let (a_component, b_component) = (
+                interner.get_or_intern(a_component),
+                interner.get_or_intern(b_component),
+            );
+            let component = self.common_supertype(a_component, b_component)?;
+            let component = interner.resolve(&component);
+            let array = if component.starts_with('[') {
+                format!("[{component}")
+            } else {
+                format!("[L{component};")
+            };
+            return Ok(interner.get_or_intern(array));
+        }
+        if self.verifier.env.is_interface(a)? || self.verifier.env.is_interface(b)? {
+            return Ok(object);
+        }
+
+        let mut a_supertypes = HashSet::from([a]);
+        let mut current = a;
+        while let Some(super_name) = self.verifier.env.super_class(current)? {
+            a_supertypes.insert(super_name);
+            current = super_name;
+        }
+        let mut current = b;
+        loop {
+            if a_supertypes.contains(&current) {
+                return Ok(current);
+            }
+            match self.verifier.env.super_class(current)? {
+                Some(super_name) => current = super_name,
+                None => return Ok(object),
+            }
+        }
+    }
+}
+
+// a long or a double whose second slot got another value is gone
+fn drop_split_values(locals: &mut [VType]) {
+    for i in 0..locals.len() {
+        if locals[i].is_category2() && locals.get(i + 1) != Some(&VType::Top) {
+            locals[i] = VType::Top;
+        }
+    }
+}
+
+// the locals written by each subroutine, found by following its instructions up to its rets
+fn find_subroutines(
+    instructions: &std::collections::BTreeMap<u16, Instruction>,
+    max_locals: usize,
+) -> HashMap<u16, Vec<bool>> {
+    let entries = instructions
+        .iter()
+        .filter_map(|(pc, instruction)| jump_target(*pc, instruction))
+        .collect::<BTreeSet<_>>();
+
+    let mut written = HashMap::new();
+    let mut nested = HashMap::new();
+    for entry in &entries {
+        let mut locals = vec![false; max_locals];
+        let mut calls = Vec::new();
+        let mut visited = HashSet::new();
+        let mut pending = vec![*entry];
+        while let Some(pc) = pending.pop() {
+            let Some(instruction) = instructions.get(&pc) else {
+                continue;
+            };
+            if !visited.insert(pc) {
+                continue;
+            }
+            if let Some((index, size)) = written_local(instruction) {
+                for slot in locals.iter_mut().skip(index).take(size) {
+                    *slot = true;
+                }
+            }
+            let next = pc.checked_add(instruction.byte_size());
+            match instruction {
+                Instruction::Jsr(_) | Instruction::JsrW(_) => {
+                    calls.extend(jump_target(pc, instruction));
+                    pending.extend(next);
+                }
+                _ => {
+                    let (targets, falls_through) = flow(pc, instruction);
+                    pending.extend(targets);
+                    if falls_through {
+                        pending.extend(next);
+                    }
+                }
+            }
+        }
+        written.insert(*entry, locals);
+        nested.insert(*entry, calls);
+    }
+
+    // a subroutine also writes whatever the ones it calls write
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (entry, calls) in &nested {
+            for call in calls {
+                let callee = written[call].clone();
+                let locals = written.get_mut(entry).expect("every entry has its locals");
+                for (slot, callee_slot) in locals.iter_mut().zip(callee) {
+                    if callee_slot && !*slot {
+                        *slot = true;
+                        changed = true;
+                    }
+                }
+            }
+        }
+    }
+    written
+}
+
+fn jump_target(pc: u16, instruction: &Instruction) -> Option<u16> {
+    let offset = match instruction {
+        Instruction::Jsr(offset) => i32::from(*offset),
+        Instruction::JsrW(offset) => *offset,
+        _ => return None,
+    };
+    u16::try_from(i32::from(pc) + offset).ok()
+}
+
+// the branch targets of an instruction other than jsr and whether it falls through
+fn flow(pc: u16, instruction: &Instruction) -> (Vec<u16>, bool) {
+    let target = |offset: i32| u16::try_from(i32::from(pc) + offset).ok();
+    match instruction {
+        Instruction::IfEq(offset)
+        | Instruction::IfNe(offset)
+        | Instruction::IfLt(offset)
+        | Instruction::IfGe(offset)
+        | Instruction::IfGt(offset)
+        | Instruction::IfLe(offset)
+        | Instruction::IfIcmpeq(offset)
+        | Instruction::IfIcmpne(offset)
+        | Instruction::IfIcmplt(offset)
+        | Instruction::IfIcmpge(offset)
+        | Instruction::IfIcmpgt(offset)
+        | Instruction::IfIcmple(offset)
+        | Instruction::IfAcmpEq(offset)
+        | Instruction::IfAcmpNe(offset)
+        | Instruction::Ifnull(offset)
+        | Instruction::Ifnonnull(offset) => {
+            (target(i32::from(*offset)).into_iter().collect(), true)
+        }
+        Instruction::Goto(offset) => (target(i32::from(*offset)).into_iter().collect(), false),
+        Instruction::GotoW(offset) => (target(*offset).into_iter().collect(), false),
+        Instruction::TableSwitch(data) => (
+            std::iter::once(data.default_offset)
+                .chain(data.offsets.iter().copied())
+                .filter_map(target)
+                .collect(),
+            false,
+        ),
+        Instruction::Lookupswitch(data) => (
+            std::iter::once(data.default_offset)
+                .chain(data.pairs.iter().map(|(_, offset)| *offset))
+                .filter_map(target)
+                .collect(),
+            false,
+        ),
+        Instruction::Ret(_)
+        | Instruction::Athrow
+        | Instruction::Return
+        | Instruction::Ireturn
+        | Instruction::Lreturn
+        | Instruction::Freturn
+        | Instruction::Dreturn
+        | Instruction::Areturn => (Vec::new(), false),
+        _ => (Vec::new(), true),
+    }
+}
+
+// the first local an instruction writes and how many slots
+fn written_local(instruction: &Instruction) -> Option<(usize, usize)> {
+    Some(match instruction {
+        Instruction::Istore(n)
+        | Instruction::Fstore(n)
+        | Instruction::Astore(n)
+        | Instruction::Iinc(n, _) => (usize::from(*n), 1),
+        Instruction::Lstore(n) | Instruction::Dstore(n) => (usize::from(*n), 2),
+        Instruction::Istore0 | Instruction::Fstore0 | Instruction::Astore0 => (0, 1),
+        Instruction::Istore1 | Instruction::Fstore1 | Instruction::Astore1 => (1, 1),
+        Instruction::Istore2 | Instruction::Fstore2 | Instruction::Astore2 => (2, 1),
+        Instruction::Istore3 | Instruction::Fstore3 | Instruction::Astore3 => (3, 1),
+        Instruction::Lstore0 | Instruction::Dstore0 => (0, 2),
+        Instruction::Lstore1 | Instruction::Dstore1 => (1, 2),
+        Instruction::Lstore2 | Instruction::Dstore2 => (2, 2),
+        Instruction::Lstore3 | Instruction::Dstore3 => (3, 2),
+        _ => return None,
+    })
+}
@@ -0,0 +1,87 @@
+use crate::Symbol;
+use common::jtype::{JavaType, PrimitiveType};
+use lasso::ThreadedRodeo;
+
+/// A verification type, JVMS 4.10.1.2. Values of category 2 take two slots, the second one is
+/// `Top`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum VType {
+    Top,
+    Integer,
+    Float,
+    Long,
+    Double,
+    Null,
+    UninitializedThis,
+    /// Created by the `new` at this offset and not initialized yet.
+    Uninitialized(u16),
+    /// A class name or an array descriptor, like the names of the method area.
+    Reference(Symbol),
+    /// Pushed by a `jsr` to the subroutine at this offset, only known to the type inference.
+    ReturnAddress(u16),
+}
+
+impl VType {
+    pub fn from_java_type(java_type: &JavaType, interner: &ThreadedRodeo) -> Self {
+        match java_type {
+            JavaType::Primitive(PrimitiveType::Long) => VType::Long,
+            JavaType::Primitive(PrimitiveType::Float) => VType::Float,
+            JavaType::Primitive(PrimitiveType::Double) => VType::Double,
+            JavaType::Primitive(_) => VType::Integer,
+            JavaType::Instance(name) => VType::Reference(interner.get_or_intern(name)),
+            other => VType::Reference(interner.get_or_intern(other.as_descriptor())),
+        }
+    }
+
+    pub fn is_category2(&self) -> bool {
+        matches!(self, VType::Long | VType::Double)
+    }
+
+    /// Anything an `aload` or an `astore` can move, initialized or not.
+    pub fn is_reference(&self) -> bool {
+        matches!(
+            self,
+            VType::Null | VType::UninitializedThis | VType::Uninitialized(_) | VType::Reference(_)
+        )
+    }
+
+    /// How HotSpot prints the type in verification errors.
+    pub fn describe(&self, interner: &ThreadedRodeo) -> String {
+        match self {
+            VType::Top => "top".to_string(),
+            VType::Integer => "integer".to_string(),
+            VType::Float => "float".to_string(),
+            VType::Long => "long".to_string(),
+            VType::Double => "double".to_string(),
+            VType::Null => "null".to_string(),
+            VType::UninitializedThis => "uninitializedThis".to_string(),
+            VType::Uninitialized(offset) => format!("uninitialized({offset})"),
+            VType::Reference(name) => format!("'{}'", interner.resolve(name)),
+            VType::ReturnAddress(_) => "returnAddress".to_string(),
+        }
+    }
+}
+
+/// The types of the locals and of the operand stack before an instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Frame {
+    /// Always `max_locals` long, unused slots are `Top`.
+    pub locals: Vec<VType>,
+    pub stack: Vec<VType>,
+    /// `flagThisUninit`, set while a constructor has not called `super()` or `this()` yet.
+    pub this_uninit: bool,
+}
+
+impl Frame {
+    /// Replaces the uninitialized type once its `<init>` has been called.
+    pub fn initialize(&mut self, uninitialized: VType, initialized: VType) {
+        for slot in self.locals.iter_mut().chain(self.stack.iter_mut()) {
+            if *slot == uninitialized {
+                *slot = initialized;
+            }
+        }
+        if uninitialized == VType::UninitializedThis {
+            self.this_uninit = false;
+        }
+    }
+}
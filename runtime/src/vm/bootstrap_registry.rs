@@ -17,10 +17,23 @@ pub struct BootstrapRegistry {
     pub thread_thread_group_and_name_constructor_mk: MethodKey,
     pub thread_group_uncaught_exception_mk: MethodKey,
     pub thread_get_thread_group_mk: MethodKey,
+    pub thread_run_mk: MethodKey,
+    pub thread_exit_mk: MethodKey,
+    pub class_loader_load_class_mk: MethodKey,
+    pub class_loader_get_system_class_loader_mk: MethodKey,
+    pub class_loaders_app_class_loader_mk: MethodKey,
+    pub throwable_cause_constructor_mk: MethodKey,
 
     // Common field keys
     pub class_name_fk: FieldKey,
     pub class_primitive_fk: FieldKey,
+    pub class_class_loader_fk: FieldKey,
+    pub class_module_fk: FieldKey,
+    pub class_class_data_fk: FieldKey,
+    pub class_loader_unnamed_module_fk: FieldKey,
+    pub class_loader_name_and_id_fk: FieldKey,
+    pub module_name_fk: FieldKey,
+    pub module_loader_fk: FieldKey,
     pub system_out_fk: FieldKey,
     pub system_err_fk: FieldKey,
     pub file_output_stream_fd_fk: FieldKey,
@@ -34,17 +47,34 @@ pub struct BootstrapRegistry {
     pub stack_trace_declaring_class_name_fk: FieldKey,
     pub reference_referent_fk: FieldKey,
     pub file_path_fk: FieldKey,
+    pub thread_name_fk: FieldKey,
+    pub thread_holder_fk: FieldKey,
+    pub thread_eetop_fk: FieldKey,
+    pub thread_interrupted_fk: FieldKey,
+    pub thread_status_fk: FieldKey,
+    pub thread_daemon_fk: FieldKey,
+    pub thread_priority_fk: FieldKey,
+    pub thread_tid_fk: FieldKey,
+    pub thread_park_blocker_fk: FieldKey,
 
     // Common class names (interned)
     pub java_lang_object_sym: Symbol,
     pub java_lang_class_sym: Symbol,
     pub java_lang_throwable_sym: Symbol,
+    pub java_lang_error_sym: Symbol,
+    pub java_lang_class_not_found_exception_sym: Symbol,
+    pub java_lang_exception_in_initializer_error_sym: Symbol,
     pub java_lang_string_sym: Symbol,
     pub java_lang_system_sym: Symbol,
     pub java_lang_thread_sym: Symbol,
     pub java_lang_thread_group_sym: Symbol,
+    pub java_lang_class_loader_sym: Symbol,
     pub java_lang_ref_reference_sym: Symbol,
     pub java_io_file_sym: Symbol,
+    pub java_lang_assertion_status_directives_sym: Symbol,
+    pub jdk_internal_loader_class_loaders_sym: Symbol,
+    pub jdk_internal_loader_platform_class_loader_sym: Symbol,
+    pub java_lang_module_sym: Symbol,
 
     // Primitive name symbols
     pub int_sym: Symbol,
@@ -104,6 +134,7 @@ impl BootstrapRegistry {
         let int_desc = interner.get_or_intern("I");
         let boolean_desc = interner.get_or_intern("Z");
         let desc_print_stream_sym = interner.get_or_intern("Ljava/io/PrintStream;");
+        let module_desc = interner.get_or_intern("Ljava/lang/Module;");
 
         // Primitive type names
         let int_sym = interner.get_or_intern("int");
@@ -165,6 +196,30 @@ impl BootstrapRegistry {
                 name: interner.get_or_intern("getThreadGroup"),
                 desc: interner.get_or_intern("()Ljava/lang/ThreadGroup;"),
             },
+            thread_run_mk: MethodKey {
+                name: interner.get_or_intern("run"),
+                desc: void_desc,
+            },
+            thread_exit_mk: MethodKey {
+                name: interner.get_or_intern("exit"),
+                desc: void_desc,
+            },
+            class_loader_load_class_mk: MethodKey {
+                name: interner.get_or_intern("loadClass"),
+                desc: interner.get_or_intern("(Ljava/lang/String;)Ljava/lang/Class;"),
+            },
+            class_loader_get_system_class_loader_mk: MethodKey {
+                name: interner.get_or_intern("getSystemClassLoader"),
+                desc: interner.get_or_intern("()Ljava/lang/ClassLoader;"),
+            },
+            class_loaders_app_class_loader_mk: MethodKey {
+                name: interner.get_or_intern("appClassLoader"),
+                desc: interner.get_or_intern("()Ljava/lang/ClassLoader;"),
+            },
+            throwable_cause_constructor_mk: MethodKey {
+                name: init_sym,
+                desc: interner.get_or_intern("(Ljava/lang/Throwable;)V"),
+            },
 
             // Field keys
             class_name_fk: FieldKey {
@@ -175,6 +230,34 @@ impl BootstrapRegistry {
                 name: interner.get_or_intern("primitive"),
                 desc: boolean_desc,
             },
+            class_class_loader_fk: FieldKey {
+                name: interner.get_or_intern("classLoader"),
+                desc: interner.get_or_intern("Ljava/lang/ClassLoader;"),
+            },
+            class_module_fk: FieldKey {
+                name: interner.get_or_intern("module"),
+                desc: module_desc,
+            },
+            class_class_data_fk: FieldKey {
+                name: interner.get_or_intern("classData"),
+                desc: object_desc,
+            },
+            class_loader_unnamed_module_fk: FieldKey {
+                name: interner.get_or_intern("unnamedModule"),
+                desc: module_desc,
+            },
+            class_loader_name_and_id_fk: FieldKey {
+                name: interner.get_or_intern("nameAndId"),
+                desc: string_desc,
+            },
+            module_name_fk: FieldKey {
+                name: interner.get_or_intern("name"),
+                desc: string_desc,
+            },
+            module_loader_fk: FieldKey {
+                name: interner.get_or_intern("loader"),
+                desc: interner.get_or_intern("Ljava/lang/ClassLoader;"),
+            },
             throwable_backtrace_fk: FieldKey {
                 name: interner.get_or_intern("backtrace"),
                 desc: object_desc,
@@ -227,17 +310,66 @@ impl BootstrapRegistry {
                 name: interner.get_or_intern("path"),
                 desc: string_desc,
             },
+            thread_name_fk: FieldKey {
+                name: name_field,
+                desc: string_desc,
+            },
+            thread_holder_fk: FieldKey {
+                name: interner.get_or_intern("holder"),
+                desc: interner.get_or_intern("Ljava/lang/Thread$FieldHolder;"),
+            },
+            thread_eetop_fk: FieldKey {
+                name: interner.get_or_intern("eetop"),
+                desc: interner.get_or_intern("J"),
+            },
+            thread_interrupted_fk: FieldKey {
+                name: interner.get_or_intern("interrupted"),
+                desc: boolean_desc,
+            },
+            thread_status_fk: FieldKey {
+                name: interner.get_or_intern("threadStatus"),
+                desc: int_desc,
+            },
+            thread_daemon_fk: FieldKey {
+                name: interner.get_or_intern("daemon"),
+                desc: boolean_desc,
+            },
+            thread_priority_fk: FieldKey {
+                name: interner.get_or_intern("priority"),
+                desc: int_desc,
+            },
+            thread_tid_fk: FieldKey {
+                name: interner.get_or_intern("tid"),
+                desc: interner.get_or_intern("J"),
+            },
+            thread_park_blocker_fk: FieldKey {
+                name: interner.get_or_intern("parkBlocker"),
+                desc: object_desc,
+            },
 
             // Class names
             java_lang_object_sym: interner.get_or_intern("java/lang/Object"),
             java_lang_class_sym: interner.get_or_intern("java/lang/Class"),
             java_lang_throwable_sym: interner.get_or_intern("java/lang/Throwable"),
+            java_lang_error_sym: interner.get_or_intern("java/lang/Error"),
+            java_lang_class_not_found_exception_sym: interner
+                .get_or_intern("java/lang/ClassNotFoundException"),
+            java_lang_exception_in_initializer_error_sym: interner
+                .get_or_intern("java/lang/ExceptionInInitializerError"),
             java_lang_string_sym: interner.get_or_intern("java/lang/String"),
             java_lang_system_sym: interner.get_or_intern("java/lang/System"),
             java_lang_thread_sym: interner.get_or_intern("java/lang/Thread"),
             java_lang_thread_group_sym: interner.get_or_intern("java/lang/ThreadGroup"),
+            java_lang_class_loader_sym: interner.get_or_intern("java/lang/ClassLoader"),
             java_lang_ref_reference_sym: interner.get_or_intern("java/lang/ref/Reference"),
             java_io_file_sym: interner.get_or_intern("java/io/File"),
+            java_lang_assertion_status_directives_sym: interner
+                .get_or_intern("java/lang/AssertionStatusDirectives"),
+            jdk_internal_loader_class_loaders_sym: interner
+                .get_or_intern("jdk/internal/loader/ClassLoaders"),
+            jdk_internal_loader_platform_class_loader_sym: interner
+                .get_or_intern("jdk/internal/loader/ClassLoaders$PlatformClassLoader"),
+            java_lang_module_sym: interner.get_or_intern("java/lang/Module"),
 
             // Method names
             init_sym,
@@ -1,5 +1,8 @@
 use clap::Parser;
-use runtime::VmConfig;
+use runtime::{JarFile, SchedulerMode, VerifyMode, VmConfig};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use tracing_log::log::debug;
 
 #[derive(Parser, Debug)]
@@ -11,9 +14,18 @@ pub struct Args {
         visible_alias = "cp",
         visible_alias = "class-path",
         value_delimiter = ';',
-        help = "Classpath entries (only dirs, no jars(todo)); use ';' as separator"
+        help = "Classpath entries (directories and JAR files); use ';' as separator"
     )]
     pub class_path: Vec<String>,
+    #[arg(
+        long = "boot-class-path-append",
+        value_name = "PATHS",
+        value_delimiter = ';',
+        help = "Directories and JAR files searched by the bootstrap loader after the runtime \
+        image; use ';' as separator (also accepted as -Xbootclasspath/a:PATHS, separated by \
+        the platform path separator)"
+    )]
+    pub boot_class_path_append: Vec<String>,
     #[arg(
         short = 'j',
         long = "jdwp-port",
@@ -21,56 +33,215 @@ pub struct Args {
     )]
     pub jdwp_port: Option<u16>,
     #[arg(
+        long = "green-threads",
+        value_name = "SEED",
+        help = "Run all Java threads one at a time with a deterministic scheduler seeded with SEED"
+    )]
+    pub green_threads_seed: Option<u64>,
+    #[arg(
+        long = "green-quantum",
+        value_name = "BYTECODES",
+        default_value_t = 1000,
+        requires = "green_threads_seed",
+        help = "Bytecodes a green thread executes before the scheduler may switch to another one"
+    )]
+    pub green_quantum: u32,
+    #[arg(
+        long = "deadlock-watchdog",
+        value_name = "MILLIS",
+        help = "Check for Java-level deadlocks every MILLIS milliseconds and report them"
+    )]
+    pub deadlock_watchdog_millis: Option<u64>,
+    #[arg(
+        long = "jar",
+        value_name = "JARFILE",
+        conflicts_with = "class_path",
+        help = "Run the Main-Class of an executable JAR (also accepted as -jar)"
+    )]
+    pub jar: Option<String>,
+    #[arg(
+        long = "verify",
+        value_name = "MODE",
+        value_parser = ["remote", "all", "none"],
+        default_value = "remote",
+        help = "Verify the classes of non-bootstrap loaders (remote), all classes or none \
+        (also accepted as -Xverify:MODE, -noverify is -Xverify:none)"
+    )]
+    pub verify: String,
+    #[arg(
+        long = "patch-module",
+        value_name = "MODULE=PATH",
+        help = "Override or augment a module with classes from directories and JAR files; \
+        use ';' as separator, may be repeated"
+    )]
+    pub patch_modules: Vec<String>,
+    #[arg(
+        long = "java-home",
+        value_name = "DIR",
+        help = "The JDK whose class library is used, JAVA_HOME when not given"
+    )]
+    pub java_home: Option<String>,
+    #[arg(
+        long = "class-archive",
+        value_name = "FILE",
+        help = "Read the boot classes from the archive FILE, and write it at exit when it is \
+        missing, stale or lacks classes of the run"
+    )]
+    pub class_archive: Option<String>,
+    #[arg(
+        required_unless_present = "jar",
+        conflicts_with = "jar",
         help = "Main class to run from path that matches the package structure \
         (e.g. com.example.Main or com/example/Main for com/example/Main.class)"
     )]
-    pub main_class_path: String,
+    pub main_class_path: Option<String>,
+}
+
+/// `java -jar` semantics: the JAR is the whole user classpath (its manifest Class-Path is
+/// followed by the class loader) and the main class comes from its manifest.
+fn resolve_jar(args: &mut Args, jar_path: &str) -> Result<String, String> {
+    let jar = JarFile::open(jar_path)
+        .map_err(|e| format!("Invalid or corrupt jarfile {jar_path}: {e}"))?;
+    let manifest = jar
+        .manifest()
+        .map_err(|e| format!("Invalid or corrupt jarfile {jar_path}: {e}"))?;
+    let main_class = manifest
+        .as_ref()
+        .and_then(|manifest| manifest.main_class())
+        .ok_or_else(|| format!("no main manifest attribute, in {jar_path}"))?;
+    args.class_path = vec![jar_path.to_string()];
+    Ok(main_class.trim().replace('.', "/"))
+}
+
+fn parse_patch_modules(patch_modules: &[String]) -> Result<Vec<(String, Vec<String>)>, String> {
+    let mut patches: Vec<(String, Vec<String>)> = Vec::new();
+    for patch in patch_modules {
+        let (module, path) = patch
+            .split_once('=')
+            .filter(|(module, path)| !module.is_empty() && !path.is_empty())
+            .ok_or_else(|| format!("--patch-module expects <module>=<path>, got: {patch}"))?;
+        if patches.iter().any(|(patched, _)| patched == module) {
+            return Err(format!(
+                "--patch-module specified more than once for module {module}"
+            ));
+        }
+        patches.push((
+            module.to_string(),
+            path.split(';').map(str::to_string).collect(),
+        ));
+    }
+    Ok(patches)
+}
+
+// the JAVA_VERSION of the release file, like "25.0.2"
+fn read_java_version(home: &Path) -> Result<String, String> {
+    let release_file = home.join("release");
+    let contents = std::fs::read_to_string(&release_file)
+        .map_err(|e| format!("cannot read {}: {e}", release_file.display()))?;
+    contents
+        .lines()
+        .find_map(|line| line.strip_prefix("JAVA_VERSION="))
+        .map(|value| value.trim().trim_matches('"').to_string())
+        .ok_or_else(|| format!("JAVA_VERSION not found in {}", release_file.display()))
 }
 
 fn create_vm_configuration(mut args: Args, main_class: String) -> Result<VmConfig, String> {
-    let java_home = std::env::var("JAVA_HOME").expect("JAVA_HOME not set");
+    let home = args
+        .java_home
+        .clone()
+        .or_else(|| std::env::var("JAVA_HOME").ok())
+        .map(PathBuf::from)
+        .ok_or("JAVA_HOME is not set, set it or pass --java-home")?;
     if args.class_path.is_empty() {
         let current_dir = std::env::current_dir()
-            .map(|v| v.to_string_lossy().to_string())
-            .expect("cannot get current dir");
-        args.class_path.push(current_dir);
+            .map_err(|e| format!("cannot get the current directory: {e}"))?;
+        args.class_path
+            .push(current_dir.to_string_lossy().to_string());
     }
-    let home = std::path::PathBuf::from(&java_home);
-    let release_file = format!("{}/release", java_home);
+    let version = read_java_version(&home)?;
+    // an exploded JDK build has a directory per module instead of the lib/modules image
+    let exploded_modules = Some(home.join("modules"))
+        .filter(|modules| modules.is_dir() && !home.join("lib").join("modules").is_file());
+    let patch_modules = parse_patch_modules(&args.patch_modules)?;
 
-    let contents = std::fs::read_to_string(release_file).expect("cannot read release file");
+    Ok(VmConfig {
+        home,
+        main_class,
+        version,
+        class_path: args.class_path,
+        boot_class_path_append: args.boot_class_path_append,
+        initial_heap_size: 0,
+        max_heap_size: 0,
+        frame_stack_size: 256,
+        jdwp_port: args.jdwp_port,
+        scheduler: match args.green_threads_seed {
+            Some(seed) => SchedulerMode::Green {
+                seed,
+                quantum: args.green_quantum,
+            },
+            None => SchedulerMode::Native,
+        },
+        deadlock_watchdog: args.deadlock_watchdog_millis.map(Duration::from_millis),
+        verify: match args.verify.as_str() {
+            "all" => VerifyMode::All,
+            "none" => VerifyMode::None,
+            _ => VerifyMode::Remote,
+        },
+        patch_modules,
+        exploded_modules,
+        class_archive: args.class_archive.map(PathBuf::from),
+    })
+}
 
-    for line in contents.lines() {
-        if let Some(value) = line.strip_prefix("JAVA_VERSION=") {
-            return Ok(VmConfig {
-                home,
-                main_class,
-                version: value.trim_matches('"').to_string(),
-                class_path: args.class_path,
-                initial_heap_size: 0,
-                max_heap_size: 0,
-                frame_stack_size: 256,
-                jdwp_port: args.jdwp_port,
-            });
-        }
-    }
-    Err("JAVA_VERSION not found in release file".to_string())
+// clap only knows `--jar`, `--verify` and `--boot-class-path-append`, but everyone types
+// `java -jar`, `-Xverify:none` and `-Xbootclasspath/a:`. Arguments that aren't valid UTF-8 are
+// none of them and are passed on untouched
+fn java_style_arg(arg: OsString) -> OsString {
+    let Some(arg) = arg.to_str() else {
+        return arg;
+    };
+    let arg = if arg == "-jar" {
+        "--jar".to_string()
+    } else if arg == "-noverify" {
+        "--verify=none".to_string()
+    } else if let Some(mode) = arg.strip_prefix("-Xverify:") {
+        format!("--verify={mode}")
+    } else if let Some(paths) = arg.strip_prefix("-Xbootclasspath/a:") {
+        // the -X form takes the platform path separator like java does
+        let paths = std::env::split_paths(paths)
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        format!("--boot-class-path-append={}", paths.join(";"))
+    } else {
+        arg.to_string()
+    };
+    OsString::from(arg)
 }
 
 #[hotpath::main]
 fn main() {
     #[cfg(feature = "log-runtime-traces")]
     common::utils::telemetry::init_tracing();
-    let args = Args::parse();
+    let mut args = Args::parse_from(std::env::args_os().map(java_style_arg));
     debug!("Provided command line arguments: {:?}", args);
 
-    let main_class = args.main_class_path.replace('.', "/");
+    let main_class = match (args.jar.clone(), &args.main_class_path) {
+        (Some(jar_path), _) => match resolve_jar(&mut args, &jar_path) {
+            Ok(main_class) => main_class,
+            Err(e) => {
+                eprintln!("Error: {e}");
+                std::process::exit(1);
+            }
+        },
+        (None, Some(main_class_path)) => main_class_path.replace('.', "/"),
+        (None, None) => unreachable!("clap requires the main class without -jar"),
+    };
 
     let vm_config = match create_vm_configuration(args, main_class) {
         Ok(config) => config,
         Err(e) => {
             eprintln!("Error creating VM configuration: {}", e);
-            return;
+            std::process::exit(1);
         }
     };
     if let Err(_) = runtime::start(vm_config) {
@@ -35,41 +35,62 @@ fn to_snapshot_name(path: &Path) -> String {
         .join("-")
 }
 
-#[rstest]
-#[trace]
-fn non_error_cases(
-    #[base_dir = "tests/testdata/compiled"]
-    #[files("**/*OkMain.class")]
-    path: PathBuf,
-) {
+// Seed of every green thread run, the snapshots of *GreenMain depend on it
+const GREEN_THREADS_SEED: &str = "42";
+
+// Runs the main class with `extra_args` before it and returns stdout and stderr the way the
+// snapshots store them
+fn run_main_class(main_class_path: &Path, extra_args: &[&str], expect_success: bool) -> String {
     // requires cargo build
     let current_dir = std::env::current_dir().expect("Cannot get current dir");
     let class_path = current_dir.join("tests/testdata/compiled");
-    let main_class_path = transform_absolute_path_to_package(&path);
     let mut cmd = cargo_bin_cmd!("vm");
-    cmd.arg("-c").arg(class_path).arg(&main_class_path);
+    cmd.arg("-c")
+        .arg(class_path)
+        .args(extra_args)
+        .arg(main_class_path);
 
-    let output = cmd.assert().success().get_output().clone();
+    let assert = cmd.assert();
+    let assert = if expect_success {
+        assert.success()
+    } else {
+        assert.failure()
+    };
+    let output = assert.get_output();
     let stdout = String::from_utf8_lossy(&output.stdout);
     let stderr = String::from_utf8_lossy(&output.stderr);
 
-    let combined = format!(
+    format!(
         "----- STDOUT -----\n{}\n----- STDERR -----\n{}",
         stdout.trim_end(),
         stderr.trim_end()
-    );
+    )
+}
 
+fn assert_output_snapshot(main_class_path: &Path, combined: &str) {
     with_settings!(
         {
             snapshot_path => DISPLAY_SNAPSHOT_PATH,
             prepend_module_to_snapshot => false,
         },
         {
-            insta::assert_snapshot!(to_snapshot_name(&main_class_path), combined);
+            insta::assert_snapshot!(to_snapshot_name(main_class_path), combined);
         }
     );
 }
 
+#[rstest]
+#[trace]
+fn non_error_cases(
+    #[base_dir = "tests/testdata/compiled"]
+    #[files("**/*OkMain.class")]
+    path: PathBuf,
+) {
+    let main_class_path = transform_absolute_path_to_package(&path);
+    let combined = run_main_class(&main_class_path, &[], true);
+    assert_output_snapshot(&main_class_path, &combined);
+}
+
 #[rstest]
 #[trace]
 fn error_cases(
@@ -78,32 +99,416 @@ fn error_cases(
     path: PathBuf,
 ) {
     // given
+    let main_class_path = transform_absolute_path_to_package(&path);
+
+    // when
+    let combined = run_main_class(&main_class_path, &[], false);
+
+    // then
+    assert_output_snapshot(&main_class_path, &combined);
+}
+
+#[rstest]
+#[trace]
+fn green_thread_cases(
+    #[base_dir = "tests/testdata/compiled"]
+    #[files("**/*GreenMain.class")]
+    path: PathBuf,
+) {
+    let main_class_path = transform_absolute_path_to_package(&path);
+    let combined = run_main_class(
+        &main_class_path,
+        &["--green-threads", GREEN_THREADS_SEED],
+        true,
+    );
+    assert_output_snapshot(&main_class_path, &combined);
+}
+
+#[test]
+fn green_threads_replay_the_same_interleaving() {
+    // given
+    let main_class_path = Path::new("threads/green/RacyCounterGreenMain");
+    let args = ["--green-threads", GREEN_THREADS_SEED];
+
+    // when
+    let first = run_main_class(main_class_path, &args, true);
+    let second = run_main_class(main_class_path, &args, true);
+
+    // then
+    assert_eq!(first, second);
+}
+
+#[test]
+fn thread_dump_on_sigquit() {
+    use std::io::{BufRead, BufReader, Read};
+    use std::process::{Command, Stdio};
+
     // requires cargo build
     let current_dir = std::env::current_dir().expect("Cannot get current dir");
     let class_path = current_dir.join("tests/testdata/compiled");
-    let main_class_path = transform_absolute_path_to_package(&path);
+    let mut child = Command::new(assert_cmd::cargo::cargo_bin!("vm"))
+        .arg("-c")
+        .arg(class_path)
+        .arg("threads/dump/ThreadDumpMain")
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Failed to start vm");
+
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut line = String::new();
+    while line.trim_end() != "ready" {
+        line.clear();
+        assert_ne!(stdout.read_line(&mut line).unwrap(), 0, "vm exited early");
+    }
+    let status = Command::new("kill")
+        .arg("-QUIT")
+        .arg(child.id().to_string())
+        .status()
+        .expect("Failed to run kill");
+    assert!(status.success());
+
+    let mut rest = String::new();
+    stdout.read_to_string(&mut rest).unwrap();
+    assert!(child.wait().unwrap().success());
+
+    assert!(rest.contains("Full thread dump lagertha-vm"), "{rest}");
+    assert!(rest.contains("\"main\" #1 prio=5"), "{rest}");
+    assert!(
+        rest.contains("java.lang.Thread.State: TIMED_WAITING (sleeping)"),
+        "{rest}"
+    );
+    assert!(
+        rest.contains("\tat threads.dump.ThreadDumpMain.main(ThreadDumpMain.java:27)"),
+        "{rest}"
+    );
+    assert!(rest.contains("\"contender\" #"), "{rest}");
+    assert!(
+        rest.contains("java.lang.Thread.State: BLOCKED (on object monitor)"),
+        "{rest}"
+    );
+    assert!(rest.contains("- waiting to lock <0x"), "{rest}");
+    assert!(rest.contains("- locked <0x"), "{rest}");
+    assert!(rest.contains("Contender got the lock."), "{rest}");
+}
+
+#[test]
+fn deadlock_watchdog_reports_deadlock() {
+    use std::io::{BufRead, BufReader};
+    use std::process::{Command, Stdio};
+
+    // requires cargo build
+    let current_dir = std::env::current_dir().expect("Cannot get current dir");
+    let class_path = current_dir.join("tests/testdata/compiled");
+    let mut child = Command::new(assert_cmd::cargo::cargo_bin!("vm"))
+        .arg("-c")
+        .arg(class_path)
+        .arg("--deadlock-watchdog")
+        .arg("100")
+        .arg("threads/deadlock/DeadlockMain")
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Failed to start vm");
+
+    // the program never terminates on its own
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut report = String::new();
+    while !report.contains("Found 1 deadlock.") {
+        let read = stdout.read_line(&mut report).unwrap();
+        if read == 0 {
+            break;
+        }
+    }
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    assert!(
+        report.contains("Found one Java-level deadlock:"),
+        "{report}"
+    );
+    assert!(report.contains("\"locker-a\":"), "{report}");
+    assert!(report.contains("\"locker-b\":"), "{report}");
+    assert!(report.contains("which is held by \"locker-a\""), "{report}");
+    assert!(report.contains("which is held by \"locker-b\""), "{report}");
+    assert!(
+        report.contains("Java stack information for the threads listed above:"),
+        "{report}"
+    );
+    assert!(
+        report.contains("\tat threads.deadlock.DeadlockMain$Locker.run(DeadlockMain.java:29)"),
+        "{report}"
+    );
+    assert!(report.contains("\t- waiting to lock <0x"), "{report}");
+    assert!(report.contains("\t- locked <0x"), "{report}");
+    assert!(report.contains("Found 1 deadlock."), "{report}");
+}
+
+fn jar_tool() -> PathBuf {
+    std::env::var("JAVA_HOME")
+        .map(|home| Path::new(&home).join("bin/jar"))
+        .unwrap_or_else(|_| PathBuf::from("jar"))
+}
+
+#[test]
+fn run_executable_jar() {
+    use std::process::Command;
+
+    let current_dir = std::env::current_dir().expect("Cannot get current dir");
+    let compiled = current_dir.join("tests/testdata/compiled");
+    let out_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("run_executable_jar");
+    let _ = std::fs::remove_dir_all(&out_dir);
+    std::fs::create_dir_all(&out_dir).unwrap();
+    let jar_tool = jar_tool();
+
+    // the library is stored, the application deflated and pointing at the library
+    let status = Command::new(&jar_tool)
+        .arg("--create")
+        .arg("--no-compress")
+        .arg("--file")
+        .arg(out_dir.join("lib.jar"))
+        .arg("-C")
+        .arg(&compiled)
+        .arg("classpath/jar/lib/Greeter.class")
+        .status()
+        .expect("Failed to run jar");
+    assert!(status.success());
+    let manifest = out_dir.join("manifest.txt");
+    std::fs::write(&manifest, "Class-Path: lib.jar\n").unwrap();
+    let status = Command::new(&jar_tool)
+        .arg("--create")
+        .arg("--file")
+        .arg(out_dir.join("app.jar"))
+        .arg("--manifest")
+        .arg(&manifest)
+        .arg("--main-class")
+        .arg("classpath.jar.JarAppMain")
+        .arg("-C")
+        .arg(&compiled)
+        .arg("classpath/jar/JarAppMain.class")
+        .status()
+        .expect("Failed to run jar");
+    assert!(status.success());
+
+    // requires cargo build
     let mut cmd = cargo_bin_cmd!("vm");
-    cmd.arg("-c").arg(class_path).arg(&main_class_path);
+    cmd.arg("-jar").arg(out_dir.join("app.jar"));
+    let output = cmd.assert().success().get_output().clone();
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout).trim_end(),
+        "Hello, jar!"
+    );
+}
 
-    // when
+#[test]
+fn patch_module_adds_boot_class() {
+    use std::process::Command;
+
+    let current_dir = std::env::current_dir().expect("Cannot get current dir");
+    let compiled = current_dir.join("tests/testdata/compiled");
+    let source = current_dir.join("tests/testdata/patch/java.base");
+    let patch_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("patch_module");
+    let _ = std::fs::remove_dir_all(&patch_dir);
+    std::fs::create_dir_all(&patch_dir).unwrap();
+    let javac = std::env::var("JAVA_HOME")
+        .map(|home| Path::new(&home).join("bin/javac"))
+        .unwrap_or_else(|_| PathBuf::from("javac"));
+
+    let status = Command::new(&javac)
+        .arg("--patch-module")
+        .arg(format!("java.base={}", source.display()))
+        .arg("-d")
+        .arg(&patch_dir)
+        .arg(source.join("java/lang/PatchedGreeting.java"))
+        .status()
+        .expect("Failed to run javac");
+    assert!(status.success());
+
+    // requires cargo build
+    let mut cmd = cargo_bin_cmd!("vm");
+    cmd.arg("--patch-module")
+        .arg(format!("java.base={}", patch_dir.display()))
+        .arg("-c")
+        .arg(compiled)
+        .arg("classes/modules/PatchModuleMain");
+    let output = cmd.assert().success().get_output().clone();
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout).trim_end(),
+        "greeting: Hello from the patched java.base\n\
+        module: java.base\n\
+        loader: null\n\
+        same module as Object: true"
+    );
+}
+
+// a JDK home with a release file and, if given, a lib/modules image
+fn fake_java_home(name: &str, java_version: &str, modules: Option<&[u8]>) -> PathBuf {
+    let home = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = std::fs::remove_dir_all(&home);
+    std::fs::create_dir_all(home.join("lib")).unwrap();
+    std::fs::write(
+        home.join("release"),
+        format!("JAVA_VERSION=\"{java_version}\"\n"),
+    )
+    .unwrap();
+    if let Some(modules) = modules {
+        std::fs::write(home.join("lib/modules"), modules).unwrap();
+    }
+    home
+}
+
+fn vm_errors_with_java_home(home: &Path) -> String {
+    let current_dir = std::env::current_dir().expect("Cannot get current dir");
+    let class_path = current_dir.join("tests/testdata/compiled");
+    let mut cmd = cargo_bin_cmd!("vm");
+    cmd.arg("--java-home")
+        .arg(home)
+        .arg("-c")
+        .arg(class_path)
+        .arg("hello_world/basic/HelloWorldOkMain");
     let output = cmd.assert().failure().get_output().clone();
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let stderr = String::from_utf8_lossy(&output.stderr);
+    String::from_utf8_lossy(&output.stderr)
+        .trim_end()
+        .to_string()
+}
 
-    let combined = format!(
-        "----- STDOUT -----\n{}\n----- STDERR -----\n{}",
-        stdout.trim_end(),
-        stderr.trim_end()
+#[test]
+fn java_home_without_release_is_reported() {
+    // given
+    let home = Path::new(env!("CARGO_TARGET_TMPDIR")).join("no_such_java_home");
+
+    // when
+    let stderr = vm_errors_with_java_home(&home);
+
+    // then
+    assert_eq!(
+        stderr,
+        format!(
+            "Error creating VM configuration: cannot read {}/release: No such file or directory (os error 2)",
+            home.display()
+        )
     );
+}
+
+#[test]
+fn unsupported_java_version_is_reported() {
+    // given
+    let home = fake_java_home("java_home_24", "24.0.2", None);
+
+    // when
+    let stderr = vm_errors_with_java_home(&home);
 
     // then
-    with_settings!(
-        {
-            snapshot_path => DISPLAY_SNAPSHOT_PATH,
-            prepend_module_to_snapshot => false,
-        },
-        {
-            insta::assert_snapshot!(to_snapshot_name(&main_class_path), &combined);
-        }
+    assert_eq!(
+        stderr,
+        format!(
+            "Error: Unsupported Java version 24.0.2 in {}, only Java 25 is supported.",
+            home.display()
+        )
     );
 }
+
+#[test]
+fn unsupported_jimage_version_is_reported() {
+    // given
+    let header = [0xCAFE_DADAu32, 2 << 16, 0, 0, 0, 0, 0]
+        .iter()
+        .flat_map(|field| field.to_le_bytes())
+        .collect::<Vec<_>>();
+    let home = fake_java_home("java_home_jimage_2", "25.0.2", Some(&header));
+
+    // when
+    let stderr = vm_errors_with_java_home(&home);
+
+    // then
+    assert_eq!(
+        stderr,
+        format!(
+            "Error: Cannot use the runtime image {}/lib/modules: unsupported jimage version 2.0",
+            home.display()
+        )
+    );
+}
+
+#[test]
+fn class_archive_is_dumped_and_reused() {
+    let current_dir = std::env::current_dir().expect("Cannot get current dir");
+    let class_path = current_dir.join("tests/testdata/compiled");
+    let archive = Path::new(env!("CARGO_TARGET_TMPDIR")).join("hello_world.lgca");
+    let _ = std::fs::remove_file(&archive);
+    let run = || {
+        // requires cargo build
+        let mut cmd = cargo_bin_cmd!("vm");
+        cmd.arg("--class-archive")
+            .arg(&archive)
+            .arg("-c")
+            .arg(&class_path)
+            .arg("hello_world/basic/HelloWorldOkMain");
+        let output = cmd.assert().success().get_output().clone();
+        String::from_utf8_lossy(&output.stdout).to_string()
+    };
+
+    let dumping = run();
+    let dumped = std::fs::metadata(&archive)
+        .and_then(|metadata| metadata.modified())
+        .expect("no class archive");
+    let reusing = run();
+
+    assert_eq!(dumping, reusing);
+    // nothing new to add, the archive stays as it is
+    assert_eq!(
+        std::fs::metadata(&archive).unwrap().modified().unwrap(),
+        dumped
+    );
+}
+
+#[test]
+fn boot_class_path_append_defines_classes_with_the_bootstrap_loader() {
+    use std::process::Command;
+
+    let current_dir = std::env::current_dir().expect("Cannot get current dir");
+    let compiled = current_dir.join("tests/testdata/compiled");
+    let helper = "classes/loaders/bootappend/BootHelper.class";
+    let out_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("boot_class_path_append");
+    let _ = std::fs::remove_dir_all(&out_dir);
+    let boot_dir = out_dir.join("classes");
+    std::fs::create_dir_all(boot_dir.join("classes/loaders/bootappend")).unwrap();
+    std::fs::copy(compiled.join(helper), boot_dir.join(helper)).unwrap();
+    let jar_tool = jar_tool();
+    let boot_jar = out_dir.join("boot.jar");
+    let status = Command::new(&jar_tool)
+        .arg("--create")
+        .arg("--file")
+        .arg(&boot_jar)
+        .arg("-C")
+        .arg(&boot_dir)
+        .arg(helper)
+        .status()
+        .expect("Failed to run jar");
+    assert!(status.success());
+
+    // several entries are separated like java does, a missing one is skipped
+    let missing_and_jar =
+        std::env::join_paths([out_dir.join("missing"), boot_jar.clone()]).unwrap();
+    for boot_path in [
+        boot_dir.into_os_string(),
+        boot_jar.into_os_string(),
+        missing_and_jar,
+    ] {
+        // requires cargo build
+        let mut cmd = cargo_bin_cmd!("vm");
+        cmd.arg(format!("-Xbootclasspath/a:{}", boot_path.display()))
+            .arg("-c")
+            .arg(&compiled)
+            .arg("classes/loaders/BootClassPathAppendMain");
+        let output = cmd.assert().success().get_output().clone();
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout).trim_end(),
+            "helper: Hello from the boot class path\n\
+            helper loader: null\n\
+            helper module named: false\n\
+            forName through the system loader: true\n\
+            main loader is the system loader: true",
+            "{}",
+            boot_path.display()
+        );
+    }
+}
@@ -0,0 +1,145 @@
+package classes.access;
+
+import support.BytesLoader;
+import support.ClassBuilder;
+
+public class AccessOkMain {
+    static final String A = "classes/access/a/";
+    static final String B = "classes/access/b/";
+    static final String OBJECT = "java/lang/Object";
+
+    static final int DUP = 0x59;
+    static final int POP = 0x57;
+    static final int RETURN = 0xb1;
+    static final int GETSTATIC = 0xb2;
+    static final int GETFIELD = 0xb4;
+    static final int INVOKESPECIAL = 0xb7;
+    static final int INVOKESTATIC = 0xb8;
+    static final int NEW = 0xbb;
+
+    static final int PUBLIC = ClassBuilder.PUBLIC;
+    static final int PRIVATE = ClassBuilder.PRIVATE;
+    static final int PROTECTED = ClassBuilder.PROTECTED;
+    static final int STATIC = ClassBuilder.STATIC;
+    static final int SUPER = ClassBuilder.SUPER;
+
+    public static void main(String[] args) throws Exception {
+        BytesLoader loader = new BytesLoader("access");
+
+        ClassBuilder target = newClass(PUBLIC | SUPER, A + "Target", OBJECT);
+        target.field(STATIC, "packageField", "I");
+        target.field(PRIVATE | STATIC, "privateField", "I");
+        target.field(PROTECTED | STATIC, "protectedStaticField", "I");
+        target.field(PROTECTED, "protectedField", "I");
+        target.method(STATIC, "packageMethod", "()V", 0, 0, RETURN);
+        target.method(PRIVATE | STATIC, "privateMethod", "()V", 0, 0, RETURN);
+        target.constructor(OBJECT);
+        loader.add(target);
+
+        ClassBuilder hidden = newClass(SUPER, A + "Hidden", OBJECT);
+        hidden.field(STATIC, "value", "I");
+        loader.add(hidden);
+
+        loader.add(getStatic(A + "SamePackage", OBJECT, A + "Target", "packageField"));
+        loader.add(getStatic(B + "PackageField", OBJECT, A + "Target", "packageField"));
+        loader.add(getStatic(B + "PrivateField", OBJECT, A + "Target", "privateField"));
+        loader.add(invokeStatic(B + "PackageMethod", A + "Target", "packageMethod"));
+        loader.add(invokeStatic(B + "PrivateMethod", A + "Target", "privateMethod"));
+        loader.add(getStatic(B + "ProtectedFromSubclass", A + "Target", A + "Target", "protectedStaticField"));
+        loader.add(getStatic(B + "ProtectedFromOther", OBJECT, A + "Target", "protectedStaticField"));
+        loader.add(getStatic(B + "NonPublicClass", OBJECT, A + "Hidden", "value"));
+
+        // nestmates share their private members, but only with a nest host that lists them
+        ClassBuilder host = newClass(PUBLIC | SUPER, A + "Host", OBJECT);
+        host.field(PRIVATE | STATIC, "secret", "I");
+        host.nestMembers(A + "Host$Inner");
+        loader.add(host);
+        loader.add(getStatic(A + "Host$Inner", OBJECT, A + "Host", "secret").nestHost(A + "Host"));
+        loader.add(getStatic(A + "Intruder", OBJECT, A + "Host", "secret").nestHost(A + "Host"));
+
+        // a subclass may only use protected instance members on objects of its own hierarchy
+        ClassBuilder cousin = newClass(PUBLIC | SUPER, B + "Cousin", A + "Target");
+        cousin.constructor(A + "Target");
+        loader.add(cousin);
+        ClassBuilder sibling = newClass(PUBLIC | SUPER, B + "Sibling", A + "Target");
+        getFieldOfNew(sibling, B + "Cousin", B + "Cousin");
+        loader.add(sibling);
+        ClassBuilder badReceiver = newClass(PUBLIC | SUPER, B + "BadReceiver", A + "Target");
+        getFieldOfNew(badReceiver, A + "Target", A + "Target");
+        loader.add(badReceiver);
+
+        String[] names = {
+            A + "SamePackage", B + "PackageField", B + "PrivateField", B + "PackageMethod", B + "PrivateMethod",
+            B + "ProtectedFromSubclass", B + "ProtectedFromOther", B + "NonPublicClass", A + "Host$Inner",
+            A + "Intruder", B + "Sibling", B + "BadReceiver",
+        };
+        for (String name : names) {
+            initialize(loader, name);
+        }
+    }
+
+    static ClassBuilder getStatic(String name, String superName, String owner, String field) {
+        ClassBuilder builder = newClass(PUBLIC | SUPER, name, superName);
+        int ref = builder.fieldRef(owner, field, "I");
+        builder.method(STATIC, "<clinit>", "()V", 1, 0, GETSTATIC, ref >> 8, ref & 0xff, POP, RETURN);
+        return builder;
+    }
+
+    static ClassBuilder invokeStatic(String name, String owner, String method) {
+        ClassBuilder builder = newClass(PUBLIC | SUPER, name, OBJECT);
+        int ref = builder.methodRef(owner, method, "()V");
+        builder.method(STATIC, "<clinit>", "()V", 0, 0, INVOKESTATIC, ref >> 8, ref & 0xff, RETURN);
+        return builder;
+    }
+
+    static void initialize(ClassLoader loader, String name) {
+        String simpleName = name.substring(name.lastIndexOf('/') + 1);
+        try {
+            Class.forName(name.replace('/', '.'), true, loader);
+            System.out.print(simpleName);
+            System.out.println(": initialized");
+        } catch (ClassNotFoundException | LinkageError e) {
+            // HotSpot adds the frames and the bytecode to verify errors
+            String[] lines = String.valueOf(e.getMessage()).split("\n");
+            System.out.print(simpleName);
+            System.out.print(": ");
+            System.out.print(e.getClass().getName());
+            System.out.print(": ");
+            System.out.print(withoutHashes(lines[0]));
+            if (lines.length > 3) {
+                System.out.print(" at ");
+                System.out.print(lines[3].trim());
+            }
+            System.out.println();
+        }
+    }
+
+    // HotSpot adds identity hashes to the names of loaders, like 'access' @1b6d3586
+    static String withoutHashes(String message) {
+        StringBuilder result = new StringBuilder();
+        int start = 0;
+        int at;
+        while ((at = message.indexOf("' @", start)) >= 0) {
+            result.append(message, start, at + 1);
+            start = at + 3;
+            while (start < message.length() && Character.digit(message.charAt(start), 16) >= 0) {
+                start++;
+            }
+        }
+        return result.append(message.substring(start)).toString();
+    }
+
+    // nest attributes need version 55
+    static ClassBuilder newClass(int flags, String name, String superName) {
+        return new ClassBuilder(flags, name, superName).version(55);
+    }
+
+    // a <clinit> reading protectedField of a new instance of `instance` through `owner`
+    static void getFieldOfNew(ClassBuilder builder, String instance, String owner) {
+        int instanceClass = builder.classRef(instance);
+        int init = builder.methodRef(instance, "<init>", "()V");
+        int field = builder.fieldRef(owner, "protectedField", "I");
+        builder.method(STATIC, "<clinit>", "()V", 2, 0, NEW, instanceClass >> 8, instanceClass & 0xff, DUP,
+                INVOKESPECIAL, init >> 8, init & 0xff, GETFIELD, field >> 8, field & 0xff, POP, RETURN);
+    }
+}
@@ -0,0 +1,83 @@
+package classes.hidden;
+
+import java.lang.invoke.MethodHandles;
+import java.lang.invoke.MethodHandles.Lookup;
+import java.lang.invoke.MethodHandles.Lookup.ClassOption;
+import support.ClassBuilder;
+
+public class HiddenClassOkMain {
+    private static String secret = "nest secret";
+
+    public static void main(String[] args) throws Exception {
+        Lookup lookup = MethodHandles.lookup();
+        byte[] peer = peerBytes();
+
+        Lookup first = lookup.defineHiddenClass(peer, true, ClassOption.NESTMATE);
+        Class<?> hidden = first.lookupClass();
+        print("hidden: ", String.valueOf(hidden.isHidden()));
+        print("visible class hidden: ", String.valueOf(HiddenClassOkMain.class.isHidden()));
+        String name = hidden.getName();
+        int slash = name.indexOf('/');
+        print("name: ", name.substring(0, slash));
+        print("suffix: ", String.valueOf(name.startsWith("/0x", slash)));
+        print("nest host: ", hidden.getNestHost().getName());
+        print("loader: ", String.valueOf(hidden.getClassLoader() == HiddenClassOkMain.class.getClassLoader()));
+        print("no class data: ", String.valueOf(MethodHandles.classData(first, "_", String.class)));
+
+        Lookup second = lookup.defineHiddenClassWithClassData(peer, "class data", true, ClassOption.NESTMATE);
+        print("class data: ", MethodHandles.classData(second, "_", String.class));
+        print("distinct: ", String.valueOf(hidden != second.lookupClass()));
+        print("same name: ", String.valueOf(name.equals(second.lookupClass().getName())));
+
+        try {
+            Class.forName(name);
+            System.out.println("found by name");
+        } catch (ClassNotFoundException e) {
+            System.out.println("not found by name");
+        }
+
+        // without NESTMATE the hidden class is in a nest of its own
+        try {
+            lookup.defineHiddenClass(peer, true);
+            System.out.println("private access allowed");
+        } catch (IllegalAccessError e) {
+            System.out.println("private access denied");
+        }
+    }
+
+    static void print(String label, String value) {
+        System.out.print(label);
+        System.out.println(value);
+    }
+
+    static void reveal(String value) {
+        print("  secret: ", value);
+    }
+
+    static void self(Class<?> self) {
+        print("  self hidden: ", String.valueOf(self.isHidden()));
+    }
+
+    static void constant(String value) {
+        print("  name constant: ", value);
+    }
+
+    // classes/hidden/Peer, whose <clinit> passes its own class to self(), the private
+    // HiddenClassOkMain.secret to reveal() and a string constant sharing the class name to
+    // constant()
+    static byte[] peerBytes() {
+        String main = "classes/hidden/HiddenClassOkMain";
+        ClassBuilder builder = new ClassBuilder(ClassBuilder.FINAL | ClassBuilder.SUPER, "classes/hidden/Peer",
+                "java/lang/Object");
+        int self = builder.classRef("classes/hidden/Peer");
+        int secret = builder.fieldRef(main, "secret", "Ljava/lang/String;");
+        int reveal = builder.methodRef(main, "reveal", "(Ljava/lang/String;)V");
+        int selfMethod = builder.methodRef(main, "self", "(Ljava/lang/Class;)V");
+        int name = builder.string("classes/hidden/Peer");
+        int constant = builder.methodRef(main, "constant", "(Ljava/lang/String;)V");
+        builder.method(ClassBuilder.STATIC, "<clinit>", "()V", 1, 0, 0x12, self, 0xb8, selfMethod >> 8,
+                selfMethod & 0xff, 0xb2, secret >> 8, secret & 0xff, 0xb8, reveal >> 8, reveal & 0xff, 0x12, name,
+                0xb8, constant >> 8, constant & 0xff, 0xb1);
+        return builder.build();
+    }
+}
@@ -0,0 +1,103 @@
+package classes.initialization;
+
+public class ClassInitStateOkMain {
+    static class Broken {
+        static int value = fail();
+
+        static int fail() {
+            throw new IllegalStateException("boom");
+        }
+    }
+
+    static class BrokenParent {
+        static int value = Broken.value;
+    }
+
+    static class ChildOfBroken extends BrokenParent {
+        static int own = 1;
+    }
+
+    static class Slow {
+        static int initCount;
+        static final int VALUE;
+
+        static {
+            initCount++;
+            try {
+                Thread.sleep(50);
+            } catch (InterruptedException e) {
+                throw new RuntimeException(e);
+            }
+            VALUE = 42;
+        }
+    }
+
+    static class SlowReader extends Thread {
+        int seen;
+
+        @Override
+        public void run() {
+            seen = Slow.VALUE;
+        }
+    }
+
+    static class Recursive {
+        static int seenByOther = Other.readRecursive();
+        static int value = 7;
+    }
+
+    static class Other {
+        static int readRecursive() {
+            return Recursive.value;
+        }
+    }
+
+    public static void main(String[] args) throws Exception {
+        try {
+            System.out.println(Broken.value);
+        } catch (ExceptionInInitializerError e) {
+            System.out.print("First use: ");
+            System.out.print(e.getClass().getName());
+            System.out.print(" caused by ");
+            System.out.println(e.getCause());
+        }
+        try {
+            System.out.println(Broken.value);
+        } catch (NoClassDefFoundError e) {
+            System.out.print("Second use: ");
+            System.out.println(e.getMessage());
+        }
+
+        try {
+            System.out.println(ChildOfBroken.own);
+        } catch (NoClassDefFoundError e) {
+            System.out.print("Subclass: ");
+            System.out.println(e.getMessage());
+        }
+        try {
+            System.out.println(ChildOfBroken.own);
+        } catch (NoClassDefFoundError e) {
+            System.out.print("Subclass again: ");
+            System.out.println(e.getMessage());
+        }
+
+        SlowReader first = new SlowReader();
+        SlowReader second = new SlowReader();
+        first.start();
+        second.start();
+        first.join();
+        second.join();
+        System.out.print("Slow values: ");
+        System.out.print(first.seen);
+        System.out.print(" ");
+        System.out.print(second.seen);
+        System.out.print(", initialized ");
+        System.out.print(Slow.initCount);
+        System.out.println(" time(s)");
+
+        System.out.print("Recursive: ");
+        System.out.print(Recursive.seenByOther);
+        System.out.print(" ");
+        System.out.println(Recursive.value);
+    }
+}
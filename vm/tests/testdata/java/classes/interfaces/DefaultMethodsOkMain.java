@@ -0,0 +1,186 @@
+package classes.interfaces.defaults;
+
+import support.BytesLoader;
+import support.ClassBuilder;
+
+public class DefaultMethodsOkMain {
+    static final String PKG = "classes/interfaces/defaults/";
+    static final String MAIN = PKG + "DefaultMethodsOkMain";
+
+    public static void main(String[] args) throws Exception {
+        print("inherited: ", new Plain().greet());
+        print("static: ", Greeter.create().greet());
+        print("class wins: ", new Derived().name());
+        print("more specific: ", new AB().who());
+        print("diamond: ", new BC().who());
+        A deep = new DeepImpl();
+        print("superinterface: ", deep.who());
+        print("explicit super: ", new Both().side());
+        DefaultMethodsOkMain.Left left = new Both();
+        print("through interface: ", left.side());
+
+        System.out.println("initializing LoudImpl");
+        new LoudImpl().loud();
+        System.out.println("initializing QuietImpl");
+        new QuietImpl().quiet();
+
+        // javac refuses to compile these, a class with conflicting defaults and one without an implementation
+        BytesLoader loader = new BytesLoader();
+        String[] sides = {MAIN + "$Left", MAIN + "$Right"};
+        loader.add(implementor("Conflict", sides, MAIN + "$Left", "side", false));
+        loader.add(implementor("Virtual", sides, MAIN + "$Left", "side", true));
+        loader.add(implementor("Missing", new String[] {MAIN + "$Named"}, MAIN + "$Named", "name", false));
+        initialize(loader, "Conflict");
+        initialize(loader, "Virtual");
+        initialize(loader, "Missing");
+    }
+
+    public interface Left {
+        default String side() {
+            return "Left";
+        }
+    }
+
+    public interface Right {
+        default String side() {
+            return "Right";
+        }
+    }
+
+    public interface Named {
+        String name();
+    }
+
+    static Object print(String message) {
+        System.out.println(message);
+        return message;
+    }
+
+    static void print(String label, String value) {
+        System.out.print(label);
+        System.out.println(value);
+    }
+
+    static void initialize(ClassLoader loader, String simpleName) {
+        try {
+            Class.forName(PKG.replace('/', '.').concat(simpleName), true, loader);
+            print(simpleName, ": initialized");
+        } catch (ClassNotFoundException | LinkageError e) {
+            System.out.print(simpleName);
+            System.out.print(": ");
+            System.out.println(e);
+        }
+    }
+
+    // a class implementing `interfaces` whose <clinit> calls `owner.method()` on a new instance, or
+    // its own method with invokevirtual
+    static ClassBuilder implementor(String simpleName, String[] interfaces, String owner, String method,
+            boolean virtual) {
+        String name = PKG.concat(simpleName);
+        ClassBuilder builder = new ClassBuilder(ClassBuilder.PUBLIC | ClassBuilder.SUPER, name, "java/lang/Object")
+                .implement(interfaces);
+        builder.constructor("java/lang/Object");
+        int thisClass = builder.classRef(name);
+        int init = builder.methodRef(name, "<init>", "()V");
+        if (virtual) {
+            int ref = builder.methodRef(name, method, "()Ljava/lang/String;");
+            builder.method(ClassBuilder.STATIC, "<clinit>", "()V", 2, 0, 0xbb, thisClass >> 8, thisClass & 0xff,
+                    0x59, 0xb7, init >> 8, init & 0xff, 0xb6, ref >> 8, ref & 0xff, 0x57, 0xb1);
+        } else {
+            int ref = builder.interfaceMethodRef(owner, method, "()Ljava/lang/String;");
+            builder.method(ClassBuilder.STATIC, "<clinit>", "()V", 2, 0, 0xbb, thisClass >> 8, thisClass & 0xff,
+                    0x59, 0xb7, init >> 8, init & 0xff, 0xb9, ref >> 8, ref & 0xff, 1, 0, 0x57, 0xb1);
+        }
+        return builder;
+    }
+}
+
+interface Greeter {
+    static Greeter create() {
+        return new Plain();
+    }
+
+    default String greet() {
+        return prefix().concat("hello");
+    }
+
+    private String prefix() {
+        return separator("greeter");
+    }
+
+    private static String separator(String name) {
+        return name.concat(": ");
+    }
+}
+
+class Plain implements Greeter {
+}
+
+class Base {
+    public String name() {
+        return "Base";
+    }
+}
+
+interface NamedByDefault {
+    default String name() {
+        return "NamedByDefault";
+    }
+}
+
+class Derived extends Base implements NamedByDefault {
+}
+
+interface A {
+    default String who() {
+        return "A";
+    }
+}
+
+interface B extends A {
+    default String who() {
+        return "B";
+    }
+}
+
+interface C extends A {
+}
+
+class AB implements A, B {
+}
+
+class BC implements B, C {
+}
+
+interface Deep extends B {
+}
+
+class DeepImpl implements Deep {
+}
+
+class Both implements DefaultMethodsOkMain.Left, DefaultMethodsOkMain.Right {
+    public String side() {
+        return DefaultMethodsOkMain.Left.super.side().concat("+").concat(DefaultMethodsOkMain.Right.super.side());
+    }
+}
+
+interface Loud {
+    Object INIT = DefaultMethodsOkMain.print("Loud initialized");
+
+    default void loud() {
+    }
+}
+
+interface Quiet {
+    Object INIT = DefaultMethodsOkMain.print("Quiet initialized");
+
+    void quiet();
+}
+
+class LoudImpl implements Loud {
+}
+
+class QuietImpl implements Quiet {
+    public void quiet() {
+    }
+}
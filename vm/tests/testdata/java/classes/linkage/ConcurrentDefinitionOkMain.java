@@ -0,0 +1,79 @@
+package classes.linkage;
+
+import java.util.concurrent.CountDownLatch;
+import java.util.concurrent.TimeUnit;
+import support.ClassBuilder;
+
+public class ConcurrentDefinitionOkMain {
+    public static class Base {
+    }
+
+    // holds both definers in the superclass lookup, so their definitions overlap
+    static class RacingLoader extends ClassLoader {
+        private final CountDownLatch inSuperclass = new CountDownLatch(2);
+
+        RacingLoader() {
+            super(RacingLoader.class.getClassLoader());
+        }
+
+        @Override
+        public Class<?> loadClass(String name) throws ClassNotFoundException {
+            if (name.equals(Base.class.getName())) {
+                inSuperclass.countDown();
+                try {
+                    inSuperclass.await(1, TimeUnit.SECONDS);
+                } catch (InterruptedException e) {
+                    throw new ClassNotFoundException(name, e);
+                }
+            }
+            return super.loadClass(name);
+        }
+
+        Class<?> define(byte[] bytes) {
+            return defineClass("classes.linkage.Defined", bytes, 0, bytes.length);
+        }
+    }
+
+    static class Definer extends Thread {
+        private final RacingLoader loader;
+        private final byte[] bytes;
+        String result;
+
+        Definer(RacingLoader loader, byte[] bytes) {
+            this.loader = loader;
+            this.bytes = bytes;
+        }
+
+        @Override
+        public void run() {
+            try {
+                loader.define(bytes);
+                result = "defined";
+            } catch (Throwable t) {
+                result = t.getClass().getName();
+            }
+        }
+    }
+
+    public static void main(String[] args) throws InterruptedException {
+        // public class classes.linkage.Defined extends classes.linkage.ConcurrentDefinitionOkMain$Base
+        ClassBuilder defined = new ClassBuilder(ClassBuilder.PUBLIC | ClassBuilder.SUPER, "classes/linkage/Defined",
+                "classes/linkage/ConcurrentDefinitionOkMain$Base");
+        byte[] bytes = defined.build();
+        RacingLoader loader = new RacingLoader();
+        Definer first = new Definer(loader, bytes);
+        Definer second = new Definer(loader, bytes);
+        first.start();
+        second.start();
+        first.join();
+        second.join();
+
+        // either one may win, the other one is a duplicate definition, not a circularity
+        String winner = first.result.equals("defined") ? first.result : second.result;
+        String loser = first.result.equals("defined") ? second.result : first.result;
+        System.out.print("First definition: ");
+        System.out.println(winner);
+        System.out.print("Second definition: ");
+        System.out.println(loser);
+    }
+}
@@ -0,0 +1,62 @@
+package classes.linkage;
+
+import support.BytesLoader;
+import support.ClassBuilder;
+
+public class LinkageErrorsOkMain {
+    static final String PKG = "classes/linkage/";
+    static final String OBJECT = "java/lang/Object";
+    static final int PUBLIC_CLASS = ClassBuilder.PUBLIC | ClassBuilder.SUPER;
+
+    public static void main(String[] args) throws Exception {
+        BytesLoader loader = new BytesLoader();
+        loader.add(newClass("BadMagic", OBJECT, PUBLIC_CLASS).magic(0xCAFEBABF));
+        loader.add(newClass("Future", OBJECT, PUBLIC_CLASS).version(70));
+        loader.add(newClass("Iface", OBJECT, ClassBuilder.PUBLIC | ClassBuilder.INTERFACE | ClassBuilder.ABSTRACT));
+        loader.add(newClass("ExtendsIface", PKG + "Iface", PUBLIC_CLASS));
+        loader.add(newClass("Sealed", OBJECT, PUBLIC_CLASS | ClassBuilder.FINAL));
+        loader.add(newClass("ExtendsFinal", PKG + "Sealed", PUBLIC_CLASS));
+        loader.add(newClass("Orphan", PKG + "Missing", PUBLIC_CLASS));
+        loader.add(newClass("CycleA", PKG + "CycleB", PUBLIC_CLASS));
+        loader.add(newClass("CycleB", PKG + "CycleA", PUBLIC_CLASS));
+        loader.add(PKG + "Misnamed", newClass("Actual", OBJECT, PUBLIC_CLASS).build());
+        loader.add(newClass("NoSuper", null, PUBLIC_CLASS));
+        loader.add(newClass("ImplementsClass", OBJECT, PUBLIC_CLASS).implement(PKG + "Sealed"));
+
+        load(loader, "BadMagic", true);
+        load(loader, "Future", false);
+        load(loader, "ExtendsIface", true);
+        load(loader, "ExtendsFinal", true);
+        load(loader, "Orphan", true);
+        load(loader, "CycleA", true);
+        load(loader, "Misnamed", true);
+        load(loader, "NoSuper", true);
+        load(loader, "ImplementsClass", true);
+        load(loader, "Iface", true);
+    }
+
+    static void load(ClassLoader loader, String simpleName, boolean printMessage) {
+        try {
+            Class<?> c = loader.loadClass("classes.linkage.".concat(simpleName));
+            System.out.print(simpleName);
+            System.out.print(": loaded ");
+            System.out.println(c.getName());
+        } catch (ClassNotFoundException | LinkageError e) {
+            String message = String.valueOf(e.getMessage());
+            // HotSpot appends module and loader details to some messages
+            int details = message.indexOf(" (classes.linkage.");
+            if (details >= 0) {
+                message = message.substring(0, details);
+            }
+            System.out.print(simpleName);
+            System.out.print(": ");
+            System.out.print(e.getClass().getName());
+            System.out.println(printMessage ? ": ".concat(message) : "");
+        }
+    }
+
+    // a class without members
+    static ClassBuilder newClass(String simpleName, String superName, int flags) {
+        return new ClassBuilder(flags, PKG.concat(simpleName), superName);
+    }
+}
@@ -0,0 +1,22 @@
+package classes.loaders;
+
+import classes.loaders.bootappend.BootHelper;
+
+// run with BootHelper on -Xbootclasspath/a, which wins over the class path copy
+public class BootClassPathAppendMain {
+    public static void main(String[] args) throws Exception {
+        print("helper: ", BootHelper.greeting());
+        print("helper loader: ", String.valueOf(BootHelper.class.getClassLoader()));
+        print("helper module named: ", String.valueOf(BootHelper.class.getModule().isNamed()));
+        print("forName through the system loader: ", String.valueOf(
+                Class.forName("classes.loaders.bootappend.BootHelper", false, ClassLoader.getSystemClassLoader())
+                        == BootHelper.class));
+        print("main loader is the system loader: ", String.valueOf(
+                BootClassPathAppendMain.class.getClassLoader() == ClassLoader.getSystemClassLoader()));
+    }
+
+    static void print(String label, String value) {
+        System.out.print(label);
+        System.out.println(value);
+    }
+}
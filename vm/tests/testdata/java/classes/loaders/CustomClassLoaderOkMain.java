@@ -0,0 +1,58 @@
+package classes.loaders;
+
+import support.BytesLoader;
+import support.ClassBuilder;
+
+public class CustomClassLoaderOkMain {
+    static final String PLUGIN = "classes.loaders.Plugin";
+
+    static class PluginLoader extends BytesLoader {
+        private final byte[] bytes;
+
+        PluginLoader(byte[] bytes) {
+            this.bytes = bytes;
+            add(PLUGIN, bytes);
+        }
+
+        Class<?> define() {
+            return defineClass(PLUGIN, bytes, 0, bytes.length);
+        }
+    }
+
+    public static void main(String[] args) throws Exception {
+        // public class classes.loaders.Plugin extends java.lang.Object, no members
+        byte[] bytes = new ClassBuilder(ClassBuilder.PUBLIC | ClassBuilder.SUPER, "classes/loaders/Plugin",
+                "java/lang/Object").build();
+        PluginLoader first = new PluginLoader(bytes);
+        PluginLoader second = new PluginLoader(bytes);
+
+        Class<?> a = first.loadClass(PLUGIN);
+        Class<?> b = second.loadClass(PLUGIN);
+        System.out.print("Name: ");
+        System.out.println(a.getName());
+        System.out.print("Defined by first: ");
+        System.out.println(a.getClassLoader() == first);
+        System.out.print("Defined by second: ");
+        System.out.println(b.getClassLoader() == second);
+        System.out.print("Same class in both loaders: ");
+        System.out.println(a == b);
+        System.out.print("Cached: ");
+        System.out.println(first.loadClass(PLUGIN) == a);
+        System.out.print("Superclass: ");
+        System.out.println(a.getSuperclass().getName());
+        System.out.print("String delegated: ");
+        System.out.println(first.loadClass("java.lang.String") == String.class);
+        System.out.print("Bootstrap loader: ");
+        System.out.println(String.class.getClassLoader());
+        System.out.print("App loader: ");
+        System.out.println(CustomClassLoaderOkMain.class.getClassLoader() == ClassLoader.getSystemClassLoader());
+
+        try {
+            first.define();
+            System.out.println("Defined twice");
+        } catch (LinkageError e) {
+            System.out.print("Duplicate definition: ");
+            System.out.println(e.getClass().getName());
+        }
+    }
+}
@@ -0,0 +1,87 @@
+package classes.loaders;
+
+import support.BytesLoader;
+import support.ClassBuilder;
+
+public class ForNameOkMain {
+    static class Lazy {
+        static {
+            System.out.println("Lazy initialized");
+        }
+    }
+
+    static class FailsWithException {
+        static int value = fail();
+
+        static int fail() {
+            throw new IllegalStateException("boom");
+        }
+    }
+
+    static class FailsWithError {
+        static int value = fail();
+
+        static int fail() {
+            throw new InternalError("bang");
+        }
+    }
+
+    public static void main(String[] args) throws Exception {
+        ClassLoader loader = ForNameOkMain.class.getClassLoader();
+
+        Class<?> lazy = Class.forName("classes.loaders.ForNameOkMain$Lazy", false, loader);
+        System.out.print("Loaded without initializing: ");
+        System.out.println(lazy.getName());
+        boolean same = Class.forName("classes.loaders.ForNameOkMain$Lazy") == lazy;
+        System.out.print("Same class: ");
+        System.out.println(same);
+
+        System.out.print("String: ");
+        System.out.println(Class.forName("java.lang.String") == String.class);
+        System.out.print("String[]: ");
+        System.out.println(Class.forName("[Ljava.lang.String;") == String[].class);
+        System.out.print("int[][]: ");
+        System.out.println(Class.forName("[[I") == int[][].class);
+        System.out.print("Bootstrap: ");
+        System.out.println(Class.forName("java.util.ArrayList", true, null) == java.util.ArrayList.class);
+
+        for (String name : new String[] {"classes.loaders.Missing", "java/lang/String", "int", "[Lclasses.loaders.Missing;"}) {
+            try {
+                Class.forName(name);
+                System.out.print("Found ");
+                System.out.println(name);
+            } catch (ClassNotFoundException e) {
+                System.out.print("ClassNotFoundException: ");
+                System.out.println(e.getMessage());
+            }
+        }
+
+        try {
+            Class.forName("classes.loaders.ForNameOkMain$FailsWithException");
+            System.out.println("Initialized FailsWithException");
+        } catch (ExceptionInInitializerError e) {
+            System.out.print("ExceptionInInitializerError caused by ");
+            System.out.println(e.getCause());
+        }
+
+        try {
+            Class.forName("classes.loaders.ForNameOkMain$FailsWithError");
+            System.out.println("Initialized FailsWithError");
+        } catch (InternalError e) {
+            System.out.print("InternalError: ");
+            System.out.println(e.getMessage());
+        }
+
+        // classes.loaders.Orphan, whose superclass does not exist
+        BytesLoader orphanLoader = new BytesLoader();
+        orphanLoader.add(new ClassBuilder(ClassBuilder.PUBLIC | ClassBuilder.SUPER, "classes/loaders/Orphan",
+                "classes/loaders/Absent"));
+        try {
+            Class.forName("classes.loaders.Orphan", false, orphanLoader);
+            System.out.println("Loaded Orphan");
+        } catch (NoClassDefFoundError e) {
+            System.out.print("NoClassDefFoundError: ");
+            System.out.println(e.getMessage());
+        }
+    }
+}
@@ -0,0 +1,41 @@
+package classes.loaders;
+
+public class SystemClassLoaderOkMain {
+    static class Helper {
+    }
+
+    static class LoaderProbe extends Thread {
+        ClassLoader seen;
+
+        @Override
+        public void run() {
+            seen = Thread.currentThread().getContextClassLoader();
+        }
+    }
+
+    static void print(String label, Object value) {
+        System.out.print(label);
+        System.out.print(": ");
+        System.out.println(value);
+    }
+
+    public static void main(String[] args) throws Exception {
+        ClassLoader system = ClassLoader.getSystemClassLoader();
+        print("System loader", system.getName());
+        print("Defines main class", SystemClassLoaderOkMain.class.getClassLoader() == system);
+        print("Parent", system.getParent().getName());
+        print("Parent is platform loader", system.getParent() == ClassLoader.getPlatformClassLoader());
+        print("Main thread context loader", Thread.currentThread().getContextClassLoader() == system);
+
+        LoaderProbe probe = new LoaderProbe();
+        probe.start();
+        probe.join();
+        print("Started thread context loader", probe.seen == system);
+
+        print("loadClass main class", system.loadClass("classes.loaders.SystemClassLoaderOkMain") == SystemClassLoaderOkMain.class);
+        Class<?> helper = Class.forName("classes.loaders.SystemClassLoaderOkMain$Helper", false, system);
+        print("forName helper", helper.getName());
+        print("Helper loader", helper.getClassLoader() == system);
+        print("String through system loader", system.loadClass("java.lang.String") == String.class);
+    }
+}
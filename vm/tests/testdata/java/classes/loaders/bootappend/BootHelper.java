@@ -0,0 +1,7 @@
+package classes.loaders.bootappend;
+
+public class BootHelper {
+    public static String greeting() {
+        return "Hello from the boot class path";
+    }
+}
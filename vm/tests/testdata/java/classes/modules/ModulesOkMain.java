@@ -0,0 +1,66 @@
+package classes.modules;
+
+import java.lang.invoke.MethodHandles;
+import support.ClassBuilder;
+
+public class ModulesOkMain {
+    public static void main(String[] args) throws Exception {
+        Module base = Object.class.getModule();
+        print("object module: ", base.getName());
+        print("named: ", String.valueOf(base.isNamed()));
+        print("string in java.base: ", String.valueOf(String.class.getModule() == base));
+        print("int module: ", int.class.getModule().getName());
+        print("array module: ", String[].class.getModule().getName());
+        print("descriptor: ", base.getDescriptor().name());
+        print("loader: ", String.valueOf(base.getClassLoader()));
+        print("boot layer: ", String.valueOf(base.getLayer() == ModuleLayer.boot()));
+        print("exports java.lang: ", String.valueOf(base.isExported("java.lang")));
+        print("exports jdk.internal.misc: ", String.valueOf(base.isExported("jdk.internal.misc")));
+
+        Module mine = ModulesOkMain.class.getModule();
+        print("main named: ", String.valueOf(mine.isNamed()));
+        print("loader's unnamed module: ",
+                String.valueOf(mine == ModulesOkMain.class.getClassLoader().getUnnamedModule()));
+        print("main reads java.base: ", String.valueOf(mine.canRead(base)));
+        print("java.base reads main: ", String.valueOf(base.canRead(mine)));
+
+        // a public class of a package java.base does not export
+        Class<?> intruder = MethodHandles.lookup().defineClass(intruderBytes());
+        try {
+            Class.forName(intruder.getName(), true, intruder.getClassLoader());
+            System.out.println("encapsulation broken");
+        } catch (IllegalAccessError e) {
+            print("denied: ", withoutHashes(e.getMessage()));
+        }
+    }
+
+    static void print(String label, String value) {
+        System.out.print(label);
+        System.out.println(value);
+    }
+
+    // the identity hashes of unnamed modules differ between runs
+    static String withoutHashes(String message) {
+        StringBuilder result = new StringBuilder();
+        int from = 0;
+        int at;
+        while ((at = message.indexOf("@0x", from)) >= 0) {
+            result.append(message, from, at).append("@hash");
+            from = at + 3;
+            while (from < message.length() && Character.digit(message.charAt(from), 16) >= 0) {
+                from++;
+            }
+        }
+        return result.append(message.substring(from)).toString();
+    }
+
+    // classes/modules/Intruder, whose <clinit> calls jdk.internal.misc.VM.initLevel()
+    static byte[] intruderBytes() {
+        ClassBuilder builder = new ClassBuilder(ClassBuilder.FINAL | ClassBuilder.SUPER, "classes/modules/Intruder",
+                "java/lang/Object");
+        int initLevel = builder.methodRef("jdk/internal/misc/VM", "initLevel", "()I");
+        builder.method(ClassBuilder.STATIC, "<clinit>", "()V", 1, 0, 0xb8, initLevel >> 8, initLevel & 0xff, 0x57,
+                0xb1);
+        return builder.build();
+    }
+}
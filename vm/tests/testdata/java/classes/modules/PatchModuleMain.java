@@ -0,0 +1,20 @@
+package classes.modules;
+
+import java.util.function.Supplier;
+
+// run with java.lang.PatchedGreeting patched into java.base
+public class PatchModuleMain {
+    public static void main(String[] args) throws Exception {
+        Class<?> greeting = Class.forName("java.lang.PatchedGreeting");
+        Supplier<?> supplier = (Supplier<?>) greeting.getDeclaredConstructor().newInstance();
+        print("greeting: ", String.valueOf(supplier.get()));
+        print("module: ", greeting.getModule().getName());
+        print("loader: ", String.valueOf(greeting.getClassLoader()));
+        print("same module as Object: ", String.valueOf(greeting.getModule() == Object.class.getModule()));
+    }
+
+    static void print(String label, String value) {
+        System.out.print(label);
+        System.out.println(value);
+    }
+}
@@ -0,0 +1,40 @@
+package classes.modules;
+
+import java.util.logging.Logger;
+
+public class PlatformModulesOkMain {
+    public static void main(String[] args) throws Exception {
+        Class<?> logger = Logger.class;
+        print("logger: ", logger.getName());
+        print("logger module: ", logger.getModule().getName());
+        print("logger loader: ", String.valueOf(logger.getClassLoader()));
+        print("exports java.util.logging: ",
+                String.valueOf(logger.getModule().isExported("java.util.logging")));
+        print("java.base reads java.logging: ",
+                String.valueOf(Object.class.getModule().canRead(logger.getModule())));
+
+        Class<?> handler = Class.forName("java.util.logging.Handler", false,
+                PlatformModulesOkMain.class.getClassLoader());
+        print("handler: ", handler.getName());
+        print("handler abstract: ", String.valueOf(java.lang.reflect.Modifier.isAbstract(handler.getModifiers())));
+        print("same module: ", String.valueOf(handler.getModule() == logger.getModule()));
+
+        Class<?> connection = Class.forName("java.sql.Connection", false,
+                PlatformModulesOkMain.class.getClassLoader());
+        print("connection: ", connection.getName());
+        print("connection module: ", connection.getModule().getName());
+        print("connection interface: ", String.valueOf(connection.isInterface()));
+
+        try {
+            Class.forName("java.util.logging.NoSuchLogger");
+            System.out.println("found a missing class");
+        } catch (ClassNotFoundException e) {
+            print("missing: ", e.getMessage());
+        }
+    }
+
+    static void print(String label, String value) {
+        System.out.print(label);
+        System.out.println(value);
+    }
+}
@@ -0,0 +1,115 @@
+package classes.verification;
+
+import support.BytesLoader;
+import support.ClassBuilder;
+
+public class VerifierOkMain {
+    static final String PKG = "classes/verification/";
+
+    static final int ICONST_0 = 0x03;
+    static final int ICONST_1 = 0x04;
+    static final int FCONST_1 = 0x0c;
+    static final int ILOAD_0 = 0x1a;
+    static final int ISTORE_0 = 0x3b;
+    static final int FSTORE_0 = 0x43;
+    static final int ASTORE_1 = 0x4c;
+    static final int POP = 0x57;
+    static final int IADD = 0x60;
+    static final int IINC = 0x84;
+    static final int IFEQ = 0x99;
+    static final int GOTO = 0xa7;
+    static final int JSR = 0xa8;
+    static final int RET = 0xa9;
+    static final int IRETURN = 0xac;
+    static final int RETURN = 0xb1;
+
+    public static void main(String[] args) throws Exception {
+        BytesLoader loader = new BytesLoader();
+        add(loader, "Valid", "run", "()V", 1, 1, null, ICONST_1, ISTORE_0, RETURN);
+        add(loader, "Underflow", "run", "()V", 1, 0, null, POP, RETURN);
+        add(loader, "BadOperand", "run", "()V", 2, 0, null, ICONST_1, FCONST_1, IADD, POP, RETURN);
+        add(loader, "BadLocal", "run", "()V", 1, 1, null, ILOAD_0, POP, RETURN);
+        add(loader, "NoValue", "run", "()I", 1, 0, null, RETURN);
+        add(loader, "UnexpectedValue", "run", "()V", 1, 0, null, ICONST_0, IRETURN);
+        add(loader, "FallsOff", "run", "()V", 1, 0, null, ICONST_1, POP);
+        add(loader, "NoFrame", "run", "()V", 1, 0, null, ICONST_0, IFEQ, 0, 4, RETURN, RETURN);
+        // a single full_frame at offset 5 expecting an int in local 0
+        byte[] intLocal = {0, 1, (byte) 255, 0, 5, 0, 1, 1, 0, 0};
+        add(loader, "Inconsistent", "run", "()V", 1, 1, intLocal, ICONST_0, IFEQ, 0, 4, RETURN, RETURN);
+        add(loader, "NoSuperCall", "<init>", "()V", 1, 1, null, RETURN);
+
+        String[] names = {
+            "Valid", "Underflow", "BadOperand", "BadLocal", "NoValue", "UnexpectedValue", "FallsOff",
+            "NoFrame", "Inconsistent", "NoSuperCall",
+        };
+        for (String name : names) {
+            initialize(loader, name, true);
+        }
+        // a class failing verification is not marked as linked and fails again
+        initialize(loader, "Underflow", true);
+
+        // version 49 class files have their frames inferred, subroutines included
+        addOld(loader, "OldSubroutine", 1, 2,
+                ICONST_1, ISTORE_0, JSR, 0, 6, ILOAD_0, POP, RETURN, ASTORE_1, IINC, 0, 1, RET, 1);
+        addOld(loader, "OldClobberingSubroutine", 1, 2,
+                ICONST_1, ISTORE_0, JSR, 0, 6, ILOAD_0, POP, RETURN, ASTORE_1, FCONST_1, FSTORE_0, RET, 1);
+        addOld(loader, "OldStackHeight", 1, 0, ICONST_0, IFEQ, 0, 4, ICONST_1, RETURN);
+        addOld(loader, "OldMergedLocal", 1, 1,
+                ICONST_0, IFEQ, 0, 8, ICONST_1, ISTORE_0, GOTO, 0, 5, FCONST_1, FSTORE_0, ILOAD_0, POP, RETURN);
+        addOld(loader, "OldUnderflow", 1, 0, POP, RETURN);
+        addOld(loader, "OldRetWithoutJsr", 1, 1, ICONST_0, ISTORE_0, RET, 0);
+        // a version 50 class whose stack map fails falls back to the type inference
+        loader.add(singleMethod(50, PKG + "FailOver", "run", "()V", 1, 0, null,
+                new int[] {ICONST_0, IFEQ, 0, 4, RETURN, RETURN}));
+
+        String[] oldNames = {
+            "OldSubroutine", "OldClobberingSubroutine", "OldStackHeight", "OldMergedLocal", "OldUnderflow",
+            "OldRetWithoutJsr", "FailOver",
+        };
+        for (String name : oldNames) {
+            // the messages of HotSpot's old verifier have their own format
+            initialize(loader, name, false);
+        }
+    }
+
+    static void add(BytesLoader loader, String simpleName, String method, String desc, int maxStack, int maxLocals,
+            byte[] stackMap, int... code) {
+        loader.add(singleMethod(52, PKG.concat(simpleName), method, desc, maxStack, maxLocals, stackMap, code));
+    }
+
+    static void addOld(BytesLoader loader, String simpleName, int maxStack, int maxLocals, int... code) {
+        loader.add(singleMethod(49, PKG.concat(simpleName), "run", "()V", maxStack, maxLocals, null, code));
+    }
+
+    static void initialize(ClassLoader loader, String simpleName, boolean printMessage) {
+        try {
+            Class.forName("classes.verification.".concat(simpleName), true, loader);
+            System.out.print(simpleName);
+            System.out.println(": initialized");
+        } catch (ClassNotFoundException | LinkageError e) {
+            System.out.print(simpleName);
+            System.out.print(": ");
+            System.out.print(e.getClass().getName());
+            if (printMessage) {
+                // HotSpot adds the frames and the bytecode after the location
+                String[] lines = String.valueOf(e.getMessage()).split("\n");
+                System.out.print(": ");
+                System.out.print(lines[0]);
+                if (lines.length > 3) {
+                    System.out.print(" at ");
+                    System.out.print(lines[3].trim());
+                }
+            }
+            System.out.println();
+        }
+    }
+
+    // a public class with a single method, static unless it is a constructor
+    static ClassBuilder singleMethod(int major, String name, String method, String desc, int maxStack,
+            int maxLocals, byte[] stackMap, int[] code) {
+        ClassBuilder builder = new ClassBuilder(ClassBuilder.PUBLIC | ClassBuilder.SUPER, name, "java/lang/Object");
+        int access = method.equals("<init>") ? ClassBuilder.PUBLIC : ClassBuilder.PUBLIC | ClassBuilder.STATIC;
+        builder.method(access, method, desc, maxStack, maxLocals, stackMap, code);
+        return builder.version(major);
+    }
+}
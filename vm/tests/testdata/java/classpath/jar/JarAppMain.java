@@ -0,0 +1,9 @@
+package classpath.jar;
+
+import classpath.jar.lib.Greeter;
+
+public class JarAppMain {
+    public static void main(String[] args) {
+        System.out.println(Greeter.greet("jar"));
+    }
+}
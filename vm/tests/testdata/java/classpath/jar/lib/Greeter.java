@@ -0,0 +1,7 @@
+package classpath.jar.lib;
+
+public class Greeter {
+    public static String greet(String name) {
+        return "Hello, ".concat(name).concat("!");
+    }
+}
@@ -0,0 +1,35 @@
+package support;
+
+import java.util.HashMap;
+import java.util.Map;
+
+/** Defines the classes it was given the bytes of, everything else is left to the app loader. */
+public class BytesLoader extends ClassLoader {
+    private final Map<String, byte[]> classes = new HashMap<>();
+
+    public BytesLoader() {
+        super(BytesLoader.class.getClassLoader());
+    }
+
+    public BytesLoader(String name) {
+        super(name, BytesLoader.class.getClassLoader());
+    }
+
+    // `name` may differ from the name in `bytes`
+    public void add(String name, byte[] bytes) {
+        classes.put(name.replace('/', '.'), bytes);
+    }
+
+    public void add(ClassBuilder builder) {
+        add(builder.name, builder.build());
+    }
+
+    @Override
+    protected Class<?> findClass(String name) throws ClassNotFoundException {
+        byte[] bytes = classes.get(name);
+        if (bytes == null) {
+            throw new ClassNotFoundException(name);
+        }
+        return defineClass(name, bytes, 0, bytes.length);
+    }
+}
@@ -0,0 +1,222 @@
+package support;
+
+import java.io.ByteArrayOutputStream;
+import java.io.DataOutputStream;
+import java.io.IOException;
+import java.io.UncheckedIOException;
+import java.util.ArrayList;
+import java.util.Arrays;
+import java.util.HashMap;
+import java.util.List;
+import java.util.Map;
+
+/**
+ * Writes class files javac refuses to produce. Constants are added on first use, so the
+ * references returned by the builder can go straight into the bytecode.
+ */
+public class ClassBuilder {
+    public static final int PUBLIC = 0x0001;
+    public static final int PRIVATE = 0x0002;
+    public static final int PROTECTED = 0x0004;
+    public static final int STATIC = 0x0008;
+    public static final int FINAL = 0x0010;
+    public static final int SUPER = 0x0020;
+    public static final int INTERFACE = 0x0200;
+    public static final int ABSTRACT = 0x0400;
+
+    public final String name;
+    private final int flags;
+    private int magic = 0xCAFEBABE;
+    private int major = 52;
+    private final List<Object[]> constants = new ArrayList<>();
+    private final Map<String, Integer> indices = new HashMap<>();
+    private final List<Integer> interfaces = new ArrayList<>();
+    private final List<byte[]> fieldInfos = new ArrayList<>();
+    private final List<byte[]> methodInfos = new ArrayList<>();
+    private final List<byte[]> attributes = new ArrayList<>();
+    private final int thisClass;
+    private final int superClass;
+
+    // a null `superName` leaves the superclass index 0
+    public ClassBuilder(int flags, String name, String superName) {
+        this.flags = flags;
+        this.name = name;
+        this.thisClass = classRef(name);
+        this.superClass = superName == null ? 0 : classRef(superName);
+    }
+
+    public ClassBuilder magic(int magic) {
+        this.magic = magic;
+        return this;
+    }
+
+    public ClassBuilder version(int major) {
+        this.major = major;
+        return this;
+    }
+
+    public ClassBuilder implement(String... names) {
+        for (String name : names) {
+            interfaces.add(classRef(name));
+        }
+        return this;
+    }
+
+    private int constant(Object... entry) {
+        String key = Arrays.toString(entry);
+        Integer index = indices.get(key);
+        if (index == null) {
+            constants.add(entry);
+            index = constants.size();
+            indices.put(key, index);
+        }
+        return index;
+    }
+
+    public int utf8(String value) {
+        return constant(1, value);
+    }
+
+    public int classRef(String className) {
+        return constant(7, utf8(className));
+    }
+
+    public int string(String value) {
+        return constant(8, utf8(value));
+    }
+
+    public int fieldRef(String owner, String field, String desc) {
+        return constant(9, classRef(owner), nameAndType(field, desc));
+    }
+
+    public int methodRef(String owner, String method, String desc) {
+        return constant(10, classRef(owner), nameAndType(method, desc));
+    }
+
+    public int interfaceMethodRef(String owner, String method, String desc) {
+        return constant(11, classRef(owner), nameAndType(method, desc));
+    }
+
+    private int nameAndType(String name, String desc) {
+        return constant(12, utf8(name), utf8(desc));
+    }
+
+    public void field(int access, String field, String desc) {
+        fieldInfos.add(u2s(access, utf8(field), utf8(desc), 0));
+    }
+
+    public void method(int access, String method, String desc, int maxStack, int maxLocals, int... code) {
+        method(access, method, desc, maxStack, maxLocals, null, code);
+    }
+
+    // `stackMap` is the body of the StackMapTable attribute of the code, starting with the number
+    // of entries; without it the code has no attributes
+    public void method(int access, String method, String desc, int maxStack, int maxLocals, byte[] stackMap,
+            int[] code) {
+        ByteArrayOutputStream bytes = new ByteArrayOutputStream();
+        DataOutputStream out = new DataOutputStream(bytes);
+        try {
+            out.write(u2s(access, utf8(method), utf8(desc), 1, utf8("Code")));
+            int stackMapLength = stackMap == null ? 0 : 6 + stackMap.length;
+            out.writeInt(12 + code.length + stackMapLength);
+            out.writeShort(maxStack);
+            out.writeShort(maxLocals);
+            out.writeInt(code.length);
+            for (int b : code) {
+                out.writeByte(b);
+            }
+            out.writeShort(0);
+            if (stackMap == null) {
+                out.writeShort(0);
+            } else {
+                out.write(u2s(1, utf8("StackMapTable")));
+                out.writeInt(stackMap.length);
+                out.write(stackMap);
+            }
+        } catch (IOException e) {
+            throw new UncheckedIOException(e);
+        }
+        methodInfos.add(bytes.toByteArray());
+    }
+
+    // a public constructor calling the no-arg one of `superName`
+    public void constructor(String superName) {
+        int init = methodRef(superName, "<init>", "()V");
+        method(PUBLIC, "<init>", "()V", 1, 1, 0x2a, 0xb7, init >> 8, init & 0xff, 0xb1);
+    }
+
+    public ClassBuilder nestHost(String host) {
+        attribute("NestHost", classRef(host));
+        return this;
+    }
+
+    public ClassBuilder nestMembers(String... names) {
+        int[] values = new int[names.length + 1];
+        values[0] = names.length;
+        for (int i = 0; i < names.length; i++) {
+            values[i + 1] = classRef(names[i]);
+        }
+        attribute("NestMembers", values);
+        return this;
+    }
+
+    private void attribute(String attribute, int... values) {
+        ByteArrayOutputStream bytes = new ByteArrayOutputStream();
+        DataOutputStream out = new DataOutputStream(bytes);
+        try {
+            out.writeShort(utf8(attribute));
+            out.writeInt(2 * values.length);
+            out.write(u2s(values));
+        } catch (IOException e) {
+            throw new UncheckedIOException(e);
+        }
+        attributes.add(bytes.toByteArray());
+    }
+
+    public byte[] build() {
+        ByteArrayOutputStream bytes = new ByteArrayOutputStream();
+        DataOutputStream out = new DataOutputStream(bytes);
+        try {
+            out.writeInt(magic);
+            out.writeShort(0);
+            out.writeShort(major);
+            out.writeShort(constants.size() + 1);
+            for (Object[] entry : constants) {
+                out.writeByte((Integer) entry[0]);
+                if (entry[1] instanceof String) {
+                    out.writeUTF((String) entry[1]);
+                } else {
+                    for (int i = 1; i < entry.length; i++) {
+                        out.writeShort((Integer) entry[i]);
+                    }
+                }
+            }
+            out.write(u2s(flags, thisClass, superClass, interfaces.size()));
+            for (int index : interfaces) {
+                out.writeShort(index);
+            }
+            writeAll(out, fieldInfos);
+            writeAll(out, methodInfos);
+            writeAll(out, attributes);
+        } catch (IOException e) {
+            throw new UncheckedIOException(e);
+        }
+        return bytes.toByteArray();
+    }
+
+    private static void writeAll(DataOutputStream out, List<byte[]> items) throws IOException {
+        out.writeShort(items.size());
+        for (byte[] item : items) {
+            out.write(item);
+        }
+    }
+
+    private static byte[] u2s(int... values) {
+        byte[] bytes = new byte[2 * values.length];
+        for (int i = 0; i < values.length; i++) {
+            bytes[2 * i] = (byte) (values[i] >> 8);
+            bytes[2 * i + 1] = (byte) values[i];
+        }
+        return bytes;
+    }
+}
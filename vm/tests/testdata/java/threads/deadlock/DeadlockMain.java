@@ -0,0 +1,44 @@
+package threads.deadlock;
+
+import java.util.concurrent.CountDownLatch;
+
+public class DeadlockMain {
+    private static final Object first = new Object();
+    private static final Object second = new Object();
+    private static final CountDownLatch bothLocked = new CountDownLatch(2);
+
+    static class Locker extends Thread {
+        private final Object own;
+        private final Object other;
+
+        Locker(String name, Object own, Object other) {
+            super(name);
+            this.own = own;
+            this.other = other;
+        }
+
+        @Override
+        public void run() {
+            synchronized (own) {
+                bothLocked.countDown();
+                try {
+                    bothLocked.await();
+                } catch (InterruptedException e) {
+                    return;
+                }
+                synchronized (other) {
+                    System.out.println("Unreachable");
+                }
+            }
+        }
+    }
+
+    public static void main(String[] args) throws InterruptedException {
+        Locker a = new Locker("locker-a", first, second);
+        Locker b = new Locker("locker-b", second, first);
+        a.start();
+        b.start();
+        a.join();
+        b.join();
+    }
+}
@@ -0,0 +1,31 @@
+package threads.dump;
+
+public class ThreadDumpMain {
+    private static final Object lock = new Object();
+
+    static class Contender extends Thread {
+        Contender() {
+            super("contender");
+        }
+
+        @Override
+        public void run() {
+            synchronized (lock) {
+                System.out.println("Contender got the lock.");
+            }
+        }
+    }
+
+    public static void main(String[] args) throws InterruptedException {
+        Contender contender = new Contender();
+        synchronized (lock) {
+            contender.start();
+            while (contender.getState() != Thread.State.BLOCKED) {
+                Thread.yield();
+            }
+            System.out.println("ready");
+            Thread.sleep(2000);
+        }
+        contender.join();
+    }
+}
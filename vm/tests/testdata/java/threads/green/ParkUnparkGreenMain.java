@@ -0,0 +1,74 @@
+package threads.green;
+
+import java.util.concurrent.CountDownLatch;
+import java.util.concurrent.atomic.AtomicInteger;
+import java.util.concurrent.locks.LockSupport;
+
+public class ParkUnparkGreenMain {
+    private static final AtomicInteger counter = new AtomicInteger();
+
+    static class Parked extends Thread {
+        @Override
+        public void run() {
+            LockSupport.park();
+            counter.incrementAndGet();
+        }
+    }
+
+    static class Incrementer extends Thread {
+        private final CountDownLatch latch;
+
+        Incrementer(CountDownLatch latch) {
+            this.latch = latch;
+        }
+
+        @Override
+        public void run() {
+            for (int i = 0; i < 1000; i++) {
+                counter.incrementAndGet();
+            }
+            latch.countDown();
+        }
+    }
+
+    static class Interrupted extends Thread {
+        boolean wasInterrupted;
+
+        @Override
+        public void run() {
+            LockSupport.park();
+            wasInterrupted = isInterrupted();
+        }
+    }
+
+    public static void main(String[] args) throws InterruptedException {
+        // the permit is already there, park returns immediately
+        LockSupport.unpark(Thread.currentThread());
+        LockSupport.park();
+
+        // no permit, the timed park just times out
+        LockSupport.parkNanos(1_000_000);
+
+        Parked parked = new Parked();
+        parked.start();
+        LockSupport.unpark(parked);
+        parked.join();
+        System.out.print("after unpark: ");
+        System.out.println(counter.get());
+
+        CountDownLatch latch = new CountDownLatch(4);
+        for (int i = 0; i < 4; i++) {
+            new Incrementer(latch).start();
+        }
+        latch.await();
+        System.out.print("latch total: ");
+        System.out.println(counter.get());
+
+        Interrupted interrupted = new Interrupted();
+        interrupted.start();
+        interrupted.interrupt();
+        interrupted.join();
+        System.out.print("interrupt status: ");
+        System.out.println(interrupted.wasInterrupted);
+    }
+}
@@ -0,0 +1,28 @@
+package threads.green;
+
+public class RacyCounterGreenMain {
+    private static int counter = 0;
+
+    static class Racer extends Thread {
+        @Override
+        public void run() {
+            for (int i = 0; i < 10000; i++) {
+                // not atomic, a switch between the read and the write loses the other's updates
+                counter++;
+            }
+        }
+    }
+
+    public static void main(String[] args) throws InterruptedException {
+        Racer first = new Racer();
+        Racer second = new Racer();
+        first.start();
+        second.start();
+        first.join();
+        second.join();
+
+        // depends on where the scheduler switched threads, so on the seed only
+        System.out.print("counter: ");
+        System.out.println(counter);
+    }
+}
@@ -0,0 +1,34 @@
+package threads.green;
+
+public class SynchronizedCounterGreenMain {
+    private static int counter = 0;
+
+    static synchronized void increment() {
+        counter++;
+    }
+
+    public static void main(String[] args) throws InterruptedException {
+        Worker first = new Worker();
+        Worker second = new Worker();
+        first.start();
+        second.start();
+        first.join();
+        second.join();
+
+        System.out.print("counter: ");
+        System.out.println(counter);
+        System.out.print("first alive: ");
+        System.out.println(first.isAlive());
+        System.out.print("second alive: ");
+        System.out.println(second.isAlive());
+    }
+}
+
+class Worker extends Thread {
+    @Override
+    public void run() {
+        for (int i = 0; i < 10000; i++) {
+            SynchronizedCounterGreenMain.increment();
+        }
+    }
+}
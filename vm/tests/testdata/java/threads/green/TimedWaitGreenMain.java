@@ -0,0 +1,88 @@
+package threads.green;
+
+import java.util.concurrent.CountDownLatch;
+import java.util.concurrent.TimeUnit;
+import java.util.concurrent.locks.Condition;
+import java.util.concurrent.locks.LockSupport;
+import java.util.concurrent.locks.ReentrantLock;
+
+public class TimedWaitGreenMain {
+    private static final ReentrantLock lock = new ReentrantLock();
+
+    static class Holder extends Thread {
+        private final CountDownLatch locked;
+        private final CountDownLatch release;
+
+        Holder(CountDownLatch locked, CountDownLatch release) {
+            this.locked = locked;
+            this.release = release;
+        }
+
+        @Override
+        public void run() {
+            lock.lock();
+            try {
+                locked.countDown();
+                release.await();
+            } catch (InterruptedException e) {
+                throw new RuntimeException(e);
+            } finally {
+                lock.unlock();
+            }
+        }
+    }
+
+    static class Opener extends Thread {
+        private final CountDownLatch gate;
+
+        Opener(CountDownLatch gate) {
+            this.gate = gate;
+        }
+
+        @Override
+        public void run() {
+            gate.countDown();
+        }
+    }
+
+    public static void main(String[] args) throws InterruptedException {
+        long start = System.nanoTime();
+        LockSupport.parkNanos(2_000_000);
+        System.out.print("parkNanos waited: ");
+        System.out.println(System.nanoTime() - start >= 2_000_000);
+
+        CountDownLatch locked = new CountDownLatch(1);
+        CountDownLatch release = new CountDownLatch(1);
+        Holder holder = new Holder(locked, release);
+        holder.start();
+        locked.await();
+        System.out.print("tryLock while held: ");
+        System.out.println(lock.tryLock(10, TimeUnit.MILLISECONDS));
+        release.countDown();
+        holder.join();
+        boolean acquired = lock.tryLock(10, TimeUnit.MILLISECONDS);
+        System.out.print("tryLock after release: ");
+        System.out.println(acquired);
+        if (acquired) {
+            lock.unlock();
+        }
+
+        CountDownLatch closed = new CountDownLatch(1);
+        System.out.print("await on closed latch: ");
+        System.out.println(closed.await(5, TimeUnit.MILLISECONDS));
+        CountDownLatch gate = new CountDownLatch(1);
+        new Opener(gate).start();
+        System.out.print("await on opened latch: ");
+        System.out.println(gate.await(10, TimeUnit.SECONDS));
+
+        Condition condition = lock.newCondition();
+        lock.lock();
+        try {
+            long left = condition.awaitNanos(3_000_000);
+            System.out.print("awaitNanos timed out: ");
+            System.out.println(left <= 0);
+        } finally {
+            lock.unlock();
+        }
+    }
+}
@@ -0,0 +1,10 @@
+package java.lang;
+
+import java.util.function.Supplier;
+
+public class PatchedGreeting implements Supplier<String> {
+    @Override
+    public String get() {
+        return "Hello from the patched java.base";
+    }
+}